@@ -0,0 +1,138 @@
+//! AIFF and CAF, the containers macOS tools such as Logic and Final Cut prefer.
+//! Both are written whole from interleaved float samples: AIFF as big-endian integer
+//! PCM, CAF as integer or 32-bit float linear PCM.
+
+use std::fs;
+use std::io;
+
+/// How samples are stored in the file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PcmFormat {
+    /// Signed integers of this many bits, a multiple of 8 from 8 to 32.
+    Int(u16),
+    Float32,
+}
+
+/// Appends `samples` to `out` as big-endian PCM.
+fn push_samples(out: &mut Vec<u8>, samples: &[f32], format: PcmFormat) {
+    match format {
+        PcmFormat::Int(bits) => {
+            let bytes = (bits / 8).clamp(1, 4) as usize;
+            let scale = ((1i64 << (bytes * 8 - 1)) - 1) as f32;
+            for &s in samples {
+                let value = (s.clamp(-1.0, 1.0) * scale).round() as i32;
+                out.extend_from_slice(&value.to_be_bytes()[4 - bytes..]);
+            }
+        }
+        PcmFormat::Float32 => {
+            for &s in samples {
+                out.extend_from_slice(&s.to_be_bytes());
+            }
+        }
+    }
+}
+
+fn bytes_per_sample(format: PcmFormat) -> u32 {
+    match format {
+        PcmFormat::Int(bits) => (bits / 8).clamp(1, 4) as u32,
+        PcmFormat::Float32 => 4,
+    }
+}
+
+/// A sample rate as the 80-bit extended float AIFF uses.
+fn extended(rate: u32) -> [u8; 10] {
+    let mut out = [0; 10];
+    if rate == 0 {
+        return out;
+    }
+    let shift = rate.leading_zeros();
+    let exponent = 16383 + 31 - shift as u16;
+    let mantissa = ((rate as u64) << shift) << 32;
+    out[..2].copy_from_slice(&exponent.to_be_bytes());
+    out[2..].copy_from_slice(&mantissa.to_be_bytes());
+    out
+}
+
+/// Writes an AIFF file of `bits`-bit integer samples.
+pub fn write_aiff(
+    path: &str,
+    samples: &[f32],
+    channels: u16,
+    sample_rate: u32,
+    bits: u16,
+) -> io::Result<()> {
+    let format = PcmFormat::Int(bits);
+    let frames = samples.len() / channels.max(1) as usize;
+    let data_len = (frames * channels.max(1) as usize) as u32 * bytes_per_sample(format);
+
+    let mut out = Vec::with_capacity(54 + data_len as usize + 1);
+    out.extend_from_slice(b"FORM");
+    out.extend_from_slice(&[0; 4]);
+    out.extend_from_slice(b"AIFF");
+
+    out.extend_from_slice(b"COMM");
+    out.extend_from_slice(&18u32.to_be_bytes());
+    out.extend_from_slice(&channels.to_be_bytes());
+    out.extend_from_slice(&(frames as u32).to_be_bytes());
+    out.extend_from_slice(&(bytes_per_sample(format) as u16 * 8).to_be_bytes());
+    out.extend_from_slice(&extended(sample_rate));
+
+    out.extend_from_slice(b"SSND");
+    out.extend_from_slice(&(8 + data_len).to_be_bytes());
+    // Offset and block size, both unused
+    out.extend_from_slice(&[0; 8]);
+    push_samples(
+        &mut out,
+        &samples[..frames * channels.max(1) as usize],
+        format,
+    );
+    if data_len % 2 == 1 {
+        out.push(0);
+    }
+
+    let form_size = (out.len() - 8) as u32;
+    out[4..8].copy_from_slice(&form_size.to_be_bytes());
+    fs::write(path, out)
+}
+
+/// Writes a CAF file of linear PCM samples.
+pub fn write_caf(
+    path: &str,
+    samples: &[f32],
+    channels: u16,
+    sample_rate: u32,
+    format: PcmFormat,
+) -> io::Result<()> {
+    let frames = samples.len() / channels.max(1) as usize;
+    let sample_bytes = bytes_per_sample(format);
+    let data_len = (frames * channels.max(1) as usize) as u64 * sample_bytes as u64;
+
+    let mut out = Vec::with_capacity(68 + data_len as usize);
+    out.extend_from_slice(b"caff");
+    out.extend_from_slice(&1u16.to_be_bytes());
+    out.extend_from_slice(&0u16.to_be_bytes());
+
+    out.extend_from_slice(b"desc");
+    out.extend_from_slice(&32i64.to_be_bytes());
+    out.extend_from_slice(&(sample_rate as f64).to_be_bytes());
+    out.extend_from_slice(b"lpcm");
+    // Format flags: bit 0 is "float"; samples are big-endian, so bit 1 stays clear
+    let flags: u32 = if format == PcmFormat::Float32 { 1 } else { 0 };
+    out.extend_from_slice(&flags.to_be_bytes());
+    out.extend_from_slice(&(sample_bytes * channels as u32).to_be_bytes());
+    out.extend_from_slice(&1u32.to_be_bytes());
+    out.extend_from_slice(&(channels as u32).to_be_bytes());
+    out.extend_from_slice(&(sample_bytes * 8).to_be_bytes());
+
+    out.extend_from_slice(b"data");
+    out.extend_from_slice(&(4 + data_len as i64).to_be_bytes());
+    // Edit count
+    out.extend_from_slice(&0u32.to_be_bytes());
+    push_samples(
+        &mut out,
+        &samples[..frames * channels.max(1) as usize],
+        format,
+    );
+
+    fs::write(path, out)
+}
@@ -0,0 +1,655 @@
+//! Audio backend abstraction. The recorder only talks to devices through
+//! `AudioBackend`, so the engine can run against real cpal devices or a scripted mock.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{BufferSize, SampleFormat, StreamConfig, SupportedBufferSize};
+
+/// A running stream. Dropping it stops the stream.
+pub trait AudioStream {}
+
+impl AudioStream for cpal::Stream {}
+
+/// Receives interleaved input samples converted to f32.
+/// Gets each captured buffer as f32 and, from 16-bit devices, as the device's own
+/// samples (unsigned ones recentred, which is exact).
+pub type InputCallback = Box<dyn FnMut(&[f32], Option<&[i16]>) + Send + 'static>;
+/// Fills an interleaved f32 output buffer; the backend converts to the device format.
+pub type OutputCallback = Box<dyn FnMut(&mut [f32]) + Send + 'static>;
+/// Receives errors reported by a running stream, on the audio thread.
+pub type ErrorCallback = Box<dyn FnMut(StreamError) + Send + 'static>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamError {
+    /// The device went away, e.g. headphones were unplugged. The stream is dead.
+    DeviceLost,
+    Other(String),
+}
+
+impl From<cpal::StreamError> for StreamError {
+    fn from(err: cpal::StreamError) -> Self {
+        match err {
+            cpal::StreamError::DeviceNotAvailable => StreamError::DeviceLost,
+            other => StreamError::Other(other.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StreamFormat {
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub sample_format: SampleFormat,
+    pub buffer_size: BufferSize,
+}
+
+impl StreamFormat {
+    fn stream_config(&self) -> StreamConfig {
+        StreamConfig {
+            channels: self.channels,
+            sample_rate: cpal::SampleRate(self.sample_rate),
+            buffer_size: self.buffer_size,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SupportedFormat {
+    pub channels: u16,
+    pub min_sample_rate: u32,
+    pub max_sample_rate: u32,
+    pub sample_format: SampleFormat,
+    pub buffer_size: SupportedBufferSize,
+}
+
+impl SupportedFormat {
+    pub fn contains_rate(&self, sample_rate: u32) -> bool {
+        (self.min_sample_rate..=self.max_sample_rate).contains(&sample_rate)
+    }
+
+    pub fn with_sample_rate(&self, sample_rate: u32) -> StreamFormat {
+        StreamFormat {
+            channels: self.channels,
+            sample_rate,
+            sample_format: self.sample_format,
+            buffer_size: BufferSize::Default,
+        }
+    }
+}
+
+impl From<cpal::SupportedStreamConfigRange> for SupportedFormat {
+    fn from(range: cpal::SupportedStreamConfigRange) -> Self {
+        Self {
+            channels: range.channels(),
+            min_sample_rate: range.min_sample_rate().0,
+            max_sample_rate: range.max_sample_rate().0,
+            sample_format: range.sample_format(),
+            buffer_size: *range.buffer_size(),
+        }
+    }
+}
+
+/// Triangular (TPDF) dither: the sum of two independent uniform values, spanning
+/// +-1 step of the output format. Added before rounding, it turns the distortion that
+/// quantizing quiet material causes (a gritty, signal-dependent buzz on fades and
+/// reverb tails) into a constant, much less objectionable low-level hiss.
+pub struct Tpdf(u32);
+
+impl Tpdf {
+    pub fn new() -> Self {
+        Self(0x9E37_79B9)
+    }
+
+    fn uniform(&mut self) -> f32 {
+        // xorshift32
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0 as f32 / u32::MAX as f32 - 0.5
+    }
+
+    pub fn sample(&mut self) -> f32 {
+        self.uniform() + self.uniform()
+    }
+}
+
+impl Default for Tpdf {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Rounds a sample already scaled to integer steps, dithered first if `dither` is
+/// given; without it the value is truncated, as the output paths always did.
+pub fn quantize(scaled: f32, dither: Option<&mut Tpdf>) -> f32 {
+    match dither {
+        Some(tpdf) => (scaled + tpdf.sample()).round(),
+        None => scaled.trunc(),
+    }
+}
+
+pub trait AudioBackend {
+    fn input_device_name(&self) -> Result<String, String>;
+    fn output_device_name(&self) -> Result<String, String>;
+    fn default_input_format(&self) -> Result<StreamFormat, String>;
+    fn default_output_format(&self) -> Result<StreamFormat, String>;
+    fn supported_input_formats(&self) -> Result<Vec<SupportedFormat>, String>;
+    fn supported_output_formats(&self) -> Result<Vec<SupportedFormat>, String>;
+    /// Builds and starts an input stream on the default input device.
+    fn build_input_stream(
+        &self,
+        format: &StreamFormat,
+        on_data: InputCallback,
+    ) -> Result<Box<dyn AudioStream>, String>;
+    /// Builds and starts an output stream on the default output device.
+    fn build_output_stream(
+        &self,
+        format: &StreamFormat,
+        fill: OutputCallback,
+        on_error: ErrorCallback,
+    ) -> Result<Box<dyn AudioStream>, String>;
+    /// Names of every output device, for playing to one besides the default.
+    fn output_device_names(&self) -> Result<Vec<String>, String>;
+    fn named_output_format(&self, name: &str) -> Result<StreamFormat, String>;
+    /// Builds and starts an output stream on the output device called `name`.
+    fn build_named_output_stream(
+        &self,
+        name: &str,
+        format: &StreamFormat,
+        fill: OutputCallback,
+        on_error: ErrorCallback,
+    ) -> Result<Box<dyn AudioStream>, String>;
+    /// Whether integer output formats get TPDF dither; applies to running streams.
+    fn set_output_dither(&self, enabled: bool);
+    /// Audio hosts (APIs such as WASAPI, ASIO, ALSA or JACK) built into the app, each
+    /// with whether it can be used on this machine right now.
+    fn host_names(&self) -> Vec<(String, bool)>;
+    /// Looks devices up through the host called `name` from now on, or the system
+    /// default for `None`. Streams already running stay where they are.
+    fn set_host(&self, name: Option<&str>) -> Result<(), String>;
+}
+
+/// The real backend, using the cpal host picked in settings (the default one unless
+/// another is set).
+pub struct CpalBackend {
+    dither: Arc<AtomicBool>,
+    // Built once when picked, since building a host can mean connecting to its server
+    host: Mutex<cpal::Host>,
+}
+
+impl Default for CpalBackend {
+    fn default() -> Self {
+        Self {
+            dither: Arc::default(),
+            host: Mutex::new(cpal::default_host()),
+        }
+    }
+}
+
+impl CpalBackend {
+    fn host(&self) -> MutexGuard<'_, cpal::Host> {
+        self.host.lock().unwrap()
+    }
+
+    fn input_device(&self) -> Result<cpal::Device, String> {
+        self.host()
+            .default_input_device()
+            .ok_or_else(|| "No input device found.".to_string())
+    }
+
+    fn output_device(&self) -> Result<cpal::Device, String> {
+        self.host()
+            .default_output_device()
+            .ok_or_else(|| "Failed to find default output device".to_string())
+    }
+
+    fn named_output_device(&self, name: &str) -> Result<cpal::Device, String> {
+        self.host()
+            .output_devices()
+            .map_err(|e| format!("Error listing output devices: {}", e))?
+            .find(|d| d.name().is_ok_and(|n| n == name))
+            .ok_or_else(|| format!("Output device '{}' not found.", name))
+    }
+
+    fn output_format_of(device: &cpal::Device) -> Result<StreamFormat, String> {
+        let config = device
+            .default_output_config()
+            .map_err(|e| format!("Failed to get default output config: {}", e))?;
+        Ok(StreamFormat {
+            channels: config.channels(),
+            sample_rate: config.sample_rate().0,
+            sample_format: config.sample_format(),
+            buffer_size: BufferSize::Default,
+        })
+    }
+
+    fn build_output_stream_on(
+        &self,
+        device: &cpal::Device,
+        format: &StreamFormat,
+        mut fill: OutputCallback,
+        mut on_error: ErrorCallback,
+    ) -> Result<Box<dyn AudioStream>, String> {
+        let config = format.stream_config();
+        let mut scratch: Vec<f32> = Vec::new();
+        let dither = Arc::clone(&self.dither);
+        let mut tpdf = Tpdf::new();
+
+        let build_result = match format.sample_format {
+            SampleFormat::F32 => device.build_output_stream(
+                &config,
+                move |out: &mut [f32], _| fill(out),
+                move |err| on_error(err.into()),
+                None,
+            ),
+            SampleFormat::I16 => device.build_output_stream(
+                &config,
+                move |out: &mut [i16], _| {
+                    scratch.resize(out.len(), 0.0);
+                    fill(&mut scratch);
+                    let dithered = dither.load(Ordering::Relaxed);
+                    for (o, &s) in out.iter_mut().zip(&scratch) {
+                        let scaled = s.clamp(-1.0, 1.0) * i16::MAX as f32;
+                        *o = quantize(scaled, dithered.then_some(&mut tpdf))
+                            .clamp(i16::MIN as f32, i16::MAX as f32)
+                            as i16;
+                    }
+                },
+                move |err| on_error(err.into()),
+                None,
+            ),
+            SampleFormat::U16 => device.build_output_stream(
+                &config,
+                move |out: &mut [u16], _| {
+                    scratch.resize(out.len(), 0.0);
+                    fill(&mut scratch);
+                    let dithered = dither.load(Ordering::Relaxed);
+                    for (o, &s) in out.iter_mut().zip(&scratch) {
+                        let scaled = (s.clamp(-1.0, 1.0) + 1.0) * 0.5 * u16::MAX as f32;
+                        *o = quantize(scaled, dithered.then_some(&mut tpdf))
+                            .clamp(0.0, u16::MAX as f32) as u16;
+                    }
+                },
+                move |err| on_error(err.into()),
+                None,
+            ),
+            SampleFormat::U8 => device.build_output_stream(
+                &config,
+                move |out: &mut [u8], _| {
+                    scratch.resize(out.len(), 0.0);
+                    fill(&mut scratch);
+                    let dithered = dither.load(Ordering::Relaxed);
+                    for (o, &s) in out.iter_mut().zip(&scratch) {
+                        let scaled = (s.clamp(-1.0, 1.0) + 1.0) * 127.5;
+                        *o =
+                            quantize(scaled, dithered.then_some(&mut tpdf)).clamp(0.0, 255.0) as u8;
+                    }
+                },
+                move |err| on_error(err.into()),
+                None,
+            ),
+            other => return Err(format!("Unsupported output sample format: {:?}", other)),
+        };
+
+        let stream = build_result.map_err(|e| format!("Failed to build output stream: {}", e))?;
+        stream
+            .play()
+            .map_err(|e| format!("Failed to start output stream: {}", e))?;
+        Ok(Box::new(stream))
+    }
+}
+
+impl AudioBackend for CpalBackend {
+    fn input_device_name(&self) -> Result<String, String> {
+        self.input_device()?
+            .name()
+            .map_err(|e| format!("Failed to get input device name: {}", e))
+    }
+
+    fn output_device_name(&self) -> Result<String, String> {
+        self.output_device()?
+            .name()
+            .map_err(|e| format!("Failed to get output device name: {}", e))
+    }
+
+    fn default_input_format(&self) -> Result<StreamFormat, String> {
+        let config = self
+            .input_device()?
+            .default_input_config()
+            .map_err(|e| format!("Failed to get default input config: {}", e))?;
+        Ok(StreamFormat {
+            channels: config.channels(),
+            sample_rate: config.sample_rate().0,
+            sample_format: config.sample_format(),
+            buffer_size: BufferSize::Default,
+        })
+    }
+
+    fn default_output_format(&self) -> Result<StreamFormat, String> {
+        Self::output_format_of(&self.output_device()?)
+    }
+
+    fn supported_input_formats(&self) -> Result<Vec<SupportedFormat>, String> {
+        let configs = self
+            .input_device()?
+            .supported_input_configs()
+            .map_err(|e| format!("Error querying input configs: {}", e))?;
+        Ok(configs.map(SupportedFormat::from).collect())
+    }
+
+    fn supported_output_formats(&self) -> Result<Vec<SupportedFormat>, String> {
+        let configs = self
+            .output_device()?
+            .supported_output_configs()
+            .map_err(|e| format!("Error querying output configs: {}", e))?;
+        Ok(configs.map(SupportedFormat::from).collect())
+    }
+
+    fn build_input_stream(
+        &self,
+        format: &StreamFormat,
+        mut on_data: InputCallback,
+    ) -> Result<Box<dyn AudioStream>, String> {
+        let device = self.input_device()?;
+        let config = format.stream_config();
+        let mut scratch: Vec<f32> = Vec::new();
+        let mut native: Vec<i16> = Vec::new();
+
+        let build_result = match format.sample_format {
+            SampleFormat::F32 => device.build_input_stream(
+                &config,
+                move |data: &[f32], _| on_data(data, None),
+                move |err| eprintln!("Input stream error: {}", err),
+                None,
+            ),
+            SampleFormat::I16 => device.build_input_stream(
+                &config,
+                move |data: &[i16], _| {
+                    scratch.clear();
+                    scratch.extend(data.iter().map(|&s| (s as f32) / (i16::MAX as f32)));
+                    on_data(&scratch, Some(data));
+                },
+                move |err| eprintln!("Input stream error: {}", err),
+                None,
+            ),
+            SampleFormat::U16 => device.build_input_stream(
+                &config,
+                move |data: &[u16], _| {
+                    native.clear();
+                    native.extend(data.iter().map(|&s| (s ^ 0x8000) as i16));
+                    scratch.clear();
+                    scratch.extend(native.iter().map(|&s| (s as f32) / (i16::MAX as f32)));
+                    on_data(&scratch, Some(&native));
+                },
+                move |err| eprintln!("Input stream error: {}", err),
+                None,
+            ),
+            _ => return Err("Unsupported input sample format".into()),
+        };
+
+        let stream = build_result.map_err(|e| format!("Failed to build input stream: {}", e))?;
+        stream
+            .play()
+            .map_err(|e| format!("Failed to start input stream: {}", e))?;
+        Ok(Box::new(stream))
+    }
+
+    fn build_output_stream(
+        &self,
+        format: &StreamFormat,
+        fill: OutputCallback,
+        on_error: ErrorCallback,
+    ) -> Result<Box<dyn AudioStream>, String> {
+        self.build_output_stream_on(&self.output_device()?, format, fill, on_error)
+    }
+
+    fn output_device_names(&self) -> Result<Vec<String>, String> {
+        let devices = self
+            .host()
+            .output_devices()
+            .map_err(|e| format!("Error listing output devices: {}", e))?;
+        Ok(devices.filter_map(|d| d.name().ok()).collect())
+    }
+
+    fn named_output_format(&self, name: &str) -> Result<StreamFormat, String> {
+        Self::output_format_of(&self.named_output_device(name)?)
+    }
+
+    fn build_named_output_stream(
+        &self,
+        name: &str,
+        format: &StreamFormat,
+        fill: OutputCallback,
+        on_error: ErrorCallback,
+    ) -> Result<Box<dyn AudioStream>, String> {
+        self.build_output_stream_on(&self.named_output_device(name)?, format, fill, on_error)
+    }
+
+    fn set_output_dither(&self, enabled: bool) {
+        self.dither.store(enabled, Ordering::Relaxed);
+    }
+
+    fn host_names(&self) -> Vec<(String, bool)> {
+        let available = cpal::available_hosts();
+        cpal::ALL_HOSTS
+            .iter()
+            .map(|id| (id.name().to_string(), available.contains(id)))
+            .collect()
+    }
+
+    fn set_host(&self, name: Option<&str>) -> Result<(), String> {
+        let host = match name {
+            Some(name) => {
+                let id = cpal::ALL_HOSTS
+                    .iter()
+                    .copied()
+                    .find(|id| id.name() == name)
+                    .ok_or_else(|| format!("Audio host {} isn't built into this version.", name))?;
+                // Compiled in doesn't mean usable: e.g. JACK needs its server running
+                cpal::host_from_id(id)
+                    .map_err(|e| format!("Audio host {} is unavailable: {}", name, e))?
+            }
+            None => cpal::default_host(),
+        };
+        *self.host.lock().unwrap() = host;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod mock {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    struct MockStream;
+
+    impl AudioStream for MockStream {}
+
+    /// A scripted backend: input streams are fed `input_signal` synchronously in
+    /// `block_frames` chunks, and output streams are pulled synchronously until
+    /// `max_render_samples` have been rendered into `rendered`.
+    pub struct MockBackend {
+        pub input_format: StreamFormat,
+        pub input_formats: Vec<SupportedFormat>,
+        pub output_formats: Vec<SupportedFormat>,
+        pub input_signal: Vec<f32>,
+        pub block_frames: usize,
+        pub max_render_samples: usize,
+        pub rendered: Arc<Mutex<Vec<f32>>>,
+        /// Number of output streams built so far.
+        pub output_streams_built: Arc<Mutex<usize>>,
+        /// Reported by the next output stream once it has rendered.
+        pub output_error: Arc<Mutex<Option<StreamError>>>,
+        /// When false there's no output device and output streams fail to build.
+        pub output_present: Arc<Mutex<bool>>,
+        /// Extra output devices by name and format; streams on them render into
+        /// `named_rendered`, the same way as the default output.
+        pub named_outputs: Vec<(String, StreamFormat)>,
+        pub named_rendered: Arc<Mutex<Vec<f32>>>,
+        /// Last output dither setting; the mock renders f32, so nothing is quantized.
+        pub output_dither: Arc<Mutex<bool>>,
+        /// Hosts on offer and whether each is available, and the one picked.
+        pub hosts: Vec<(String, bool)>,
+        pub host: Arc<Mutex<Option<String>>>,
+    }
+
+    impl MockBackend {
+        pub fn new(channels: u16, sample_rate: u32) -> Self {
+            let supported = SupportedFormat {
+                channels,
+                min_sample_rate: 8000,
+                max_sample_rate: 96000,
+                sample_format: SampleFormat::F32,
+                buffer_size: SupportedBufferSize::Range { min: 64, max: 8192 },
+            };
+            Self {
+                input_format: StreamFormat {
+                    channels,
+                    sample_rate,
+                    sample_format: SampleFormat::F32,
+                    buffer_size: BufferSize::Default,
+                },
+                input_formats: vec![supported],
+                output_formats: vec![supported],
+                input_signal: Vec::new(),
+                block_frames: 256,
+                max_render_samples: 0,
+                rendered: Arc::new(Mutex::new(Vec::new())),
+                output_streams_built: Arc::new(Mutex::new(0)),
+                output_error: Arc::new(Mutex::new(None)),
+                output_present: Arc::new(Mutex::new(true)),
+                named_outputs: Vec::new(),
+                named_rendered: Arc::new(Mutex::new(Vec::new())),
+                output_dither: Arc::new(Mutex::new(false)),
+                hosts: vec![("Mock".into(), true)],
+                host: Arc::new(Mutex::new(None)),
+            }
+        }
+    }
+
+    impl AudioBackend for MockBackend {
+        fn input_device_name(&self) -> Result<String, String> {
+            Ok("Mock input".into())
+        }
+
+        fn output_device_name(&self) -> Result<String, String> {
+            Ok("Mock output".into())
+        }
+
+        fn default_input_format(&self) -> Result<StreamFormat, String> {
+            Ok(self.input_format)
+        }
+
+        fn default_output_format(&self) -> Result<StreamFormat, String> {
+            self.output_formats
+                .first()
+                .map(|f| f.with_sample_rate(f.max_sample_rate))
+                .ok_or_else(|| "Failed to find default output device".to_string())
+        }
+
+        fn supported_input_formats(&self) -> Result<Vec<SupportedFormat>, String> {
+            Ok(self.input_formats.clone())
+        }
+
+        fn supported_output_formats(&self) -> Result<Vec<SupportedFormat>, String> {
+            Ok(self.output_formats.clone())
+        }
+
+        fn build_input_stream(
+            &self,
+            format: &StreamFormat,
+            mut on_data: InputCallback,
+        ) -> Result<Box<dyn AudioStream>, String> {
+            let block = self.block_frames * format.channels as usize;
+            let integer = matches!(format.sample_format, SampleFormat::I16 | SampleFormat::U16);
+            for chunk in self.input_signal.chunks(block) {
+                let native: Vec<i16> = chunk
+                    .iter()
+                    .map(|&s| (s * i16::MAX as f32).round() as i16)
+                    .collect();
+                on_data(chunk, integer.then_some(&native[..]));
+            }
+            Ok(Box::new(MockStream))
+        }
+
+        fn build_output_stream(
+            &self,
+            format: &StreamFormat,
+            mut fill: OutputCallback,
+            mut on_error: ErrorCallback,
+        ) -> Result<Box<dyn AudioStream>, String> {
+            if !*self.output_present.lock().unwrap() {
+                return Err("No output device found.".into());
+            }
+            *self.output_streams_built.lock().unwrap() += 1;
+            let mut block = vec![0.0; self.block_frames * format.channels as usize];
+            let mut rendered = self.rendered.lock().unwrap();
+            while rendered.len() < self.max_render_samples {
+                fill(&mut block);
+                rendered.extend_from_slice(&block);
+            }
+            if let Some(err) = self.output_error.lock().unwrap().take() {
+                on_error(err);
+            }
+            Ok(Box::new(MockStream))
+        }
+
+        fn output_device_names(&self) -> Result<Vec<String>, String> {
+            let mut names = vec!["Mock output".to_string()];
+            names.extend(self.named_outputs.iter().map(|(name, _)| name.clone()));
+            Ok(names)
+        }
+
+        fn named_output_format(&self, name: &str) -> Result<StreamFormat, String> {
+            self.named_outputs
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, format)| *format)
+                .ok_or_else(|| format!("Output device '{}' not found.", name))
+        }
+
+        fn build_named_output_stream(
+            &self,
+            name: &str,
+            format: &StreamFormat,
+            mut fill: OutputCallback,
+            _on_error: ErrorCallback,
+        ) -> Result<Box<dyn AudioStream>, String> {
+            self.named_output_format(name)?;
+            let mut block = vec![0.0; self.block_frames * format.channels as usize];
+            let mut rendered = self.named_rendered.lock().unwrap();
+            while rendered.len() < self.max_render_samples {
+                fill(&mut block);
+                rendered.extend_from_slice(&block);
+            }
+            Ok(Box::new(MockStream))
+        }
+
+        fn set_output_dither(&self, enabled: bool) {
+            *self.output_dither.lock().unwrap() = enabled;
+        }
+
+        fn host_names(&self) -> Vec<(String, bool)> {
+            self.hosts.clone()
+        }
+
+        fn set_host(&self, name: Option<&str>) -> Result<(), String> {
+            if let Some(name) = name {
+                match self.hosts.iter().find(|(host, _)| host == name) {
+                    None => {
+                        return Err(format!(
+                            "Audio host {} isn't built into this version.",
+                            name
+                        ));
+                    }
+                    Some((_, false)) => return Err(format!("Audio host {} is unavailable.", name)),
+                    Some(_) => {}
+                }
+            }
+            *self.host.lock().unwrap() = name.map(str::to_string);
+            Ok(())
+        }
+    }
+}
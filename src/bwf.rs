@@ -0,0 +1,295 @@
+//! Broadcast Wave (BWF) metadata, the RIFF `LIST`/`INFO` comment and `cue ` points.
+//! hound doesn't know about these chunks, so they're spliced into the RIFF structure
+//! after the file has been written. Readers that don't understand them (hound included) skip them like any
+//! other unknown chunk.
+
+use std::fs;
+use std::io;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const BEXT: [u8; 4] = *b"bext";
+pub const LIST: [u8; 4] = *b"LIST";
+const INFO: [u8; 4] = *b"INFO";
+const ICMT: [u8; 4] = *b"ICMT";
+pub const CUE: [u8; 4] = *b"cue ";
+const ORIGINATOR: &str = "rust_voice";
+
+/// Contents of a version 1 `bext` chunk.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bext {
+    pub description: String,
+    pub originator_reference: String,
+    /// Origination date and time, UTC
+    pub origination_date: String,
+    pub origination_time: String,
+    /// Start of the recording in samples since midnight
+    pub time_reference: u64,
+}
+
+impl Bext {
+    pub fn new(description: &str, reference: &str, started: SystemTime, sample_rate: u32) -> Self {
+        let secs = started.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let (date, time) = utc_date_time(secs.as_secs());
+        let since_midnight = secs.as_secs_f64() % 86_400.0;
+        Self {
+            description: description.to_string(),
+            originator_reference: reference.to_string(),
+            origination_date: date,
+            origination_time: time,
+            time_reference: (since_midnight * sample_rate as f64) as u64,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(602);
+        push_fixed(&mut out, &self.description, 256);
+        push_fixed(&mut out, ORIGINATOR, 32);
+        push_fixed(&mut out, &self.originator_reference, 32);
+        push_fixed(&mut out, &self.origination_date, 10);
+        push_fixed(&mut out, &self.origination_time, 8);
+        out.extend_from_slice(&self.time_reference.to_le_bytes());
+        out.extend_from_slice(&1u16.to_le_bytes()); // Version
+        out.resize(out.len() + 64 + 190, 0); // UMID and reserved
+        out
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        if data.len() < 346 {
+            return None;
+        }
+        Some(Self {
+            description: read_fixed(&data[0..256]),
+            originator_reference: read_fixed(&data[288..320]),
+            origination_date: read_fixed(&data[320..330]),
+            origination_time: read_fixed(&data[330..338]),
+            time_reference: u64::from_le_bytes(data[338..346].try_into().ok()?),
+        })
+    }
+}
+
+/// A `LIST` chunk body of type `INFO` with its comment (`ICMT`) set to `comment`.
+/// The other entries of `info`, the list already in the file if there is one, are kept.
+pub fn info_comment(info: Option<&[u8]>, comment: &str) -> Vec<u8> {
+    let mut out = INFO.to_vec();
+    for (id, value) in info.map(info_entries).unwrap_or_default() {
+        if id != ICMT {
+            push_entry(&mut out, id, value);
+        }
+    }
+    let mut text: Vec<u8> = comment.bytes().filter(|&b| b != 0).collect();
+    text.push(0);
+    push_entry(&mut out, ICMT, &text);
+    out
+}
+
+/// The `ICMT` comment in a `LIST` chunk body, if it's an `INFO` list that has one.
+pub fn read_info_comment(data: &[u8]) -> Option<String> {
+    info_entries(data)
+        .into_iter()
+        .find(|&(id, _)| id == ICMT)
+        .map(|(_, value)| read_fixed(value))
+}
+
+/// The entries of an `INFO` list body, or none if it's another kind of list.
+fn info_entries(data: &[u8]) -> Vec<([u8; 4], &[u8])> {
+    let mut entries = Vec::new();
+    if data.get(0..4) != Some(&INFO[..]) {
+        return entries;
+    }
+    let mut pos = 4;
+    while pos + 8 <= data.len() {
+        let id: [u8; 4] = data[pos..pos + 4].try_into().unwrap();
+        let size = u32::from_le_bytes(data[pos + 4..pos + 8].try_into().unwrap()) as usize;
+        let end = (pos + 8 + size).min(data.len());
+        entries.push((id, &data[pos + 8..end]));
+        pos = end + size % 2;
+    }
+    entries
+}
+
+fn push_entry(out: &mut Vec<u8>, id: [u8; 4], value: &[u8]) {
+    out.extend_from_slice(&id);
+    out.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    out.extend_from_slice(value);
+    if value.len() % 2 == 1 {
+        out.push(0);
+    }
+}
+
+/// A `cue ` chunk body with a point at each of `frames` (sample frames from the
+/// start of the audio), numbered from 1.
+pub fn cue_points(frames: &[u32]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(4 + frames.len() * 24);
+    out.extend_from_slice(&(frames.len() as u32).to_le_bytes());
+    for (i, &frame) in frames.iter().enumerate() {
+        out.extend_from_slice(&(i as u32 + 1).to_le_bytes()); // ID
+        out.extend_from_slice(&frame.to_le_bytes()); // Position
+        out.extend_from_slice(b"data");
+        out.extend_from_slice(&0u32.to_le_bytes()); // Chunk start
+        out.extend_from_slice(&0u32.to_le_bytes()); // Block start
+        out.extend_from_slice(&frame.to_le_bytes()); // Sample offset
+    }
+    out
+}
+
+/// Sample frames of the points in a `cue ` chunk body, in order.
+pub fn read_cue_points(data: &[u8]) -> Vec<u32> {
+    let count = data
+        .get(0..4)
+        .map_or(0, |b| u32::from_le_bytes(b.try_into().unwrap()) as usize);
+    let mut frames: Vec<u32> = data[4.min(data.len())..]
+        .chunks_exact(24)
+        .take(count)
+        .map(|point| u32::from_le_bytes(point[20..24].try_into().unwrap()))
+        .collect();
+    frames.sort_unstable();
+    frames
+}
+
+fn push_fixed(out: &mut Vec<u8>, value: &str, len: usize) {
+    // Fields are ASCII; anything else is replaced rather than cut mid-character
+    let mut bytes: Vec<u8> = value
+        .chars()
+        .map(|c| if c.is_ascii() { c as u8 } else { b'?' })
+        .take(len)
+        .collect();
+    bytes.resize(len, 0);
+    out.extend_from_slice(&bytes);
+}
+
+fn read_fixed(field: &[u8]) -> String {
+    let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).into_owned()
+}
+
+/// `yyyy-mm-dd` and `hh:mm:ss` for a Unix timestamp.
+pub fn utc_date_time(secs: u64) -> (String, String) {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+
+    // Civil-from-days, counting eras of 400 years from 0000-03-01
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (
+        format!("{:04}-{:02}-{:02}", year, month, day),
+        format!("{:02}:{:02}:{:02}", rem / 3600, rem / 60 % 60, rem % 60),
+    )
+}
+
+/// Byte ranges of each chunk in a RIFF/WAVE file: (id, header start, end including padding).
+fn chunks(bytes: &[u8]) -> io::Result<Vec<([u8; 4], usize, usize)>> {
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "not a WAV file"));
+    }
+    let mut found = Vec::new();
+    let mut pos = 12;
+    while pos + 8 <= bytes.len() {
+        let id: [u8; 4] = bytes[pos..pos + 4].try_into().unwrap();
+        let size = u32::from_le_bytes(bytes[pos + 4..pos + 8].try_into().unwrap()) as usize;
+        let end = (pos + 8 + size + size % 2).min(bytes.len());
+        found.push((id, pos, end));
+        pos = end;
+    }
+    Ok(found)
+}
+
+/// The body of chunk `id`, the first one if there are several. `LIST` chunks are
+/// better found with `read_info`, as a file can hold other kinds of list too.
+pub fn read_chunk(path: &str, id: [u8; 4]) -> io::Result<Option<Vec<u8>>> {
+    Ok(read_chunks(path, &[id])?
+        .into_iter()
+        .next()
+        .map(|(_, data)| data))
+}
+
+/// The body of the `LIST` chunk of type `INFO`, if the file has one.
+pub fn read_info(path: &str) -> io::Result<Option<Vec<u8>>> {
+    Ok(read_chunks(path, &[LIST])?
+        .into_iter()
+        .map(|(_, data)| data)
+        .find(|data| data.starts_with(&INFO)))
+}
+
+/// Every chunk whose id is in `ids`, in the order they appear in the file.
+pub fn read_chunks(path: &str, ids: &[[u8; 4]]) -> io::Result<Vec<([u8; 4], Vec<u8>)>> {
+    let bytes = fs::read(path)?;
+    Ok(chunks(&bytes)?
+        .into_iter()
+        .filter(|(id, _, _)| ids.contains(id))
+        .map(|(id, start, end)| (id, body(&bytes, start, end).to_vec()))
+        .collect())
+}
+
+fn body(bytes: &[u8], start: usize, end: usize) -> &[u8] {
+    let size = u32::from_le_bytes(bytes[start + 4..start + 8].try_into().unwrap()) as usize;
+    &bytes[start + 8..(start + 8 + size).min(end)]
+}
+
+/// What makes two chunks the same for replacing one with the other: the id, and for
+/// a `LIST` its list type, so writing the `INFO` list leaves an `adtl` one alone.
+fn chunk_key(id: [u8; 4], body: &[u8]) -> ([u8; 4], Option<&[u8]>) {
+    (id, (id == LIST).then(|| body.get(0..4).unwrap_or_default()))
+}
+
+/// Adds chunk `id` to the file just before the audio data, replacing any existing one.
+/// The new file is written next to the original and renamed over it.
+pub fn write_chunk(path: &str, id: [u8; 4], data: &[u8]) -> io::Result<()> {
+    write_chunks(path, &[(id, data.to_vec())])
+}
+
+/// Adds each of `new` to the file just before the audio data, replacing the chunks
+/// already there that they stand in for (see `chunk_key`), in a single rewrite.
+pub fn write_chunks(path: &str, new: &[([u8; 4], Vec<u8>)]) -> io::Result<()> {
+    let out = with_chunks(&fs::read(path)?, new)?;
+    let tmp_path = format!("{}.tmp", path);
+    match fs::write(&tmp_path, &out) {
+        Ok(()) => fs::rename(&tmp_path, path),
+        Err(e) => {
+            let _ = fs::remove_file(&tmp_path);
+            Err(e)
+        }
+    }
+}
+
+/// The WAV file in `bytes` with each of `new` added as `write_chunks` would, for
+/// a file that's still in memory.
+pub fn with_chunks(bytes: &[u8], new: &[([u8; 4], Vec<u8>)]) -> io::Result<Vec<u8>> {
+    let chunks = chunks(bytes)?;
+    let Some(&(_, data_start, _)) = chunks
+        .iter()
+        .find(|&&(chunk_id, _, _)| &chunk_id == b"data")
+    else {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "no data chunk"));
+    };
+    let replaced: Vec<_> = new.iter().map(|(id, data)| chunk_key(*id, data)).collect();
+
+    let added: usize = new.iter().map(|(_, data)| data.len() + 9).sum();
+    let mut out = Vec::with_capacity(bytes.len() + added);
+    out.extend_from_slice(&bytes[..12]);
+    for &(chunk_id, start, end) in &chunks {
+        if start == data_start {
+            for (id, data) in new {
+                out.extend_from_slice(id);
+                out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+                out.extend_from_slice(data);
+                if data.len() % 2 == 1 {
+                    out.push(0);
+                }
+            }
+        }
+        if !replaced.contains(&chunk_key(chunk_id, body(bytes, start, end))) {
+            out.extend_from_slice(&bytes[start..end]);
+        }
+    }
+    let riff_size = (out.len() - 8) as u32;
+    out[4..8].copy_from_slice(&riff_size.to_le_bytes());
+    Ok(out)
+}
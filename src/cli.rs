@@ -0,0 +1,208 @@
+//! Headless command line. With no subcommand the GUI starts as usual; the subcommands
+//! drive the same recorder engine without opening a window, for scripting and SSH.
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use clap::{Parser, Subcommand};
+
+use crate::{
+    ExportFormat, ExportSampleRate, JobControl, MAX_EXPORT_SAMPLE_RATE, Message, PlaybackState,
+    RecordingPhase, ResampleQuality, VoiceRecorder, export_file_to,
+};
+
+/// How often the engine is ticked while waiting on a device.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Parser)]
+#[command(
+    version,
+    about = "Voice recorder. Run without a command to open the app."
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Record from the default input device
+    Record {
+        /// Length of the recording
+        #[arg(long)]
+        seconds: f32,
+        /// WAV file to write
+        #[arg(long)]
+        out: String,
+    },
+    /// Play a WAV file on the default output device
+    Play { file: String },
+    /// Convert a WAV file; the output extension picks WAV, FLAC, MP3, AIFF or CAF
+    Convert {
+        input: String,
+        output: String,
+        /// Start from an export preset, e.g. Podcast
+        #[arg(long)]
+        preset: Option<String>,
+        /// Sample rate in Hz; MP3 takes 8000 to 48000 in the standard steps
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..=MAX_EXPORT_SAMPLE_RATE as i64))]
+        sample_rate: Option<u32>,
+        #[arg(long)]
+        mono: bool,
+        /// Normalise to this loudness, in LUFS
+        #[arg(long, allow_hyphen_values = true)]
+        lufs: Option<f32>,
+        /// Resampling quality: fast (linear) or high (windowed sinc, the default)
+        #[arg(long, value_parser = ["fast", "high"])]
+        resample: Option<String>,
+    },
+}
+
+/// Runs one command, printing the engine's status messages to stderr.
+pub fn run(recorder: &mut VoiceRecorder, command: Command) -> Result<(), String> {
+    let result = match command {
+        Command::Record { seconds, out } => record(recorder, seconds, &out),
+        Command::Play { file } => play(recorder, &file),
+        Command::Convert {
+            input,
+            output,
+            preset,
+            sample_rate,
+            mono,
+            lufs,
+            resample,
+        } => {
+            let quality = resample.map(|name| match name.as_str() {
+                "fast" => ResampleQuality::Fast,
+                _ => ResampleQuality::High,
+            });
+            convert(
+                recorder,
+                &input,
+                &output,
+                preset,
+                ConvertOptions {
+                    sample_rate,
+                    mono,
+                    lufs,
+                    quality,
+                },
+            )
+        }
+    };
+    for (_, line) in &recorder.status_log {
+        eprintln!("{}", line);
+    }
+    result
+}
+
+fn tick(recorder: &mut VoiceRecorder) {
+    thread::sleep(POLL_INTERVAL);
+    let _ = recorder.update(Message::Tick(Instant::now()));
+}
+
+fn record(recorder: &mut VoiceRecorder, seconds: f32, out: &str) -> Result<(), String> {
+    if !seconds.is_finite() || seconds < 0.0 {
+        return Err("--seconds must be zero or more.".into());
+    }
+    // Saving is driven from here, and there's nobody to hear the cues
+    recorder.config.auto_save_recordings = false;
+    recorder.config.audio_cues_enabled = false;
+
+    let _ = recorder.update(Message::StartRecording);
+    if !recorder.recording_phase.is_recording() {
+        return Err(recorder.status_message.clone());
+    }
+    thread::sleep(Duration::from_secs_f32(seconds));
+    let _ = recorder.update(Message::StopRecording);
+
+    // Wait out the flush delay so the last buffers make it into the take
+    while matches!(recorder.recording_phase, RecordingPhase::Stopping { .. }) {
+        tick(recorder);
+    }
+    if recorder.save_recording_as(out) {
+        Ok(())
+    } else {
+        Err(recorder.status_message.clone())
+    }
+}
+
+fn play(recorder: &mut VoiceRecorder, file: &str) -> Result<(), String> {
+    recorder.play_file_from(file, None);
+    if recorder.playback_state == PlaybackState::Stopped {
+        return Err(recorder.status_message.clone());
+    }
+    while recorder.playback_state != PlaybackState::Stopped {
+        tick(recorder);
+    }
+    Ok(())
+}
+
+/// Overrides on top of the preset or saved export settings.
+struct ConvertOptions {
+    sample_rate: Option<u32>,
+    mono: bool,
+    lufs: Option<f32>,
+    quality: Option<ResampleQuality>,
+}
+
+fn convert(
+    recorder: &mut VoiceRecorder,
+    input: &str,
+    output: &str,
+    preset: Option<String>,
+    options: ConvertOptions,
+) -> Result<(), String> {
+    let ConvertOptions {
+        sample_rate,
+        mono,
+        lufs,
+        quality,
+    } = options;
+    let mut settings = match preset {
+        Some(name) => recorder
+            .export_presets()
+            .into_iter()
+            .find(|p| p.name.eq_ignore_ascii_case(&name))
+            .map(|p| p.settings)
+            .ok_or_else(|| format!("No export preset named '{}'.", name))?,
+        None => recorder.config.export.clone(),
+    };
+
+    let extension = output
+        .rsplit_once('.')
+        .map(|(_, ext)| ext.to_lowercase())
+        .unwrap_or_default();
+    // Keep the preset's format when it already writes this kind of file
+    if settings.format.extension() != extension {
+        settings.format = match extension.as_str() {
+            "wav" => ExportFormat::WavFloat,
+            "flac" => ExportFormat::Flac,
+            "mp3" => ExportFormat::Mp3,
+            "aif" | "aiff" => ExportFormat::Aiff,
+            "caf" => ExportFormat::Caf,
+            _ => return Err("Output must end in .wav, .flac, .mp3, .aiff or .caf.".into()),
+        };
+    }
+    if let Some(rate) = sample_rate {
+        settings.sample_rate = ExportSampleRate::Hz(rate);
+    }
+    settings.mono |= mono;
+    if lufs.is_some() {
+        settings.normalize_lufs = lufs;
+    }
+    if let Some(quality) = quality {
+        settings.resample_quality = quality;
+    }
+
+    let output = export_file_to(input, output, &settings, &JobControl::default())?;
+    let loudness = output
+        .measured_lufs
+        .map(|lufs| format!(", normalised from {:.1} LUFS", lufs))
+        .unwrap_or_default();
+    eprintln!(
+        "Converted '{}' to '{}' ({} Hz, {} ch{})",
+        input, output.path, output.sample_rate, output.channels, loudness
+    );
+    Ok(())
+}
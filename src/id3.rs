@@ -0,0 +1,98 @@
+//! ID3v2.3 tags for MP3 exports: title, artist, album and front cover art, the
+//! fields podcast apps and feeds read. Written whole, to go in front of the MP3 frames.
+
+/// An image to embed as the front cover.
+pub struct Picture<'a> {
+    pub mime: &'static str,
+    pub data: &'a [u8],
+}
+
+/// The MIME type of PNG or JPEG image data, by its signature.
+pub fn image_mime(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if data.starts_with(&[0xff, 0xd8, 0xff]) {
+        Some("image/jpeg")
+    } else {
+        None
+    }
+}
+
+/// `text` with its encoding byte: Latin-1 where it fits, otherwise UTF-16 with a BOM.
+/// With `terminate`, ends it with that encoding's null.
+fn encode_text(text: &str, terminate: bool) -> Vec<u8> {
+    let mut out = Vec::with_capacity(text.len() * 2 + 4);
+    if text.chars().all(|c| (c as u32) < 0x100) {
+        out.push(0);
+        out.extend(text.chars().map(|c| c as u8));
+        if terminate {
+            out.push(0);
+        }
+    } else {
+        out.extend_from_slice(&[1, 0xff, 0xfe]);
+        for unit in text.encode_utf16() {
+            out.extend_from_slice(&unit.to_le_bytes());
+        }
+        if terminate {
+            out.extend_from_slice(&[0, 0]);
+        }
+    }
+    out
+}
+
+fn push_frame(out: &mut Vec<u8>, id: &[u8; 4], body: &[u8]) {
+    out.extend_from_slice(id);
+    out.extend_from_slice(&(body.len() as u32).to_be_bytes());
+    // Flags
+    out.extend_from_slice(&[0, 0]);
+    out.extend_from_slice(body);
+}
+
+/// The largest tag body the header's 28-bit size can describe.
+const MAX_TAG_SIZE: usize = (1 << 28) - 1;
+
+/// A tag holding the non-empty text fields and `cover`; empty if there's nothing
+/// to put in one. Fails if it's too big for a tag to hold (a huge cover image).
+pub fn tag(
+    title: &str,
+    artist: &str,
+    album: &str,
+    cover: Option<Picture>,
+) -> Result<Vec<u8>, String> {
+    let mut frames = Vec::new();
+    for (id, value) in [(b"TIT2", title), (b"TPE1", artist), (b"TALB", album)] {
+        if !value.is_empty() {
+            push_frame(&mut frames, id, &encode_text(value, false));
+        }
+    }
+    if let Some(picture) = cover {
+        let mut body = vec![0];
+        body.extend_from_slice(picture.mime.as_bytes());
+        body.push(0);
+        // Picture type 3 is the front cover; the description stays empty
+        body.extend_from_slice(&[3, 0]);
+        body.extend_from_slice(picture.data);
+        push_frame(&mut frames, b"APIC", &body);
+    }
+    if frames.is_empty() {
+        return Ok(frames);
+    }
+    if frames.len() > MAX_TAG_SIZE {
+        return Err(format!(
+            "The ID3 tag would be {} MB, more than the {} MB it can hold; use a smaller \
+             cover image.",
+            frames.len() >> 20,
+            MAX_TAG_SIZE >> 20
+        ));
+    }
+
+    // The tag size is "syncsafe": seven bits to a byte
+    let size = frames.len() as u32;
+    let mut out = Vec::with_capacity(10 + frames.len());
+    out.extend_from_slice(b"ID3");
+    // Version 2.3.0, no flags
+    out.extend_from_slice(&[3, 0, 0]);
+    out.extend((0..4).rev().map(|i| ((size >> (i * 7)) & 0x7f) as u8));
+    out.extend_from_slice(&frames);
+    Ok(out)
+}
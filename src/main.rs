@@ -1,880 +1,11452 @@
 #![cfg_attr(windows, windows_subsystem = "windows")]
-use iced::keyboard::{self, Key};
-use iced::widget::{button, center, column, row, scrollable, text, text_input};
+mod apple;
+mod audio;
+mod bwf;
+mod cli;
+mod id3;
+#[cfg(test)]
+mod tests;
+mod transcribe;
+#[cfg(feature = "tray")]
+mod tray;
+mod waveform;
+
+use iced::keyboard::{self, Key, Modifiers};
+use iced::widget::{
+    Column, button, canvas, center, checkbox, column, container, pick_list, progress_bar, row,
+    scrollable, slider, text, text_input,
+};
 use iced::{Element, Length, Subscription, Task, Theme, time};
 
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::fs;
+use std::hash::{Hash, Hasher};
 use std::io;
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex, mpsc};
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime};
 
-use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use cpal::{BufferSize, SampleFormat, Stream, StreamConfig};
+use clap::Parser;
+use cpal::{BufferSize, SampleFormat};
 use hound::{WavReader, WavSpec};
+use serde::{Deserialize, Serialize};
 
-fn write_wav_file_f32(path: &str, spec: WavSpec, samples: &[f32]) -> io::Result<()> {
-    let mut writer = hound::WavWriter::create(path, spec)
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-    for &s in samples {
-        writer
-            .write_sample(s)
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-    }
-    writer
-        .finalize()
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-    Ok(())
-}
+use audio::{
+    AudioBackend, AudioStream, CpalBackend, ErrorCallback, StreamError, StreamFormat,
+    SupportedFormat,
+};
+use waveform::{LevelHistory, LevelMeter, SpeechTimeline, Waveform, WaveformData};
 
-fn write_wav_file_i16(path: &str, spec: WavSpec, samples: &[i16]) -> io::Result<()> {
-    let mut writer = hound::WavWriter::create(path, spec)
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-    for &s in samples {
-        writer
-            .write_sample(s)
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-    }
-    writer
-        .finalize()
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-    Ok(())
+const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(400);
+const CONFIG_PATH: &str = "voice_recorder_config.json";
+const NOTES_PATH: &str = "recording_notes.json";
+const PROJECTS_PATH: &str = "recording_projects.json";
+/// The take being recorded, kept on disk so a crash doesn't lose it. Not a `.wav`
+/// name, so it never shows up in the library.
+const RECOVERY_PATH: &str = ".recording_in_progress.partial";
+/// How often the take is written to `RECOVERY_PATH`; a crash loses at most this much.
+const RECOVERY_FLUSH_MS: u64 = 1000;
+/// Largest width or height of an exported waveform image, in pixels.
+const MAX_IMAGE_SIDE: u32 = 8000;
+/// Sample rates the MP3 encoder can write.
+const MP3_SAMPLE_RATES: [u32; 9] = [8000, 11025, 12000, 16000, 22050, 24000, 32000, 44100, 48000];
+/// Highest sample rate an export can have; FLAC's limit, and far past any device.
+const MAX_EXPORT_SAMPLE_RATE: u32 = 655_350;
+/// Longest pre-roll offered; it's held in memory while armed.
+const MAX_PRE_ROLL_SECS: f32 = 30.0;
+/// Longest preview offered, in seconds.
+const MAX_PREVIEW_SECS: f32 = 600.0;
+/// Longest skip back or forward, in seconds.
+const MAX_SKIP_SECS: f32 = 3600.0;
+/// Widest band around the meter target that still counts as on target, in dB.
+const MAX_METER_TOLERANCE_DB: f32 = 24.0;
+/// Longest stretch of silence that can be inserted at once, in seconds.
+const MAX_SILENCE_SECS: f32 = 3600.0;
+/// How many folders the recent folders list remembers.
+const RECENT_FOLDERS_LEN: usize = 8;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct Config {
+    /// Beep when recording starts and stops. Off by default: capture is already
+    /// running when the start beep plays, so a nearby mic puts it in the take.
+    audio_cues_enabled: bool,
+    chapter_min_gap_secs: f32,
+    skip_intro_secs: f32,
+    resume_last_position: bool,
+    resume_over_skip: bool,
+    /// Metronome tempo, set by typing or tapping.
+    metronome_bpm: f32,
+    /// Play the input through the output while it's open, and whether to play it
+    /// after the input processing (gain, DC block) rather than as it arrives.
+    monitor_input: bool,
+    monitor_processed: bool,
+    /// Keyboard shortcuts changed in settings; actions not listed keep their default key.
+    keymap: BTreeMap<ShortcutAction, String>,
+    /// Show a strip with the whole file's waveform above the zoomable one.
+    waveform_overview: bool,
+    /// Size and colors of exported waveform images.
+    waveform_image_width: u32,
+    waveform_image_height: u32,
+    waveform_image_foreground: String,
+    waveform_image_background: String,
+    /// The voice chain, kept flat in the file alongside everything else.
+    #[serde(flatten)]
+    processing: ProcessingChain,
+    auto_save_recordings: bool,
+    low_latency: bool,
+    /// List recordings in subfolders as well, down to `listing_depth` levels.
+    recursive_listing: bool,
+    /// List newest first under a heading per day, instead of by folder and name.
+    group_by_date: bool,
+    /// Folders recordings were kept in lately, most recent first.
+    recent_folders: Vec<String>,
+    listing_depth: usize,
+    /// Transcribe while recording and save the transcript beside the take.
+    live_captions: bool,
+    /// Name new takes after the first words spoken in them instead of `recording_N`.
+    name_by_speech: bool,
+    /// Program (and arguments, split on spaces unless quoted) run after each take is
+    /// saved, with the take's full path added as the last argument. Empty runs nothing.
+    post_save_command: String,
+    /// Last measured round-trip (output to input) latency. Takes recorded while the
+    /// metronome clicks are moved earlier by it when saved, so they sit on the beat.
+    round_trip_latency_ms: Option<f32>,
+    /// Length of a file preview.
+    preview_secs: f32,
+    /// How far the Left and Right arrow keys jump during playback.
+    skip_back_secs: f32,
+    skip_forward_secs: f32,
+    /// Level files during playback so they all sound about as loud as the reference.
+    auto_level: bool,
+    /// Auto-level reference loudness, in LUFS.
+    auto_level_target_lufs: f32,
+    /// Keep the output stream open between plays, playing silence while idle.
+    keep_output_warm: bool,
+    /// TPDF dither when playing to 8- and 16-bit output formats.
+    output_dither: bool,
+    /// Close an idle warm output stream after this many seconds; 0 keeps it open.
+    warm_output_idle_secs: u32,
+    /// Fade-up at the start of each new output stream, so a device waking up doesn't
+    /// thump; 0 starts at full level.
+    output_start_ramp_ms: u32,
+    /// How playback converts a file to the device's rate (or speed).
+    playback_resample_quality: ResampleQuality,
+    /// Extra audio kept after Stop, on top of the automatic flush delay.
+    stop_tail_ms: u32,
+    /// Audio from before Record was pressed, kept while armed and put at the start
+    /// of the take. 0 turns it off.
+    pre_roll_secs: f32,
+    /// Stop recording before the saved file would grow past this many megabytes
+    /// (10^6 bytes); 0 means no limit.
+    max_file_size_mb: f32,
+    /// Save recordings from 16-bit devices as 16-bit PCM instead of 32-bit float.
+    preserve_source_format: bool,
+    /// Write a Broadcast Wave `bext` chunk with the start time and description.
+    bwf_metadata: bool,
+    /// Note the input processing used (gain, DC filter, trim) in the WAV's INFO comment.
+    processing_note: bool,
+    dark_theme: bool,
+    /// Smallest unit shown on the timer.
+    timer_precision: TimerPrecision,
+    /// Show hours on the timer from the start rather than only past the first hour.
+    timer_always_hours: bool,
+    /// What Space does; R and K always do just recording and just playback.
+    space_action: SpaceAction,
+    /// Another output device that plays along with the default one, e.g. headphones.
+    second_output: Option<String>,
+    /// Audio host (API) to find devices through, e.g. ASIO or JACK; `None` for the
+    /// system default.
+    audio_host: Option<String>,
+    /// Playback volume (0 to 1) on the default output and on the second one.
+    output_volume: f32,
+    second_output_volume: f32,
+    /// Device output channel (0-based) that the file's first channel plays on, for
+    /// routing playback to e.g. outputs 3-4 of a multichannel interface. Channels
+    /// before it and after the file's are silent.
+    output_channel_offset: u16,
+    /// What the input meter shows, and how quickly it rises and falls.
+    meter_mode: MeterMode,
+    meter_attack_ms: f32,
+    meter_fall_db_per_sec: f32,
+    /// Level to aim for, in dB on the meter's scale (so roughly the loudness in RMS
+    /// mode), marked on the meter; `None` for no target.
+    meter_target_db: Option<f32>,
+    /// How far either side of the target still counts as on target, in dB.
+    meter_target_tolerance_db: f32,
+    export: ExportSettings,
+    /// User-defined presets, shown after the built-in ones.
+    export_presets: Vec<ExportPreset>,
+    /// Extra attempts for a file that fails in a batch export.
+    export_retries: u32,
+    /// Pause before the first retry; it doubles for each one after that.
+    export_retry_backoff_ms: u32,
 }
 
-fn list_wav_files() -> Vec<String> {
-    let mut files = Vec::new();
-    if let Ok(entries) = fs::read_dir(".") {
-        for entry in entries.flatten() {
-            if let Some(name) = entry.file_name().to_str() {
-                if name.to_lowercase().ends_with(".wav") {
-                    files.push(name.to_string());
-                }
-            }
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            audio_cues_enabled: false,
+            chapter_min_gap_secs: 2.0,
+            skip_intro_secs: 0.0,
+            resume_last_position: false,
+            resume_over_skip: true,
+            waveform_overview: true,
+            metronome_bpm: 120.0,
+            monitor_input: false,
+            monitor_processed: false,
+            keymap: BTreeMap::new(),
+            waveform_image_width: 1200,
+            waveform_image_height: 200,
+            waveform_image_foreground: "#3070d0".into(),
+            waveform_image_background: "#ffffff".into(),
+            processing: ProcessingChain::default(),
+            auto_save_recordings: false,
+            low_latency: false,
+            recursive_listing: false,
+            group_by_date: false,
+            recent_folders: Vec::new(),
+            listing_depth: 3,
+            live_captions: false,
+            name_by_speech: false,
+            post_save_command: String::new(),
+            round_trip_latency_ms: None,
+            preview_secs: 5.0,
+            skip_back_secs: 5.0,
+            skip_forward_secs: 10.0,
+            auto_level: false,
+            auto_level_target_lufs: -20.0,
+            keep_output_warm: false,
+            output_dither: true,
+            warm_output_idle_secs: 60,
+            output_start_ramp_ms: 20,
+            playback_resample_quality: ResampleQuality::Fast,
+            stop_tail_ms: 0,
+            pre_roll_secs: 0.0,
+            max_file_size_mb: 0.0,
+            preserve_source_format: true,
+            bwf_metadata: false,
+            processing_note: true,
+            dark_theme: true,
+            timer_precision: TimerPrecision::Hundredths,
+            timer_always_hours: false,
+            second_output: None,
+            audio_host: None,
+            output_volume: 1.0,
+            second_output_volume: 1.0,
+            output_channel_offset: 0,
+            meter_mode: MeterMode::Peak,
+            meter_attack_ms: 0.0,
+            meter_fall_db_per_sec: 60.0,
+            meter_target_db: None,
+            meter_target_tolerance_db: 3.0,
+            space_action: SpaceAction::Context,
+            export: ExportSettings::default(),
+            export_presets: Vec::new(),
+            export_retries: 2,
+            export_retry_backoff_ms: 500,
         }
     }
-    files.sort();
-    files
-}
-
-#[derive(Debug, Clone)]
-enum Message {
-    StartRecording,
-    StopRecording,
-    PlayFile(String),
-    PausePlayback,
-    ResumePlayback,
-    StopPlayback,
-    DeleteFile(String),
-    StartRename(String),
-    UpdateRenameName(String),
-    ConfirmRename,
-    CancelRename,
-    Tick(Instant),
-    Toggle,
-    Reset,
-    FinalizeRecording,
-}
-
-#[derive(Debug, Clone, PartialEq)]
-enum PlaybackState {
-    Stopped,
-    Playing,
-    Paused,
 }
 
-struct VoiceRecorder {
-    is_recording: bool,
-    playback_state: PlaybackState,
-    currently_playing_file: Option<String>,
-    status_message: String,
-    files: Vec<String>,
-    audio_data: Arc<Mutex<Vec<f32>>>,
-    input_stream: Option<Stream>,
-    output_stream: Option<Stream>,
-    playback_status_tx: mpsc::Sender<()>,
-    playback_status_rx: mpsc::Receiver<()>,
-    start_time: Option<Instant>,
-    elapsed_time: Duration,
-    stopping_time: Option<Instant>,
-    recording_sample_rate: u32,
-    recording_channels: u16,
-    renaming_file: Option<String>,
-    new_name: String,
-    // For pause/resume functionality
-    playback_samples: Arc<Mutex<Vec<f32>>>,
-    playback_position: Arc<Mutex<usize>>,
-    is_stream_paused: Arc<Mutex<bool>>,
+/// Processing applied on the way in and on save. Restored at startup, and reset as a
+/// whole from Settings.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+struct ProcessingChain {
+    dc_block_enabled: bool,
+    /// Per-channel input gain, in dB. Channels past the end get 0 dB.
+    input_gains_db: Vec<f32>,
+    auto_trim_on_save: bool,
+    /// Level below which leading/trailing audio counts as silence, in dBFS.
+    trim_threshold_db: f32,
+    /// Audio kept either side of the trimmed region so word edges aren't cut.
+    trim_padding_ms: u32,
+    /// Loudness the Normalize action aims for, in LUFS.
+    normalize_target_lufs: f32,
 }
 
-impl Default for VoiceRecorder {
+impl Default for ProcessingChain {
     fn default() -> Self {
-        let (tx, rx) = mpsc::channel();
         Self {
-            is_recording: false,
-            playback_state: PlaybackState::Stopped,
-            currently_playing_file: None,
-            status_message: "Ready to record.".into(),
-            files: list_wav_files(),
-            audio_data: Arc::new(Mutex::new(Vec::new())),
-            input_stream: None,
-            output_stream: None,
-            playback_status_tx: tx,
-            playback_status_rx: rx,
-            start_time: None,
-            elapsed_time: Duration::from_secs(0),
-            stopping_time: None,
-            recording_sample_rate: 48000,
-            recording_channels: 1,
-            renaming_file: None,
-            new_name: String::new(),
-            playback_samples: Arc::new(Mutex::new(Vec::new())),
-            playback_position: Arc::new(Mutex::new(0)),
-            is_stream_paused: Arc::new(Mutex::new(false)),
+            dc_block_enabled: false,
+            input_gains_db: Vec::new(),
+            auto_trim_on_save: false,
+            trim_threshold_db: -34.0,
+            trim_padding_ms: 750,
+            normalize_target_lufs: -16.0,
         }
     }
 }
 
-impl VoiceRecorder {
-    fn start_recording_impl(&mut self) {
-        if self.is_recording || self.playback_state != PlaybackState::Stopped {
-            return;
-        }
-
-        self.audio_data.lock().unwrap().clear();
-        let host = cpal::default_host();
+impl ProcessingChain {
+    /// Linear gains for `channels` input channels.
+    fn input_gains(&self, channels: usize) -> Vec<f32> {
+        (0..channels)
+            .map(|ch| {
+                self.input_gains_db
+                    .get(ch)
+                    .map_or(1.0, |&db| db_to_amplitude(db))
+            })
+            .collect()
+    }
+}
 
-        let device = match host.default_input_device() {
-            Some(d) => d,
-            None => {
-                self.status_message = "No input device found.".into();
-                return;
-            }
-        };
+impl Config {
+    /// How deep `list_wav_files` looks.
+    fn listing_depth(&self) -> Option<usize> {
+        self.recursive_listing.then_some(self.listing_depth)
+    }
 
-        let default_config = match device.default_input_config() {
-            Ok(c) => c,
-            Err(e) => {
-                self.status_message = format!("Failed to get default input config: {}", e);
-                return;
-            }
+    /// The saved settings, or the defaults if none have been saved yet. A file that
+    /// can't be read or parsed is an error, not a quiet reset, so it isn't saved over.
+    fn load() -> Result<Self, String> {
+        let json = match fs::read_to_string(CONFIG_PATH) {
+            Ok(json) => json,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e.to_string()),
         };
+        serde_json::from_str(&json).map_err(|e| e.to_string())
+    }
 
-        let preferred_sample_rate = cpal::SampleRate(48000);
-        let config = if default_config.sample_rate() <= preferred_sample_rate
-            && preferred_sample_rate <= default_config.sample_rate()
-        {
-            StreamConfig {
-                channels: default_config.channels(),
-                sample_rate: preferred_sample_rate,
-                buffer_size: BufferSize::Fixed(1024),
-            }
-        } else {
-            StreamConfig {
-                channels: default_config.channels(),
-                sample_rate: default_config.sample_rate(),
-                buffer_size: BufferSize::Fixed(1024),
+    /// Puts the default back for any number a hand edit has left out of range
+    /// (negative, NaN, infinite and so on). Returns the settings it reset.
+    fn validate(&mut self) -> Vec<&'static str> {
+        fn check(
+            reset: &mut Vec<&'static str>,
+            name: &'static str,
+            value: &mut f32,
+            default: f32,
+            valid: impl Fn(f32) -> bool,
+        ) {
+            if !value.is_finite() || !valid(*value) {
+                *value = default;
+                reset.push(name);
             }
-        };
-
-        self.recording_sample_rate = config.sample_rate.0;
-        self.recording_channels = config.channels as u16;
-
-        println!(
-            "Recording with: channels={}, sample_rate={}, format={:?}",
-            config.channels,
-            config.sample_rate.0,
-            default_config.sample_format()
-        );
-
-        let audio_buf = Arc::clone(&self.audio_data);
+        }
 
-        let build_result = match default_config.sample_format() {
-            SampleFormat::F32 => device.build_input_stream(
-                &config,
-                move |data: &[f32], _| {
-                    let mut buf = audio_buf.lock().unwrap();
-                    buf.extend_from_slice(data);
-                },
-                move |err| {
-                    eprintln!("Input stream error: {}", err);
-                },
-                None,
+        let defaults = Self::default();
+        let mut reset = Vec::new();
+        let positive: fn(f32) -> bool = |v| v > 0.0;
+        let not_negative: fn(f32) -> bool = |v| v >= 0.0;
+        let lufs: fn(f32) -> bool = |v| (-70.0..0.0).contains(&v);
+        let volume: fn(f32) -> bool = |v| (0.0..=1.0).contains(&v);
+        let skip: fn(f32) -> bool = |v| v > 0.0 && v <= MAX_SKIP_SECS;
+        let floats = [
+            (
+                "chapter_min_gap_secs",
+                &mut self.chapter_min_gap_secs,
+                defaults.chapter_min_gap_secs,
+                positive,
             ),
-            SampleFormat::I16 => {
-                let audio_buf = Arc::clone(&self.audio_data);
-                device.build_input_stream(
-                    &config,
-                    move |data: &[i16], _| {
-                        let mut buf = audio_buf.lock().unwrap();
-                        buf.extend(data.iter().map(|&s| (s as f32) / (i16::MAX as f32)));
-                    },
-                    move |err| {
-                        eprintln!("Input stream error: {}", err);
-                    },
-                    None,
-                )
-            }
-            SampleFormat::U16 => {
-                let audio_buf = Arc::clone(&self.audio_data);
-                device.build_input_stream(
-                    &config,
-                    move |data: &[u16], _| {
-                        let mut buf = audio_buf.lock().unwrap();
-                        buf.extend(
-                            data.iter()
-                                .map(|&s| (s as f32) / (u16::MAX as f32) * 2.0 - 1.0),
-                        );
-                    },
-                    move |err| {
-                        eprintln!("Input stream error: {}", err);
-                    },
-                    None,
-                )
-            }
-            _ => {
-                self.status_message = "Unsupported input sample format".into();
-                return;
-            }
-        };
+            (
+                "skip_intro_secs",
+                &mut self.skip_intro_secs,
+                defaults.skip_intro_secs,
+                not_negative,
+            ),
+            (
+                "preview_secs",
+                &mut self.preview_secs,
+                defaults.preview_secs,
+                |v| v > 0.0 && v <= MAX_PREVIEW_SECS,
+            ),
+            (
+                "skip_back_secs",
+                &mut self.skip_back_secs,
+                defaults.skip_back_secs,
+                skip,
+            ),
+            (
+                "skip_forward_secs",
+                &mut self.skip_forward_secs,
+                defaults.skip_forward_secs,
+                skip,
+            ),
+            (
+                "pre_roll_secs",
+                &mut self.pre_roll_secs,
+                defaults.pre_roll_secs,
+                |v| (0.0..=MAX_PRE_ROLL_SECS).contains(&v),
+            ),
+            (
+                "max_file_size_mb",
+                &mut self.max_file_size_mb,
+                defaults.max_file_size_mb,
+                not_negative,
+            ),
+            (
+                "metronome_bpm",
+                &mut self.metronome_bpm,
+                defaults.metronome_bpm,
+                |v| (MIN_METRONOME_BPM..=MAX_METRONOME_BPM).contains(&v),
+            ),
+            (
+                "auto_level_target_lufs",
+                &mut self.auto_level_target_lufs,
+                defaults.auto_level_target_lufs,
+                lufs,
+            ),
+            (
+                "output_volume",
+                &mut self.output_volume,
+                defaults.output_volume,
+                volume,
+            ),
+            (
+                "second_output_volume",
+                &mut self.second_output_volume,
+                defaults.second_output_volume,
+                volume,
+            ),
+            (
+                "meter_attack_ms",
+                &mut self.meter_attack_ms,
+                defaults.meter_attack_ms,
+                not_negative,
+            ),
+            (
+                "meter_fall_db_per_sec",
+                &mut self.meter_fall_db_per_sec,
+                defaults.meter_fall_db_per_sec,
+                positive,
+            ),
+            (
+                "meter_target_tolerance_db",
+                &mut self.meter_target_tolerance_db,
+                defaults.meter_target_tolerance_db,
+                |v| v > 0.0 && v <= MAX_METER_TOLERANCE_DB,
+            ),
+            (
+                "trim_threshold_db",
+                &mut self.processing.trim_threshold_db,
+                defaults.processing.trim_threshold_db,
+                |v| (-96.0..0.0).contains(&v),
+            ),
+            (
+                "normalize_target_lufs",
+                &mut self.processing.normalize_target_lufs,
+                defaults.processing.normalize_target_lufs,
+                lufs,
+            ),
+        ];
+        for (name, value, default, valid) in floats {
+            check(&mut reset, name, value, default, valid);
+        }
 
-        match build_result {
-            Ok(stream) => {
-                if let Err(e) = stream.play() {
-                    self.status_message = format!("Failed to start input stream: {}", e);
-                    return;
-                }
-                self.input_stream = Some(stream);
-                self.is_recording = true;
-                self.status_message = "Recording...".into();
-                self.start_time = Some(Instant::now());
-                self.elapsed_time = Duration::from_secs(0);
-                self.stopping_time = None;
-            }
-            Err(e) => {
-                self.status_message = format!("Failed to build input stream: {}", e);
+        if self
+            .meter_target_db
+            .is_some_and(|db| !db.is_finite() || db > 0.0)
+        {
+            self.meter_target_db = None;
+            reset.push("meter_target_db");
+        }
+        if self
+            .round_trip_latency_ms
+            .is_some_and(|ms| !ms.is_finite() || ms < 0.0)
+        {
+            self.round_trip_latency_ms = None;
+            reset.push("round_trip_latency_ms");
+        }
+        if let Some(target) = self.export.normalize_lufs
+            && (!target.is_finite() || !lufs(target))
+        {
+            self.export.normalize_lufs = defaults.export.normalize_lufs;
+            reset.push("export.normalize_lufs");
+        }
+        for gain in &mut self.processing.input_gains_db {
+            if !gain.is_finite() || gain.abs() > INPUT_GAIN_RANGE_DB {
+                *gain = if gain.is_nan() {
+                    0.0
+                } else {
+                    gain.clamp(-INPUT_GAIN_RANGE_DB, INPUT_GAIN_RANGE_DB)
+                };
+                reset.push("input_gains_db");
             }
         }
+        if self.waveform_image_width == 0 || self.waveform_image_height == 0 {
+            self.waveform_image_width = defaults.waveform_image_width;
+            self.waveform_image_height = defaults.waveform_image_height;
+            reset.push("waveform image size");
+        }
+        reset.dedup();
+        reset
     }
 
-    fn stop_recording_impl(&mut self) {
-        if !self.is_recording {
-            return;
-        }
+    fn save(&self, path: &std::path::Path) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        fs::write(path, json)
+    }
+}
 
-        self.is_recording = false;
-        self.start_time = None;
-        self.stopping_time = Some(Instant::now());
-        self.status_message = "Stopping recording...".into();
+fn write_wav_file_f32(path: &str, spec: WavSpec, samples: &[f32]) -> io::Result<()> {
+    let mut writer = hound::WavWriter::create(path, spec).map_err(io::Error::other)?;
+    for &s in samples {
+        writer.write_sample(s).map_err(io::Error::other)?;
+    }
+    writer.finalize().map_err(io::Error::other)?;
+    Ok(())
+}
+
+fn write_wav_file_i16(path: &str, spec: WavSpec, samples: &[i16]) -> io::Result<()> {
+    let mut writer = hound::WavWriter::create(path, spec).map_err(io::Error::other)?;
+    for &s in samples {
+        writer.write_sample(s).map_err(io::Error::other)?;
     }
+    writer.finalize().map_err(io::Error::other)?;
+    Ok(())
+}
 
-    fn finalize_recording(&mut self) {
-        self.input_stream = None;
-        self.stopping_time = None;
+/// Writes `samples` to `path` with `chunks` (say `bext` or `cue `) already in
+/// place, so the file is written once rather than saved and then rewritten.
+fn write_wav_file_with_chunks<S: hound::Sample + Copy>(
+    path: &str,
+    spec: WavSpec,
+    samples: &[S],
+    chunks: &[([u8; 4], Vec<u8>)],
+) -> io::Result<()> {
+    let mut bytes = io::Cursor::new(Vec::new());
+    let mut writer = hound::WavWriter::new(&mut bytes, spec).map_err(io::Error::other)?;
+    for &s in samples {
+        writer.write_sample(s).map_err(io::Error::other)?;
+    }
+    writer.finalize().map_err(io::Error::other)?;
+    fs::write(path, bwf::with_chunks(bytes.get_ref(), chunks)?)
+}
 
-        let filename = format!("recording_{}.wav", self.files.len() + 1);
-        let samples: Vec<f32> = std::mem::take(&mut *self.audio_data.lock().unwrap());
+/// A take left in the crash journal (`RECOVERY_PATH`) by a crash.
+#[derive(Debug, Clone, PartialEq)]
+enum InterruptedTake {
+    /// Seconds of audio the journal holds.
+    Readable(f64),
+    /// The journal is there but couldn't be read, perhaps only for now (locked, or
+    /// without permission). It's kept until the user discards it.
+    Unreadable(String),
+}
 
-        if samples.is_empty() {
-            self.status_message = "Error saving file: No audio data captured".into();
-            return;
+fn find_interrupted_take(journal: &str) -> Option<InterruptedTake> {
+    if !std::path::Path::new(journal).exists() {
+        return None;
+    }
+    Some(match read_wav_recoverable(journal) {
+        Ok((spec, samples, _)) => InterruptedTake::Readable(
+            (samples.len() / spec.channels.max(1) as usize) as f64 / spec.sample_rate.max(1) as f64,
+        ),
+        Err(e) => InterruptedTake::Unreadable(e),
+    })
+}
+
+/// Rewrites `path` with `samples`, keeping 16-bit integer files as 16-bit and storing
+/// everything else as 32-bit float. Writes to a temporary file first and renames it
+/// over the original so a failure never leaves a half-written recording.
+/// Any `bext`, `LIST` and `cue ` chunks in the original are carried over.
+fn rewrite_wav_file(path: &str, spec: WavSpec, samples: &[f32]) -> io::Result<()> {
+    let metadata = bwf::read_chunks(path, &[bwf::BEXT, bwf::LIST, bwf::CUE]).unwrap_or_default();
+    let tmp_path = format!("{}.tmp", path);
+    match write_wav_file_like(&tmp_path, spec, samples) {
+        Ok(()) => fs::rename(&tmp_path, path)?,
+        Err(e) => {
+            let _ = fs::remove_file(&tmp_path);
+            return Err(e);
         }
+    }
+    if !metadata.is_empty() {
+        bwf::write_chunks(path, &metadata)?;
+    }
+    Ok(())
+}
 
-        let spec = WavSpec {
-            channels: self.recording_channels,
-            sample_rate: self.recording_sample_rate,
+/// Writes `samples` as 16-bit PCM if the source `spec` is 16-bit integer, otherwise
+/// as 32-bit float.
+fn write_wav_file_like(path: &str, spec: WavSpec, samples: &[f32]) -> io::Result<()> {
+    if spec.sample_format == hound::SampleFormat::Int && spec.bits_per_sample == 16 {
+        let converted: Vec<i16> = samples
+            .iter()
+            .map(|&s| (s.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16)
+            .collect();
+        write_wav_file_i16(path, spec, &converted)
+    } else {
+        let float_spec = WavSpec {
             bits_per_sample: 32,
             sample_format: hound::SampleFormat::Float,
+            ..spec
         };
+        write_wav_file_f32(path, float_spec, samples)
+    }
+}
 
-        println!(
-            "Saving WAV file with: channels={}, sample_rate={}, bits_per_sample={}, format=F32",
-            spec.channels, spec.sample_rate, spec.bits_per_sample
-        );
+/// Inverse of the backend's i16 input conversion (`s / i16::MAX`), so 16-bit device
+/// samples come back out bit-exact.
+fn input_f32_to_i16(sample: f32) -> i16 {
+    (sample * i16::MAX as f32)
+        .round()
+        .clamp(i16::MIN as f32, i16::MAX as f32) as i16
+}
 
-        match write_wav_file_f32(&filename, spec, &samples) {
-            Ok(()) => {
-                self.status_message = format!("Recording saved as '{}'", filename);
-                self.files = list_wav_files();
-            }
-            Err(e) => {
-                self.status_message = format!("Error saving file: {}", e);
-            }
-        }
-    }
+/// WAV files in the working directory, or with `max_depth` in its subfolders too, as
+/// paths relative to it. Sorted by folder and then name, top level first.
+fn list_wav_files(max_depth: Option<usize>) -> Vec<String> {
+    let mut files: Vec<String> = walkdir::WalkDir::new(".")
+        .min_depth(1)
+        .max_depth(max_depth.map_or(1, |depth| depth + 1))
+        .into_iter()
+        .filter_entry(|entry| entry.file_name() != UNDO_DIR)
+        .flatten()
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| {
+            let path = entry
+                .path()
+                .strip_prefix(".")
+                .ok()?
+                .to_str()?
+                .replace('\\', "/");
+            path.to_lowercase().ends_with(".wav").then_some(path)
+        })
+        .collect();
+    files.sort_by(|a, b| (file_folder(a), a).cmp(&(file_folder(b), b)));
+    files
+}
 
-    fn start_rename_impl(&mut self, filename: &str) {
-        // Can't rename while playing or recording
-        if self.is_recording || self.playback_state != PlaybackState::Stopped {
-            return;
-        }
+/// How far local time is ahead of UTC at `secs` (a Unix timestamp), in seconds.
+/// Assumes UTC if the system can't say.
+fn local_offset_secs(secs: u64) -> i64 {
+    let time = secs as libc::time_t;
+    // SAFETY: `tm` is plain data, and both calls only write to it
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    #[cfg(unix)]
+    let found = !unsafe { libc::localtime_r(&time, &mut tm) }.is_null();
+    #[cfg(windows)]
+    let found = unsafe { libc::localtime_s(&mut tm, &time) } == 0;
+    if !found {
+        return 0;
+    }
+    // Days-from-civil, the inverse of `bwf::utc_date_time`
+    let (year, month) = (tm.tm_year as i64 + 1900, tm.tm_mon as i64 + 1);
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + tm.tm_mday as i64 - 1;
+    let days = era * 146_097 + yoe * 365 + yoe / 4 - yoe / 100 + doy - 719_468;
+    let local = days * 86_400 + tm.tm_hour as i64 * 3600 + tm.tm_min as i64 * 60 + tm.tm_sec as i64;
+    local - secs as i64
+}
 
-        self.renaming_file = Some(filename.to_string());
-        let name_without_ext = filename.strip_suffix(".wav").unwrap_or(filename);
-        self.new_name = name_without_ext.to_string();
+/// The heading a file last modified at `modified` is listed under: "Today",
+/// "Yesterday", or its date. Days run midnight to midnight local time.
+fn date_heading(modified: SystemTime, now: SystemTime) -> String {
+    let local_secs = |t: SystemTime| {
+        let secs = t
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        (secs as i64 + local_offset_secs(secs)).max(0) as u64
+    };
+    let (modified, now) = (local_secs(modified), local_secs(now));
+    match (now / 86_400).checked_sub(modified / 86_400) {
+        Some(0) => "Today".into(),
+        Some(1) => "Yesterday".into(),
+        _ => bwf::utc_date_time(modified).0,
     }
+}
 
-    fn confirm_rename_impl(&mut self) {
-        if let Some(old_name) = &self.renaming_file {
-            let mut new_filename = self.new_name.trim().to_string();
-            if new_filename.is_empty() {
-                self.status_message = "Filename cannot be empty.".into();
-                return;
-            }
+/// Files a batch deletes or replaces are moved here, into a folder per batch, so the
+/// batch can be undone. They're deleted for good when the batch drops out of the
+/// undo history, another folder is opened or the app closes (or, after a crash, the
+/// next time it starts).
+const UNDO_DIR: &str = ".rust_voice_undo";
+const UNDO_HISTORY_LEN: usize = 10;
 
-            if !new_filename.to_lowercase().ends_with(".wav") {
-                new_filename.push_str(".wav");
-            }
+/// An operation on many files, confirmed as a whole before it runs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BatchKind {
+    Rename,
+    DeleteDuplicates,
+    ExportAll,
+}
 
-            if new_filename != *old_name && std::path::Path::new(&new_filename).exists() {
-                self.status_message = "File with that name already exists.".into();
-                return;
-            }
+/// How to revert a batch.
+#[derive(Debug, Clone)]
+enum BatchUndo {
+    /// Recordings renamed, as (old, new); their sidecars and notes went with them.
+    Renamed(Vec<(String, String)>),
+    /// Recordings moved into the holding folder `held`, with the notes they had.
+    Deleted {
+        held: String,
+        files: Vec<(String, Option<FileNotes>)>,
+    },
+    /// Exports the batch writes, and earlier exports it replaced, moved into `held`.
+    Exported {
+        held: String,
+        outputs: Vec<String>,
+        replaced: Vec<String>,
+    },
+}
 
-            match std::fs::rename(old_name, &new_filename) {
-                Ok(()) => {
-                    self.status_message = format!("Renamed '{}' to '{}'", old_name, new_filename);
-                    self.files = list_wav_files();
-                    self.renaming_file = None;
-                    self.new_name.clear();
-                }
-                Err(e) => {
-                    self.status_message = format!("Error renaming file: {}", e);
-                }
-            }
-        }
+#[derive(Debug, Clone)]
+struct UndoEntry {
+    /// What the batch did, e.g. "rename of 12 files".
+    label: String,
+    undo: BatchUndo,
+}
+
+/// Moves `path` under the holding folder `held`, keeping its relative path.
+fn hold_file(held: &str, path: &str) -> io::Result<()> {
+    let dest = std::path::Path::new(held).join(path);
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
     }
+    fs::rename(path, dest)
+}
 
-    fn cancel_rename_impl(&mut self) {
-        self.renaming_file = None;
-        self.new_name.clear();
-        self.status_message = "Rename cancelled.".into();
+fn unhold_file(held: &str, path: &str) -> io::Result<()> {
+    fs::rename(std::path::Path::new(held).join(path), path)
+}
+
+/// The first of `paths` that something is already at.
+fn first_existing<'a>(mut paths: impl Iterator<Item = &'a String>) -> Option<&'a String> {
+    paths.find(|path| std::path::Path::new(path.as_str()).exists())
+}
+
+/// Deletes the files held for `entry`, so it can no longer be undone.
+fn discard_held_files(entry: &UndoEntry) {
+    if let BatchUndo::Deleted { held, .. } | BatchUndo::Exported { held, .. } = &entry.undo {
+        let _ = fs::remove_dir_all(held);
     }
+}
 
-    fn play_file_impl(&mut self, filename: &str) {
-        if self.playback_state != PlaybackState::Stopped || self.is_recording {
-            return;
-        }
+/// Folder part of a listed file, empty at the top level.
+fn file_folder(path: &str) -> &str {
+    path.rsplit_once('/').map_or("", |(folder, _)| folder)
+}
 
-        self.stop_playback_impl();
+/// First free name for a trimmed copy of `path`: `take_trim.wav`, then
+/// `take_trim_2.wav` and so on, in the same folder.
+fn trimmed_copy_name(path: &str) -> String {
+    let stem = sidecar_path(path, "");
+    (1..)
+        .map(|n| match n {
+            1 => format!("{}_trim.wav", stem),
+            n => format!("{}_trim_{}.wav", stem, n),
+        })
+        .find(|name| !std::path::Path::new(name).exists())
+        .unwrap()
+}
 
-        let reader = match WavReader::open(filename) {
-            Ok(r) => r,
-            Err(e) => {
-                self.status_message = format!("Error opening file: {}", e);
-                return;
-            }
-        };
+/// Brings a WAV from outside the recordings folder in for editing, returning its
+/// name in the folder, whether it was converted, and its audio (read just the once).
+/// Bit depths the editor can't read (8 or 24-bit) come in as 32-bit float, keeping
+/// their cue points; anything else is copied as is. A file already in the folder is
+/// used where it is.
+fn import_for_editing(source: &str) -> Result<(String, bool, WavSpec, Vec<f32>), String> {
+    let source_path = std::path::Path::new(source);
+    let full = fs::canonicalize(source_path).map_err(|e| format!("Error opening file: {}", e))?;
+    if let Ok(folder) = fs::canonicalize(".")
+        && let Ok(inside) = full.strip_prefix(&folder)
+        && let Some(name) = inside.to_str()
+    {
+        let name = name.replace('\\', "/");
+        let (spec, samples) = read_wav_file(&name)?;
+        return Ok((name, false, spec, samples));
+    }
 
-        let spec = reader.spec();
-        println!(
-            "File spec: channels={}, sample_rate={}, bits_per_sample={}",
-            spec.channels, spec.sample_rate, spec.bits_per_sample
-        );
+    let reader = WavReader::open(source).map_err(|e| format!("Error opening file: {}", e))?;
+    let spec = reader.spec();
+    let stem = source_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("imported");
+    let target = (1..)
+        .map(|n| match n {
+            1 => format!("{}.wav", stem),
+            n => format!("{}_{}.wav", stem, n),
+        })
+        .find(|name| !std::path::Path::new(name).exists())
+        .unwrap();
 
-        let samples = match spec.sample_format {
-            hound::SampleFormat::Float => {
-                match reader
-                    .into_samples::<f32>()
-                    .collect::<Result<Vec<f32>, _>>()
-                {
-                    Ok(s) => s,
-                    Err(e) => {
-                        self.status_message = format!("Error reading float samples: {}", e);
-                        return;
-                    }
-                }
-            }
-            hound::SampleFormat::Int => match spec.bits_per_sample {
-                16 => {
-                    match reader
-                        .into_samples::<i16>()
-                        .collect::<Result<Vec<i16>, _>>()
-                    {
-                        Ok(samples_i16) => samples_i16
-                            .into_iter()
-                            .map(|s| s as f32 / i16::MAX as f32)
-                            .collect(),
-                        Err(e) => {
-                            self.status_message = format!("Error reading i16 samples: {}", e);
-                            return;
-                        }
-                    }
-                }
-                32 => {
-                    match reader
-                        .into_samples::<i32>()
-                        .collect::<Result<Vec<i32>, _>>()
-                    {
-                        Ok(samples_i32) => samples_i32
-                            .into_iter()
-                            .map(|s| s as f32 / i32::MAX as f32)
-                            .collect(),
-                        Err(e) => {
-                            self.status_message = format!("Error reading i32 samples: {}", e);
-                            return;
-                        }
-                    }
-                }
-                _ => {
-                    self.status_message =
-                        format!("Unsupported bit depth: {}", spec.bits_per_sample);
-                    return;
-                }
-            },
-        };
-
-        if samples.is_empty() {
-            self.status_message = "File contains no samples.".into();
-            return;
-        }
+    if spec.sample_format == hound::SampleFormat::Float || matches!(spec.bits_per_sample, 16 | 32) {
+        fs::copy(source, &target).map_err(|e| format!("Error copying file: {}", e))?;
+        let (spec, samples) = read_wav_file(&target)?;
+        return Ok((target, false, spec, samples));
+    }
+    let scale = ((1i64 << (spec.bits_per_sample.clamp(1, 32) - 1)) - 1) as f32;
+    let samples = reader
+        .into_samples::<i32>()
+        .map(|s| s.map(|s| s as f32 / scale))
+        .collect::<Result<Vec<f32>, _>>()
+        .map_err(|e| format!("Error reading samples: {}", e))?;
+    let float_spec = WavSpec {
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+        ..spec
+    };
+    write_wav_file_f32(&target, float_spec, &samples)
+        .map_err(|e| format!("Error writing '{}': {}", target, e))?;
+    if let Some(marks) = load_cue_marks(source) {
+        save_marks(&target, &marks).map_err(|e| format!("Error saving cue points: {}", e))?;
+    }
+    Ok((target, true, float_spec, samples))
+}
 
-        // Store samples for pause/resume functionality
-        *self.playback_samples.lock().unwrap() = samples;
-        *self.playback_position.lock().unwrap() = 0;
-        *self.is_stream_paused.lock().unwrap() = false;
+/// Where renaming `old_name` to `new_name` (as typed) puts it: `.wav` is added if
+/// missing, and a bare name stays in the file's folder.
+fn rename_target(old_name: &str, new_name: &str) -> Result<String, String> {
+    let mut new_filename = new_name.trim().to_string();
+    if new_filename.is_empty() {
+        return Err("Filename cannot be empty.".into());
+    }
 
-        let samples_arc = Arc::clone(&self.playback_samples);
-        let position_arc = Arc::clone(&self.playback_position);
-        let paused_arc = Arc::clone(&self.is_stream_paused);
-        let play_tx = self.playback_status_tx.clone();
+    if !new_filename.to_lowercase().ends_with(".wav") {
+        new_filename.push_str(".wav");
+    }
+    let folder = file_folder(old_name);
+    if !folder.is_empty() && !new_filename.contains('/') {
+        new_filename = format!("{}/{}", folder, new_filename);
+    }
+    Ok(new_filename)
+}
 
-        let host = cpal::default_host();
-        let device = match host.default_output_device() {
-            Some(d) => d,
-            None => {
-                self.status_message = "Failed to find default output device".into();
-                return;
-            }
+/// The name for file number `index` from a batch rename template: `{n}` is the
+/// number, and `{n:3}` the number zero-padded to three digits.
+fn expand_rename_template(template: &str, index: usize) -> Result<String, String> {
+    let mut name = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{n") {
+        name.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after
+            .find('}')
+            .ok_or_else(|| "Unclosed { in the template.".to_string())?;
+        let spec = &after[..end];
+        let width = if spec.is_empty() {
+            0
+        } else {
+            spec.strip_prefix(':')
+                .and_then(|w| w.parse::<usize>().ok())
+                .ok_or_else(|| {
+                    format!("Unknown placeholder {{n{}}}; use {{n}} or {{n:3}}.", spec)
+                })?
         };
+        name.push_str(&format!("{:0width$}", index, width = width));
+        rest = &after[end + 1..];
+    }
+    name.push_str(rest);
+    Ok(name)
+}
 
-        let supported_cfgs = match device.supported_output_configs() {
-            Ok(v) => {
-                let configs: Vec<_> = v.collect();
-                println!("Supported output configs:");
-                for cfg in &configs {
-                    println!(
-                        "  Channels: {}, Sample rate range: {}-{}, Format: {:?}",
-                        cfg.channels(),
-                        cfg.min_sample_rate().0,
-                        cfg.max_sample_rate().0,
-                        cfg.sample_format()
-                    );
-                }
-                configs
-            }
-            Err(e) => {
-                self.status_message = format!("Error querying output configs: {}", e);
-                return;
-            }
+/// Renames every `(from, to)` pair, passing through temporary names so files can
+/// take each other's names. If any rename fails, the ones already made are undone
+/// and the error returned.
+fn rename_all(pairs: &[(String, String)]) -> io::Result<()> {
+    let temporary: Vec<String> = pairs
+        .iter()
+        .map(|(from, _)| format!("{}.batch-rename", from))
+        .collect();
+    let moves = pairs
+        .iter()
+        .zip(&temporary)
+        .map(|((from, _), temp)| (from.as_str(), temp.as_str()))
+        .chain(
+            pairs
+                .iter()
+                .zip(&temporary)
+                .map(|((_, to), temp)| (temp.as_str(), to.as_str())),
+        );
+    let mut done: Vec<(&str, &str)> = Vec::new();
+    for (from, to) in moves {
+        let result = if std::path::Path::new(to).exists() {
+            Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("'{}' already exists", to),
+            ))
+        } else {
+            fs::rename(from, to)
         };
+        if let Err(e) = result {
+            for (from, to) in done.into_iter().rev() {
+                let _ = fs::rename(to, from);
+            }
+            return Err(e);
+        }
+        done.push((from, to));
+    }
+    Ok(())
+}
 
-        let matched = supported_cfgs
-            .into_iter()
-            .filter(|c| c.channels() == spec.channels as u16)
-            .min_by_key(|c| {
-                let format_priority = match c.sample_format() {
-                    SampleFormat::F32 => 0,
-                    SampleFormat::I16 => 1,
-                    SampleFormat::I32 => 2,
-                    SampleFormat::U16 => 3,
-                    SampleFormat::U8 => 100,
-                    _ => 50,
-                };
-                let rate_diff = ((c.max_sample_rate().0 as i64) - (spec.sample_rate as i64)).abs();
-                (format_priority, rate_diff)
-            });
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct FileNotes {
+    notes: String,
+    tags: Vec<String>,
+    label: Option<ColorLabel>,
+}
 
-        let chosen = match matched {
-            Some(c) => {
-                let sample_rate = if spec.sample_rate >= c.min_sample_rate().0
-                    && spec.sample_rate <= c.max_sample_rate().0
-                {
-                    cpal::SampleRate(spec.sample_rate)
-                } else {
-                    c.max_sample_rate()
-                };
-                c.with_sample_rate(sample_rate)
-            }
-            None => {
-                let fallback = device
-                    .supported_output_configs()
-                    .ok()
-                    .and_then(|mut configs| configs.next());
-
-                match fallback {
-                    Some(c) => {
-                        self.status_message = format!(
-                            "Using fallback config (channels: {} -> {})",
-                            spec.channels,
-                            c.channels()
-                        );
-                        c.with_sample_rate(c.max_sample_rate())
-                    }
-                    None => {
-                        self.status_message = "No compatible output configuration found.".into();
-                        return;
-                    }
-                }
-            }
-        };
+impl FileNotes {
+    fn is_empty(&self) -> bool {
+        self.notes.is_empty() && self.tags.is_empty() && self.label.is_none()
+    }
+
+    /// `label:<color>` only matches that label; anything else is looked for in the
+    /// notes, tags and label name.
+    fn matches(&self, query: &str) -> bool {
+        if let Some(color) = query.strip_prefix("label:") {
+            return self
+                .label
+                .is_some_and(|label| label.to_string().to_lowercase() == color.trim());
+        }
+        self.notes.to_lowercase().contains(query)
+            || self.tags.iter().any(|t| t.to_lowercase().contains(query))
+            || self
+                .label
+                .is_some_and(|label| label.to_string().to_lowercase().contains(query))
+    }
+}
 
-        let sample_format = chosen.sample_format();
-        let stream_config: StreamConfig = chosen.into();
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum ColorLabel {
+    Red,
+    Orange,
+    Yellow,
+    Green,
+    Blue,
+    Purple,
+}
 
-        println!(
-            "File sample rate: {}, Device will use: {}",
-            spec.sample_rate, stream_config.sample_rate.0
-        );
+impl ColorLabel {
+    const ALL: [ColorLabel; 6] = [
+        ColorLabel::Red,
+        ColorLabel::Orange,
+        ColorLabel::Yellow,
+        ColorLabel::Green,
+        ColorLabel::Blue,
+        ColorLabel::Purple,
+    ];
 
-        if spec.sample_rate != stream_config.sample_rate.0 {
-            println!("WARNING: Sample rate mismatch detected! This may cause pitch issues.");
-            self.status_message = format!(
-                "Sample rate mismatch: file={}Hz, device={}Hz",
-                spec.sample_rate, stream_config.sample_rate.0
-            );
+    fn color(self) -> iced::Color {
+        match self {
+            ColorLabel::Red => iced::Color::from_rgb(0.9, 0.2, 0.2),
+            ColorLabel::Orange => iced::Color::from_rgb(1.0, 0.6, 0.1),
+            ColorLabel::Yellow => iced::Color::from_rgb(0.95, 0.85, 0.1),
+            ColorLabel::Green => iced::Color::from_rgb(0.2, 0.75, 0.3),
+            ColorLabel::Blue => iced::Color::from_rgb(0.25, 0.5, 0.95),
+            ColorLabel::Purple => iced::Color::from_rgb(0.65, 0.35, 0.85),
         }
+    }
+}
 
-        println!("Using sample format: {:?}", sample_format);
+impl std::fmt::Display for ColorLabel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
 
-        let samples_for_callback = Arc::clone(&samples_arc);
-        let position_for_callback = Arc::clone(&position_arc);
-        let paused_for_callback = Arc::clone(&paused_arc);
-        let play_tx_clone = play_tx.clone();
+fn load_notes() -> BTreeMap<String, FileNotes> {
+    fs::read_to_string(NOTES_PATH)
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
 
-        let build_out = match sample_format {
-            SampleFormat::F32 => device.build_output_stream(
-                &stream_config,
-                move |out: &mut [f32], _| {
-                    let is_paused = *paused_for_callback.lock().unwrap();
-                    if is_paused {
-                        out.fill(0.0);
-                        return;
-                    }
+fn save_notes(notes: &BTreeMap<String, FileNotes>) -> io::Result<()> {
+    let json = serde_json::to_string_pretty(notes).map_err(io::Error::other)?;
+    fs::write(NOTES_PATH, json)
+}
 
-                    let samples = samples_for_callback.lock().unwrap();
-                    let mut position = position_for_callback.lock().unwrap();
+/// The file beside `wav_path` named after it, with `suffix` in place of its `.wav`
+/// extension whatever the case: `take.WAV` and `.marks.json` give `take.marks.json`.
+fn sidecar_path(wav_path: &str, suffix: &str) -> String {
+    let stem = std::path::Path::new(wav_path).with_extension("");
+    format!("{}{}", stem.to_string_lossy(), suffix)
+}
 
-                    let len = out.len().min(samples.len() - *position);
-                    if len > 0 {
-                        out[..len].copy_from_slice(&samples[*position..*position + len]);
-                        *position += len;
+/// Sidecar holding the cue points dropped while recording `wav_path`, in seconds.
+fn marks_path(wav_path: &str) -> String {
+    sidecar_path(wav_path, ".marks.json")
+}
 
-                        if len < out.len() {
-                            out[len..].fill(0.0);
-                        }
-                    } else {
-                        out.fill(0.0);
-                    }
+/// Sidecar holding the live-caption transcript of `wav_path`.
+fn transcript_path(wav_path: &str) -> String {
+    sidecar_path(wav_path, ".transcript.txt")
+}
 
-                    if *position >= samples.len() {
-                        let _ = play_tx_clone.send(());
-                    }
-                },
-                move |err| eprintln!("Output stream error: {}", err),
-                None,
-            ),
-            SampleFormat::I16 => {
-                let samples_for_callback = Arc::clone(&samples_arc);
-                let position_for_callback = Arc::clone(&position_arc);
-                let paused_for_callback = Arc::clone(&paused_arc);
-                device.build_output_stream(
-                    &stream_config,
-                    move |out: &mut [i16], _| {
-                        let is_paused = *paused_for_callback.lock().unwrap();
-                        if is_paused {
-                            out.fill(0);
-                            return;
-                        }
+/// Saves caption `lines` beside `wav_path`, one per line. Nothing is written if there
+/// are none.
+fn write_transcript(wav_path: &str, lines: &[String]) -> io::Result<()> {
+    if lines.is_empty() {
+        return Ok(());
+    }
+    let mut transcript = lines.join("\n");
+    transcript.push('\n');
+    fs::write(transcript_path(wav_path), transcript)
+}
 
-                        let samples = samples_for_callback.lock().unwrap();
-                        let mut position = position_for_callback.lock().unwrap();
+/// The name a take recorded with naming by speech on gets when nothing usable was
+/// said: the local time recording began, e.g. `recording_2024-05-01_14-03-22.wav`.
+fn timestamp_filename(started: SystemTime) -> String {
+    let secs = started
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let local = (secs as i64 + local_offset_secs(secs)).max(0) as u64;
+    let (date, time) = bwf::utc_date_time(local);
+    format!("recording_{}_{}.wav", date, time.replace(':', "-"))
+}
 
-                        let len = out.len().min(samples.len() - *position);
-                        for i in 0..len {
-                            out[i] =
-                                (samples[*position + i].clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
-                        }
-                        if len < out.len() {
-                            out[len..].fill(0);
-                        }
+/// How much of a take is transcribed to name it.
+const SPOKEN_TITLE_SECS: f32 = 6.0;
+const SPOKEN_TITLE_WORDS: usize = 5;
 
-                        *position += len;
+/// A filename made from the first few words of `transcript`, lowercased and joined
+/// with underscores, e.g. `meeting_agenda_for_q3.wav`. `None` if nothing usable was said.
+fn spoken_title_filename(transcript: &str) -> Option<String> {
+    let words: Vec<String> = transcript
+        .split_whitespace()
+        .map(|word| {
+            word.chars()
+                .filter(|c| c.is_alphanumeric())
+                .flat_map(char::to_lowercase)
+                .collect::<String>()
+        })
+        .filter(|word| !word.is_empty())
+        .take(SPOKEN_TITLE_WORDS)
+        .collect();
+    (!words.is_empty()).then(|| format!("{}.wav", words.join("_")))
+}
 
-                        if *position >= samples.len() {
-                            let _ = play_tx_clone.send(());
-                        }
-                    },
-                    move |err| eprintln!("Output stream error: {}", err),
-                    None,
-                )
-            }
-            SampleFormat::U16 => {
-                let samples_for_callback = Arc::clone(&samples_arc);
-                let position_for_callback = Arc::clone(&position_arc);
-                let paused_for_callback = Arc::clone(&paused_arc);
-                device.build_output_stream(
-                    &stream_config,
-                    move |out: &mut [u16], _| {
-                        let is_paused = *paused_for_callback.lock().unwrap();
-                        if is_paused {
-                            out.fill(u16::MAX / 2);
-                            return;
-                        }
+/// Playback settings remembered for one recording and put back when it plays.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+struct FilePlayback {
+    speed: f32,
+    /// Output volume while this file plays, in place of the usual one.
+    volume: Option<f32>,
+    stereo_width: Option<f32>,
+}
 
-                        let samples = samples_for_callback.lock().unwrap();
-                        let mut position = position_for_callback.lock().unwrap();
+impl Default for FilePlayback {
+    fn default() -> Self {
+        Self {
+            speed: 1.0,
+            volume: None,
+            stereo_width: None,
+        }
+    }
+}
 
-                        let len = out.len().min(samples.len() - *position);
-                        for i in 0..len {
-                            let v = ((samples[*position + i].clamp(-1.0, 1.0) + 1.0)
-                                * 0.5
-                                * u16::MAX as f32);
-                            out[i] = v as u16;
-                        }
-                        if len < out.len() {
-                            out[len..].fill(u16::MAX / 2);
-                        }
+/// Sidecar holding the playback settings saved for `wav_path`.
+fn playback_settings_path(wav_path: &str) -> String {
+    sidecar_path(wav_path, ".playback.json")
+}
 
-                        *position += len;
+/// Sidecar older versions kept just the playback speed in; still read.
+fn speed_path(wav_path: &str) -> String {
+    sidecar_path(wav_path, ".speed.json")
+}
 
-                        if *position >= samples.len() {
-                            let _ = play_tx_clone.send(());
-                        }
-                    },
-                    move |err| eprintln!("Output stream error: {}", err),
-                    None,
-                )
-            }
-            SampleFormat::U8 => {
-                let samples_for_callback = Arc::clone(&samples_arc);
-                let position_for_callback = Arc::clone(&position_arc);
-                let paused_for_callback = Arc::clone(&paused_arc);
-                device.build_output_stream(
-                    &stream_config,
-                    move |out: &mut [u8], _| {
-                        let is_paused = *paused_for_callback.lock().unwrap();
-                        if is_paused {
-                            out.fill(128);
-                            return;
-                        }
+fn load_file_playback(wav_path: &str) -> Option<FilePlayback> {
+    if let Ok(json) = fs::read_to_string(playback_settings_path(wav_path)) {
+        return serde_json::from_str(&json).ok();
+    }
+    let json = fs::read_to_string(speed_path(wav_path)).ok()?;
+    Some(FilePlayback {
+        speed: serde_json::from_str(&json).ok()?,
+        ..FilePlayback::default()
+    })
+}
 
-                        let samples = samples_for_callback.lock().unwrap();
-                        let mut position = position_for_callback.lock().unwrap();
+/// Remembers `settings` for `wav_path`, replacing any speed-only sidecar; the
+/// defaults need no sidecar at all.
+fn save_file_playback(wav_path: &str, settings: &FilePlayback) -> io::Result<()> {
+    let remove = |path: String| match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    };
+    remove(speed_path(wav_path))?;
+    if *settings == FilePlayback::default() {
+        return remove(playback_settings_path(wav_path));
+    }
+    let json = serde_json::to_string_pretty(settings).map_err(io::Error::other)?;
+    fs::write(playback_settings_path(wav_path), json)
+}
 
-                        let len = out.len().min(samples.len() - *position);
-                        for i in 0..len {
-                            let sample = samples[*position + i].clamp(-1.0, 1.0);
-                            let scaled = (sample + 1.0) * 127.5;
-                            let dithered = scaled + ((i as f32 * 0.618033988749) % 1.0 - 0.5);
-                            out[i] = dithered.clamp(0.0, 255.0) as u8;
-                        }
-                        if len < out.len() {
-                            out[len..].fill(128);
-                        }
+/// Formats for exchanging markers with other tools.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MarkerFormat {
+    /// `cue ` chunk inside the WAV itself
+    Cue,
+    /// `name.markers.csv`: `time,label` lines under a header
+    Csv,
+    /// `name.labels.txt`: an Audacity label track, `start<TAB>end<TAB>label`
+    Audacity,
+}
 
-                        *position += len;
+impl MarkerFormat {
+    const ALL: [MarkerFormat; 3] = [MarkerFormat::Cue, MarkerFormat::Csv, MarkerFormat::Audacity];
 
-                        if *position >= samples.len() {
-                            let _ = play_tx_clone.send(());
-                        }
-                    },
-                    move |err| eprintln!("Output stream error: {}", err),
-                    None,
-                )
+    /// The text file markers of `wav_path` go to, or `None` for the WAV itself.
+    fn path(self, wav_path: &str) -> Option<String> {
+        match self {
+            MarkerFormat::Cue => None,
+            MarkerFormat::Csv => Some(sidecar_path(wav_path, ".markers.csv")),
+            MarkerFormat::Audacity => Some(sidecar_path(wav_path, ".labels.txt")),
+        }
+    }
+}
+
+impl std::fmt::Display for MarkerFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MarkerFormat::Cue => write!(f, "WAV cue points"),
+            MarkerFormat::Csv => write!(f, "CSV"),
+            MarkerFormat::Audacity => write!(f, "Audacity labels"),
+        }
+    }
+}
+
+/// Markers (seconds and label) as CSV or an Audacity label track. Markers without
+/// a label are labelled by number.
+fn marks_to_text(marks: &[(f64, String)], format: MarkerFormat) -> String {
+    let mut text = match format {
+        MarkerFormat::Csv => "time,label\n".to_string(),
+        _ => String::new(),
+    };
+    for (i, (secs, label)) in marks.iter().enumerate() {
+        let label = if label.is_empty() {
+            format!("Marker {}", i + 1)
+        } else {
+            label.clone()
+        };
+        text += &match format {
+            MarkerFormat::Audacity => {
+                let label = label.replace(['\t', '\n', '\r'], " ");
+                format!("{:.6}\t{:.6}\t{}\n", secs, secs, label)
             }
-            _ => {
-                self.status_message =
-                    format!("Unsupported output sample format: {:?}", sample_format);
-                return;
+            _ if label.contains([',', '"', '\n', '\r']) => {
+                format!("{:.6},\"{}\"\n", secs, label.replace('"', "\"\""))
             }
+            _ => format!("{:.6},{}\n", secs, label),
         };
+    }
+    text
+}
 
-        match build_out {
-            Ok(stream) => {
-                if let Err(e) = stream.play() {
-                    self.status_message = format!("Failed to start output stream: {}", e);
-                    return;
-                }
-                self.output_stream = Some(stream);
-                self.playback_state = PlaybackState::Playing;
-                self.currently_playing_file = Some(filename.to_string());
-                self.status_message = format!("Playing: {}", filename);
-                self.start_time = Some(Instant::now());
-                self.elapsed_time = Duration::from_secs(0);
-            }
-            Err(e) => {
-                self.status_message = format!("Failed to build output stream: {}", e);
+/// Markers (seconds and label) from CSV or an Audacity label track, sorted by time.
+/// A region comes in as a marker at its start. A first line that isn't a time is
+/// taken as a header.
+fn parse_marks_text(text: &str) -> Result<Vec<(f64, String)>, String> {
+    let mut marks = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (first, label) = match line.split_once('\t') {
+            // Audacity: start, end, label
+            Some((start, rest)) => (start, rest.split_once('\t').map_or("", |(_, l)| l)),
+            None => line.split_once(',').unwrap_or((line, "")),
+        };
+        let label = label.trim();
+        let label = match label.strip_prefix('"').and_then(|l| l.strip_suffix('"')) {
+            Some(quoted) => quoted.replace("\"\"", "\""),
+            None => label.to_string(),
+        };
+        let first = first.trim();
+        match first.parse::<f64>() {
+            Ok(secs) if secs.is_finite() && secs >= 0.0 => marks.push((secs, label)),
+            _ if i == 0 => {}
+            _ => {
+                return Err(format!(
+                    "line {}: '{}' isn't a time in seconds",
+                    i + 1,
+                    first
+                ));
             }
         }
     }
+    marks.sort_by(|a, b| a.0.total_cmp(&b.0));
+    marks.dedup_by(|a, b| a.0 == b.0);
+    Ok(marks)
+}
 
-    fn pause_playback_impl(&mut self) {
-        if self.playback_state == PlaybackState::Playing {
-            *self.is_stream_paused.lock().unwrap() = true;
-            self.playback_state = PlaybackState::Paused;
-            self.status_message = if let Some(file) = &self.currently_playing_file {
-                format!("Paused: {}", file)
-            } else {
-                "Playback paused.".into()
-            };
-            self.start_time = None;
-        }
+/// A marker as saved beside its file. Unlabelled markers are saved as a bare time in
+/// seconds, as they always have been.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+enum SavedMark {
+    Time(f64),
+    Labelled { time: f64, label: String },
+}
+
+/// Markers saved beside `wav_path`, as seconds and label (empty if it has none).
+fn load_marks(wav_path: &str) -> Option<Vec<(f64, String)>> {
+    let json = fs::read_to_string(marks_path(wav_path)).ok()?;
+    let saved: Vec<SavedMark> = serde_json::from_str(&json).ok()?;
+    Some(
+        saved
+            .into_iter()
+            .map(|mark| match mark {
+                SavedMark::Time(time) => (time, String::new()),
+                SavedMark::Labelled { time, label } => (time, label),
+            })
+            .collect(),
+    )
+}
+
+/// Markers from the `cue ` chunk of `wav_path`, in seconds, if it has any. Cue
+/// points carry no labels here.
+fn load_cue_marks(wav_path: &str) -> Option<Vec<(f64, String)>> {
+    let sample_rate = WavReader::open(wav_path).ok()?.spec().sample_rate.max(1);
+    let data = bwf::read_chunk(wav_path, bwf::CUE).ok()??;
+    let marks: Vec<(f64, String)> = bwf::read_cue_points(&data)
+        .into_iter()
+        .map(|frame| (frame as f64 / sample_rate as f64, String::new()))
+        .collect();
+    (!marks.is_empty()).then_some(marks)
+}
+
+/// Moves the markers saved with `wav_path`, both beside it and in its `cue ` chunk,
+/// to where `map` puts them after an edit. Ones it gives `None` for are dropped.
+fn remap_saved_marks(
+    wav_path: &str,
+    sample_rate: u32,
+    map: impl Fn(f64) -> Option<f64>,
+) -> io::Result<()> {
+    if let Some(marks) = load_marks(wav_path) {
+        let moved: Vec<(f64, String)> = marks
+            .into_iter()
+            .filter_map(|(secs, label)| Some((map(secs)?, label)))
+            .collect();
+        save_marks(wav_path, &moved)?;
+    }
+    if let Some(data) = bwf::read_chunk(wav_path, bwf::CUE)? {
+        let rate = sample_rate.max(1) as f64;
+        let frames: Vec<u32> = bwf::read_cue_points(&data)
+            .into_iter()
+            .filter_map(|frame| map(frame as f64 / rate))
+            .map(|secs| (secs * rate).round() as u32)
+            .collect();
+        bwf::write_chunk(wav_path, bwf::CUE, &bwf::cue_points(&frames))?;
     }
+    Ok(())
+}
 
-    fn resume_playback_impl(&mut self) {
-        if self.playback_state == PlaybackState::Paused {
-            *self.is_stream_paused.lock().unwrap() = false;
-            self.playback_state = PlaybackState::Playing;
-            self.status_message = if let Some(file) = &self.currently_playing_file {
-                format!("Playing: {}", file)
+fn save_marks(wav_path: &str, marks: &[(f64, String)]) -> io::Result<()> {
+    let saved: Vec<SavedMark> = marks
+        .iter()
+        .map(|(time, label)| {
+            if label.is_empty() {
+                SavedMark::Time(*time)
             } else {
-                "Playback resumed.".into()
-            };
-            self.start_time = Some(Instant::now());
-        }
+                SavedMark::Labelled {
+                    time: *time,
+                    label: label.clone(),
+                }
+            }
+        })
+        .collect();
+    let json = serde_json::to_string_pretty(&saved).map_err(io::Error::other)?;
+    fs::write(marks_path(wav_path), json)
+}
+
+/// A set of clips that get recorded again and again, e.g. intro, body and outro.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Project {
+    name: String,
+    clips: Vec<String>,
+}
+
+impl Project {
+    /// Where `clip` is saved: `{project}_{clip}.wav`, with anything that isn't safe in
+    /// a filename replaced.
+    fn clip_filename(&self, clip: &str) -> String {
+        let safe = |s: &str| -> String {
+            s.trim()
+                .chars()
+                .map(|c| {
+                    if c.is_alphanumeric() || matches!(c, '-' | '_' | ' ') {
+                        c
+                    } else {
+                        '_'
+                    }
+                })
+                .collect()
+        };
+        format!("{}_{}.wav", safe(&self.name), safe(clip))
     }
+}
 
-    fn stop_playback_impl(&mut self) {
-        if self.playback_state != PlaybackState::Stopped {
-            self.output_stream = None;
-            self.playback_state = PlaybackState::Stopped;
-            self.currently_playing_file = None;
-            self.status_message = "Playback stopped.".into();
-            self.start_time = None;
-            self.elapsed_time = Duration::from_secs(0);
-            *self.is_stream_paused.lock().unwrap() = false;
-            *self.playback_position.lock().unwrap() = 0;
+impl std::fmt::Display for Project {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+fn load_projects() -> Vec<Project> {
+    fs::read_to_string(PROJECTS_PATH)
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+fn save_projects(projects: &[Project]) -> io::Result<()> {
+    let json = serde_json::to_string_pretty(projects).map_err(io::Error::other)?;
+    fs::write(PROJECTS_PATH, json)
+}
+
+fn read_wav_file(path: &str) -> Result<(WavSpec, Vec<f32>), String> {
+    match read_wav_recoverable(path)? {
+        (spec, samples, None) => Ok((spec, samples)),
+        (_, _, Some(e)) => Err(format!("Error reading samples: {}", e)),
+    }
+}
+
+/// Reads as much of `path` as can be read. A file whose data stops short or goes
+/// bad part way still gives back the whole frames before the problem, along with
+/// what went wrong.
+fn read_wav_recoverable(path: &str) -> Result<(WavSpec, Vec<f32>, Option<String>), String> {
+    let reader = WavReader::open(path).map_err(|e| format!("Error opening file: {}", e))?;
+    let spec = reader.spec();
+
+    fn read_until_error<T>(
+        samples: impl Iterator<Item = hound::Result<T>>,
+        convert: impl Fn(T) -> f32,
+    ) -> (Vec<f32>, Option<String>) {
+        let mut out = Vec::new();
+        for sample in samples {
+            match sample {
+                Ok(s) => out.push(convert(s)),
+                Err(e) => return (out, Some(e.to_string())),
+            }
         }
+        (out, None)
     }
 
-    fn delete_file_impl(&mut self, filename: &str) {
-        // Can't delete while recording, playing, or renaming
-        if self.is_recording
-            || self.playback_state != PlaybackState::Stopped
-            || self.renaming_file.is_some()
-        {
-            return;
+    let (mut samples, error) = match spec.sample_format {
+        hound::SampleFormat::Float => read_until_error(reader.into_samples::<f32>(), |s| s),
+        hound::SampleFormat::Int => match spec.bits_per_sample {
+            16 => read_until_error(reader.into_samples::<i16>(), |s| s as f32 / i16::MAX as f32),
+            32 => read_until_error(reader.into_samples::<i32>(), |s| s as f32 / i32::MAX as f32),
+            _ => return Err(format!("Unsupported bit depth: {}", spec.bits_per_sample)),
+        },
+    };
+    if error.is_some() {
+        samples.truncate(samples.len() - samples.len() % spec.channels.max(1) as usize);
+    }
+
+    Ok((spec, samples, error))
+}
+
+/// Hashes the decoded audio (quantised to 16 bits) plus its format, so the same take
+/// saved as float or integer WAV produces the same fingerprint.
+fn audio_fingerprint(path: &str) -> Result<u64, String> {
+    let (spec, samples) = read_wav_file(path)?;
+    let mut hasher = DefaultHasher::new();
+    spec.channels.hash(&mut hasher);
+    spec.sample_rate.hash(&mut hasher);
+    for s in samples {
+        ((s.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16).hash(&mut hasher);
+    }
+    Ok(hasher.finish())
+}
+
+/// Whether two files hold the same audio by the measure `audio_fingerprint` uses: the
+/// same channels and rate, and every sample equal once quantised to 16 bits.
+fn same_audio(a: &str, b: &str) -> Result<bool, String> {
+    let (spec_a, samples_a) = read_wav_file(a).map_err(|e| format!("'{}': {}", a, e))?;
+    let (spec_b, samples_b) = read_wav_file(b).map_err(|e| format!("'{}': {}", b, e))?;
+    let quantise = |s: f32| (s.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16;
+    Ok(spec_a.channels == spec_b.channels
+        && spec_a.sample_rate == spec_b.sample_rate
+        && samples_a.len() == samples_b.len()
+        && samples_a
+            .iter()
+            .zip(&samples_b)
+            .all(|(&x, &y)| quantise(x) == quantise(y)))
+}
+
+/// Audio fingerprints by file, with the modification time each was taken at.
+type FingerprintCache = HashMap<std::path::PathBuf, (SystemTime, u64)>;
+
+/// What the per-file caches are keyed by: the full path, so an entry is never taken
+/// for a file of the same name in another folder.
+fn cache_key(path: &str) -> std::path::PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.into())
+}
+
+/// `filename`'s fingerprint, from `cache` unless the file has changed since.
+fn cached_fingerprint(cache: &mut FingerprintCache, filename: &str) -> Result<u64, String> {
+    let modified = fs::metadata(filename)
+        .and_then(|m| m.modified())
+        .map_err(|e| format!("Error reading '{}': {}", filename, e))?;
+
+    if let Some(&(cached_mtime, hash)) = cache.get(&cache_key(filename))
+        && cached_mtime == modified
+    {
+        return Ok(hash);
+    }
+
+    let hash = audio_fingerprint(filename).map_err(|e| format!("'{}': {}", filename, e))?;
+    cache.insert(cache_key(filename), (modified, hash));
+    Ok(hash)
+}
+
+/// Outcome of a duplicate search, picked up when its job finishes.
+struct DuplicateScan {
+    duplicates: HashSet<String>,
+    /// Fingerprints worked out along the way, kept for the next search.
+    fingerprints: FingerprintCache,
+    /// Files that couldn't be read or compared.
+    errors: Vec<String>,
+}
+
+/// Finds the files whose audio matches an earlier file in `files`. Fingerprints only
+/// pick out candidates; the samples are compared to confirm each one, so a hash
+/// collision never offers a different recording for deletion.
+fn find_duplicates(
+    files: &[String],
+    mut fingerprints: FingerprintCache,
+    job: &JobControl,
+) -> Result<DuplicateScan, String> {
+    // Files kept as originals, by fingerprint; more than one means a collision
+    let mut originals: HashMap<u64, Vec<&String>> = HashMap::new();
+    let mut duplicates = HashSet::new();
+    let mut errors = Vec::new();
+    for (i, file) in files.iter().enumerate() {
+        if job.is_cancelled() {
+            return Err("Duplicate search cancelled.".into());
         }
+        job.set_progress(i as f32 / files.len() as f32);
+        let hash = match cached_fingerprint(&mut fingerprints, file) {
+            Ok(hash) => hash,
+            Err(e) => {
+                errors.push(e);
+                continue;
+            }
+        };
 
-        match fs::remove_file(filename) {
-            Ok(_) => {
-                self.status_message = format!("Deleted file: {}", filename);
-                self.files = list_wav_files();
+        let candidates = originals.entry(hash).or_default();
+        let mut found = Ok(false);
+        for original in candidates.iter() {
+            found = same_audio(original, file);
+            if !matches!(found, Ok(false)) {
+                break;
             }
-            Err(e) => {
-                self.status_message = format!("Error deleting file: {}", e);
+        }
+        match found {
+            Ok(true) => {
+                duplicates.insert(file.clone());
+            }
+            Ok(false) => candidates.push(file),
+            Err(e) => errors.push(e),
+        }
+    }
+    Ok(DuplicateScan {
+        duplicates,
+        fingerprints,
+        errors,
+    })
+}
+
+/// Applies raised-cosine fades to the start and end of an interleaved buffer. Gain is
+/// computed per frame so every channel fades identically.
+fn apply_fades(
+    samples: &mut [f32],
+    channels: u16,
+    sample_rate: u32,
+    fade_in_ms: u32,
+    fade_out_ms: u32,
+) {
+    let channels = channels.max(1) as usize;
+    let total_frames = samples.len() / channels;
+    let ms_to_frames =
+        |ms: u32| ((sample_rate as u64 * ms as u64 / 1000) as usize).min(total_frames);
+    let fade_in = ms_to_frames(fade_in_ms);
+    let fade_out = ms_to_frames(fade_out_ms);
+    let curve = |t: f32| 0.5 - 0.5 * (std::f32::consts::PI * t).cos();
+
+    for (i, frame) in samples.chunks_exact_mut(channels).enumerate() {
+        let mut gain = 1.0;
+        if i < fade_in {
+            gain *= curve(i as f32 / fade_in as f32);
+        }
+        let from_end = total_frames - 1 - i;
+        if from_end < fade_out {
+            gain *= curve(from_end as f32 / fade_out as f32);
+        }
+        if gain != 1.0 {
+            frame.iter_mut().for_each(|s| *s *= gain);
+        }
+    }
+}
+
+const SILENCE_THRESHOLD: f32 = 0.02;
+const SILENCE_WINDOW_MS: u32 = 20;
+
+/// Returns the sample indices (into the interleaved buffer) at the middle of every
+/// silent stretch of at least `min_gap`. Leading and trailing silence is ignored.
+fn detect_silence_boundaries(
+    samples: &[f32],
+    channels: u16,
+    sample_rate: u32,
+    min_gap: Duration,
+) -> Vec<usize> {
+    let channels = channels.max(1) as usize;
+    let window = ((sample_rate * SILENCE_WINDOW_MS / 1000) as usize).max(1) * channels;
+    let min_windows = (min_gap.as_millis() as usize / SILENCE_WINDOW_MS as usize).max(1);
+
+    let mut boundaries = Vec::new();
+    let mut silent_run_start: Option<usize> = None;
+    let mut heard_sound = false;
+
+    for (i, chunk) in samples.chunks(window).enumerate() {
+        let peak = chunk.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+        if peak < SILENCE_THRESHOLD {
+            if silent_run_start.is_none() {
+                silent_run_start = Some(i);
+            }
+        } else {
+            if let Some(run_start) = silent_run_start.take()
+                && heard_sound
+                && i - run_start >= min_windows
+            {
+                let mid = (run_start + i) / 2;
+                boundaries.push(mid * window);
+            }
+            heard_sound = true;
+        }
+    }
+
+    boundaries
+}
+
+/// Splices `frames` frames of silence into interleaved `samples` at sample index `at`,
+/// moved back onto a frame boundary and clamped to the end.
+fn insert_silence(samples: &[f32], channels: u16, at: usize, frames: usize) -> Vec<f32> {
+    let channels = channels.max(1) as usize;
+    let at = (at - at % channels).min(samples.len() - samples.len() % channels);
+    let mut out = Vec::with_capacity(samples.len() + frames * channels);
+    out.extend_from_slice(&samples[..at]);
+    out.resize(at + frames * channels, 0.0);
+    out.extend_from_slice(&samples[at..]);
+    out
+}
+
+/// Negates every channel flagged in `inverted` (by index) in interleaved `samples`,
+/// flipping its polarity.
+fn invert_polarity(samples: &mut [f32], channels: usize, inverted: &[bool]) {
+    for frame in samples.chunks_exact_mut(channels.max(1)) {
+        for (sample, _) in frame.iter_mut().zip(inverted).filter(|(_, flip)| **flip) {
+            *sample = -*sample;
+        }
+    }
+}
+
+/// The part of the buffer worth keeping: from the first to the last frame reaching
+/// `threshold`, widened by `padding_ms` each side. `None` if nothing reaches it.
+fn silence_trim_range(
+    samples: &[f32],
+    channels: u16,
+    sample_rate: u32,
+    threshold: f32,
+    padding_ms: u32,
+) -> Option<std::ops::Range<usize>> {
+    let channels = channels.max(1) as usize;
+    let loud = |frame: &[f32]| frame.iter().any(|s| s.abs() >= threshold);
+    let first = samples.chunks(channels).position(loud)?;
+    let last = samples.chunks(channels).rposition(loud)?;
+
+    let frames = samples.len().div_ceil(channels);
+    let padding = (sample_rate as u64 * padding_ms as u64 / 1000) as usize;
+    let start = first.saturating_sub(padding);
+    let end = (last + 1 + padding).min(frames);
+    Some(start * channels..(end * channels).min(samples.len()))
+}
+
+/// How far two takes of the same format differ, sample by sample from the start.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct SampleDifference {
+    /// Frames present in both takes; only these are compared.
+    compared_frames: usize,
+    max: f32,
+    rms: f32,
+    /// Frames the second take has beyond the first (negative if it's shorter).
+    extra_frames: i64,
+}
+
+fn sample_difference(a: &[f32], b: &[f32], channels: u16) -> SampleDifference {
+    let channels = channels.max(1) as usize;
+    let frames_a = a.len() / channels;
+    let frames_b = b.len() / channels;
+    let compared = frames_a.min(frames_b) * channels;
+    let (max, sum) =
+        a[..compared]
+            .iter()
+            .zip(&b[..compared])
+            .fold((0.0f32, 0.0f64), |(max, sum), (x, y)| {
+                let d = x - y;
+                (max.max(d.abs()), sum + (d as f64) * (d as f64))
+            });
+    SampleDifference {
+        compared_frames: compared / channels,
+        max,
+        rms: (sum / compared.max(1) as f64).sqrt() as f32,
+        extra_frames: frames_b as i64 - frames_a as i64,
+    }
+}
+
+struct WaveformOverlay {
+    /// The file the overlay is drawn over.
+    base: String,
+    data: WaveformData,
+    difference: Option<SampleDifference>,
+    sample_rate: u32,
+}
+
+impl WaveformOverlay {
+    fn readout(&self) -> String {
+        let Some(diff) = self.difference else {
+            return "(formats differ, samples not compared)".into();
+        };
+        let secs = |frames: f64| frames / self.sample_rate.max(1) as f64;
+        let db = |level: f32| 20.0 * level.max(f32::MIN_POSITIVE).log10();
+        let mut readout = if diff.max == 0.0 {
+            format!("identical over {:.1}s", secs(diff.compared_frames as f64))
+        } else {
+            format!(
+                "max difference {:.1} dBFS, RMS difference {:.1} dBFS over {:.1}s",
+                db(diff.max),
+                db(diff.rms),
+                secs(diff.compared_frames as f64)
+            )
+        };
+        if diff.extra_frames != 0 {
+            readout += &format!(
+                "; B is {:.2}s {}",
+                secs(diff.extra_frames.unsigned_abs() as f64),
+                if diff.extra_frames > 0 {
+                    "longer"
+                } else {
+                    "shorter"
+                }
+            );
+        }
+        readout
+    }
+}
+
+fn db_to_amplitude(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+/// Writes the chapters of `path` between `boundaries` as `<name>_chNN.wav`, in
+/// 32-bit float. Returns how many were written.
+fn write_chapter_files(
+    path: &str,
+    spec: WavSpec,
+    samples: &[f32],
+    boundaries: &[usize],
+    job: &JobControl,
+) -> io::Result<usize> {
+    let stem = sidecar_path(path, "");
+    let out_spec = WavSpec {
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+        ..spec
+    };
+    let segments = split_at(samples, spec, boundaries);
+    let names: Vec<String> = (1..=segments.len())
+        .map(|i| format!("{}_ch{:02}.wav", stem, i))
+        .collect();
+    write_segment_files(&names, out_spec, &segments, job)?;
+    Ok(names.len())
+}
+
+/// Cuts an interleaved buffer into the segments between `boundaries` (sample indices).
+/// Cuts that fall mid-frame are moved back to the start of that frame, and empty
+/// segments are dropped.
+fn split_at<'a>(samples: &'a [f32], spec: WavSpec, boundaries: &[usize]) -> Vec<&'a [f32]> {
+    let channels = spec.channels.max(1) as usize;
+    let mut cuts: Vec<usize> = boundaries
+        .iter()
+        .map(|&b| (b - b % channels).min(samples.len()))
+        .collect();
+    cuts.sort_unstable();
+    cuts.push(samples.len());
+
+    let mut segments = Vec::new();
+    let mut start = 0;
+    for end in cuts {
+        if end > start {
+            segments.push(&samples[start..end]);
+            start = end;
+        }
+    }
+    segments
+}
+
+/// Writes each segment of `path` as `<name>_NN.wav` in the source format, refusing
+/// if any of those names is taken.
+fn write_split_files(
+    path: &str,
+    spec: WavSpec,
+    segments: &[&[f32]],
+    job: &JobControl,
+) -> io::Result<Vec<String>> {
+    let stem = sidecar_path(path, "");
+    let names: Vec<String> = (1..=segments.len())
+        .map(|i| format!("{}_{:02}.wav", stem, i))
+        .collect();
+    if let Some(existing) = names.iter().find(|name| fs::metadata(name).is_ok()) {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("'{}' already exists", existing),
+        ));
+    }
+    write_segment_files(&names, spec, segments, job)?;
+    Ok(names)
+}
+
+/// Writes `segments[i]` to `names[i]` as `spec`. Every segment goes to a temporary
+/// file first and they're only renamed into place once all of them have been
+/// written; if anything fails, even a rename, whatever was written is removed, so
+/// no partial set is left behind.
+fn write_segment_files(
+    names: &[String],
+    spec: WavSpec,
+    segments: &[&[f32]],
+    job: &JobControl,
+) -> io::Result<()> {
+    let mut written: Vec<String> = Vec::new();
+    let mut result = Ok(());
+    for (i, (name, segment)) in names.iter().zip(segments).enumerate() {
+        if job.is_cancelled() {
+            result = Err(io::Error::new(io::ErrorKind::Interrupted, "cancelled"));
+            break;
+        }
+        job.set_progress(i as f32 / segments.len() as f32);
+        let tmp_path = format!("{}.tmp", name);
+        result = write_wav_file_like(&tmp_path, spec, segment);
+        written.push(tmp_path);
+        if result.is_err() {
+            break;
+        }
+    }
+    if let Err(e) = result {
+        for tmp_path in &written {
+            let _ = fs::remove_file(tmp_path);
+        }
+        return Err(e);
+    }
+
+    for (i, (tmp_path, name)) in written.iter().zip(names).enumerate() {
+        if let Err(e) = fs::rename(tmp_path, name) {
+            for name in &names[..i] {
+                let _ = fs::remove_file(name);
+            }
+            for tmp_path in &written[i..] {
+                let _ = fs::remove_file(tmp_path);
+            }
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+/// Writes each channel of `path` to its own mono file, `name_ch1.wav` and so on, at
+/// the source's sample rate and in its sample format, so the samples are unchanged.
+/// Returns the files written; on failure none are left behind.
+fn write_channel_files(path: &str, job: &JobControl) -> io::Result<Vec<String>> {
+    let mut reader = hound::WavReader::open(path).map_err(io::Error::other)?;
+    let spec = reader.spec();
+    if spec.channels < 2 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "it has only one channel",
+        ));
+    }
+    let stem = sidecar_path(path, "");
+    let names: Vec<String> = (1..=spec.channels)
+        .map(|ch| format!("{}_ch{}.wav", stem, ch))
+        .collect();
+    if let Some(existing) = names.iter().find(|name| fs::metadata(name).is_ok()) {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("'{}' already exists", existing),
+        ));
+    }
+
+    match spec.sample_format {
+        hound::SampleFormat::Float => {
+            let samples: Vec<f32> = reader
+                .samples::<f32>()
+                .collect::<Result<_, _>>()
+                .map_err(io::Error::other)?;
+            write_deinterleaved(&names, spec, &samples, job)?;
+        }
+        hound::SampleFormat::Int => {
+            let samples: Vec<i32> = reader
+                .samples::<i32>()
+                .collect::<Result<_, _>>()
+                .map_err(io::Error::other)?;
+            write_deinterleaved(&names, spec, &samples, job)?;
+        }
+    }
+    Ok(names)
+}
+
+/// Writes channel `i` of the interleaved `samples` to `names[i]` as mono `spec`.
+fn write_deinterleaved<S: hound::Sample + Copy>(
+    names: &[String],
+    spec: WavSpec,
+    samples: &[S],
+    job: &JobControl,
+) -> io::Result<()> {
+    let mono = WavSpec {
+        channels: 1,
+        ..spec
+    };
+    let mut written: Vec<String> = Vec::new();
+    let mut result = Ok(());
+    for (ch, name) in names.iter().enumerate() {
+        if job.is_cancelled() {
+            result = Err(io::Error::new(io::ErrorKind::Interrupted, "cancelled"));
+            break;
+        }
+        job.set_progress(ch as f32 / names.len() as f32);
+        let tmp_path = format!("{}.tmp", name);
+        result = hound::WavWriter::create(&tmp_path, mono)
+            .and_then(|mut writer| {
+                for &s in samples.iter().skip(ch).step_by(names.len()) {
+                    writer.write_sample(s)?;
+                }
+                writer.finalize()
+            })
+            .map_err(io::Error::other);
+        written.push(tmp_path);
+        if result.is_err() {
+            break;
+        }
+    }
+
+    if let Err(e) = result {
+        for tmp_path in &written {
+            let _ = fs::remove_file(tmp_path);
+        }
+        return Err(e);
+    }
+    for (tmp_path, name) in written.iter().zip(names) {
+        fs::rename(tmp_path, name)?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum TimerPrecision {
+    Seconds,
+    Tenths,
+    Hundredths,
+}
+
+impl TimerPrecision {
+    const ALL: [TimerPrecision; 3] = [
+        TimerPrecision::Seconds,
+        TimerPrecision::Tenths,
+        TimerPrecision::Hundredths,
+    ];
+}
+
+impl std::fmt::Display for TimerPrecision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TimerPrecision::Seconds => write!(f, "Seconds"),
+            TimerPrecision::Tenths => write!(f, "Tenths"),
+            TimerPrecision::Hundredths => write!(f, "Hundredths"),
+        }
+    }
+}
+
+/// `mm:ss`, `mm:ss.t` or `mm:ss.cc`, with an `hh:` in front once past the first hour
+/// (or always, if asked).
+fn format_timer(elapsed: Duration, precision: TimerPrecision, always_hours: bool) -> String {
+    let secs = elapsed.as_secs();
+    let clock = if always_hours || secs >= 3600 {
+        format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else {
+        format!("{:02}:{:02}", secs / 60, secs % 60)
+    };
+    let millis = elapsed.subsec_millis();
+    match precision {
+        TimerPrecision::Seconds => clock,
+        TimerPrecision::Tenths => format!("{}.{}", clock, millis / 100),
+        TimerPrecision::Hundredths => format!("{}.{:02}", clock, millis / 10),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum SpaceAction {
+    /// Stop a recording, else pause/resume playback, else start recording.
+    Context,
+    Record,
+    PlayPause,
+}
+
+impl SpaceAction {
+    const ALL: [SpaceAction; 3] = [
+        SpaceAction::Context,
+        SpaceAction::Record,
+        SpaceAction::PlayPause,
+    ];
+}
+
+impl std::fmt::Display for SpaceAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SpaceAction::Context => write!(f, "Depends on what's happening"),
+            SpaceAction::Record => write!(f, "Start/stop recording"),
+            SpaceAction::PlayPause => write!(f, "Play/pause"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum ExportSampleRate {
+    Original,
+    Hz(u32),
+}
+
+impl ExportSampleRate {
+    const ALL: [ExportSampleRate; 6] = [
+        ExportSampleRate::Original,
+        ExportSampleRate::Hz(8000),
+        ExportSampleRate::Hz(16000),
+        ExportSampleRate::Hz(22050),
+        ExportSampleRate::Hz(44100),
+        ExportSampleRate::Hz(48000),
+    ];
+}
+
+impl std::fmt::Display for ExportSampleRate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExportSampleRate::Original => write!(f, "Original"),
+            ExportSampleRate::Hz(rate) => write!(f, "{} Hz", rate),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum ExportFormat {
+    WavPcm16,
+    WavFloat,
+    Flac,
+    Mp3,
+    Aiff,
+    Caf,
+}
+
+impl ExportFormat {
+    const ALL: [ExportFormat; 6] = [
+        ExportFormat::WavPcm16,
+        ExportFormat::WavFloat,
+        ExportFormat::Flac,
+        ExportFormat::Mp3,
+        ExportFormat::Aiff,
+        ExportFormat::Caf,
+    ];
+
+    fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::WavPcm16 | ExportFormat::WavFloat => "wav",
+            ExportFormat::Flac => "flac",
+            ExportFormat::Mp3 => "mp3",
+            ExportFormat::Aiff => "aiff",
+            ExportFormat::Caf => "caf",
+        }
+    }
+}
+
+impl std::fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExportFormat::WavPcm16 => write!(f, "WAV (16-bit)"),
+            ExportFormat::WavFloat => write!(f, "WAV (32-bit float)"),
+            ExportFormat::Flac => write!(f, "FLAC"),
+            ExportFormat::Mp3 => write!(f, "MP3"),
+            ExportFormat::Aiff => write!(f, "AIFF"),
+            ExportFormat::Caf => write!(f, "CAF"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+struct ExportSettings {
+    format: ExportFormat,
+    sample_rate: ExportSampleRate,
+    mono: bool,
+    /// Integrated loudness target; `None` leaves levels untouched.
+    normalize_lufs: Option<f32>,
+    mp3_kbps: u32,
+    resample_quality: ResampleQuality,
+    mp3_tags: Mp3Tags,
+}
+
+/// ID3 tags written into MP3 exports. Empty fields are left out.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+struct Mp3Tags {
+    /// Blank titles each export after its recording.
+    title: String,
+    artist: String,
+    album: String,
+    /// PNG or JPEG image embedded as the front cover, as a full path so it's found
+    /// whichever folder is open.
+    cover_art: String,
+}
+
+impl Mp3Tags {
+    /// The ID3 tag for an export of `source`, with the cover art read in.
+    fn to_id3(&self, source: &str) -> Result<Vec<u8>, String> {
+        let title = if self.title.trim().is_empty() {
+            let name = source.rsplit('/').next().unwrap_or(source);
+            sidecar_path(name, "")
+        } else {
+            self.title.trim().to_string()
+        };
+        let cover_path = self.cover_art.trim();
+        let cover = if cover_path.is_empty() {
+            None
+        } else {
+            let data = fs::read(cover_path)
+                .map_err(|e| format!("Couldn't read cover art '{}': {}", cover_path, e))?;
+            Some(data)
+        };
+        let picture = match &cover {
+            Some(data) => Some(id3::Picture {
+                mime: id3::image_mime(data).ok_or_else(|| {
+                    format!("Cover art '{}' isn't a PNG or JPEG image.", cover_path)
+                })?,
+                data,
+            }),
+            None => None,
+        };
+        id3::tag(&title, self.artist.trim(), self.album.trim(), picture)
+    }
+}
+
+impl Default for ExportSettings {
+    fn default() -> Self {
+        Self {
+            format: ExportFormat::WavFloat,
+            sample_rate: ExportSampleRate::Original,
+            mono: false,
+            normalize_lufs: None,
+            mp3_kbps: 128,
+            resample_quality: ResampleQuality::High,
+            mp3_tags: Mp3Tags::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ExportPreset {
+    name: String,
+    settings: ExportSettings,
+}
+
+impl ExportPreset {
+    /// Whether `settings` are this preset's, allowing for tags filled in on top of
+    /// a preset that has none.
+    fn matches(&self, settings: &ExportSettings) -> bool {
+        if self.settings.mp3_tags == Mp3Tags::default() {
+            let untagged = ExportSettings {
+                mp3_tags: Mp3Tags::default(),
+                ..settings.clone()
+            };
+            self.settings == untagged
+        } else {
+            self.settings == *settings
+        }
+    }
+}
+
+impl std::fmt::Display for ExportPreset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+fn builtin_presets() -> Vec<ExportPreset> {
+    vec![
+        ExportPreset {
+            name: "Podcast".into(),
+            settings: ExportSettings {
+                format: ExportFormat::Mp3,
+                sample_rate: ExportSampleRate::Hz(44100),
+                mono: true,
+                normalize_lufs: Some(-16.0),
+                mp3_kbps: 128,
+                ..ExportSettings::default()
+            },
+        },
+        ExportPreset {
+            name: "Voicemail".into(),
+            settings: ExportSettings {
+                format: ExportFormat::WavPcm16,
+                sample_rate: ExportSampleRate::Hz(8000),
+                mono: true,
+                ..ExportSettings::default()
+            },
+        },
+        ExportPreset {
+            name: "Archive".into(),
+            settings: ExportSettings {
+                format: ExportFormat::Flac,
+                ..ExportSettings::default()
+            },
+        },
+        ExportPreset {
+            name: "Logic / Final Cut".into(),
+            settings: ExportSettings {
+                format: ExportFormat::Aiff,
+                sample_rate: ExportSampleRate::Hz(48000),
+                ..ExportSettings::default()
+            },
+        },
+    ]
+}
+
+fn downmix_to_mono(samples: &[f32], channels: u16) -> Vec<f32> {
+    let channels = channels.max(1) as usize;
+    samples
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+        .collect()
+}
+
+const RESAMPLE_ZERO_CROSSINGS: f32 = 16.0;
+/// A shorter kernel for resampling in the audio callback, where every tap is paid
+/// for on each buffer.
+const PLAYBACK_SINC_ZERO_CROSSINGS: f32 = 8.0;
+const PROGRESS_INTERVAL_FRAMES: usize = 8192;
+
+/// How sample-rate conversion fills in the points between the source's frames.
+///
+/// `Fast` draws a straight line between neighbouring frames. It costs next to
+/// nothing, but dulls the top octave and lets high frequencies fold back as aliases
+/// when the rate goes down. `High` uses a Blackman-windowed sinc that keeps the band
+/// flat and the aliases far down, for tens of times the work per frame. Playback
+/// defaults to `Fast` so the audio callback stays light; export defaults to `High`,
+/// where the cost is only a longer wait.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+enum ResampleQuality {
+    Fast,
+    #[default]
+    High,
+}
+
+impl ResampleQuality {
+    const ALL: [ResampleQuality; 2] = [ResampleQuality::Fast, ResampleQuality::High];
+}
+
+impl std::fmt::Display for ResampleQuality {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResampleQuality::Fast => write!(f, "Fast (linear)"),
+            ResampleQuality::High => write!(f, "High (windowed sinc)"),
+        }
+    }
+}
+
+/// Converts an interleaved buffer between sample rates. With `High` quality the
+/// sinc's cutoff follows the lower of the two Nyquist frequencies, so downsampling
+/// doesn't alias. `progress` gets the fraction done every few thousand frames;
+/// returns `None` as soon as it returns `false`.
+fn resample_with_progress(
+    samples: &[f32],
+    channels: u16,
+    from_rate: u32,
+    to_rate: u32,
+    quality: ResampleQuality,
+    progress: &mut dyn FnMut(f32) -> bool,
+) -> Option<Vec<f32>> {
+    if from_rate == to_rate || samples.is_empty() {
+        return Some(samples.to_vec());
+    }
+
+    let channels = channels.max(1) as usize;
+    let in_frames = samples.len() / channels;
+    let ratio = from_rate as f64 / to_rate as f64;
+    let out_frames = (in_frames as f64 / ratio).floor() as usize;
+    let cutoff = (to_rate as f32 / from_rate as f32).min(1.0);
+    let half_width = (RESAMPLE_ZERO_CROSSINGS / cutoff).ceil() as isize;
+
+    let mut out = Vec::with_capacity(out_frames * channels);
+    let mut position = 0.0f64;
+    for n in (0..out_frames).step_by(PROGRESS_INTERVAL_FRAMES) {
+        if !progress(n as f32 / out_frames as f32) {
+            return None;
+        }
+        let frames = PROGRESS_INTERVAL_FRAMES.min(out_frames - n);
+        match quality {
+            ResampleQuality::Fast => interpolate_frames(
+                samples,
+                channels,
+                &mut position,
+                ratio,
+                frames,
+                ResampleQuality::Fast,
+                &mut out,
+            ),
+            ResampleQuality::High => {
+                for i in n..n + frames {
+                    sinc_frame(
+                        samples,
+                        channels,
+                        i as f64 * ratio,
+                        cutoff,
+                        half_width,
+                        &mut out,
+                    );
+                }
+            }
+        }
+    }
+
+    Some(out)
+}
+
+/// Appends the frame at fractional frame `t` of interleaved `samples`, interpolated
+/// with a Blackman-windowed sinc of `half_width` taps a side, low-passed at `cutoff`
+/// (a fraction of the source's Nyquist frequency).
+fn sinc_frame(
+    samples: &[f32],
+    channels: usize,
+    t: f64,
+    cutoff: f32,
+    half_width: isize,
+    out: &mut Vec<f32>,
+) {
+    let in_frames = samples.len() / channels;
+    let center = t.floor() as isize;
+    let frac = (t - center as f64) as f32;
+
+    let first = out.len();
+    out.resize(first + channels, 0.0);
+    let acc = &mut out[first..];
+    let mut weight_sum = 0.0f32;
+    for k in (center - half_width + 1)..=(center + half_width) {
+        if k < 0 || k as usize >= in_frames {
+            continue;
+        }
+        let x = (k - center) as f32 - frac;
+        let weight = cutoff * sinc(cutoff * x) * blackman(x / half_width as f32);
+        weight_sum += weight;
+        let frame = &samples[k as usize * channels..(k as usize + 1) * channels];
+        for (a, &s) in acc.iter_mut().zip(frame) {
+            *a += s * weight;
+        }
+    }
+
+    // Normalising by the kernel sum keeps DC gain at unity near the buffer edges
+    let norm = if weight_sum.abs() > f32::EPSILON {
+        1.0 / weight_sum
+    } else {
+        0.0
+    };
+    acc.iter_mut().for_each(|a| *a *= norm);
+}
+
+fn sinc(x: f32) -> f32 {
+    if x.abs() < 1e-6 {
+        1.0
+    } else {
+        let px = std::f32::consts::PI * x;
+        px.sin() / px
+    }
+}
+
+fn blackman(x: f32) -> f32 {
+    if x.abs() >= 1.0 {
+        return 0.0;
+    }
+    let px = std::f32::consts::PI * x;
+    0.42 + 0.5 * px.cos() + 0.08 * (2.0 * px).cos()
+}
+
+/// Second-order IIR section in direct form I, used for the K-weighting filter.
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Self {
+            b,
+            a,
+            x: [0.0; 2],
+            y: [0.0; 2],
+        }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+            - self.a[0] * self.y[0]
+            - self.a[1] * self.y[1];
+        self.x = [x, self.x[0]];
+        self.y = [y, self.y[0]];
+        y
+    }
+}
+
+/// The two ITU-R BS.1770 K-weighting stages (high shelf, then high-pass), with
+/// coefficients derived for the given sample rate.
+fn k_weighting(sample_rate: u32) -> [Biquad; 2] {
+    let rate = sample_rate as f64;
+
+    let f0 = 1_681.974_450_955_533;
+    let gain_db = 3.999_843_853_973_347;
+    let q = 0.707_175_236_955_419_6;
+    let k = (std::f64::consts::PI * f0 / rate).tan();
+    let vh = 10f64.powf(gain_db / 20.0);
+    let vb = vh.powf(0.499_666_774_154_541_6);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad::new(
+        [
+            (vh + vb * k / q + k * k) / a0,
+            2.0 * (k * k - vh) / a0,
+            (vh - vb * k / q + k * k) / a0,
+        ],
+        [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    );
+
+    let f0 = 38.135_470_876_024_44;
+    let q = 0.500_327_037_323_877_3;
+    let k = (std::f64::consts::PI * f0 / rate).tan();
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad::new(
+        [1.0, -2.0, 1.0],
+        [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    );
+
+    [shelf, high_pass]
+}
+
+const LOUDNESS_BLOCK_MS: usize = 400;
+const LOUDNESS_STEP_MS: usize = 100;
+const LOUDNESS_ABSOLUTE_GATE: f64 = -70.0;
+const LOUDNESS_RELATIVE_GATE: f64 = -10.0;
+
+fn block_loudness(power: f64) -> f64 {
+    -0.691 + 10.0 * power.log10()
+}
+
+/// Gated integrated loudness (BS.1770) in LUFS, or `None` if the audio is too short
+/// or too quiet to measure.
+fn integrated_loudness(samples: &[f32], channels: u16, sample_rate: u32) -> Option<f64> {
+    let channels = channels.max(1) as usize;
+    let frames = samples.len() / channels;
+    let block = sample_rate as usize * LOUDNESS_BLOCK_MS / 1000;
+    let step = sample_rate as usize * LOUDNESS_STEP_MS / 1000;
+    if block == 0 || frames < block {
+        return None;
+    }
+
+    // Square the K-weighted signal once, summed across channels
+    let mut filters: Vec<[Biquad; 2]> = (0..channels).map(|_| k_weighting(sample_rate)).collect();
+    let squared: Vec<f64> = samples
+        .chunks_exact(channels)
+        .map(|frame| {
+            frame
+                .iter()
+                .zip(filters.iter_mut())
+                .map(|(&s, [shelf, high_pass])| {
+                    let y = high_pass.process(shelf.process(s as f64));
+                    y * y
+                })
+                .sum()
+        })
+        .collect();
+
+    let mut prefix = Vec::with_capacity(squared.len() + 1);
+    prefix.push(0.0);
+    for s in &squared {
+        prefix.push(prefix.last().unwrap() + s);
+    }
+    let powers: Vec<f64> = (0..=(frames - block) / step)
+        .map(|i| (prefix[i * step + block] - prefix[i * step]) / block as f64)
+        .filter(|&p| p > 0.0 && block_loudness(p) > LOUDNESS_ABSOLUTE_GATE)
+        .collect();
+    if powers.is_empty() {
+        return None;
+    }
+
+    let relative_gate =
+        block_loudness(powers.iter().sum::<f64>() / powers.len() as f64) + LOUDNESS_RELATIVE_GATE;
+    let gated: Vec<f64> = powers
+        .into_iter()
+        .filter(|&p| block_loudness(p) > relative_gate)
+        .collect();
+    if gated.is_empty() {
+        return None;
+    }
+    Some(block_loudness(
+        gated.iter().sum::<f64>() / gated.len() as f64,
+    ))
+}
+
+const TRUE_PEAK_OVERSAMPLE: usize = 4;
+const TRUE_PEAK_TAPS: usize = 12;
+
+/// Highest level of the signal between samples as well as at them (BS.1770 true
+/// peak), estimated by 4x oversampling with a Hann-windowed sinc interpolator.
+fn true_peak(samples: &[f32], channels: u16) -> f32 {
+    let channels = channels.max(1) as usize;
+    let half = TRUE_PEAK_TAPS / 2;
+    // Filter for each point between samples n and n + 1, over samples
+    // n + 1 - half ..= n + half
+    let phases: Vec<Vec<f32>> = (1..TRUE_PEAK_OVERSAMPLE)
+        .map(|phase| {
+            let frac = phase as f64 / TRUE_PEAK_OVERSAMPLE as f64;
+            (0..TRUE_PEAK_TAPS)
+                .map(|k| {
+                    let d = frac + half as f64 - 1.0 - k as f64;
+                    let x = std::f64::consts::PI * d;
+                    let sinc = if d == 0.0 { 1.0 } else { x.sin() / x };
+                    let window = 0.5 + 0.5 * (x / half as f64).cos();
+                    (sinc * window) as f32
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut peak = samples.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+    let frames = samples.len() / channels;
+    for ch in 0..channels {
+        let at = |i: isize| -> f32 {
+            if i < 0 || i as usize >= frames {
+                0.0
+            } else {
+                samples[i as usize * channels + ch]
+            }
+        };
+        for n in 0..frames.saturating_sub(1) as isize {
+            // Overshoot between two quiet samples can't reach the peak; skipping
+            // them keeps long files quick
+            if at(n).abs().max(at(n + 1).abs()) < peak * 0.5 {
+                continue;
+            }
+            for taps in &phases {
+                let value: f32 = taps
+                    .iter()
+                    .enumerate()
+                    .map(|(k, &w)| w * at(n + 1 - half as isize + k as isize))
+                    .sum();
+                peak = peak.max(value.abs());
+            }
+        }
+    }
+    peak
+}
+
+/// Lowest level counted as clipped: full scale for 16-bit audio.
+const FILE_CLIP_LEVEL: f32 = 32767.0 / 32768.0;
+
+/// Level statistics of a whole file, for the info panel. Levels are linear.
+#[derive(Debug, Clone, PartialEq)]
+struct Analysis {
+    duration: Duration,
+    peak: f32,
+    true_peak: f32,
+    rms: f32,
+    lufs: Option<f64>,
+    /// Samples at full scale, on any channel.
+    clipped_samples: usize,
+}
+
+impl Analysis {
+    fn readout(&self) -> String {
+        let db = |level: f32| {
+            if level > 0.0 {
+                format!("{:.1}", 20.0 * level.log10())
+            } else {
+                "-inf".to_string()
+            }
+        };
+        format!(
+            "Peak {} dBFS, true peak {} dBTP, RMS {} dBFS, {}, {} clipped sample{}",
+            db(self.peak),
+            db(self.true_peak),
+            db(self.rms),
+            match self.lufs {
+                Some(lufs) => format!("{:.1} LUFS", lufs),
+                None => "loudness n/a".to_string(),
+            },
+            self.clipped_samples,
+            if self.clipped_samples == 1 { "" } else { "s" }
+        )
+    }
+}
+
+fn analyze(path: &str) -> Result<Analysis, String> {
+    let (spec, samples) = read_wav_file(path)?;
+    let channels = spec.channels.max(1);
+    let frames = samples.len() / channels as usize;
+    let sum_squares: f64 = samples.iter().map(|&s| s as f64 * s as f64).sum();
+    Ok(Analysis {
+        duration: Duration::from_secs_f64(frames as f64 / spec.sample_rate.max(1) as f64),
+        peak: samples.iter().fold(0.0f32, |m, s| m.max(s.abs())),
+        true_peak: true_peak(&samples, channels),
+        rms: (sum_squares / samples.len().max(1) as f64).sqrt() as f32,
+        lufs: integrated_loudness(&samples, channels, spec.sample_rate),
+        clipped_samples: samples
+            .iter()
+            .filter(|s| s.abs() >= FILE_CLIP_LEVEL)
+            .count(),
+    })
+}
+
+/// Highest sample peak allowed after loudness normalisation, in dBFS.
+const NORMALIZE_PEAK_CEILING_DB: f32 = -1.0;
+
+/// Scales the buffer towards `target_lufs`, holding back the gain if it would push
+/// peaks above the ceiling. Returns the measured loudness before normalising.
+fn normalize_loudness(
+    samples: &mut [f32],
+    channels: u16,
+    sample_rate: u32,
+    target_lufs: f32,
+) -> Option<f64> {
+    let (measured, gain) = loudness_gain(samples, channels, sample_rate, target_lufs)?;
+    for s in samples.iter_mut() {
+        *s *= gain;
+    }
+    Some(measured)
+}
+
+/// The measured loudness and the gain `normalize_loudness` would apply, without
+/// applying it.
+fn loudness_gain(
+    samples: &[f32],
+    channels: u16,
+    sample_rate: u32,
+    target_lufs: f32,
+) -> Option<(f64, f32)> {
+    let measured = integrated_loudness(samples, channels, sample_rate)?;
+    let peak = samples.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+    let ceiling = 10f32.powf(NORMALIZE_PEAK_CEILING_DB / 20.0);
+    let mut gain = 10f32.powf((target_lufs - measured as f32) / 20.0);
+    if peak * gain > ceiling {
+        gain = ceiling / peak;
+    }
+    Some((measured, gain))
+}
+
+/// Loss on folding to mono beyond which a file is flagged, in dB. Unrelated left and
+/// right channels lose about 3 dB, which is normal for wide stereo.
+const MONO_LOSS_WARNING_DB: f64 = 6.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct MonoCheck {
+    /// Loudness of the mono fold-down relative to the stereo original, in dB.
+    /// `None` when the fold-down cancels out completely.
+    difference_db: Option<f64>,
+    /// Left/right correlation: 1 for identical channels, -1 for one inverted.
+    correlation: f64,
+}
+
+impl MonoCheck {
+    fn cancels(&self) -> bool {
+        self.correlation < 0.0
+            || self
+                .difference_db
+                .is_none_or(|db| db < -MONO_LOSS_WARNING_DB)
+    }
+}
+
+/// How a stereo buffer survives being summed to mono: the (L+R)/2 fold-down is
+/// measured as it would play on both speakers and compared with the original.
+fn mono_compatibility(samples: &[f32], sample_rate: u32) -> Option<MonoCheck> {
+    let stereo = integrated_loudness(samples, 2, sample_rate)?;
+    let folded: Vec<f32> = samples
+        .chunks_exact(2)
+        .flat_map(|f| {
+            let mid = (f[0] + f[1]) * 0.5;
+            [mid, mid]
+        })
+        .collect();
+    let difference_db = integrated_loudness(&folded, 2, sample_rate).map(|mono| mono - stereo);
+
+    let (mut lr, mut ll, mut rr) = (0.0f64, 0.0f64, 0.0f64);
+    for f in samples.chunks_exact(2) {
+        let (l, r) = (f[0] as f64, f[1] as f64);
+        lr += l * r;
+        ll += l * l;
+        rr += r * r;
+    }
+    let correlation = if ll > 0.0 && rr > 0.0 {
+        lr / (ll * rr).sqrt()
+    } else {
+        1.0
+    };
+    Some(MonoCheck {
+        difference_db,
+        correlation,
+    })
+}
+
+fn mp3_bitrate(kbps: u32) -> mp3lame_encoder::Bitrate {
+    use mp3lame_encoder::Bitrate;
+    match kbps {
+        0..=64 => Bitrate::Kbps64,
+        65..=96 => Bitrate::Kbps96,
+        97..=128 => Bitrate::Kbps128,
+        129..=160 => Bitrate::Kbps160,
+        161..=192 => Bitrate::Kbps192,
+        193..=256 => Bitrate::Kbps256,
+        _ => Bitrate::Kbps320,
+    }
+}
+
+/// Encodes an MP3, with `tag` (an ID3 tag, or nothing) in front of the frames.
+fn write_mp3_file(
+    path: &str,
+    samples: &[f32],
+    channels: u16,
+    sample_rate: u32,
+    kbps: u32,
+    tag: &[u8],
+) -> Result<(), String> {
+    use mp3lame_encoder::{Builder, FlushNoGap, InterleavedPcm, MonoPcm, Quality};
+
+    let mut builder = Builder::new().ok_or("Failed to create MP3 encoder")?;
+    builder
+        .set_num_channels(channels as u8)
+        .and_then(|_| builder.set_sample_rate(sample_rate))
+        .and_then(|_| builder.set_brate(mp3_bitrate(kbps)))
+        .and_then(|_| builder.set_quality(Quality::Best))
+        .map_err(|e| format!("Error configuring MP3 encoder: {}", e))?;
+    let mut encoder = builder
+        .build()
+        .map_err(|e| format!("Error configuring MP3 encoder: {}", e))?;
+
+    let frames = samples.len() / channels as usize;
+    let mut out =
+        Vec::with_capacity(tag.len() + mp3lame_encoder::max_required_buffer_size(frames) + 7200);
+    out.extend_from_slice(tag);
+    let encoded = if channels == 1 {
+        encoder.encode_to_vec(MonoPcm(samples), &mut out)
+    } else {
+        encoder.encode_to_vec(InterleavedPcm(samples), &mut out)
+    };
+    encoded
+        .and_then(|_| encoder.flush_to_vec::<FlushNoGap>(&mut out))
+        .map_err(|e| format!("Error encoding MP3: {}", e))?;
+
+    fs::write(path, out).map_err(|e| format!("Error writing export: {}", e))
+}
+
+fn write_flac_file(
+    path: &str,
+    samples: &[f32],
+    channels: u16,
+    sample_rate: u32,
+    bits_per_sample: u16,
+) -> Result<(), String> {
+    use flacenc::component::BitRepr;
+    use flacenc::error::Verify;
+
+    let scale = ((1i32 << (bits_per_sample - 1)) - 1) as f32;
+    let ints: Vec<i32> = samples
+        .iter()
+        .map(|&s| (s.clamp(-1.0, 1.0) * scale).round() as i32)
+        .collect();
+
+    let config = flacenc::config::Encoder::default()
+        .into_verified()
+        .map_err(|(_, e)| format!("Error configuring FLAC encoder: {}", e))?;
+    let source = flacenc::source::MemSource::from_samples(
+        &ints,
+        channels as usize,
+        bits_per_sample as usize,
+        sample_rate as usize,
+    );
+    let stream = flacenc::encode_with_fixed_block_size(&config, source, config.block_size)
+        .map_err(|e| format!("Error encoding FLAC: {:?}", e))?;
+
+    let mut sink = flacenc::bitsink::ByteSink::new();
+    stream
+        .write(&mut sink)
+        .map_err(|e| format!("Error encoding FLAC: {}", e))?;
+    fs::write(path, sink.as_slice()).map_err(|e| format!("Error writing export: {}", e))
+}
+
+/// Output of a finished export, for the status line.
+struct ExportOutput {
+    path: String,
+    sample_rate: u32,
+    channels: u16,
+    measured_lufs: Option<f64>,
+}
+
+/// Runs the export pipeline (downmix, resample, loudness normalisation, then encode)
+/// and writes the result next to the source as `<name>_export.<ext>`.
+fn export_file(
+    path: &str,
+    settings: &ExportSettings,
+    job: &JobControl,
+) -> Result<ExportOutput, String> {
+    export_file_to(path, &export_path(path, settings), settings, job)
+}
+
+/// Where `export_file` writes: `<name>_export.<ext>` next to the source.
+fn export_path(path: &str, settings: &ExportSettings) -> String {
+    sidecar_path(path, &format!("_export.{}", settings.format.extension()))
+}
+
+/// Whether `path` is an earlier export of a recording that's still beside it.
+fn is_export_output(path: &str) -> bool {
+    sidecar_path(path, "")
+        .strip_suffix("_export")
+        .is_some_and(|source| std::path::Path::new(&format!("{}.wav", source)).exists())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum QueueStatus {
+    Waiting,
+    Exporting {
+        attempt: u32,
+    },
+    /// The last attempt failed; it'll be tried again after the backoff.
+    Retrying {
+        attempt: u32,
+        error: String,
+    },
+    Done(String),
+    Failed(String),
+}
+
+impl std::fmt::Display for QueueStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueueStatus::Waiting => write!(f, "Waiting"),
+            QueueStatus::Exporting { attempt: 1 } => write!(f, "Exporting..."),
+            QueueStatus::Exporting { attempt } => write!(f, "Exporting (attempt {})...", attempt),
+            QueueStatus::Retrying { attempt, error } => {
+                write!(f, "Attempt {} failed, retrying: {}", attempt, error)
+            }
+            QueueStatus::Done(path) => write!(f, "Done: {}", path),
+            QueueStatus::Failed(error) => write!(f, "Failed: {}", error),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct QueueItem {
+    file: String,
+    status: QueueStatus,
+}
+
+/// Exports every waiting item in `queue` one at a time. An item that fails is tried
+/// again up to `retries` times, waiting `backoff` and then twice as long each time, so
+/// a locked file or a disk hiccup doesn't sink the whole batch.
+/// Returns how many items were exported and how many failed.
+fn run_export_queue(
+    queue: &Mutex<Vec<QueueItem>>,
+    settings: &ExportSettings,
+    retries: u32,
+    backoff: Duration,
+    job: &JobControl,
+) -> (usize, usize) {
+    let total = queue.lock().unwrap().len();
+    let (mut done, mut failed) = (0, 0);
+    for index in 0..total {
+        let file = {
+            let items = queue.lock().unwrap();
+            if items[index].status != QueueStatus::Waiting {
+                continue;
+            }
+            items[index].file.clone()
+        };
+        let set_status = |status| queue.lock().unwrap()[index].status = status;
+
+        let mut attempt = 1;
+        let status = loop {
+            if job.is_cancelled() {
+                set_status(QueueStatus::Waiting);
+                return (done, failed);
+            }
+            set_status(QueueStatus::Exporting { attempt });
+            // Items get their own progress; the job's tracks the queue as a whole
+            let item_job = JobControl {
+                cancel: Arc::clone(&job.cancel),
+                progress: Arc::default(),
+            };
+            let error = match export_file(&file, settings, &item_job) {
+                Ok(output) => break QueueStatus::Done(output.path),
+                Err(e) => e,
+            };
+            if attempt > retries || job.is_cancelled() {
+                break QueueStatus::Failed(error);
+            }
+
+            set_status(QueueStatus::Retrying {
+                attempt,
+                error: error.clone(),
+            });
+            let wait = backoff * 2u32.saturating_pow(attempt - 1);
+            let until = Instant::now() + wait;
+            while Instant::now() < until && !job.is_cancelled() {
+                std::thread::sleep((until - Instant::now()).min(Duration::from_millis(50)));
+            }
+            attempt += 1;
+        };
+
+        match status {
+            QueueStatus::Done(_) => done += 1,
+            _ => failed += 1,
+        }
+        set_status(status);
+        job.set_progress((index + 1) as f32 / total as f32);
+    }
+    (done, failed)
+}
+
+/// Audio that's been through the export chain but not yet encoded.
+#[derive(Debug, Clone)]
+struct ExportAudio {
+    /// The source file's format, which picks the bit depth of some encoders.
+    source: WavSpec,
+    samples: Vec<f32>,
+    channels: u16,
+    sample_rate: u32,
+    measured_lufs: Option<f64>,
+}
+
+/// The processing half of an export: reads `path` and downmixes, resamples and
+/// normalises it per `settings`, leaving the result in memory.
+fn process_for_export(
+    path: &str,
+    settings: &ExportSettings,
+    job: &JobControl,
+) -> Result<ExportAudio, String> {
+    let check_cancel = || {
+        if job.is_cancelled() {
+            Err("Export cancelled.".to_string())
+        } else {
+            Ok(())
+        }
+    };
+
+    let (spec, mut samples) = read_wav_file(path)?;
+    let mut channels = spec.channels;
+    check_cancel()?;
+    job.set_progress(0.1);
+
+    // LAME only encodes mono or stereo
+    let force_mono = settings.format == ExportFormat::Mp3 && channels > 2;
+    if (settings.mono || force_mono) && channels > 1 {
+        samples = downmix_to_mono(&samples, channels);
+        channels = 1;
+    }
+
+    let sample_rate = match settings.sample_rate {
+        ExportSampleRate::Original => spec.sample_rate,
+        ExportSampleRate::Hz(rate) => rate,
+    };
+    if !(1..=MAX_EXPORT_SAMPLE_RATE).contains(&sample_rate) {
+        return Err(format!(
+            "Export sample rates run from 1 to {} Hz, not {} Hz.",
+            MAX_EXPORT_SAMPLE_RATE, sample_rate
+        ));
+    }
+    if settings.format == ExportFormat::Mp3 && !MP3_SAMPLE_RATES.contains(&sample_rate) {
+        let rates: Vec<String> = MP3_SAMPLE_RATES.iter().map(u32::to_string).collect();
+        return Err(format!(
+            "MP3 export supports sample rates of {} Hz, not {} Hz.",
+            rates.join(", "),
+            sample_rate
+        ));
+    }
+    samples = resample_with_progress(
+        &samples,
+        channels,
+        spec.sample_rate,
+        sample_rate,
+        settings.resample_quality,
+        &mut job.stage(0.1, 0.7),
+    )
+    .ok_or_else(|| "Export cancelled.".to_string())?;
+
+    let measured_lufs = match settings.normalize_lufs {
+        Some(target) => normalize_loudness(&mut samples, channels, sample_rate, target),
+        None => None,
+    };
+    check_cancel()?;
+    job.set_progress(0.8);
+
+    Ok(ExportAudio {
+        source: spec,
+        samples,
+        channels,
+        sample_rate,
+        measured_lufs,
+    })
+}
+
+fn export_file_to(
+    path: &str,
+    out_path: &str,
+    settings: &ExportSettings,
+    job: &JobControl,
+) -> Result<ExportOutput, String> {
+    // Read the cover art up front, so a bad path fails before the slow part
+    let id3_tag = if settings.format == ExportFormat::Mp3 {
+        settings.mp3_tags.to_id3(path)?
+    } else {
+        Vec::new()
+    };
+    let audio = process_for_export(path, settings, job)?;
+
+    // Encoded beside the destination and moved into place once complete, so a failed
+    // or cancelled export never leaves a partial file under the real name
+    let tmp_path = format!("{}.tmp", out_path);
+    let encoded = encode_export(&tmp_path, &audio, settings, &id3_tag).and_then(|()| {
+        if job.is_cancelled() {
+            Err("Export cancelled.".to_string())
+        } else {
+            fs::rename(&tmp_path, out_path).map_err(|e| format!("Error writing export: {}", e))
+        }
+    });
+    if let Err(e) = encoded {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(e);
+    }
+
+    Ok(ExportOutput {
+        path: out_path.to_string(),
+        sample_rate: audio.sample_rate,
+        channels: audio.channels,
+        measured_lufs: audio.measured_lufs,
+    })
+}
+
+/// Encodes processed audio to `out_path` in the export format.
+fn encode_export(
+    out_path: &str,
+    audio: &ExportAudio,
+    settings: &ExportSettings,
+    id3_tag: &[u8],
+) -> Result<(), String> {
+    let (spec, samples, channels, sample_rate) = (
+        audio.source,
+        &audio.samples,
+        audio.channels,
+        audio.sample_rate,
+    );
+    match settings.format {
+        ExportFormat::WavPcm16 => {
+            let spec = WavSpec {
+                channels,
+                sample_rate,
+                bits_per_sample: 16,
+                sample_format: hound::SampleFormat::Int,
+            };
+            let ints: Vec<i16> = samples
+                .iter()
+                .map(|&s| (s.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)
+                .collect();
+            write_wav_file_i16(out_path, spec, &ints)
+                .map_err(|e| format!("Error writing export: {}", e))?;
+        }
+        ExportFormat::WavFloat => {
+            let spec = WavSpec {
+                channels,
+                sample_rate,
+                bits_per_sample: 32,
+                sample_format: hound::SampleFormat::Float,
+            };
+            write_wav_file_f32(out_path, spec, samples)
+                .map_err(|e| format!("Error writing export: {}", e))?;
+        }
+        ExportFormat::Flac => {
+            // 16-bit sources stay 16-bit; float recordings get 24 bits
+            let bits =
+                if spec.sample_format == hound::SampleFormat::Int && spec.bits_per_sample <= 16 {
+                    16
+                } else {
+                    24
+                };
+            write_flac_file(out_path, samples, channels, sample_rate, bits)?;
+        }
+        ExportFormat::Mp3 => write_mp3_file(
+            out_path,
+            samples,
+            channels,
+            sample_rate,
+            settings.mp3_kbps,
+            id3_tag,
+        )?,
+        ExportFormat::Aiff => {
+            // Plain AIFF has no float samples, so float recordings get 24 bits
+            let bits = match spec.sample_format {
+                hound::SampleFormat::Int => spec.bits_per_sample.div_ceil(8) * 8,
+                hound::SampleFormat::Float => 24,
+            };
+            apple::write_aiff(out_path, samples, channels, sample_rate, bits)
+                .map_err(|e| format!("Error writing export: {}", e))?;
+        }
+        ExportFormat::Caf => {
+            let format = match spec.sample_format {
+                hound::SampleFormat::Int => {
+                    apple::PcmFormat::Int(spec.bits_per_sample.div_ceil(8) * 8)
+                }
+                hound::SampleFormat::Float => apple::PcmFormat::Float32,
+            };
+            apple::write_caf(out_path, samples, channels, sample_rate, format)
+                .map_err(|e| format!("Error writing export: {}", e))?;
+        }
+    }
+    Ok(())
+}
+
+const DC_BLOCK_CUTOFF_HZ: f32 = 5.0;
+
+/// One-pole high-pass (`y = x - x[-1] + r * y[-1]`) that removes DC bias, with
+/// independent state per interleaved channel.
+struct DcBlocker {
+    r: f32,
+    channels: usize,
+    prev_x: Vec<f32>,
+    prev_y: Vec<f32>,
+}
+
+impl DcBlocker {
+    fn new(channels: u16, sample_rate: u32) -> Self {
+        let channels = channels.max(1) as usize;
+        Self {
+            r: 1.0 - std::f32::consts::TAU * DC_BLOCK_CUTOFF_HZ / sample_rate.max(1) as f32,
+            channels,
+            prev_x: vec![0.0; channels],
+            prev_y: vec![0.0; channels],
+        }
+    }
+
+    /// Filters whole interleaved frames in place.
+    fn process(&mut self, samples: &mut [f32]) {
+        for frame in samples.chunks_mut(self.channels) {
+            for (ch, s) in frame.iter_mut().enumerate() {
+                let y = *s - self.prev_x[ch] + self.r * self.prev_y[ch];
+                self.prev_x[ch] = *s;
+                self.prev_y[ch] = y;
+                *s = y;
+            }
+        }
+    }
+}
+
+/// Scales each channel of an interleaved buffer by its own gain.
+fn apply_channel_gains(samples: &mut [f32], channels: usize, gains: &[f32]) {
+    if gains.iter().all(|&g| g == 1.0) {
+        return;
+    }
+    for frame in samples.chunks_mut(channels) {
+        for (s, &gain) in frame.iter_mut().zip(gains) {
+            *s *= gain;
+        }
+    }
+}
+
+/// Fades a new stream up from silence over its first `ramp_frames` frames, so a
+/// device waking up doesn't thump. `ramped` counts the frames done, across callbacks;
+/// seeking doesn't reset it.
+fn apply_start_ramp(out: &mut [f32], channels: usize, ramped: &mut usize, ramp_frames: usize) {
+    if *ramped >= ramp_frames {
+        return;
+    }
+    for frame in out.chunks_mut(channels).take(ramp_frames - *ramped) {
+        let gain = *ramped as f32 / ramp_frames as f32;
+        frame.iter_mut().for_each(|s| *s *= gain);
+        *ramped += 1;
+    }
+}
+
+/// Scales the side (L-R) component of interleaved stereo frames: 0 collapses to mono,
+/// 1 leaves the signal unchanged, and values above 1 widen the image.
+fn apply_stereo_width(samples: &mut [f32], width: f32) {
+    for frame in samples.chunks_exact_mut(2) {
+        let mid = (frame[0] + frame[1]) * 0.5;
+        let side = (frame[0] - frame[1]) * 0.5 * width;
+        frame[0] = mid + side;
+        frame[1] = mid - side;
+    }
+}
+
+/// Gain of the centre and surround channels in a 5.1 or 7.1 fold-down (-3 dB, as in
+/// ITU-R BS.775).
+const SURROUND_DOWNMIX_GAIN: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+/// Folds a 5.1 or 7.1 frame (WAV order: L, R, C, LFE, then the surround pairs) down
+/// to stereo. The centre goes to both sides, each surround to its own, and the LFE
+/// is dropped.
+fn surround_to_stereo(frame: &[f32]) -> [f32; 2] {
+    let centre = frame[2] * SURROUND_DOWNMIX_GAIN;
+    let (mut left, mut right) = (frame[0] + centre, frame[1] + centre);
+    for pair in frame[4..].chunks_exact(2) {
+        left += pair[0] * SURROUND_DOWNMIX_GAIN;
+        right += pair[1] * SURROUND_DOWNMIX_GAIN;
+    }
+    [left, right]
+}
+
+/// Fills `out` (interleaved, `out_channels` wide) from as many whole frames of `src`
+/// as fit, returning the number of `src` samples used. 5.1 and 7.1 files are folded
+/// down with `surround_to_stereo` on stereo and mono devices. Other extra channels
+/// are folded in round-robin (so 4 channels on a stereo device give L = 1+3,
+/// R = 2+4, averaged), and a file with fewer channels than the device repeats them
+/// across its outputs.
+fn remix_frames(src: &[f32], src_channels: usize, out: &mut [f32], out_channels: usize) -> usize {
+    let frames = (src.len() / src_channels).min(out.len() / out_channels);
+    let surround = matches!(src_channels, 6 | 8) && out_channels <= 2;
+    for (src_frame, out_frame) in src
+        .chunks_exact(src_channels)
+        .zip(out.chunks_exact_mut(out_channels))
+        .take(frames)
+    {
+        if surround {
+            let [left, right] = surround_to_stereo(src_frame);
+            if let [l, r] = out_frame {
+                (*l, *r) = (left, right);
+            } else {
+                out_frame[0] = (left + right) * 0.5;
+            }
+            continue;
+        }
+        for (o, sample) in out_frame.iter_mut().enumerate() {
+            *sample = if o < src_channels {
+                let folded = (o..src_channels).step_by(out_channels);
+                let count = folded.len();
+                folded.map(|c| src_frame[c]).sum::<f32>() / count as f32
+            } else {
+                src_frame[o % src_channels]
+            };
+        }
+    }
+    out[frames * out_channels..].fill(0.0);
+    frames * src_channels
+}
+
+/// Reads `frames` frames from interleaved `samples` into `out`, starting at the
+/// fractional frame `position` and moving `step` frames each time, interpolating
+/// between the source's frames at `quality`. Stops early at the end of `samples`.
+fn interpolate_frames(
+    samples: &[f32],
+    channels: usize,
+    position: &mut f64,
+    step: f64,
+    frames: usize,
+    quality: ResampleQuality,
+    out: &mut Vec<f32>,
+) {
+    let available = samples.len() / channels;
+    if quality == ResampleQuality::High {
+        let cutoff = (1.0 / step).min(1.0) as f32;
+        let half_width = (PLAYBACK_SINC_ZERO_CROSSINGS / cutoff).ceil() as isize;
+        for _ in 0..frames {
+            if *position as usize >= available {
+                break;
+            }
+            sinc_frame(samples, channels, *position, cutoff, half_width, out);
+            *position += step;
+        }
+        return;
+    }
+    for _ in 0..frames {
+        let frame = *position as usize;
+        if frame >= available {
+            break;
+        }
+        let next = (frame + 1).min(available - 1);
+        let t = (*position - frame as f64) as f32;
+        for c in 0..channels {
+            let a = samples[frame * channels + c];
+            let b = samples[next * channels + c];
+            out.push(a + (b - a) * t);
+        }
+        *position += step;
+    }
+}
+
+/// Slowest and fastest playback speeds offered.
+const MIN_PLAYBACK_SPEED: f32 = 0.5;
+const MAX_PLAYBACK_SPEED: f32 = 2.0;
+
+/// Time between meter updates, assumed for the first one.
+const METER_TICK: Duration = Duration::from_millis(16);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+enum MeterMode {
+    /// Sample peaks: shows how close the signal gets to clipping.
+    #[default]
+    Peak,
+    /// Average power: closer to how loud it sounds.
+    Rms,
+}
+
+impl MeterMode {
+    const ALL: [MeterMode; 2] = [MeterMode::Peak, MeterMode::Rms];
+}
+
+impl std::fmt::Display for MeterMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MeterMode::Peak => write!(f, "Peak"),
+            MeterMode::Rms => write!(f, "RMS"),
+        }
+    }
+}
+
+/// Moves the meter from `level` towards a new `reading` over `dt`: rising with an
+/// `attack_ms` time constant (instantly if 0) and falling at `fall_db_per_sec`.
+fn meter_ballistics(
+    level: f32,
+    reading: f32,
+    dt: Duration,
+    attack_ms: f32,
+    fall_db_per_sec: f32,
+) -> f32 {
+    let secs = dt.as_secs_f32();
+    if reading > level {
+        if attack_ms <= 0.0 {
+            return reading;
+        }
+        let coefficient = 1.0 - (-secs * 1000.0 / attack_ms).exp();
+        level + (reading - level) * coefficient
+    } else {
+        let fallen = level * db_to_amplitude(-fall_db_per_sec * secs);
+        fallen.max(reading)
+    }
+}
+
+/// The level history keeps one peak reading per interval over the last 30 seconds.
+const LEVEL_HISTORY_INTERVAL_MS: u64 = 250;
+const LEVEL_HISTORY_LEN: usize = 120;
+const CLIP_HOLD_MS: u64 = 1000;
+/// Callback load (time taken over the buffer's duration) past which the audio is
+/// shown as strained, and how long the indicator stays red after a dropout.
+const CALLBACK_STRAIN_LOAD: f32 = 0.7;
+const DROPOUT_HOLD_MS: u64 = 2000;
+/// Per-tick decay of the load shown, so a single busy callback stays readable.
+const CALLBACK_LOAD_DECAY: f32 = 0.95;
+
+/// Levels of the audio passing through a callback, for the meter: the peak (as f32
+/// bits), sum of squares and sample count since the last tick, and whether anything
+/// reached full scale. Written by the callback and read on `Tick`.
+struct LevelTap {
+    peak: AtomicU32,
+    power: Mutex<(f64, usize)>,
+    clipped: AtomicBool,
+}
+
+impl LevelTap {
+    fn new() -> Self {
+        Self {
+            peak: AtomicU32::new(0),
+            power: Mutex::new((0.0, 0)),
+            clipped: AtomicBool::new(false),
+        }
+    }
+
+    fn reset(&self) {
+        self.peak.store(0, Ordering::Relaxed);
+        *self.power.lock().unwrap() = (0.0, 0);
+        self.clipped.store(false, Ordering::Relaxed);
+    }
+
+    fn record(&self, samples: &[f32]) {
+        let peak = samples.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+        // Non-negative floats order the same as their bit patterns
+        self.peak.fetch_max(peak.to_bits(), Ordering::Relaxed);
+        let mut power = self.power.lock().unwrap();
+        power.0 += samples.iter().map(|&s| s as f64 * s as f64).sum::<f64>();
+        power.1 += samples.len();
+        drop(power);
+        if peak >= 1.0 {
+            self.clipped.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// The peak, the RMS and whether anything clipped since the last call.
+    fn take(&self) -> (f32, f32, bool) {
+        let peak = f32::from_bits(self.peak.swap(0, Ordering::Relaxed));
+        let (sum, count) = std::mem::take(&mut *self.power.lock().unwrap());
+        let rms = if count > 0 {
+            (sum / count as f64).sqrt() as f32
+        } else {
+            0.0
+        };
+        (peak, rms, self.clipped.swap(false, Ordering::Relaxed))
+    }
+}
+
+/// Timing of an audio callback, written by the callback and read on `Tick`. The
+/// callback pays for two clock reads and a few atomic operations.
+struct CallbackHealth {
+    epoch: Instant,
+    /// When the last callback started, in ns after `epoch` plus one (0 before the first).
+    last_start: AtomicU64,
+    /// Duration of the last callback's buffer, in ns.
+    last_budget: AtomicU64,
+    /// Callbacks that came too late to have kept the audio going, since the last `take`.
+    late: AtomicU32,
+    /// Highest load since the last `take`, as f32 bits.
+    load: AtomicU32,
+}
+
+impl CallbackHealth {
+    fn new() -> Self {
+        Self {
+            epoch: Instant::now(),
+            last_start: AtomicU64::new(0),
+            last_budget: AtomicU64::new(0),
+            late: AtomicU32::new(0),
+            load: AtomicU32::new(0),
+        }
+    }
+
+    /// Forgets the previous stream's callbacks.
+    fn reset(&self) {
+        self.last_start.store(0, Ordering::Relaxed);
+        self.last_budget.store(0, Ordering::Relaxed);
+        self.late.store(0, Ordering::Relaxed);
+        self.load.store(0, Ordering::Relaxed);
+    }
+
+    /// Records a callback for `frames` frames that ran from `started` to `finished`.
+    fn record(&self, started: Instant, finished: Instant, frames: usize, sample_rate: u32) {
+        let budget = frames as u64 * 1_000_000_000 / sample_rate.max(1) as u64;
+        let start = started.saturating_duration_since(self.epoch).as_nanos() as u64 + 1;
+        let last = self.last_start.swap(start, Ordering::Relaxed);
+        let last_budget = self.last_budget.swap(budget, Ordering::Relaxed);
+        // The device calls again as each buffer runs out; a gap of two buffers means
+        // the one in between ran dry
+        if last > 0 && start.saturating_sub(last) > 2 * budget.max(last_budget) {
+            self.late.fetch_add(1, Ordering::Relaxed);
+        }
+        if budget > 0 {
+            let load =
+                finished.saturating_duration_since(started).as_nanos() as f32 / budget as f32;
+            // Non-negative floats order the same as their bit patterns
+            self.load.fetch_max(load.to_bits(), Ordering::Relaxed);
+        }
+    }
+
+    /// Late callbacks and the highest load since the last call.
+    fn take(&self) -> (u32, f32) {
+        let late = self.late.swap(0, Ordering::Relaxed);
+        let load = f32::from_bits(self.load.swap(0, Ordering::Relaxed));
+        (late, load)
+    }
+}
+/// How far the second output may drift from the main one before it's pulled back.
+const SECOND_OUTPUT_RESYNC_SECS: f64 = 0.005;
+
+/// An entry in the second output picker: a device, or `None` for no second output.
+#[derive(Debug, Clone, PartialEq)]
+struct SecondOutput(Option<String>);
+
+impl std::fmt::Display for SecondOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.0.as_deref().unwrap_or("Off"))
+    }
+}
+
+/// Longest fade-up offered for a new output stream.
+const MAX_START_RAMP_MS: u32 = 500;
+
+/// An entry in the audio host list: a host by name, or the system default.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct HostChoice {
+    name: Option<String>,
+    available: bool,
+}
+
+impl std::fmt::Display for HostChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.name {
+            None => write!(f, "System default"),
+            Some(name) if self.available => write!(f, "{}", name),
+            Some(name) => write!(f, "{} (unavailable)", name),
+        }
+    }
+}
+
+const SHORT_CLIP_SECS: f32 = 10.0;
+const LONG_CLIP_SECS: f32 = 300.0;
+
+/// Picks a small output buffer for short clips (fast start) and a large one for long
+/// files (fewer glitches), falling back to the device default if it isn't supported.
+fn playback_buffer_size(
+    total_samples: usize,
+    channels: u16,
+    sample_rate: u32,
+    supported: &cpal::SupportedBufferSize,
+) -> BufferSize {
+    let secs = total_samples as f32 / (channels.max(1) as f32 * sample_rate.max(1) as f32);
+    let frames = if secs < SHORT_CLIP_SECS {
+        256
+    } else if secs < LONG_CLIP_SECS {
+        1024
+    } else {
+        4096
+    };
+
+    match supported {
+        cpal::SupportedBufferSize::Range { min, max } if (*min..=*max).contains(&frames) => {
+            BufferSize::Fixed(frames)
+        }
+        _ => BufferSize::Default,
+    }
+}
+
+/// Assumed callback size when the device picks its own buffer size.
+const DEFAULT_BUFFER_FRAMES_ESTIMATE: u32 = 2048;
+
+/// How long to keep capturing after Stop so the callbacks already queued by the
+/// device are delivered: two buffer periods, plus the user's extra tail.
+fn stop_flush_delay(buffer_size: BufferSize, sample_rate: u32, extra_ms: u32) -> Duration {
+    let frames = match buffer_size {
+        BufferSize::Fixed(frames) => frames,
+        BufferSize::Default => DEFAULT_BUFFER_FRAMES_ESTIMATE,
+    };
+    let periods = Duration::from_secs_f64(2.0 * frames as f64 / sample_rate.max(1) as f64);
+    periods + Duration::from_millis(extra_ms as u64)
+}
+
+/// Room left under the file size cap for the WAV headers and the metadata chunks
+/// (bext, comments, cue points) written alongside the audio.
+const WAV_HEADER_ALLOWANCE: usize = 4096;
+
+/// Buffer size requested in low-latency mode: about 3 ms at 44.1/48 kHz.
+const LOW_LATENCY_FRAMES: u32 = 128;
+/// Recording rate used whenever the input device supports it.
+const PREFERRED_SAMPLE_RATE: u32 = 48000;
+const LOW_LATENCY_UNSUPPORTED: &str = " (low latency not supported, using default buffer)";
+
+/// The low-latency buffer size clamped to what the device reports, or `None` if the
+/// device doesn't report a range and so can't be asked for a smaller buffer.
+fn low_latency_buffer_size(supported: &cpal::SupportedBufferSize) -> Option<BufferSize> {
+    match supported {
+        cpal::SupportedBufferSize::Range { min, max } if min <= max => {
+            Some(BufferSize::Fixed(LOW_LATENCY_FRAMES.clamp(*min, *max)))
+        }
+        _ => None,
+    }
+}
+
+/// Ranks sample formats by conversion cost, native f32 first.
+fn sample_format_priority(format: SampleFormat) -> u32 {
+    match format {
+        SampleFormat::F32 => 0,
+        SampleFormat::I16 => 1,
+        SampleFormat::I32 => 2,
+        SampleFormat::U16 => 3,
+        SampleFormat::U8 => 100,
+        _ => 50,
+    }
+}
+
+const CUE_DURATION_MS: u32 = 120;
+const CUE_FLASH_MS: u64 = 300;
+const CUE_START_FREQUENCY: f32 = 880.0;
+const CUE_STOP_FREQUENCY: f32 = 440.0;
+
+struct CueTone {
+    frame: usize,
+    total_frames: usize,
+    sample_rate: f32,
+    frequency: f32,
+}
+
+impl CueTone {
+    fn next_sample(&mut self) -> f32 {
+        if self.frame >= self.total_frames {
+            return 0.0;
+        }
+        let t = self.frame as f32 / self.sample_rate;
+        // Short linear ramps on both ends so the burst doesn't click
+        let ramp_frames = (self.total_frames / 10).max(1);
+        let edge = self.frame.min(self.total_frames - self.frame);
+        let envelope = (edge as f32 / ramp_frames as f32).min(1.0);
+        self.frame += 1;
+        (t * self.frequency * std::f32::consts::TAU).sin() * 0.3 * envelope
+    }
+}
+
+/// Errors reported by an output stream other than playback's, tagged with which
+/// stream it is, for `check_output_errors` to log.
+type SideStreamError = (&'static str, StreamError);
+
+/// Error callback that passes a side stream's errors on to `errors`.
+fn report_side_errors(
+    errors: &mpsc::Sender<SideStreamError>,
+    stream: &'static str,
+) -> ErrorCallback {
+    let errors = errors.clone();
+    Box::new(move |err| {
+        let _ = errors.send((stream, err));
+    })
+}
+
+/// Plays a brief sine burst on the default output device. The returned stream must be
+/// kept alive until the tone has finished.
+fn play_cue_tone(
+    backend: &dyn AudioBackend,
+    frequency: f32,
+    on_error: ErrorCallback,
+) -> Result<Box<dyn AudioStream>, String> {
+    let format = backend.default_output_format()?;
+    let channels = format.channels.max(1) as usize;
+    let mut tone = CueTone {
+        frame: 0,
+        total_frames: (format.sample_rate * CUE_DURATION_MS / 1000) as usize,
+        sample_rate: format.sample_rate as f32,
+        frequency,
+    };
+
+    backend.build_output_stream(
+        &format,
+        Box::new(move |out: &mut [f32]| {
+            for frame in out.chunks_mut(channels) {
+                frame.fill(tone.next_sample());
+            }
+        }),
+        on_error,
+    )
+}
+
+const MIN_METRONOME_BPM: f32 = 30.0;
+const MAX_METRONOME_BPM: f32 = 300.0;
+const METRONOME_CLICK_MS: f32 = 15.0;
+const METRONOME_CLICK_FREQUENCY: f32 = 1500.0;
+/// Tap tempo averages the intervals between this many of the latest taps...
+const TAP_TEMPO_TAPS: usize = 6;
+/// ...and starts counting afresh after a pause this long.
+const TAP_TEMPO_RESET: Duration = Duration::from_secs(2);
+
+/// Tempo from the spacing of `taps` (oldest first), or `None` before the second tap.
+fn tap_tempo_bpm(taps: &VecDeque<Instant>) -> Option<f32> {
+    let (first, last) = (taps.front()?, taps.back()?);
+    let intervals = taps.len().checked_sub(1).filter(|&n| n > 0)?;
+    let beat = last.duration_since(*first).as_secs_f32() / intervals as f32;
+    (beat > 0.0).then(|| (60.0 / beat).clamp(MIN_METRONOME_BPM, MAX_METRONOME_BPM))
+}
+
+/// Sample `frame` frames into a metronome beat: a short decaying blip, then silence.
+fn metronome_click(frame: usize, sample_rate: u32) -> f32 {
+    let len = (sample_rate as f32 * METRONOME_CLICK_MS / 1000.0) as usize;
+    if frame >= len {
+        return 0.0;
+    }
+    let t = frame as f32 / sample_rate as f32;
+    let envelope = 1.0 - frame as f32 / len as f32;
+    (t * METRONOME_CLICK_FREQUENCY * std::f32::consts::TAU).sin() * envelope * 0.5
+}
+
+/// Clicks on the default output at `bpm` (f32 bits), which can change while it runs.
+/// Only heard, never recorded.
+fn play_metronome(
+    backend: &dyn AudioBackend,
+    bpm: Arc<AtomicU32>,
+    on_error: ErrorCallback,
+) -> Result<Box<dyn AudioStream>, String> {
+    let format = backend.default_output_format()?;
+    let channels = format.channels.max(1) as usize;
+    let sample_rate = format.sample_rate.max(1);
+    let mut frame_in_beat = 0usize;
+    backend.build_output_stream(
+        &format,
+        Box::new(move |out: &mut [f32]| {
+            let bpm = f32::from_bits(bpm.load(Ordering::Relaxed));
+            let beat_frames = (sample_rate as f32 * 60.0 / bpm.max(MIN_METRONOME_BPM)) as usize;
+            for frame in out.chunks_mut(channels) {
+                if frame_in_beat >= beat_frames {
+                    frame_in_beat = 0;
+                }
+                frame.fill(metronome_click(frame_in_beat, sample_rate));
+                frame_in_beat += 1;
+            }
+        }),
+        on_error,
+    )
+}
+
+/// Most input the monitor lets queue up before dropping the oldest, so drift
+/// between the input and output clocks can't build into an audible delay.
+const MONITOR_MAX_LAG_MS: u32 = 100;
+
+/// An output stream playing whatever the input callback pushes onto `queue`, which
+/// holds interleaved frames in the `input` format. Silence while it runs dry.
+fn play_monitor(
+    backend: &dyn AudioBackend,
+    input: &StreamFormat,
+    queue: Arc<Mutex<VecDeque<f32>>>,
+    on_error: ErrorCallback,
+) -> Result<Box<dyn AudioStream>, String> {
+    let mut format = backend.default_output_format()?;
+    if format.sample_rate != input.sample_rate {
+        let supported = backend.supported_output_formats()?;
+        let at_rate = supported
+            .iter()
+            .filter(|f| f.contains_rate(input.sample_rate))
+            .min_by_key(|f| f.channels != format.channels)
+            .ok_or_else(|| {
+                format!(
+                    "the output device can't run at the input's {} Hz",
+                    input.sample_rate
+                )
+            })?;
+        format = at_rate.with_sample_rate(input.sample_rate);
+    }
+    let in_channels = input.channels.max(1) as usize;
+    let out_channels = format.channels.max(1) as usize;
+    let mut frame = vec![0.0f32; in_channels];
+    backend.build_output_stream(
+        &format,
+        Box::new(move |out: &mut [f32]| {
+            let mut queue = queue.lock().unwrap();
+            for out_frame in out.chunks_mut(out_channels) {
+                if queue.len() >= in_channels {
+                    for (slot, sample) in frame.iter_mut().zip(queue.drain(..in_channels)) {
+                        *slot = sample;
+                    }
+                } else {
+                    frame.fill(0.0);
+                }
+                // Extra output channels repeat the last input one, so mono reaches both ears
+                for (ch, sample) in out_frame.iter_mut().enumerate() {
+                    *sample = frame[ch.min(in_channels - 1)];
+                }
+            }
+        }),
+        on_error,
+    )
+}
+
+const LATENCY_LEAD_SECS: f32 = 0.25;
+const LATENCY_LISTEN_SECS: f32 = 1.0;
+const LATENCY_CLICK_MS: f32 = 2.0;
+const LATENCY_CLICK_FREQUENCY: f32 = 1000.0;
+/// Normalised correlation needed before a spot in the input counts as the click.
+const LATENCY_MATCH_THRESHOLD: f32 = 0.5;
+
+/// A short Hann-windowed burst; easier to pick out after the converters' filtering
+/// than a single-sample impulse.
+fn latency_click(sample_rate: u32) -> Vec<f32> {
+    let len = ((sample_rate as f32 * LATENCY_CLICK_MS / 1000.0) as usize).max(2);
+    (0..len)
+        .map(|i| {
+            let t = i as f32 / sample_rate as f32;
+            let window = 0.5 - 0.5 * (std::f32::consts::TAU * i as f32 / (len - 1) as f32).cos();
+            (t * LATENCY_CLICK_FREQUENCY * std::f32::consts::TAU).sin() * window * 0.8
+        })
+        .collect()
+}
+
+/// Where `click` best matches `captured`, searching from `from`, by normalised
+/// cross-correlation. `None` if nothing looks enough like it.
+fn find_click(captured: &[f32], click: &[f32], from: usize) -> Option<usize> {
+    let click_energy: f32 = click.iter().map(|s| s * s).sum();
+    let mut best = None;
+    let mut best_score = LATENCY_MATCH_THRESHOLD;
+    for start in from..captured.len().saturating_sub(click.len()) {
+        let window = &captured[start..start + click.len()];
+        let energy: f32 = window.iter().map(|s| s * s).sum();
+        if energy < 1e-6 {
+            continue;
+        }
+        let dot: f32 = window.iter().zip(click).map(|(a, b)| a * b).sum();
+        let score = dot / (energy * click_energy).sqrt();
+        if score > best_score {
+            best_score = score;
+            best = Some(start);
+        }
+    }
+    best
+}
+
+/// A loopback latency measurement in progress: a click goes out a moment after the
+/// streams start, and the input is searched for it once `LATENCY_LISTEN_SECS` are up.
+struct LatencyTest {
+    started: Instant,
+    input_rate: u32,
+    // First channel of the input
+    captured: Arc<Mutex<Vec<f32>>>,
+    // Input frames captured when the click was handed to the output
+    click_at: Arc<Mutex<Option<usize>>>,
+    _streams: (Box<dyn AudioStream>, Box<dyn AudioStream>),
+}
+
+/// Test tones are stereo at this rate and last this long unless stopped.
+const TEST_TONE_SAMPLE_RATE: u32 = 48000;
+const TEST_TONE_SECS: f32 = 10.0;
+
+/// What's playing: a file, or the test tone from Settings.
+#[derive(Debug, Clone, PartialEq)]
+enum PlaybackSource {
+    File(String),
+    TestTone,
+}
+
+impl std::fmt::Display for PlaybackSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PlaybackSource::File(file) => f.write_str(file),
+            PlaybackSource::TestTone => f.write_str("Test tone"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ToneWaveform {
+    Sine,
+    WhiteNoise,
+    PinkNoise,
+}
+
+impl ToneWaveform {
+    const ALL: [ToneWaveform; 3] = [
+        ToneWaveform::Sine,
+        ToneWaveform::WhiteNoise,
+        ToneWaveform::PinkNoise,
+    ];
+}
+
+impl std::fmt::Display for ToneWaveform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ToneWaveform::Sine => write!(f, "Sine"),
+            ToneWaveform::WhiteNoise => write!(f, "White noise"),
+            ToneWaveform::PinkNoise => write!(f, "Pink noise"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ToneSpec {
+    waveform: ToneWaveform,
+    /// Only used by the sine.
+    frequency: f32,
+    /// Peak level in dBFS.
+    level_db: f32,
+}
+
+/// `secs` of mono test signal at `sample_rate`, peaking at the spec's level. Noise
+/// comes from a fixed-seed xorshift, and pink noise from filtering it (Paul Kellet's
+/// economy filter).
+fn test_tone(spec: &ToneSpec, sample_rate: u32, secs: f32) -> Vec<f32> {
+    let len = (sample_rate as f32 * secs) as usize;
+    let mut state: u32 = 0x9e37_79b9;
+    let mut white = move || {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state as f32 / u32::MAX as f32 * 2.0 - 1.0
+    };
+    let mut tone: Vec<f32> = match spec.waveform {
+        ToneWaveform::Sine => (0..len)
+            .map(|i| (i as f32 / sample_rate as f32 * spec.frequency * std::f32::consts::TAU).sin())
+            .collect(),
+        ToneWaveform::WhiteNoise => (0..len).map(|_| white()).collect(),
+        ToneWaveform::PinkNoise => {
+            let (mut b0, mut b1, mut b2) = (0.0f32, 0.0f32, 0.0f32);
+            (0..len)
+                .map(|_| {
+                    let w = white();
+                    b0 = 0.99765 * b0 + w * 0.0990460;
+                    b1 = 0.96300 * b1 + w * 0.2965164;
+                    b2 = 0.57000 * b2 + w * 1.0526913;
+                    b0 + b1 + b2 + w * 0.1848
+                })
+                .collect()
+        }
+    };
+    let peak = tone.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+    if peak > 0.0 {
+        let gain = db_to_amplitude(spec.level_db) / peak;
+        tone.iter_mut().for_each(|s| *s *= gain);
+    }
+    tone
+}
+
+/// `path` made absolute, for commands that outlive the current folder (which may
+/// change before they start). Left as it is if that isn't possible.
+fn absolute_path(path: &str) -> String {
+    fs::canonicalize(path)
+        .map(|p| p.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| path.to_string())
+}
+
+/// Splits a command line into words on whitespace. A word in double or single quotes
+/// keeps its spaces (and the other kind of quote), so programs and arguments with
+/// spaces in them can be given.
+fn split_command(command: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    for c in command.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => word.get_or_insert_default().push(c),
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                word.get_or_insert_default();
+            }
+            None if c.is_whitespace() => words.extend(word.take()),
+            None => word.get_or_insert_default().push(c),
+        }
+    }
+    if let Some(q) = quote {
+        return Err(format!(
+            "The post-save command has an unclosed {} quote.",
+            q
+        ));
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// The post-save `command` set up to run on `path`.
+fn post_save_command(command: &str, path: &str) -> Result<std::process::Command, String> {
+    let words = split_command(command)?;
+    let (program, args) = words.split_first().ok_or("No post-save command set.")?;
+    let mut process = std::process::Command::new(program);
+    process
+        .args(args)
+        .arg(path)
+        .stdin(std::process::Stdio::null());
+    Ok(process)
+}
+
+/// Runs the post-save `command` on `path`, waiting for it to exit. Gives back a line
+/// for the log, with the end of its error output if it failed.
+fn run_post_save_command(command: &str, path: &str) -> Result<String, String> {
+    let output = post_save_command(command, path)?
+        .output()
+        .map_err(|e| format!("Couldn't run post-save command '{}': {}", command, e))?;
+    if output.status.success() {
+        return Ok(format!("Post-save command finished ({}).", output.status));
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    match stderr.lines().rev().find(|line| !line.trim().is_empty()) {
+        Some(line) => Err(format!(
+            "Post-save command failed ({}): {}",
+            output.status,
+            line.trim()
+        )),
+        None => Err(format!("Post-save command failed ({}).", output.status)),
+    }
+}
+
+/// Runs blocking work on its own thread so the UI stays responsive; the returned
+/// future resolves with the result (or `None` if the worker panicked).
+fn run_in_background<T: Send + 'static>(
+    work: impl FnOnce() -> T + Send + 'static,
+) -> impl std::future::Future<Output = Option<T>> {
+    let (tx, rx) = iced::futures::channel::oneshot::channel();
+    std::thread::spawn(move || {
+        let _ = tx.send(work());
+    });
+    async move { rx.await.ok() }
+}
+
+/// Shared between the UI and a background job: the UI sets the cancel flag and reads
+/// progress; the job checks the flag between steps and reports how far it has got.
+#[derive(Clone, Default)]
+struct JobControl {
+    cancel: Arc<AtomicBool>,
+    // Fraction done, in thousandths
+    progress: Arc<AtomicU32>,
+}
+
+impl JobControl {
+    fn is_cancelled(&self) -> bool {
+        self.cancel.load(Ordering::Relaxed)
+    }
+
+    fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+
+    fn set_progress(&self, fraction: f32) {
+        let permille = (fraction.clamp(0.0, 1.0) * 1000.0).round() as u32;
+        self.progress.store(permille, Ordering::Relaxed);
+    }
+
+    fn progress(&self) -> f32 {
+        self.progress.load(Ordering::Relaxed) as f32 / 1000.0
+    }
+
+    /// Progress callback for one step of a job that covers `from..to` of the whole.
+    /// Returns `false` once the job has been cancelled.
+    fn stage(&self, from: f32, to: f32) -> impl FnMut(f32) -> bool + '_ {
+        move |fraction| {
+            self.set_progress(from + (to - from) * fraction);
+            !self.is_cancelled()
+        }
+    }
+}
+
+struct BackgroundJob {
+    id: u64,
+    label: String,
+    control: JobControl,
+    /// The file the job reads and rewrites, if it works on just one; that file is
+    /// off limits to editing until the job finishes.
+    file: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+enum Message {
+    #[cfg(feature = "tray")]
+    Tray(tray::TrayAction),
+    StartRecording,
+    StopRecording,
+    ArmRecording,
+    DisarmRecording,
+    RerecordLastTake,
+    StartMonitorOnly,
+    PlayFile(String),
+    PreviewFile(String),
+    PreviewSelected,
+    UpdatePreviewLength(String),
+    PausePlayback,
+    SkipBackward,
+    SkipForward,
+    UpdateSkipBack(String),
+    UpdateSkipForward(String),
+    ResumePlayback,
+    StopPlayback,
+    DeleteFile(String),
+    StartRename(String),
+    UpdateRenameName(String),
+    ConfirmRename,
+    ToggleBatchRename,
+    ConfirmBatch,
+    CancelBatch,
+    UndoBatch,
+    UpdateBatchRenameTemplate(String),
+    UpdateOpenFile(String),
+    OpenForEditing(String),
+    ApplyBatchRename,
+    CancelRename,
+    Tick(Instant),
+    CheckIdleOutput(Instant),
+    Toggle,
+    ToggleRecording,
+    TogglePlayback,
+    SelectSpaceAction(SpaceAction),
+    SelectTimerPrecision(TimerPrecision),
+    SelectMeterMode(MeterMode),
+    UpdateMeterAttack(String),
+    UpdateMeterTarget(String),
+    UpdateMeterTolerance(String),
+    UpdateMeterFall(String),
+    PlayTestTone(ToneSpec),
+    SelectToneWaveform(ToneWaveform),
+    UpdateToneFrequency(String),
+    UpdateToneLevel(String),
+    ToggleTimerHours(bool),
+    FinalizeRecording,
+    DiscardRecording,
+    UpdateTakeName(String),
+    TakeNameSuggested(Result<Option<String>, String>),
+    RecoverInterruptedTake,
+    DiscardInterruptedTake,
+    ToggleAutoSave(bool),
+    ToggleLowLatency(bool),
+    ToggleLiveCaptions(bool),
+    ToggleNameBySpeech(bool),
+    UpdatePostSaveCommand(String),
+    PostSaveCommandFinished(String, Result<String, String>),
+    TranscriptSaved(Result<(), String>),
+    ToggleRecursiveListing(bool),
+    UpdateFolder(String),
+    OpenFolder(String),
+    UpdateListingDepth(String),
+    MeasureLatency,
+    ToggleAutoLevel(bool),
+    UpdateAutoLevelTarget(String),
+    ToggleKeepOutputWarm(bool),
+    ToggleOutputDither(bool),
+    UpdateWarmOutputIdle(String),
+    UpdateStartRamp(String),
+    SelectPlaybackResampleQuality(ResampleQuality),
+    TogglePreserveSourceFormat(bool),
+    DetectChapters(String),
+    UpdateChapterMinGap(String),
+    UpdateSkipIntro(String),
+    UpdateStopTail(String),
+    UpdatePreRoll(String),
+    UpdateMaxFileSize(String),
+    ToggleResumeLastPosition(bool),
+    ToggleResumeOverSkip(bool),
+    ToggleDcBlock(bool),
+    NextChapter,
+    PreviousChapter,
+    ExportChapters,
+    SplitAtMarkers,
+    ExportChannels,
+    AssignAbSlot(usize, String),
+    ToggleAbLoudnessMatch(bool),
+    StartAbCompare,
+    ToggleWaveformOverlay,
+    SwitchAbTake,
+    ToggleLog,
+    OpenDeviceInspector,
+    CloseDeviceInspector,
+    SeekWaveform(f32),
+    JumpToSpeech(f32),
+    ZoomWaveform(f32),
+    ScrollWaveform(f32),
+    SeekOverview(f32),
+    ToggleWaveformOverview(bool),
+    ClearLog,
+    ToggleAudioCues(bool),
+    ToggleDarkTheme(bool),
+    OpenSettings,
+    CloseSettings,
+    SetStereoWidth(f32),
+    SetPlaybackSpeed(f32),
+    SavePlaybackSpeed,
+    ResetPlaybackSpeed,
+    RememberPlaybackSettings,
+    ClearPlaybackSettings,
+    SetChannelGain(usize, f32),
+    SaveChannelGains,
+    SelectSecondOutput(Option<String>),
+    SelectAudioHost(HostChoice),
+    SetOutputVolume(f32),
+    SelectFirstOutputChannel(u16),
+    SetSecondOutputVolume(f32),
+    SaveOutputVolumes,
+    ResetProcessing,
+    FindDuplicates,
+    UpdateFadeIn(String),
+    UpdateSilenceLength(String),
+    InsertSilence,
+    UpdateNormalizeTarget(String),
+    NormalizeFile,
+    TogglePolarityChannel(usize, bool),
+    ToggleInvertPlayback(bool),
+    InvertPolarity,
+    ToggleMetronome,
+    ToggleInputMonitor(bool),
+    ToggleMonitorProcessed(bool),
+    UpdateMetronomeBpm(String),
+    TapTempo(Instant),
+    KeyPressed(Key, Modifiers),
+    NudgeLevel(f32),
+    DismissTakeSummary,
+    CloseRequested(iced::window::Id),
+    RemapShortcut(ShortcutAction),
+    ResetShortcuts,
+    SelectMarkerFormat(MarkerFormat),
+    ExportMarkers,
+    ImportMarkers,
+    UpdateImageWidth(String),
+    UpdateImageHeight(String),
+    UpdateImageForeground(String),
+    UpdateImageBackground(String),
+    ExportWaveformImage,
+    MonoCheck,
+    UpdateFadeOut(String),
+    ApplyFades,
+    RepairFile,
+    TrimSilence,
+    DuplicateAndTrim,
+    ToggleAutoTrim(bool),
+    ToggleBwfMetadata(bool),
+    ToggleProcessingNote(bool),
+    DropMark,
+    UpdateRecordingDescription(String),
+    SelectProject(Project),
+    CloseProject,
+    SelectProjectClip(String),
+    UpdateProjectName(String),
+    UpdateProjectClips(String),
+    CreateProject,
+    UpdateTrimThreshold(String),
+    UpdateTrimPadding(String),
+    DeleteDuplicates,
+    ShowHelp,
+    CloseHelp,
+    ExportFile(String),
+    PreviewExport(String),
+    ExportPreviewReady(String, Result<ExportAudio, String>),
+    JobFinished(u64, Result<String, String>),
+    ExportAll,
+    RetryExportQueue,
+    ClearExportQueue,
+    UpdateExportRetries(String),
+    UpdateExportBackoff(String),
+    CancelJob,
+    SelectExportSampleRate(ExportSampleRate),
+    SelectExportResampleQuality(ResampleQuality),
+    ToggleExportMono(bool),
+    UpdateMp3Title(String),
+    UpdateMp3Artist(String),
+    UpdateMp3Album(String),
+    UpdateMp3CoverArt(String),
+    SelectExportPreset(ExportPreset),
+    SelectExportFormat(ExportFormat),
+    ToggleExportNormalize(bool),
+    UpdateExportLufs(String),
+    UpdatePresetName(String),
+    SaveExportPreset,
+    SelectFile(String),
+    AnalysisFinished(String, SystemTime, Result<Analysis, String>),
+    UpdateNotes(String),
+    SetLabel(Option<ColorLabel>),
+    UpdateTagInput(String),
+    AddTag,
+    RemoveTag(String),
+    UpdateSearch(String),
+    ToggleGroupByDate(bool),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Screen {
+    Main,
+    Settings,
+    Help,
+    Devices,
+}
+
+/// Snapshot of one device's capabilities for the inspector screen.
+struct DeviceReport {
+    role: &'static str,
+    name: Result<String, String>,
+    default_format: Result<StreamFormat, String>,
+    formats: Result<Vec<SupportedFormat>, String>,
+}
+
+impl DeviceReport {
+    fn input(backend: &dyn AudioBackend) -> Self {
+        Self {
+            role: "Input",
+            name: backend.input_device_name(),
+            default_format: backend.default_input_format(),
+            formats: backend.supported_input_formats(),
+        }
+    }
+
+    fn output(backend: &dyn AudioBackend) -> Self {
+        Self {
+            role: "Output",
+            name: backend.output_device_name(),
+            default_format: backend.default_output_format(),
+            formats: backend.supported_output_formats(),
+        }
+    }
+}
+
+fn describe_buffer_size(buffer_size: &cpal::SupportedBufferSize) -> String {
+    match buffer_size {
+        cpal::SupportedBufferSize::Range { min, max } => format!("{}-{} frames", min, max),
+        cpal::SupportedBufferSize::Unknown => "unknown".into(),
+    }
+}
+
+const STATUS_LOG_LEN: usize = 200;
+
+/// Step for the gain and volume nudge shortcuts.
+const LEVEL_NUDGE_DB: f32 = 1.0;
+/// Range of the per-channel input gains, either side of 0 dB.
+const INPUT_GAIN_RANGE_DB: f32 = 24.0;
+/// Quietest playback volume the nudge shortcuts go down to.
+const MIN_NUDGE_VOLUME_DB: f32 = -60.0;
+/// How long the level readout stays up after a nudge.
+const LEVEL_NUDGE_SHOW_MS: u64 = 1500;
+/// How long the summary of a take stays up after it's saved.
+const TAKE_SUMMARY_SECS: u64 = 10;
+
+/// Something a keyboard shortcut can do. The key for each one is remappable in
+/// settings; `Config::keymap` holds the keys that differ from the defaults.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+enum ShortcutAction {
+    Toggle,
+    ToggleRecording,
+    TogglePlayback,
+    StopPlayback,
+    SkipBackward,
+    SkipForward,
+    SwitchAbTake,
+    DropMark,
+    PreviewSelected,
+    DuplicateAndTrim,
+    TapTempo,
+    NudgeLevelUp,
+    NudgeLevelDown,
+    ShowHelp,
+    CloseHelp,
+}
+
+impl ShortcutAction {
+    const ALL: [ShortcutAction; 15] = [
+        ShortcutAction::Toggle,
+        ShortcutAction::ToggleRecording,
+        ShortcutAction::TogglePlayback,
+        ShortcutAction::StopPlayback,
+        ShortcutAction::SkipBackward,
+        ShortcutAction::SkipForward,
+        ShortcutAction::SwitchAbTake,
+        ShortcutAction::DropMark,
+        ShortcutAction::PreviewSelected,
+        ShortcutAction::DuplicateAndTrim,
+        ShortcutAction::TapTempo,
+        ShortcutAction::NudgeLevelUp,
+        ShortcutAction::NudgeLevelDown,
+        ShortcutAction::ShowHelp,
+        ShortcutAction::CloseHelp,
+    ];
+
+    /// Key name as produced by `key_name`.
+    fn default_key(self) -> &'static str {
+        match self {
+            ShortcutAction::Toggle => "Space",
+            ShortcutAction::ToggleRecording => "r",
+            ShortcutAction::TogglePlayback => "k",
+            ShortcutAction::StopPlayback => "p",
+            ShortcutAction::SkipBackward => "Left",
+            ShortcutAction::SkipForward => "Right",
+            ShortcutAction::SwitchAbTake => "a",
+            ShortcutAction::DropMark => "m",
+            ShortcutAction::PreviewSelected => "v",
+            ShortcutAction::DuplicateAndTrim => "t",
+            ShortcutAction::TapTempo => "b",
+            ShortcutAction::NudgeLevelUp => "Shift+Up",
+            ShortcutAction::NudgeLevelDown => "Shift+Down",
+            ShortcutAction::ShowHelp => "?",
+            ShortcutAction::CloseHelp => "Esc",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            ShortcutAction::Toggle => {
+                "Start/stop recording, or pause/resume playback (see Settings)"
+            }
+            ShortcutAction::ToggleRecording => "Start/stop recording",
+            ShortcutAction::TogglePlayback => "Play/pause the current or selected file",
+            ShortcutAction::StopPlayback => "Stop playback",
+            ShortcutAction::SkipBackward => "Jump back during playback (see Settings)",
+            ShortcutAction::SkipForward => "Jump forward during playback (see Settings)",
+            ShortcutAction::SwitchAbTake => "Switch between takes A and B while comparing",
+            ShortcutAction::DropMark => "Drop a cue point while recording",
+            ShortcutAction::PreviewSelected => "Preview the start of the selected file",
+            ShortcutAction::DuplicateAndTrim => "Trim silence from a copy of the selected file",
+            ShortcutAction::TapTempo => "Tap the metronome tempo",
+            ShortcutAction::NudgeLevelUp => {
+                "Raise the input gain while armed or recording, otherwise the playback volume"
+            }
+            ShortcutAction::NudgeLevelDown => {
+                "Lower the input gain while armed or recording, otherwise the playback volume"
+            }
+            ShortcutAction::ShowHelp => "Show this help",
+            ShortcutAction::CloseHelp => "Close help",
+        }
+    }
+
+    fn message(self) -> Message {
+        match self {
+            ShortcutAction::Toggle => Message::Toggle,
+            ShortcutAction::ToggleRecording => Message::ToggleRecording,
+            ShortcutAction::TogglePlayback => Message::TogglePlayback,
+            ShortcutAction::StopPlayback => Message::StopPlayback,
+            ShortcutAction::SkipBackward => Message::SkipBackward,
+            ShortcutAction::SkipForward => Message::SkipForward,
+            ShortcutAction::SwitchAbTake => Message::SwitchAbTake,
+            ShortcutAction::DropMark => Message::DropMark,
+            ShortcutAction::PreviewSelected => Message::PreviewSelected,
+            ShortcutAction::DuplicateAndTrim => Message::DuplicateAndTrim,
+            ShortcutAction::TapTempo => Message::TapTempo(Instant::now()),
+            ShortcutAction::NudgeLevelUp => Message::NudgeLevel(LEVEL_NUDGE_DB),
+            ShortcutAction::NudgeLevelDown => Message::NudgeLevel(-LEVEL_NUDGE_DB),
+            ShortcutAction::ShowHelp => Message::ShowHelp,
+            ShortcutAction::CloseHelp => Message::CloseHelp,
+        }
+    }
+}
+
+/// Name a key is stored under in the keymap: letters in lower case, so Shift
+/// doesn't change the shortcut, and the usual names for the other keys. Ctrl and
+/// Alt are written in front ("Ctrl+s"), as is Shift for the named keys
+/// ("Shift+Up"). `None` for keys that can't be shortcuts, such as modifiers on
+/// their own.
+fn key_name(key: &Key, modifiers: Modifiers) -> Option<String> {
+    use keyboard::key::Named;
+    let mut prefix = String::new();
+    if modifiers.control() {
+        prefix.push_str("Ctrl+");
+    }
+    if modifiers.alt() {
+        prefix.push_str("Alt+");
+    }
+    match key {
+        Key::Character(c) => Some(format!("{prefix}{}", c.to_lowercase())),
+        Key::Named(named) => {
+            let name = match named {
+                Named::Space => "Space",
+                Named::ArrowLeft => "Left",
+                Named::ArrowRight => "Right",
+                Named::ArrowUp => "Up",
+                Named::ArrowDown => "Down",
+                Named::Escape => "Esc",
+                Named::Enter => "Enter",
+                Named::Tab => "Tab",
+                Named::Backspace => "Backspace",
+                Named::Delete => "Delete",
+                Named::Insert => "Insert",
+                Named::Home => "Home",
+                Named::End => "End",
+                Named::PageUp => "PageUp",
+                Named::PageDown => "PageDown",
+                Named::F1 => "F1",
+                Named::F2 => "F2",
+                Named::F3 => "F3",
+                Named::F4 => "F4",
+                Named::F5 => "F5",
+                Named::F6 => "F6",
+                Named::F7 => "F7",
+                Named::F8 => "F8",
+                Named::F9 => "F9",
+                Named::F10 => "F10",
+                Named::F11 => "F11",
+                Named::F12 => "F12",
+                _ => return None,
+            };
+            if modifiers.shift() {
+                prefix.push_str("Shift+");
+            }
+            Some(format!("{prefix}{name}"))
+        }
+        _ => None,
+    }
+}
+
+/// How a stored key name is shown: single characters in upper case, after any
+/// modifiers.
+fn key_label(name: &str) -> String {
+    let upper = |key: &str| {
+        if key.chars().count() == 1 {
+            key.to_uppercase()
+        } else {
+            key.to_string()
+        }
+    };
+    match name.rsplit_once('+') {
+        Some((modifiers, key)) if !key.is_empty() => format!("{modifiers}+{}", upper(key)),
+        _ => upper(name),
+    }
+}
+
+/// The key `action` is on, falling back to its default when the keymap doesn't
+/// mention it (as in configs from before remapping, or after new actions are added).
+fn shortcut_key(keymap: &BTreeMap<ShortcutAction, String>, action: ShortcutAction) -> &str {
+    keymap
+        .get(&action)
+        .map(String::as_str)
+        .unwrap_or(action.default_key())
+}
+
+/// The action bound to the key called `name`, if any.
+fn shortcut_for(keymap: &BTreeMap<ShortcutAction, String>, name: &str) -> Option<ShortcutAction> {
+    ShortcutAction::ALL
+        .into_iter()
+        .find(|&action| shortcut_key(keymap, action) == name)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum PlaybackState {
+    Stopped,
+    Playing,
+    Paused,
+}
+
+/// Where the recorder is in taking a recording. Recording guards and controls all
+/// go by this, so only one take can be under way at a time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RecordingPhase {
+    Idle,
+    /// The input is open and metering, but nothing is kept yet.
+    Armed,
+    /// The input is open just for the meter and monitoring; nothing is kept, and
+    /// Record is refused until it's stopped.
+    Monitoring,
+    Recording,
+    /// Stop was pressed at `since`; capture runs on until the last buffers are in.
+    Stopping {
+        since: Instant,
+    },
+    /// Capture is over and the take is waiting to be saved or discarded.
+    Finalizing,
+}
+
+impl RecordingPhase {
+    fn is_recording(self) -> bool {
+        self == RecordingPhase::Recording
+    }
+
+    /// Whether the input is open for the meter without a take under way: armed, or
+    /// monitoring only.
+    fn is_armed(self) -> bool {
+        matches!(self, RecordingPhase::Armed | RecordingPhase::Monitoring)
+    }
+
+    /// Whether Record can start a take now.
+    fn can_start(self) -> bool {
+        matches!(self, RecordingPhase::Idle | RecordingPhase::Armed)
+    }
+
+    /// Whether the input stream is open (metering or capturing).
+    fn input_open(self) -> bool {
+        matches!(
+            self,
+            RecordingPhase::Armed
+                | RecordingPhase::Monitoring
+                | RecordingPhase::Recording
+                | RecordingPhase::Stopping { .. }
+        )
+    }
+}
+
+struct VoiceRecorder {
+    config: Config,
+    screen: Screen,
+    recording_phase: RecordingPhase,
+    // Input stream running for the meter, but samples are not being kept yet
+    input_capturing: Arc<AtomicBool>,
+    // The take saved last, which Re-record replaces
+    last_take: Option<String>,
+    // Name the take being recorded is saved under, in place of the one thrown away
+    rerecording: Option<String>,
+    // Name offered for the take waiting to be saved, as edited; `None` when the name
+    // is already settled (a re-record or a project clip)
+    take_name: Option<String>,
+    playback_state: PlaybackState,
+    currently_playing: Option<PlaybackSource>,
+    status_message: String,
+    // Recent status messages with the time since launch, oldest first
+    status_log: VecDeque<(Duration, String)>,
+    // The settings file didn't parse, so changes this session aren't written to it
+    config_unreadable: bool,
+    // The settings file in the folder the app started in. Settings are always saved
+    // there, so opening another folder never writes over that folder's own settings.
+    config_path: std::path::PathBuf,
+    device_reports: Vec<DeviceReport>,
+    show_log: bool,
+    launched_at: Instant,
+    files: Vec<String>,
+    audio_data: Arc<Mutex<Vec<f32>>>,
+    // The same take as the device's own 16-bit samples, kept alongside `audio_data`
+    // when it's to be saved in the source format
+    native_audio: Arc<Mutex<Vec<i16>>>,
+    backend: Box<dyn AudioBackend>,
+    input_stream: Option<Box<dyn AudioStream>>,
+    output_stream: Option<Box<dyn AudioStream>>,
+    // Plays along with `output_stream` on the second output device, if one is set
+    second_output_stream: Option<Box<dyn AudioStream>>,
+    output_devices: Vec<String>,
+    // Audio hosts on offer, listed when Settings opens
+    audio_hosts: Vec<HostChoice>,
+    // Most channels the default output device offers, for picking the first one
+    output_channel_count: u16,
+    // Playback volumes (f32 bits), read by the output callbacks
+    output_volume: Arc<AtomicU32>,
+    second_output_volume: Arc<AtomicU32>,
+    // What the open output stream was built for (device format, file channel count,
+    // file sample rate and channel offset), so a warm stream is only reused for files
+    // it can play. The callback's resampling step is fixed by the file's rate.
+    output_stream_key: Option<(StreamFormat, u16, u32, usize)>,
+    // When a warm output stream went idle, for releasing it after a while
+    output_idle_since: Option<Instant>,
+    playback_status_tx: mpsc::Sender<()>,
+    playback_status_rx: mpsc::Receiver<()>,
+    // Errors from the output stream's audio thread, checked on each tick
+    output_error_tx: mpsc::Sender<StreamError>,
+    output_error_rx: mpsc::Receiver<StreamError>,
+    // Errors from the other output streams: cues, metronome, monitor, second output
+    // and the latency test
+    side_error_tx: mpsc::Sender<SideStreamError>,
+    side_error_rx: mpsc::Receiver<SideStreamError>,
+    start_time: Option<Instant>,
+    // Wall-clock start of the current take, for the BWF origination time
+    recording_started_at: Option<SystemTime>,
+    // Description written into the next recording's BWF metadata
+    recording_description: String,
+    projects: Vec<Project>,
+    folder_input: String,
+    // The project whose clips new takes are saved as
+    active_project: Option<Project>,
+    // Clip picked to record next, instead of the first missing one
+    project_clip: Option<String>,
+    project_name_input: String,
+    project_clips_input: String,
+    elapsed_time: Duration,
+    // Live meter, fed by the input while it's open and by the output during playback
+    input_levels: Arc<LevelTap>,
+    output_levels: Arc<LevelTap>,
+    meter_level: f32,
+    meter_updated: Option<Instant>,
+    meter_attack_input: String,
+    meter_target_input: String,
+    meter_tolerance_input: String,
+    meter_fall_input: String,
+    // Peak input level (dBFS) per history interval while recording, oldest first
+    level_history: VecDeque<f32>,
+    level_history_peak: f32,
+    level_history_due: Option<Instant>,
+    clip_until: Option<Instant>,
+    // Callback timing for the input and playback streams, and what the health
+    // indicator shows: load, dropouts since the streams opened, and red until when
+    input_health: Arc<CallbackHealth>,
+    output_health: Arc<CallbackHealth>,
+    stream_load: f32,
+    dropouts: u32,
+    dropout_until: Option<Instant>,
+    // Stopped take held in `audio_data` waiting for Save/Discard
+    recording_sample_rate: u32,
+    recording_channels: u16,
+    recording_buffer_size: BufferSize,
+    recording_sample_format: SampleFormat,
+    renaming_file: Option<String>,
+    new_name: String,
+    // Batch rename panel and its template, e.g. `interview_{n:2}`
+    batch_rename_open: bool,
+    batch_rename_template: String,
+    // Path typed into the "Open for editing" field
+    open_file_input: String,
+    /// Batch waiting for the user to confirm its summary.
+    pending_batch: Option<BatchKind>,
+    /// Batches that can be undone, oldest first.
+    undo_history: Vec<UndoEntry>,
+    // For pause/resume functionality
+    playback_samples: Arc<Mutex<Vec<f32>>>,
+    playback_position: Arc<Mutex<usize>>,
+    is_stream_paused: Arc<Mutex<bool>>,
+    stereo_width: Arc<Mutex<f32>>,
+    // Whether the volume and width in use came from a file's saved settings
+    file_playback_applied: bool,
+    playback_resample_quality: Arc<Mutex<ResampleQuality>>,
+    // Playback speed (f32 bits), remembered per file in a sidecar
+    playback_speed: Arc<AtomicU32>,
+    // Linear gain per input channel, applied by the input callback
+    channel_gains: Arc<Mutex<Vec<f32>>>,
+    // Whether the current playback is a short preview
+    previewing: bool,
+    // Cue points dropped during the current take, as sample indices into `audio_data`
+    pending_marks: Vec<usize>,
+    // Chapter markers (sample indices into the interleaved buffer) for `marker_file`
+    markers: Vec<usize>,
+    marker_file: Option<String>,
+    marker_samples_per_sec: usize,
+    // Labels of `markers` that came in with an import, by index; empty if unlabelled
+    marker_labels: Vec<String>,
+    marker_format: MarkerFormat,
+    chapter_min_gap: String,
+    skip_intro_input: String,
+    preview_input: String,
+    skip_back_input: String,
+    skip_forward_input: String,
+    stop_tail_input: String,
+    pre_roll_input: String,
+    max_file_size_input: String,
+    warm_output_idle_input: String,
+    start_ramp_input: String,
+    auto_level_input: String,
+    trim_threshold_input: String,
+    trim_padding_input: String,
+    // Where playback of each file was stopped this session, for resuming
+    last_positions: HashMap<String, usize>,
+    // Notes/tags sidecar, keyed by filename
+    notes: BTreeMap<String, FileNotes>,
+    selected_file: Option<String>,
+    // Last filename click, for spotting double-clicks
+    last_file_click: Option<(String, Instant)>,
+    // BWF metadata of the selected file, if it has any
+    selected_bext: Option<bwf::Bext>,
+    // INFO comment of the selected file, i.e. the processing it was recorded with
+    selected_comment: Option<String>,
+    // Result of the last mono check, and the file it was for
+    mono_check: Option<(String, MonoCheck)>,
+    // Files found to be truncated or corrupt when played
+    damaged_files: HashSet<String>,
+    tag_input: String,
+    fade_in_input: String,
+    silence_input: String,
+    fade_out_input: String,
+    normalize_input: String,
+    image_width_input: String,
+    image_height_input: String,
+    search_query: String,
+    // Channels the polarity tools leave alone; all others are inverted
+    polarity_skipped: HashSet<usize>,
+    invert_playback: bool,
+    // Per file channel, whether playback negates it; empty while not inverting
+    playback_polarity: Arc<Mutex<Vec<bool>>>,
+    // When each listed file was last modified, read with the listing
+    file_modified: HashMap<String, SystemTime>,
+    // Audio fingerprints keyed by path, valid while the file's mtime is unchanged
+    fingerprint_cache: FingerprintCache,
+    // Result of the last duplicate search, left by its job for `job_finished_impl`
+    duplicate_scan: Arc<Mutex<Option<DuplicateScan>>>,
+    duplicates: HashSet<String>,
+    // Background processing job in progress, if any
+    background_job: Option<BackgroundJob>,
+    // Files in the current batch export and how each is getting on
+    export_queue: Arc<Mutex<Vec<QueueItem>>>,
+    export_retries_input: String,
+    listing_depth_input: String,
+    export_backoff_input: String,
+    next_job_id: u64,
+    export_lufs_input: String,
+    cover_art_input: String,
+    preset_name: String,
+    // Integrated loudness keyed by path, valid while the file's mtime is unchanged
+    loudness_cache: HashMap<std::path::PathBuf, (SystemTime, Option<f64>)>,
+    // Info panel statistics, worked out when a file is selected
+    analysis_cache: HashMap<std::path::PathBuf, (SystemTime, Result<Analysis, String>)>,
+    // Waveform of the selected or playing file, with its zoom factor and scroll
+    // position (0.0 to 1.0 across the part that doesn't fit)
+    waveform: Option<WaveformData>,
+    waveform_zoom: f32,
+    waveform_scroll: f32,
+    // A/B take comparison: the two assigned files, and while comparing, the buffer
+    // of the take that isn't currently playing
+    ab_slots: [Option<String>; 2],
+    playback_spec: Option<WavSpec>,
+    ab_active: usize,
+    ab_inactive_samples: Option<Vec<f32>>,
+    ab_match_loudness: bool,
+    // Take B's waveform drawn over take A's, with how their samples differ (`None`
+    // when the formats don't match)
+    waveform_overlay: Option<WaveformOverlay>,
+    // Recording start/stop cues
+    cue_stream: Option<Box<dyn AudioStream>>,
+    // Metronome clicking while on, its tempo (f32 bits) and recent tap-tempo taps
+    metronome_stream: Option<Box<dyn AudioStream>>,
+    // Whether the metronome clicked during the take being recorded
+    metronome_during_take: bool,
+    metronome_bpm: Arc<AtomicU32>,
+    metronome_input: String,
+    tempo_taps: VecDeque<Instant>,
+    // Input kept while armed, for the start of the next take (at most `pre_roll_secs`)
+    pre_roll: Arc<Mutex<VecDeque<f32>>>,
+    // The pre-roll as native samples, like `native_audio`; locked after `pre_roll`
+    native_pre_roll: Arc<Mutex<VecDeque<i16>>>,
+    // Input monitoring: its output stream, the input waiting to be played, whether
+    // the input callback should fill it, and whether with the processing applied
+    monitor_stream: Option<Box<dyn AudioStream>>,
+    monitor_queue: Arc<Mutex<VecDeque<f32>>>,
+    monitor_active: Arc<AtomicBool>,
+    monitor_processed: Arc<AtomicBool>,
+    /// Shortcut waiting in settings for its new key.
+    remapping_shortcut: Option<ShortcutAction>,
+    latency_test: Option<LatencyTest>,
+    tone_waveform: ToneWaveform,
+    tone_frequency_input: String,
+    tone_level_input: String,
+    captions_engine: transcribe::Engine,
+    // Captions for the current (or last) take, and how much of it has been fed in
+    captions: Option<transcribe::LiveCaptions>,
+    captions_fed: usize,
+    cue_flash: Option<(Instant, iced::Color)>,
+    /// Summary of the take just saved and when it was saved.
+    take_summary: Option<(String, Instant)>,
+    /// Readout of the last keyboard level nudge and when it was made.
+    level_nudge: Option<(String, Instant)>,
+    // Crash journal of the take in progress, how much of `audio_data` it holds and
+    // when it was last flushed to disk
+    recovery_writer: Option<hound::WavWriter<io::BufWriter<fs::File>>>,
+    recovery_written: usize,
+    recovery_flushed: Option<Instant>,
+    // Full path of the journal, fixed at startup so opening a folder doesn't move it
+    recovery_path: String,
+    // A take a crash left behind, until it's recovered or discarded
+    interrupted_take: Option<InterruptedTake>,
+    #[cfg(feature = "tray")]
+    tray: Option<tray::Tray>,
+}
+
+impl Default for VoiceRecorder {
+    fn default() -> Self {
+        Self::with_backend(Box::new(CpalBackend::default()))
+    }
+}
+
+impl VoiceRecorder {
+    fn with_backend(backend: Box<dyn AudioBackend>) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let (output_error_tx, output_error_rx) = mpsc::channel();
+        let (side_error_tx, side_error_rx) = mpsc::channel();
+        let mut startup_notes = Vec::new();
+        let recovery_path = std::env::current_dir()
+            .map(|dir| dir.join(RECOVERY_PATH).to_string_lossy().into_owned())
+            .unwrap_or_else(|_| RECOVERY_PATH.into());
+        let (mut config, config_unreadable) = match Config::load() {
+            Ok(config) => (config, false),
+            Err(e) => {
+                startup_notes.push(format!(
+                    "Couldn't read {}: {}. Using default settings; they won't be saved \
+                     over it until it's fixed or removed.",
+                    CONFIG_PATH, e
+                ));
+                (Config::default(), true)
+            }
+        };
+        let reset = config.validate();
+        if !reset.is_empty() {
+            startup_notes.push(format!(
+                "Reset settings that were out of range: {}.",
+                reset.join(", ")
+            ));
+        }
+        config
+            .recent_folders
+            .retain(|folder| std::path::Path::new(folder).is_dir());
+        backend.set_output_dither(config.output_dither);
+        if let Some(host) = config.audio_host.as_deref()
+            && let Err(e) = backend.set_host(Some(host))
+        {
+            startup_notes.push(format!("{} Using the default audio host.", e));
+        }
+        let input_channels = backend
+            .default_input_format()
+            .map_or(0, |f| f.channels as usize);
+        // Files held for undo by a session that crashed can't be undone any more
+        let _ = fs::remove_dir_all(UNDO_DIR);
+        let files = list_wav_files(config.listing_depth());
+        let normalize_input = config.processing.normalize_target_lufs.to_string();
+        let image_width_input = config.waveform_image_width.to_string();
+        let metronome_bpm = Arc::new(AtomicU32::new(config.metronome_bpm.to_bits()));
+        let metronome_input = config.metronome_bpm.to_string();
+        let monitor_processed = Arc::new(AtomicBool::new(config.monitor_processed));
+        let image_height_input = config.waveform_image_height.to_string();
+        let input_gains = config.processing.input_gains(input_channels);
+        Self {
+            chapter_min_gap: config.chapter_min_gap_secs.to_string(),
+            skip_intro_input: config.skip_intro_secs.to_string(),
+            preview_input: config.preview_secs.to_string(),
+            skip_back_input: config.skip_back_secs.to_string(),
+            skip_forward_input: config.skip_forward_secs.to_string(),
+            meter_attack_input: config.meter_attack_ms.to_string(),
+            meter_target_input: config
+                .meter_target_db
+                .map_or(String::new(), |db| db.to_string()),
+            meter_tolerance_input: config.meter_target_tolerance_db.to_string(),
+            output_volume: Arc::new(AtomicU32::new(config.output_volume.to_bits())),
+            second_output_volume: Arc::new(AtomicU32::new(config.second_output_volume.to_bits())),
+            playback_resample_quality: Arc::new(Mutex::new(config.playback_resample_quality)),
+            meter_fall_input: config.meter_fall_db_per_sec.to_string(),
+            stop_tail_input: config.stop_tail_ms.to_string(),
+            pre_roll_input: config.pre_roll_secs.to_string(),
+            max_file_size_input: config.max_file_size_mb.to_string(),
+            warm_output_idle_input: config.warm_output_idle_secs.to_string(),
+            start_ramp_input: config.output_start_ramp_ms.to_string(),
+            auto_level_input: config.auto_level_target_lufs.to_string(),
+            trim_threshold_input: config.processing.trim_threshold_db.to_string(),
+            trim_padding_input: config.processing.trim_padding_ms.to_string(),
+            export_lufs_input: config.export.normalize_lufs.unwrap_or(-16.0).to_string(),
+            cover_art_input: config.export.mp3_tags.cover_art.clone(),
+            export_retries_input: config.export_retries.to_string(),
+            listing_depth_input: config.listing_depth.to_string(),
+            folder_input: std::env::current_dir()
+                .map(|dir| dir.display().to_string())
+                .unwrap_or_default(),
+            export_backoff_input: config.export_retry_backoff_ms.to_string(),
+            preset_name: String::new(),
+            last_positions: HashMap::new(),
+            config,
+            screen: Screen::Main,
+            recording_phase: RecordingPhase::Idle,
+            input_capturing: Arc::new(AtomicBool::new(false)),
+            last_take: None,
+            rerecording: None,
+            take_name: None,
+            playback_state: PlaybackState::Stopped,
+            currently_playing: None,
+            status_message: if startup_notes.is_empty() {
+                "Ready to record.".into()
+            } else {
+                startup_notes.join(" ")
+            },
+            status_log: VecDeque::new(),
+            config_unreadable,
+            config_path: std::env::current_dir()
+                .map(|dir| dir.join(CONFIG_PATH))
+                .unwrap_or_else(|_| CONFIG_PATH.into()),
+            device_reports: Vec::new(),
+            show_log: false,
+            launched_at: Instant::now(),
+            files,
+            audio_data: Arc::new(Mutex::new(Vec::new())),
+            native_audio: Arc::new(Mutex::new(Vec::new())),
+            backend,
+            input_stream: None,
+            output_stream: None,
+            second_output_stream: None,
+            output_devices: Vec::new(),
+            output_channel_count: 0,
+            audio_hosts: Vec::new(),
+            output_stream_key: None,
+            output_idle_since: None,
+            playback_status_tx: tx,
+            playback_status_rx: rx,
+            output_error_tx,
+            output_error_rx,
+            side_error_tx,
+            side_error_rx,
+            start_time: None,
+            recording_started_at: None,
+            recording_description: String::new(),
+            projects: load_projects(),
+            active_project: None,
+            project_clip: None,
+            project_name_input: String::new(),
+            project_clips_input: String::new(),
+            elapsed_time: Duration::from_secs(0),
+            input_levels: Arc::new(LevelTap::new()),
+            output_levels: Arc::new(LevelTap::new()),
+            meter_level: 0.0,
+            meter_updated: None,
+            level_history: VecDeque::with_capacity(LEVEL_HISTORY_LEN),
+            level_history_peak: 0.0,
+            level_history_due: None,
+            clip_until: None,
+            input_health: Arc::new(CallbackHealth::new()),
+            output_health: Arc::new(CallbackHealth::new()),
+            stream_load: 0.0,
+            dropouts: 0,
+            dropout_until: None,
+            recording_sample_rate: 48000,
+            recording_channels: 1,
+            recording_buffer_size: BufferSize::Default,
+            recording_sample_format: SampleFormat::F32,
+            renaming_file: None,
+            new_name: String::new(),
+            batch_rename_open: false,
+            batch_rename_template: "recording_{n}".into(),
+            open_file_input: String::new(),
+            pending_batch: None,
+            undo_history: Vec::new(),
+            playback_samples: Arc::new(Mutex::new(Vec::new())),
+            playback_position: Arc::new(Mutex::new(0)),
+            is_stream_paused: Arc::new(Mutex::new(false)),
+            stereo_width: Arc::new(Mutex::new(1.0)),
+            file_playback_applied: false,
+            playback_speed: Arc::new(AtomicU32::new(1.0f32.to_bits())),
+            channel_gains: Arc::new(Mutex::new(input_gains)),
+            previewing: false,
+            pending_marks: Vec::new(),
+            markers: Vec::new(),
+            marker_file: None,
+            marker_samples_per_sec: 0,
+            marker_labels: Vec::new(),
+            marker_format: MarkerFormat::Csv,
+            notes: load_notes(),
+            selected_file: None,
+            last_file_click: None,
+            selected_bext: None,
+            selected_comment: None,
+            mono_check: None,
+            damaged_files: HashSet::new(),
+            tag_input: String::new(),
+            fade_in_input: "500".into(),
+            silence_input: "1.0".into(),
+            normalize_input,
+            image_width_input,
+            image_height_input,
+            fade_out_input: "1000".into(),
+            search_query: String::new(),
+            polarity_skipped: HashSet::new(),
+            invert_playback: false,
+            playback_polarity: Arc::new(Mutex::new(Vec::new())),
+            file_modified: HashMap::new(),
+            fingerprint_cache: HashMap::new(),
+            duplicate_scan: Arc::new(Mutex::new(None)),
+            loudness_cache: HashMap::new(),
+            analysis_cache: HashMap::new(),
+            duplicates: HashSet::new(),
+            background_job: None,
+            export_queue: Arc::new(Mutex::new(Vec::new())),
+            next_job_id: 0,
+            waveform: None,
+            waveform_zoom: 1.0,
+            waveform_scroll: 0.0,
+            ab_slots: [None, None],
+            waveform_overlay: None,
+            playback_spec: None,
+            ab_active: 0,
+            ab_inactive_samples: None,
+            ab_match_loudness: true,
+            cue_stream: None,
+            metronome_stream: None,
+            metronome_during_take: false,
+            metronome_bpm,
+            metronome_input,
+            tempo_taps: VecDeque::new(),
+            pre_roll: Arc::new(Mutex::new(VecDeque::new())),
+            native_pre_roll: Arc::new(Mutex::new(VecDeque::new())),
+            monitor_stream: None,
+            monitor_queue: Arc::new(Mutex::new(VecDeque::new())),
+            monitor_active: Arc::new(AtomicBool::new(false)),
+            monitor_processed,
+            remapping_shortcut: None,
+            latency_test: None,
+            tone_waveform: ToneWaveform::Sine,
+            tone_frequency_input: "1000".into(),
+            tone_level_input: "-18".into(),
+            captions_engine: transcribe::default_engine,
+            captions: None,
+            captions_fed: 0,
+            cue_flash: None,
+            take_summary: None,
+            level_nudge: None,
+            recovery_writer: None,
+            recovery_written: 0,
+            recovery_flushed: None,
+            interrupted_take: find_interrupted_take(&recovery_path),
+            recovery_path,
+            #[cfg(feature = "tray")]
+            tray: None,
+        }
+    }
+
+    fn toggle_recording_impl(&mut self) {
+        if self.recording_phase.is_recording() {
+            self.stop_recording_impl();
+        } else {
+            self.start_recording_impl();
+        }
+    }
+
+    /// Pauses or resumes playback, or plays the selected file when nothing is
+    /// playing. Never touches recording.
+    fn toggle_playback_impl(&mut self) {
+        match self.playback_state {
+            PlaybackState::Playing => self.pause_playback_impl(),
+            PlaybackState::Paused => self.resume_playback_impl(),
+            PlaybackState::Stopped => {
+                if let Some(fname) = self.selected_file.clone()
+                    && self.can_interact_with_file(&fname)
+                    && !self.recording_phase.is_armed()
+                {
+                    self.play_file_from(&fname, None);
+                }
+            }
+        }
+    }
+
+    fn start_recording_impl(&mut self) {
+        if !self.recording_phase.can_start() || self.playback_state != PlaybackState::Stopped {
+            if self.recording_phase == RecordingPhase::Monitoring {
+                self.add_log("Monitor only: stop monitoring to record.".into());
+            }
+            return;
+        }
+
+        // The new take's journal would overwrite the old one
+        if self.interrupted_take.is_some() {
+            self.recover_interrupted_take_impl();
+            if self.interrupted_take.is_some() {
+                self.add_log(
+                    "Recover or discard the interrupted recording before recording again.".into(),
+                );
+                return;
+            }
+        }
+        self.audio_data.lock().unwrap().clear();
+        self.native_audio.lock().unwrap().clear();
+        self.pending_marks.clear();
+        self.take_summary = None;
+
+        // An armed stream is already running, so capture starts immediately, from
+        // the pre-roll it has been keeping
+        let mut pre_roll_frames = 0;
+        let latency_note = if self.recording_phase.is_armed() {
+            let mut pre_roll = self.pre_roll.lock().unwrap();
+            self.audio_data.lock().unwrap().extend(pre_roll.drain(..));
+            let native = self
+                .native_pre_roll
+                .lock()
+                .unwrap()
+                .drain(..)
+                .collect::<Vec<_>>();
+            self.native_audio.lock().unwrap().extend(native);
+            self.input_capturing.store(true, Ordering::Relaxed);
+            drop(pre_roll);
+            pre_roll_frames =
+                self.audio_data.lock().unwrap().len() / self.recording_channels.max(1) as usize;
+            String::new()
+        } else {
+            match self.open_input_stream(true) {
+                Some(note) => note,
+                None => return,
+            }
+        };
+
+        self.input_capturing.store(true, Ordering::Relaxed);
+        self.recording_phase = RecordingPhase::Recording;
+        self.metronome_during_take = self.metronome_stream.is_some();
+        self.update_tray();
+        self.add_log(format!("Recording...{}", latency_note));
+        self.signal_cue(CUE_START_FREQUENCY, iced::Color::from_rgb(0.6, 0.1, 0.1));
+        // The timer counts the pre-roll as part of the take
+        let pre_roll = Duration::from_secs_f64(
+            pre_roll_frames as f64 / self.recording_sample_rate.max(1) as f64,
+        );
+        let now = Instant::now();
+        self.start_time = Some(now.checked_sub(pre_roll).unwrap_or(now));
+        self.recording_started_at = Some(SystemTime::now() - pre_roll);
+        self.elapsed_time = pre_roll;
+        self.level_history.clear();
+        self.level_history_peak = 0.0;
+        self.level_history_due = None;
+        self.start_recovery_journal();
+
+        self.captions = None;
+        self.captions_fed = 0;
+        if self.config.live_captions {
+            self.captions = Some(transcribe::LiveCaptions::start(
+                self.captions_engine,
+                self.recording_sample_rate,
+            ));
+        }
+    }
+
+    fn start_recovery_journal(&mut self) {
+        let spec = WavSpec {
+            channels: self.recording_channels,
+            sample_rate: self.recording_sample_rate,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        self.recovery_written = 0;
+        self.recovery_flushed = None;
+        self.recovery_writer = match hound::WavWriter::create(&self.recovery_path, spec) {
+            Ok(writer) => Some(writer),
+            Err(e) => {
+                self.add_log(format!("Crash recovery unavailable for this take: {}", e));
+                None
+            }
+        };
+    }
+
+    /// Appends audio captured since the last call to the crash journal and updates
+    /// its header, at most every `RECOVERY_FLUSH_MS` unless `force` is set.
+    fn journal_recording(&mut self, now: Instant, force: bool) {
+        let Some(writer) = self.recovery_writer.as_mut() else {
+            return;
+        };
+        if !force
+            && self.recovery_flushed.is_some_and(|last| {
+                now.duration_since(last) < Duration::from_millis(RECOVERY_FLUSH_MS)
+            })
+        {
+            return;
+        }
+        self.recovery_flushed = Some(now);
+
+        let channels = self.recording_channels.max(1) as usize;
+        let audio = self.audio_data.lock().unwrap();
+        let end = audio.len() - audio.len() % channels;
+        let written = audio
+            .get(self.recovery_written..end)
+            .unwrap_or_default()
+            .iter()
+            .try_for_each(|&s| writer.write_sample(s))
+            .and_then(|()| writer.flush());
+        drop(audio);
+        match written {
+            Ok(()) => self.recovery_written = end.max(self.recovery_written),
+            Err(e) => {
+                self.recovery_writer = None;
+                self.add_log(format!("Crash recovery stopped for this take: {}", e));
+            }
+        }
+    }
+
+    /// Drops the crash journal once the take it protects is saved or thrown away.
+    fn end_recovery_journal(&mut self) {
+        if self.recovery_writer.take().is_some() {
+            let _ = fs::remove_file(&self.recovery_path);
+        }
+    }
+
+    /// Saves a take left behind by a crash as a new recording.
+    fn recover_interrupted_take_impl(&mut self) {
+        if self.interrupted_take.take().is_none() {
+            return;
+        }
+        let (spec, samples, _) = match read_wav_recoverable(&self.recovery_path) {
+            Ok(take) => take,
+            Err(e) => {
+                self.add_log(format!("Couldn't recover the interrupted recording: {}", e));
+                self.interrupted_take = Some(InterruptedTake::Unreadable(e));
+                return;
+            }
+        };
+        let filename = (1..)
+            .map(|n| format!("recovered_{}.wav", n))
+            .find(|name| !std::path::Path::new(name).exists())
+            .unwrap();
+        match write_wav_file_f32(&filename, spec, &samples) {
+            Ok(()) => {
+                let _ = fs::remove_file(&self.recovery_path);
+                self.refresh_files();
+                self.add_log(format!(
+                    "Recovered the interrupted recording as '{}' ({:.1}s).",
+                    filename,
+                    (samples.len() / spec.channels.max(1) as usize) as f64
+                        / spec.sample_rate.max(1) as f64
+                ));
+            }
+            Err(e) => {
+                self.interrupted_take = find_interrupted_take(&self.recovery_path);
+                self.add_log(format!("Couldn't recover the interrupted recording: {}", e));
+            }
+        }
+    }
+
+    fn discard_interrupted_take_impl(&mut self) {
+        if self.interrupted_take.take().is_some() {
+            let _ = fs::remove_file(&self.recovery_path);
+            self.add_log("Interrupted recording discarded.".into());
+        }
+    }
+
+    /// Passes audio captured since the last call to the caption engine, as mono.
+    fn feed_captions(&mut self) {
+        let Some(captions) = &self.captions else {
+            return;
+        };
+        if let Some(e) = captions.error() {
+            self.captions = None;
+            self.add_log(format!("Live captions unavailable: {}.", e));
+            return;
+        }
+        let channels = self.recording_channels.max(1) as usize;
+        let audio = self.audio_data.lock().unwrap();
+        let end = audio.len() - audio.len() % channels;
+        if end > self.captions_fed {
+            captions.feed(downmix_to_mono(
+                &audio[self.captions_fed..end],
+                self.recording_channels,
+            ));
+            self.captions_fed = end;
+        }
+    }
+
+    /// Finishes the captions for the take just saved as `filename` and writes them out,
+    /// on its own thread since the engine may still be catching up.
+    fn save_transcript(&mut self, filename: &str) -> Task<Message> {
+        let Some(mut captions) = self.captions.take() else {
+            return Task::none();
+        };
+        let filename = filename.to_string();
+        Task::perform(
+            run_in_background(move || {
+                write_transcript(&filename, &captions.finish()).map_err(|e| e.to_string())
+            }),
+            |result| {
+                Message::TranscriptSaved(
+                    result.unwrap_or_else(|| Err("the caption engine stopped unexpectedly".into())),
+                )
+            },
+        )
+    }
+
+    fn arm_recording_impl(&mut self) {
+        if self.recording_phase != RecordingPhase::Idle
+            || self.playback_state != PlaybackState::Stopped
+        {
+            return;
+        }
+
+        if let Some(latency_note) = self.open_input_stream(false) {
+            self.recording_phase = RecordingPhase::Armed;
+            let keeping = if self.config.pre_roll_secs > 0.0 {
+                format!(" (keeping the last {} s)", self.config.pre_roll_secs)
+            } else {
+                String::new()
+            };
+            self.add_log(format!(
+                "Armed - press Record to capture{}{}",
+                keeping, latency_note
+            ));
+        }
+    }
+
+    /// Opens the input just for the meter and, if it's on, monitoring: like arming,
+    /// but without a pre-roll, and with Record refused until it's stopped.
+    fn start_monitor_only_impl(&mut self) {
+        if self.recording_phase != RecordingPhase::Idle
+            || self.playback_state != PlaybackState::Stopped
+        {
+            return;
+        }
+
+        // Set first, so the stream opens without a pre-roll
+        self.recording_phase = RecordingPhase::Monitoring;
+        match self.open_input_stream(false) {
+            Some(latency_note) => {
+                self.add_log(format!(
+                    "Monitoring only - nothing is recorded{}",
+                    latency_note
+                ));
+            }
+            None => self.recording_phase = RecordingPhase::Idle,
+        }
+    }
+
+    /// Starts playing the open input through the output.
+    fn start_monitor(&mut self) {
+        let input = StreamFormat {
+            channels: self.recording_channels,
+            sample_rate: self.recording_sample_rate,
+            sample_format: self.recording_sample_format,
+            buffer_size: self.recording_buffer_size,
+        };
+        self.monitor_active.store(true, Ordering::Relaxed);
+        match play_monitor(
+            self.backend.as_ref(),
+            &input,
+            Arc::clone(&self.monitor_queue),
+            report_side_errors(&self.side_error_tx, "Monitor"),
+        ) {
+            Ok(stream) => self.monitor_stream = Some(stream),
+            Err(e) => {
+                self.monitor_active.store(false, Ordering::Relaxed);
+                self.add_log(format!("Couldn't monitor the input: {}", e));
+            }
+        }
+    }
+
+    fn stop_monitor(&mut self) {
+        self.monitor_stream = None;
+        self.monitor_active.store(false, Ordering::Relaxed);
+        self.monitor_queue.lock().unwrap().clear();
+    }
+
+    fn disarm_recording_impl(&mut self) {
+        if !self.recording_phase.is_armed() {
+            return;
+        }
+
+        self.input_stream = None;
+        self.stop_monitor();
+        self.pre_roll.lock().unwrap().clear();
+        let monitoring = self.recording_phase == RecordingPhase::Monitoring;
+        self.recording_phase = RecordingPhase::Idle;
+        self.meter_level = 0.0;
+        if monitoring {
+            self.add_log("Stopped monitoring.".into());
+        } else {
+            self.add_log("Disarmed.".into());
+        }
+    }
+
+    /// Builds and starts the input stream. The meter runs straight away, but samples
+    /// are only kept while `input_capturing` is set, which starts out as `capture`.
+    /// Returns a note to append to the status line, or `None` if the stream couldn't
+    /// be opened.
+    fn open_input_stream(&mut self, capture: bool) -> Option<String> {
+        let default_format = match self.backend.default_input_format() {
+            Ok(f) => f,
+            Err(e) => {
+                self.add_log(e);
+                return None;
+            }
+        };
+
+        let (mut format, mut latency_note) = match self.preferred_input_format(&default_format) {
+            Some(format) => (format, String::new()),
+            None => (
+                default_format,
+                format!(
+                    " (device can't record at {} Hz, using {} Hz)",
+                    PREFERRED_SAMPLE_RATE, default_format.sample_rate
+                ),
+            ),
+        };
+        format.buffer_size = BufferSize::Fixed(1024);
+
+        if self.config.low_latency {
+            match self.low_latency_input_format(&format) {
+                Some(low_latency) => format = low_latency,
+                None => latency_note.push_str(LOW_LATENCY_UNSUPPORTED),
+            }
+        }
+
+        self.recording_sample_rate = format.sample_rate;
+        self.recording_channels = format.channels;
+        self.recording_buffer_size = format.buffer_size;
+        self.recording_sample_format = format.sample_format;
+        let mut gains = self.channel_gains.lock().unwrap();
+        if gains.len() != format.channels as usize {
+            *gains = self.config.processing.input_gains(format.channels as usize);
+        }
+        drop(gains);
+
+        println!(
+            "Recording with: channels={}, sample_rate={}, format={:?}",
+            format.channels, format.sample_rate, format.sample_format
+        );
+
+        let audio_buf = Arc::clone(&self.audio_data);
+        let capturing_arc = Arc::clone(&self.input_capturing);
+        self.input_capturing.store(capture, Ordering::Relaxed);
+        let mut scratch: Vec<f32> = Vec::new();
+        let mut dc_blocker = self
+            .config
+            .processing
+            .dc_block_enabled
+            .then(|| DcBlocker::new(format.channels, format.sample_rate));
+        let gains_arc = Arc::clone(&self.channel_gains);
+        let channels = format.channels.max(1) as usize;
+        let levels = Arc::clone(&self.input_levels);
+        self.input_levels.reset();
+        self.meter_level = 0.0;
+        self.meter_updated = None;
+        self.clip_until = None;
+        self.monitor_queue.lock().unwrap().clear();
+        self.monitor_active
+            .store(self.config.monitor_input, Ordering::Relaxed);
+        let monitor_queue = Arc::clone(&self.monitor_queue);
+        let monitor_active = Arc::clone(&self.monitor_active);
+        let monitor_processed = Arc::clone(&self.monitor_processed);
+        let monitor_max = (format.sample_rate * MONITOR_MAX_LAG_MS / 1000) as usize * channels;
+        if self.output_stream.is_none() {
+            self.reset_stream_health();
+        }
+        self.input_health.reset();
+        let health = Arc::clone(&self.input_health);
+        let sample_rate = format.sample_rate;
+        self.pre_roll.lock().unwrap().clear();
+        self.native_pre_roll.lock().unwrap().clear();
+        let pre_roll_arc = Arc::clone(&self.pre_roll);
+        let native_pre_roll_arc = Arc::clone(&self.native_pre_roll);
+        let native_buf = Arc::clone(&self.native_audio);
+        let keep_native = self.config.preserve_source_format
+            && matches!(format.sample_format, SampleFormat::I16 | SampleFormat::U16);
+        let mut native_scratch: Vec<i16> = Vec::new();
+        let pre_roll_secs = if self.recording_phase == RecordingPhase::Monitoring {
+            0.0
+        } else {
+            self.config.pre_roll_secs
+        };
+        let pre_roll_max = (format.sample_rate as f32 * pre_roll_secs) as usize * channels;
+        let build_result = self.backend.build_input_stream(
+            &format,
+            Box::new(move |data: &[f32], native: Option<&[i16]>| {
+                let started = Instant::now();
+                scratch.clear();
+                scratch.extend_from_slice(data);
+                if let Some(blocker) = dc_blocker.as_mut() {
+                    blocker.process(&mut scratch);
+                }
+                let gains = gains_arc.lock().unwrap();
+                apply_channel_gains(&mut scratch, channels, &gains);
+                // The device's samples are kept as they came unless the capture
+                // chain changed them
+                let native = match native {
+                    Some(raw) if keep_native => {
+                        native_scratch.clear();
+                        if dc_blocker.is_none() && gains.iter().all(|&g| g == 1.0) {
+                            native_scratch.extend_from_slice(raw);
+                        } else {
+                            native_scratch.extend(scratch.iter().map(|&s| input_f32_to_i16(s)));
+                        }
+                        Some(&native_scratch[..])
+                    }
+                    _ => None,
+                };
+                drop(gains);
+
+                if monitor_active.load(Ordering::Relaxed) {
+                    let heard = if monitor_processed.load(Ordering::Relaxed) {
+                        &scratch[..]
+                    } else {
+                        data
+                    };
+                    let mut queue = monitor_queue.lock().unwrap();
+                    queue.extend(heard);
+                    let excess = queue.len().saturating_sub(monitor_max);
+                    queue.drain(..excess);
+                }
+
+                levels.record(&scratch);
+
+                // The pre-roll stays locked over the capture check, so starting
+                // can't slip in between and lose this buffer
+                let mut pre_roll = (pre_roll_max > 0).then(|| pre_roll_arc.lock().unwrap());
+                if capturing_arc.load(Ordering::Relaxed) {
+                    audio_buf.lock().unwrap().extend_from_slice(&scratch);
+                    if let Some(native) = native {
+                        native_buf.lock().unwrap().extend_from_slice(native);
+                    }
+                } else if let Some(pre_roll) = pre_roll.as_mut() {
+                    pre_roll.extend(&scratch);
+                    let excess = pre_roll.len().saturating_sub(pre_roll_max);
+                    pre_roll.drain(..excess);
+                    if let Some(native) = native {
+                        let mut native_pre_roll = native_pre_roll_arc.lock().unwrap();
+                        native_pre_roll.extend(native);
+                        let excess = native_pre_roll.len().saturating_sub(pre_roll_max);
+                        native_pre_roll.drain(..excess);
+                    }
+                }
+                drop(pre_roll);
+                health.record(started, Instant::now(), data.len() / channels, sample_rate);
+            }),
+        );
+
+        match build_result {
+            Ok(stream) => {
+                self.input_stream = Some(stream);
+                if self.config.monitor_input {
+                    self.start_monitor();
+                }
+                Some(latency_note)
+            }
+            Err(e) => {
+                self.add_log(e);
+                None
+            }
+        }
+    }
+
+    /// A variant of `format` with a small buffer and the cheapest sample format the
+    /// input device supports at the same channel count and rate, if there is one.
+    fn low_latency_input_format(&self, format: &StreamFormat) -> Option<StreamFormat> {
+        let supported = self.backend.supported_input_formats().ok()?;
+        supported
+            .iter()
+            .filter(|f| f.channels == format.channels && f.contains_rate(format.sample_rate))
+            .filter_map(|f| {
+                low_latency_buffer_size(&f.buffer_size).map(|buffer_size| StreamFormat {
+                    sample_format: f.sample_format,
+                    buffer_size,
+                    ..*format
+                })
+            })
+            .min_by_key(|f| sample_format_priority(f.sample_format))
+    }
+
+    /// The default input format moved to 48 kHz, if one of the device's supported
+    /// ranges with the same channel count covers it. The default's sample format is
+    /// kept where possible.
+    fn preferred_input_format(&self, default: &StreamFormat) -> Option<StreamFormat> {
+        if default.sample_rate == PREFERRED_SAMPLE_RATE {
+            return Some(*default);
+        }
+        let supported = self.backend.supported_input_formats().ok()?;
+        supported
+            .iter()
+            .filter(|f| f.channels == default.channels && f.contains_rate(PREFERRED_SAMPLE_RATE))
+            .min_by_key(|f| f.sample_format != default.sample_format)
+            .map(|f| f.with_sample_rate(PREFERRED_SAMPLE_RATE))
+    }
+
+    /// Runs when the window is closed: a take in progress, or one waiting to be
+    /// saved or discarded, is saved with what has been captured so far, and every
+    /// stream is released before the app exits.
+    fn shutdown(&mut self) {
+        match self.recording_phase {
+            RecordingPhase::Armed | RecordingPhase::Monitoring => self.disarm_recording_impl(),
+            RecordingPhase::Recording => {
+                self.recording_phase = RecordingPhase::Stopping {
+                    since: Instant::now(),
+                };
+                self.finalize_on_exit();
+            }
+            RecordingPhase::Stopping { .. } | RecordingPhase::Finalizing => self.finalize_on_exit(),
+            RecordingPhase::Idle => {}
+        }
+        self.stop_playback_impl();
+        self.clear_undo_history();
+        self.stop_monitor();
+        self.metronome_stream = None;
+        self.cue_stream = None;
+        self.release_output_stream();
+    }
+
+    fn stop_recording_impl(&mut self) {
+        if !self.recording_phase.is_recording() {
+            return;
+        }
+
+        self.recording_phase = RecordingPhase::Stopping {
+            since: Instant::now(),
+        };
+        self.update_tray();
+        self.start_time = None;
+        self.add_log("Stopping recording...".into());
+        self.signal_cue(CUE_STOP_FREQUENCY, iced::Color::from_rgb(0.2, 0.2, 0.35));
+    }
+
+    /// Moves the meter to the latest levels of the input while it's open, otherwise
+    /// of the output.
+    fn update_meter(&mut self, now: Instant) {
+        let levels = if self.recording_phase.input_open() {
+            &self.input_levels
+        } else {
+            &self.output_levels
+        };
+        let (peak, rms, clipped) = levels.take();
+        let reading = match self.config.meter_mode {
+            MeterMode::Peak => peak,
+            MeterMode::Rms => rms,
+        };
+        let dt = self.meter_updated.map_or(METER_TICK, |last| now - last);
+        self.meter_updated = Some(now);
+        self.meter_level = meter_ballistics(
+            self.meter_level,
+            reading,
+            dt,
+            self.config.meter_attack_ms,
+            self.config.meter_fall_db_per_sec,
+        );
+        if self.recording_phase.is_recording() {
+            self.update_level_history(peak, now);
+        }
+
+        if clipped {
+            self.clip_until = Some(now + Duration::from_millis(CLIP_HOLD_MS));
+        } else if self.clip_until.is_some_and(|until| now >= until) {
+            self.clip_until = None;
+        }
+    }
+
+    /// Where the meter sits relative to the target band: `Equal` inside it, `None`
+    /// without a target.
+    fn meter_on_target(&self) -> Option<std::cmp::Ordering> {
+        let target = self.config.meter_target_db?;
+        let tolerance = self.config.meter_target_tolerance_db;
+        let db = if self.meter_level > 0.0 {
+            20.0 * self.meter_level.log10()
+        } else {
+            f32::NEG_INFINITY
+        };
+        Some(if db < target - tolerance {
+            std::cmp::Ordering::Less
+        } else if db > target + tolerance {
+            std::cmp::Ordering::Greater
+        } else {
+            std::cmp::Ordering::Equal
+        })
+    }
+
+    fn reset_stream_health(&mut self) {
+        self.stream_load = 0.0;
+        self.dropouts = 0;
+        self.dropout_until = None;
+    }
+
+    /// Reads the callbacks' timing into the health indicator.
+    fn update_stream_health(&mut self, now: Instant) {
+        let (input_late, input_load) = self.input_health.take();
+        let (output_late, output_load) = self.output_health.take();
+        let load = input_load.max(output_load);
+        self.dropouts += input_late + output_late;
+        if input_late + output_late > 0 || load >= 1.0 {
+            self.dropout_until = Some(now + Duration::from_millis(DROPOUT_HOLD_MS));
+        } else if self.dropout_until.is_some_and(|until| now >= until) {
+            self.dropout_until = None;
+        }
+        self.stream_load = load.max(self.stream_load * CALLBACK_LOAD_DECAY);
+    }
+
+    /// Folds `peak` into the current interval and, once it's over, records the
+    /// interval's peak in the history.
+    fn update_level_history(&mut self, peak: f32, now: Instant) {
+        let interval = Duration::from_millis(LEVEL_HISTORY_INTERVAL_MS);
+        let due = *self.level_history_due.get_or_insert(now + interval);
+        self.level_history_peak = self.level_history_peak.max(peak);
+        if now < due {
+            return;
+        }
+        if self.level_history.len() == LEVEL_HISTORY_LEN {
+            self.level_history.pop_front();
+        }
+        self.level_history
+            .push_back(20.0 * self.level_history_peak.max(f32::MIN_POSITIVE).log10());
+        self.level_history_peak = 0.0;
+        self.level_history_due = Some(due + interval);
+    }
+
+    fn discard_recording_impl(&mut self) {
+        if self.recording_phase != RecordingPhase::Finalizing {
+            return;
+        }
+
+        self.audio_data.lock().unwrap().clear();
+        self.native_audio.lock().unwrap().clear();
+        self.pending_marks.clear();
+        self.captions = None;
+        self.rerecording = None;
+        self.take_name = None;
+        self.recording_phase = RecordingPhase::Idle;
+        self.end_recovery_journal();
+        self.elapsed_time = Duration::from_secs(0);
+        self.add_log("Recording discarded.".into());
+    }
+
+    fn signal_cue(&mut self, frequency: f32, flash_color: iced::Color) {
+        self.cue_flash = Some((Instant::now(), flash_color));
+
+        if !self.config.audio_cues_enabled {
+            return;
+        }
+
+        let on_error = report_side_errors(&self.side_error_tx, "Cue");
+        match play_cue_tone(self.backend.as_ref(), frequency, on_error) {
+            Ok(stream) => self.cue_stream = Some(stream),
+            Err(e) => self.add_log(format!("Couldn't play the cue: {}", e)),
+        }
+    }
+
+    /// Saves the take waiting to be saved. The task runs the post-save command on it,
+    /// if there is one.
+    fn finalize_recording(&mut self) -> Task<Message> {
+        let Some(filename) = self.save_take() else {
+            return Task::none();
+        };
+        let transcript = self.save_transcript(&filename);
+        Task::batch([transcript, self.post_save_task(&filename)])
+    }
+
+    /// Saves the take as the window closes. Nothing will be around to wait on a
+    /// task, so the transcript is written before returning and the post-save
+    /// command is started without waiting for it to finish.
+    fn finalize_on_exit(&mut self) {
+        // There's no asking for another name now, so one that won't do is dropped
+        if !self.take_name.as_ref().is_some_and(|name| {
+            rename_target("", name).is_ok_and(|name| !std::path::Path::new(&name).exists())
+        }) {
+            self.take_name = None;
+        }
+        let Some(filename) = self.save_take() else {
+            return;
+        };
+        if let Some(mut captions) = self.captions.take()
+            && let Err(e) = write_transcript(&filename, &captions.finish())
+        {
+            self.add_log(format!("Error saving transcript: {}", e));
+        }
+        let command = self.config.post_save_command.trim();
+        if command.is_empty() {
+            return;
+        }
+        let path = absolute_path(&filename);
+        if let Err(e) = post_save_command(command, &path).and_then(|mut process| {
+            process
+                .spawn()
+                .map_err(|e| format!("Couldn't run post-save command '{}': {}", command, e))
+        }) {
+            self.add_log(e);
+        }
+    }
+
+    /// Saves the take waiting to be saved under its name. Gives back the name, or
+    /// `None` if there was nothing to save or it couldn't be saved.
+    fn save_take(&mut self) -> Option<String> {
+        if !matches!(
+            self.recording_phase,
+            RecordingPhase::Stopping { .. } | RecordingPhase::Finalizing
+        ) {
+            return None;
+        }
+        let target = self.active_project.clone().zip(self.next_project_clip());
+        let filename = match (self.rerecording.take(), &target) {
+            (Some(replacing), _) => replacing,
+            (None, Some((project, clip))) => project.clip_filename(clip),
+            (None, None) => match self.take_name.take() {
+                Some(typed) => match rename_target("", &typed) {
+                    Ok(name) if !std::path::Path::new(&name).exists() => name,
+                    Ok(name) => {
+                        self.add_log(format!("'{}' already exists; pick another name.", name));
+                        self.take_name = Some(typed);
+                        return None;
+                    }
+                    Err(e) => {
+                        self.add_log(e);
+                        self.take_name = Some(typed);
+                        return None;
+                    }
+                },
+                None => self.default_take_name(),
+            },
+        };
+        if !self.save_recording_as(&filename) {
+            return None;
+        }
+        self.last_take = Some(filename.clone());
+        if let Some((project, clip)) = target {
+            self.project_clip = None;
+            match self.next_project_clip() {
+                Some(next) => self.add_log(format!("Recorded '{}'. Next up: '{}'.", clip, next)),
+                None => self.add_log(format!("All clips for '{}' are recorded.", project.name)),
+            }
+        }
+        Some(filename)
+    }
+
+    /// Runs the post-save command on `filename` on its own thread; how it went comes
+    /// back as `Message::PostSaveCommandFinished`.
+    fn post_save_task(&mut self, filename: &str) -> Task<Message> {
+        let command = self.config.post_save_command.trim().to_string();
+        if command.is_empty() {
+            return Task::none();
+        }
+        let path = absolute_path(filename);
+        self.add_log(format!("Running post-save command on '{}'...", filename));
+        let file = filename.to_string();
+        Task::perform(
+            run_in_background(move || run_post_save_command(&command, &path)),
+            move |result| {
+                Message::PostSaveCommandFinished(
+                    file.clone(),
+                    result.unwrap_or_else(|| Err("Post-save command failed unexpectedly.".into())),
+                )
+            },
+        )
+    }
+
+    /// Throws away the take saved last and records it again straight away, under the
+    /// same name and with the same input settings. The old take is held, so Undo can
+    /// bring it back once the new one is out of the way.
+    fn rerecord_last_take_impl(&mut self) {
+        if self.recording_phase != RecordingPhase::Idle
+            || self.playback_state != PlaybackState::Stopped
+        {
+            return;
+        }
+        let Some(take) = self.last_take.take() else {
+            return;
+        };
+        if !std::path::Path::new(&take).exists() {
+            self.add_log(format!("'{}' is gone; nothing to re-record.", take));
+            return;
+        }
+
+        let held = self.new_holding_dir();
+        if let Err(e) = hold_file(&held, &take) {
+            self.add_log(format!("Couldn't discard '{}': {}", take, e));
+            self.last_take = Some(take);
+            return;
+        }
+        // Marks and captions were timed to the old take, so they're held with it;
+        // notes stay with the name
+        let mut files = vec![(take.clone(), None)];
+        for sidecar in [marks_path(&take), transcript_path(&take)] {
+            if std::path::Path::new(&sidecar).exists() {
+                match hold_file(&held, &sidecar) {
+                    Ok(()) => files.push((sidecar, None)),
+                    Err(e) => self.add_log(format!("Couldn't set '{}' aside: {}", sidecar, e)),
+                }
+            }
+        }
+        self.fingerprint_cache.remove(&cache_key(&take));
+        self.analysis_cache.remove(&cache_key(&take));
+        if self.waveform.as_ref().is_some_and(|w| w.file == take) {
+            self.waveform = None;
+        }
+        self.push_undo(UndoEntry {
+            label: format!("re-record of '{}'", take),
+            undo: BatchUndo::Deleted { held, files },
+        });
+        self.refresh_files();
+
+        self.rerecording = Some(take.clone());
+        self.start_recording_impl();
+        if self.recording_phase.is_recording() {
+            self.add_log(format!("Re-recording '{}'...", take));
+        } else {
+            self.rerecording = None;
+        }
+    }
+
+    /// Puts the length, peak, clip count and size of the take just saved above the
+    /// file list for a few seconds. Worked out from the samples still in memory, so
+    /// the file isn't read back.
+    fn show_take_summary(&mut self, filename: &str, spec: WavSpec, samples: &[f32]) {
+        let frames = samples.len() / spec.channels.max(1) as usize;
+        let duration = Duration::from_secs_f64(frames as f64 / spec.sample_rate.max(1) as f64);
+        let peak = samples.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+        let clipped = samples
+            .iter()
+            .filter(|s| s.abs() >= FILE_CLIP_LEVEL)
+            .count();
+        let size = fs::metadata(filename).map_or(0, |m| m.len());
+        let peak = if peak > 0.0 {
+            format!("{:.1} dBFS", 20.0 * peak.log10())
+        } else {
+            "-inf dBFS".to_string()
+        };
+        let summary = format!(
+            "'{}': {}, peak {}, {} clipped sample{}, {:.1} MB",
+            filename,
+            format_timer(duration, TimerPrecision::Tenths, false),
+            peak,
+            clipped,
+            if clipped == 1 { "" } else { "s" },
+            size as f64 / 1_000_000.0
+        );
+        self.take_summary = Some((summary, Instant::now()));
+    }
+
+    /// The name a take is saved under when none was typed: when recording began
+    /// with naming by speech on, or else the next `recording_N.wav`.
+    fn default_take_name(&self) -> String {
+        match self.recording_started_at {
+            Some(started) if self.config.name_by_speech => timestamp_filename(started),
+            _ => format!("recording_{}.wav", self.files.len() + 1),
+        }
+    }
+
+    /// Fills in the name the take just stopped is offered under. With naming by speech
+    /// on, its first few seconds are transcribed in the background, and what was said
+    /// comes back as `Message::TakeNameSuggested`.
+    fn offer_take_name(&mut self) -> Task<Message> {
+        if self.rerecording.is_some()
+            || (self.active_project.is_some() && self.next_project_clip().is_some())
+        {
+            self.take_name = None;
+            return Task::none();
+        }
+        self.take_name = Some(self.default_take_name());
+        if !self.config.name_by_speech {
+            return Task::none();
+        }
+        let engine = self.captions_engine;
+        let rate = self.recording_sample_rate;
+        let channels = self.recording_channels.max(1);
+        let opening = {
+            let audio = self.audio_data.lock().unwrap();
+            let len = (SPOKEN_TITLE_SECS * rate as f32) as usize * channels as usize;
+            downmix_to_mono(&audio[..len.min(audio.len())], channels)
+        };
+        Task::perform(
+            run_in_background(move || {
+                let engine = engine(rate)?;
+                Ok(spoken_title_filename(&transcribe::transcribe(
+                    engine, &opening,
+                )))
+            }),
+            |result| {
+                Message::TakeNameSuggested(
+                    result.unwrap_or_else(|| Err("transcription failed unexpectedly".into())),
+                )
+            },
+        )
+    }
+
+    /// The clip the next take will be saved as: the one picked in the project panel,
+    /// or else the first clip that hasn't been recorded yet.
+    fn next_project_clip(&self) -> Option<String> {
+        let project = self.active_project.as_ref()?;
+        let missing = |clip: &&String| !self.files.contains(&project.clip_filename(clip));
+        self.project_clip
+            .iter()
+            .filter(missing)
+            .chain(project.clips.iter().filter(missing))
+            .next()
+            .cloned()
+    }
+
+    fn create_project_impl(&mut self) {
+        let name = self.project_name_input.trim().to_string();
+        let clips: Vec<String> = self
+            .project_clips_input
+            .split(',')
+            .map(|c| c.trim().to_string())
+            .filter(|c| !c.is_empty())
+            .collect();
+        if name.is_empty() || clips.is_empty() {
+            self.add_log("A project needs a name and at least one clip.".into());
+            return;
+        }
+
+        let project = Project { name, clips };
+        match self.projects.iter_mut().find(|p| p.name == project.name) {
+            Some(existing) => *existing = project.clone(),
+            None => self.projects.push(project.clone()),
+        }
+        if let Err(e) = save_projects(&self.projects) {
+            self.add_log(format!("Error saving projects: {}", e));
+        }
+        self.project_name_input.clear();
+        self.project_clips_input.clear();
+        self.select_project_impl(project);
+    }
+
+    fn select_project_impl(&mut self, project: Project) {
+        self.project_clip = None;
+        self.add_log(format!(
+            "Project '{}': {} of {} clips recorded.",
+            project.name,
+            project
+                .clips
+                .iter()
+                .filter(|c| self.files.contains(&project.clip_filename(c)))
+                .count(),
+            project.clips.len()
+        ));
+        self.active_project = Some(project);
+    }
+
+    /// Whether this take will be written as 16-bit PCM rather than 32-bit float.
+    fn saves_as_i16(&self) -> bool {
+        self.config.preserve_source_format
+            && matches!(
+                self.recording_sample_format,
+                SampleFormat::I16 | SampleFormat::U16
+            )
+    }
+
+    /// Interleaved samples that fit in the configured file size cap, whole frames
+    /// only, or `None` without a cap.
+    fn max_recording_samples(&self) -> Option<usize> {
+        if self.config.max_file_size_mb <= 0.0 {
+            return None;
+        }
+        let bytes = (self.config.max_file_size_mb as f64 * 1_000_000.0) as usize;
+        let sample_bytes = if self.saves_as_i16() { 2 } else { 4 };
+        let channels = self.recording_channels.max(1) as usize;
+        let frames = bytes.saturating_sub(WAV_HEADER_ALLOWANCE) / (sample_bytes * channels);
+        Some(frames * channels)
+    }
+
+    /// Stops capture and writes the take to `filename`. Returns whether it was saved.
+    fn save_recording_as(&mut self, filename: &str) -> bool {
+        self.input_stream = None;
+        self.stop_monitor();
+        self.input_capturing.store(false, Ordering::Relaxed);
+        self.recording_phase = RecordingPhase::Idle;
+        self.feed_captions();
+        self.journal_recording(Instant::now(), true);
+
+        let mut samples: Vec<f32> = std::mem::take(&mut *self.audio_data.lock().unwrap());
+        let mut native: Vec<i16> = std::mem::take(&mut *self.native_audio.lock().unwrap());
+        let mut marks = std::mem::take(&mut self.pending_marks);
+        if let Some(limit) = self.max_recording_samples()
+            && samples.len() > limit
+        {
+            samples.truncate(limit);
+            native.truncate(limit);
+            marks.retain(|&m| m < limit);
+        }
+        // What was played along to the metronome reached the input a round trip late
+        if std::mem::take(&mut self.metronome_during_take)
+            && let Some(ms) = self.config.round_trip_latency_ms
+        {
+            let frames = (ms as f64 * self.recording_sample_rate as f64 / 1000.0).round();
+            let lag =
+                (frames as usize * self.recording_channels.max(1) as usize).min(samples.len());
+            samples.drain(..lag);
+            native.drain(..lag.min(native.len()));
+            marks.retain(|&m| m >= lag);
+            for m in &mut marks {
+                *m -= lag;
+            }
+        }
+
+        let mut trimmed = false;
+        if self.config.processing.auto_trim_on_save
+            && let Some(range) = silence_trim_range(
+                &samples,
+                self.recording_channels,
+                self.recording_sample_rate,
+                db_to_amplitude(self.config.processing.trim_threshold_db),
+                self.config.processing.trim_padding_ms,
+            )
+        {
+            samples.truncate(range.end);
+            samples.drain(..range.start);
+            native.truncate(range.end);
+            native.drain(..range.start.min(native.len()));
+            marks.retain(|m| range.contains(m));
+            for m in &mut marks {
+                *m -= range.start;
+            }
+            trimmed = true;
+        }
+
+        if samples.is_empty() {
+            self.add_log("Error saving file: No audio data captured".into());
+            return false;
+        }
+
+        let preserve_i16 = self.saves_as_i16();
+        let spec = WavSpec {
+            channels: self.recording_channels,
+            sample_rate: self.recording_sample_rate,
+            bits_per_sample: if preserve_i16 { 16 } else { 32 },
+            sample_format: if preserve_i16 {
+                hound::SampleFormat::Int
+            } else {
+                hound::SampleFormat::Float
+            },
+        };
+
+        println!(
+            "Saving WAV file with: channels={}, sample_rate={}, bits_per_sample={}, format={:?}",
+            spec.channels, spec.sample_rate, spec.bits_per_sample, spec.sample_format
+        );
+
+        let mut chunks = Vec::new();
+        if self.config.bwf_metadata {
+            let bext = bwf::Bext::new(
+                &self.recording_description,
+                filename,
+                self.recording_started_at.unwrap_or_else(SystemTime::now),
+                spec.sample_rate,
+            );
+            chunks.push((bwf::BEXT, bext.to_bytes()));
+        }
+        if self.config.processing_note {
+            let comment = bwf::info_comment(None, &self.processing_description(trimmed));
+            chunks.push((bwf::LIST, comment));
+        }
+        if !marks.is_empty() {
+            let channels = spec.channels.max(1) as usize;
+            let frames: Vec<u32> = marks.iter().map(|&m| (m / channels) as u32).collect();
+            chunks.push((bwf::CUE, bwf::cue_points(&frames)));
+        }
+
+        let result = if preserve_i16 {
+            // Written as the device sent it; converted back only if the native
+            // copy is missing (say the setting was turned on mid-take)
+            if native.len() != samples.len() {
+                native = samples.iter().map(|&s| input_f32_to_i16(s)).collect();
+            }
+            write_wav_file_with_chunks(filename, spec, &native, &chunks)
+        } else {
+            write_wav_file_with_chunks(filename, spec, &samples, &chunks)
+        };
+        match result {
+            Ok(()) => {
+                self.end_recovery_journal();
+                self.add_log(format!("Recording saved as '{}'", filename));
+                // The description was for this take only
+                self.recording_description.clear();
+                if !marks.is_empty() {
+                    self.save_recording_marks(filename, marks, spec);
+                }
+                self.show_take_summary(filename, spec, &samples);
+                self.refresh_files();
+                true
+            }
+            Err(e) => {
+                // The journal still has the take, so it can be recovered instead
+                if self.recovery_writer.take().is_some() {
+                    self.interrupted_take = find_interrupted_take(&self.recovery_path);
+                }
+                self.add_log(format!("Error saving file: {}", e));
+                false
+            }
+        }
+    }
+
+    /// What was done to the input on its way into the take, for the INFO comment.
+    fn processing_description(&self, trimmed: bool) -> String {
+        let gains = self.channel_gains.lock().unwrap();
+        let gain = if gains.iter().all(|&g| g == 1.0) {
+            "input gain 0 dB".to_string()
+        } else {
+            let per_channel: Vec<String> = gains
+                .iter()
+                .enumerate()
+                .map(|(ch, g)| format!("ch{} {:+.1} dB", ch + 1, 20.0 * g.log10()))
+                .collect();
+            format!("input gain {}", per_channel.join(", "))
+        };
+
+        let mut steps = vec![gain];
+        if self.config.processing.dc_block_enabled {
+            steps.push(format!("DC block ({} Hz high-pass)", DC_BLOCK_CUTOFF_HZ));
+        }
+        if trimmed {
+            steps.push(format!(
+                "silence trimmed below {} dBFS with {} ms padding",
+                self.config.processing.trim_threshold_db, self.config.processing.trim_padding_ms
+            ));
+        }
+        format!("Processing: {}", steps.join("; "))
+    }
+
+    /// A click on a filename toggles its selection; a second click on the same name
+    /// within `DOUBLE_CLICK_INTERVAL` starts renaming it instead.
+    fn select_file_impl(&mut self, fname: String) -> Task<Message> {
+        let now = Instant::now();
+        let double_click = self.last_file_click.take().is_some_and(|(file, at)| {
+            file == fname && now.duration_since(at) <= DOUBLE_CLICK_INTERVAL
+        });
+        if double_click {
+            let task = if self.selected_file.as_ref() != Some(&fname) {
+                self.select_file(fname.clone())
+            } else {
+                Task::none()
+            };
+            self.start_rename_impl(&fname);
+            return task;
+        }
+        self.last_file_click = Some((fname.clone(), now));
+
+        if self.selected_file.as_ref() == Some(&fname) {
+            self.tag_input.clear();
+            self.selected_file = None;
+            Task::none()
+        } else {
+            self.select_file(fname)
+        }
+    }
+
+    /// Selects `fname` and loads what the panels show for it. The task brings in
+    /// its analysis, which is worked out in the background.
+    fn select_file(&mut self, fname: String) -> Task<Message> {
+        self.tag_input.clear();
+        self.load_waveform(&fname);
+        if self.marker_file.as_ref() != Some(&fname) {
+            self.load_file_marks(&fname);
+        }
+        self.selected_bext = bwf::read_chunk(&fname, bwf::BEXT)
+            .ok()
+            .flatten()
+            .and_then(|data| bwf::Bext::from_bytes(&data));
+        self.selected_comment = bwf::read_info(&fname)
+            .ok()
+            .flatten()
+            .and_then(|data| bwf::read_info_comment(&data));
+        let analysis = self.update_analysis(&fname);
+        self.selected_file = Some(fname);
+        analysis
+    }
+
+    fn save_recording_marks(&mut self, filename: &str, marks: Vec<usize>, spec: WavSpec) {
+        let samples_per_sec = spec.sample_rate as f64 * spec.channels as f64;
+        let secs: Vec<(f64, String)> = marks
+            .iter()
+            .map(|&m| (m as f64 / samples_per_sec, String::new()))
+            .collect();
+        if let Err(e) = save_marks(filename, &secs) {
+            self.add_log(format!("Error saving cue points: {}", e));
+            return;
+        }
+        self.markers = marks;
+        self.marker_labels.clear();
+        self.marker_file = Some(filename.to_string());
+        self.marker_samples_per_sec = samples_per_sec as usize;
+    }
+
+    /// Shows the markers saved with `fname` on its waveform, which must be loaded.
+    fn load_file_marks(&mut self, fname: &str) {
+        if let Some(marks) = load_marks(fname).or_else(|| load_cue_marks(fname))
+            && let Some(data) = &self.waveform
+        {
+            let samples_per_sec = data.sample_rate as usize * data.channels.max(1) as usize;
+            let channels = data.channels.max(1) as usize;
+            (self.markers, self.marker_labels) = marks
+                .into_iter()
+                .map(|(secs, label)| ((secs * samples_per_sec as f64) as usize, label))
+                .map(|(m, label)| (m - m % channels, label))
+                .filter(|&(m, _)| m > 0 && m < data.total_samples)
+                .unzip();
+            self.marker_file = Some(fname.to_string());
+            self.marker_samples_per_sec = samples_per_sec;
+        }
+    }
+
+    /// Moves the markers of `filename` after an edit changed its timing (see
+    /// `remap_saved_marks`), and shows them again if they were on screen.
+    fn remap_file_marks(
+        &mut self,
+        filename: &str,
+        sample_rate: u32,
+        map: impl Fn(f64) -> Option<f64>,
+    ) {
+        if let Err(e) = remap_saved_marks(filename, sample_rate, map) {
+            self.add_log(format!(
+                "Couldn't move the markers of '{}': {}",
+                filename, e
+            ));
+        }
+        if self.marker_file.as_deref() == Some(filename) {
+            self.marker_file = None;
+            self.markers.clear();
+            self.marker_labels.clear();
+            if self.waveform.as_ref().is_some_and(|w| w.file == filename) {
+                self.load_file_marks(filename);
+            }
+        }
+    }
+
+    /// Writes the selected file's markers out in `marker_format`.
+    fn export_markers_impl(&mut self) {
+        let Some(filename) = self.selected_file.clone() else {
+            return;
+        };
+        if self.marker_file.as_ref() != Some(&filename) || self.markers.is_empty() {
+            self.add_log(format!("'{}' has no markers to export.", filename));
+            return;
+        }
+        let samples_per_sec = self.marker_samples_per_sec.max(1) as f64;
+        let secs: Vec<(f64, String)> = self
+            .markers
+            .iter()
+            .enumerate()
+            .map(|(i, &m)| {
+                let label = self.marker_labels.get(i).cloned().unwrap_or_default();
+                (m as f64 / samples_per_sec, label)
+            })
+            .collect();
+
+        let format = self.marker_format;
+        let (written, target) = match format.path(&filename) {
+            Some(path) => (fs::write(&path, marks_to_text(&secs, format)), path),
+            None => {
+                if !self.can_interact_with_file(&filename) {
+                    return;
+                }
+                let written = WavReader::open(&filename)
+                    .map_err(io::Error::other)
+                    .and_then(|reader| {
+                        let channels = reader.spec().channels.max(1) as usize;
+                        let frames: Vec<u32> = self
+                            .markers
+                            .iter()
+                            .map(|&m| (m / channels) as u32)
+                            .collect();
+                        drop(reader);
+                        bwf::write_chunk(&filename, bwf::CUE, &bwf::cue_points(&frames))
+                    });
+                (written, filename.clone())
+            }
+        };
+        match written {
+            Ok(()) => self.add_log(format!(
+                "Exported {} markers to '{}' ({})",
+                secs.len(),
+                target,
+                format
+            )),
+            Err(e) => self.add_log(format!("Error exporting markers: {}", e)),
+        }
+    }
+
+    /// Loads a WAV straight into the editor: brings it into the folder if it's from
+    /// elsewhere, selects it for its waveform and markers, and cues it up paused at
+    /// the start on the main screen.
+    fn open_for_editing_impl(&mut self, path: &str) -> Task<Message> {
+        let path = path.trim();
+        if path.is_empty() || self.recording_phase.is_recording() || self.recording_phase.is_armed()
+        {
+            return Task::none();
+        }
+        let (filename, converted, spec, samples) = match import_for_editing(path) {
+            Ok(imported) => imported,
+            Err(e) => {
+                self.add_log(format!("Couldn't open '{}': {}", path, e));
+                return Task::none();
+            }
+        };
+        if self.playback_state != PlaybackState::Stopped {
+            self.stop_playback_impl();
+        }
+        self.refresh_files();
+        self.marker_file = None;
+        self.markers.clear();
+        // Drawn from the audio already read, so selecting doesn't read the file again
+        self.waveform = Some(WaveformData::new(
+            &filename,
+            &samples,
+            spec.channels,
+            spec.sample_rate,
+        ));
+        self.waveform_zoom = 1.0;
+        self.waveform_scroll = 0.0;
+        let analysis = self.select_file(filename.clone());
+        self.screen = Screen::Main;
+        self.open_file_input.clear();
+
+        if self.start_playback(PlaybackSource::File(filename.clone()), spec, samples, 0, "") {
+            *self.is_stream_paused.lock().unwrap() = true;
+            *self.playback_position.lock().unwrap() = 0;
+            self.playback_state = PlaybackState::Paused;
+            self.start_time = None;
+            self.elapsed_time = Duration::ZERO;
+        }
+        self.add_log(format!(
+            "Opened '{}' for editing{}",
+            filename,
+            if converted {
+                " (converted to 32-bit float)"
+            } else {
+                ""
+            }
+        ));
+        analysis
+    }
+
+    /// Replaces the selected file's markers with those found in `marker_format`.
+    fn import_markers_impl(&mut self) -> Task<Message> {
+        let Some(filename) = self.selected_file.clone() else {
+            return Task::none();
+        };
+        let format = self.marker_format;
+        let (imported, source) = match format.path(&filename) {
+            Some(path) => (
+                fs::read_to_string(&path)
+                    .map_err(|e| e.to_string())
+                    .and_then(|text| parse_marks_text(&text)),
+                path,
+            ),
+            None => (
+                load_cue_marks(&filename).ok_or_else(|| "no cue points in the file".to_string()),
+                filename.clone(),
+            ),
+        };
+        let mut marks = match imported {
+            Ok(marks) => marks,
+            Err(e) => {
+                self.add_log(format!("Couldn't import markers from '{}': {}", source, e));
+                return Task::none();
+            }
+        };
+        if let Some(data) = self.waveform.as_ref().filter(|w| w.file == filename) {
+            let duration = data.duration_secs() as f64;
+            marks.retain(|&(secs, _)| secs > 0.0 && secs < duration);
+        }
+        if let Err(e) = save_marks(&filename, &marks) {
+            self.add_log(format!("Error saving markers: {}", e));
+            return Task::none();
+        }
+
+        // Reselecting picks the saved markers up like any others
+        self.marker_file = None;
+        self.markers.clear();
+        let analysis = self.select_file(filename);
+        self.add_log(format!(
+            "Imported {} markers from '{}' ({})",
+            marks.len(),
+            source,
+            format
+        ));
+        analysis
+    }
+
+    fn toggle_metronome_impl(&mut self) {
+        if self.metronome_stream.take().is_some() {
+            return;
+        }
+        match play_metronome(
+            self.backend.as_ref(),
+            Arc::clone(&self.metronome_bpm),
+            report_side_errors(&self.side_error_tx, "Metronome"),
+        ) {
+            Ok(stream) => {
+                self.metronome_stream = Some(stream);
+                if self.recording_phase.is_recording() {
+                    self.metronome_during_take = true;
+                }
+            }
+            Err(e) => self.add_log(format!("Couldn't start the metronome: {}", e)),
+        }
+    }
+
+    fn set_metronome_bpm(&mut self, bpm: f32) {
+        self.metronome_bpm.store(bpm.to_bits(), Ordering::Relaxed);
+        self.config.metronome_bpm = bpm;
+        self.save_config();
+    }
+
+    /// Sets the tempo from the last few taps, once there are two to measure.
+    fn tap_tempo_impl(&mut self, at: Instant) {
+        if self
+            .tempo_taps
+            .back()
+            .is_some_and(|&last| at.saturating_duration_since(last) > TAP_TEMPO_RESET)
+        {
+            self.tempo_taps.clear();
+        }
+        if self.tempo_taps.len() == TAP_TEMPO_TAPS {
+            self.tempo_taps.pop_front();
+        }
+        self.tempo_taps.push_back(at);
+        if let Some(bpm) = tap_tempo_bpm(&self.tempo_taps) {
+            let bpm = (bpm * 10.0).round() / 10.0;
+            self.set_metronome_bpm(bpm);
+            self.metronome_input = bpm.to_string();
+        }
+    }
+
+    /// Runs the shortcut on `key`, or gives the key to the shortcut being remapped.
+    fn key_pressed_impl(&mut self, key: Key, modifiers: Modifiers) -> Task<Message> {
+        let Some(name) = key_name(&key, modifiers) else {
+            return Task::none();
+        };
+        let Some(action) = self.remapping_shortcut else {
+            return match shortcut_for(&self.config.keymap, &name) {
+                Some(action) => self.update(action.message()),
+                None => Task::none(),
+            };
+        };
+        if name == "Esc" {
+            self.remapping_shortcut = None;
+            return Task::none();
+        }
+        match shortcut_for(&self.config.keymap, &name) {
+            Some(other) if other != action => {
+                self.add_log(format!(
+                    "{} is already the shortcut for \"{}\"; choose another key.",
+                    key_label(&name),
+                    other.description()
+                ));
+            }
+            _ => {
+                self.remapping_shortcut = None;
+                if name == action.default_key() {
+                    self.config.keymap.remove(&action);
+                } else {
+                    self.config.keymap.insert(action, name.clone());
+                }
+                self.save_config();
+                self.add_log(format!(
+                    "\"{}\" is now on {}.",
+                    action.description(),
+                    key_label(&name)
+                ));
+            }
+        }
+        Task::none()
+    }
+
+    fn save_channel_gains(&mut self) {
+        let gains = self.channel_gains.lock().unwrap().clone();
+        self.config.processing.input_gains_db = gains.iter().map(|g| 20.0 * g.log10()).collect();
+        self.save_config();
+    }
+
+    /// Moves the input gain of every channel by `step_db` while the input is open,
+    /// otherwise the playback volume, and shows the new level for a moment.
+    fn nudge_level_impl(&mut self, step_db: f32) {
+        let readout = if self.recording_phase.input_open() {
+            let mut gains = self.channel_gains.lock().unwrap();
+            for gain in gains.iter_mut() {
+                let db = (20.0 * gain.log10() + step_db)
+                    .clamp(-INPUT_GAIN_RANGE_DB, INPUT_GAIN_RANGE_DB);
+                *gain = db_to_amplitude(db);
+            }
+            let db = gains.first().map_or(0.0, |g| 20.0 * g.log10());
+            drop(gains);
+            self.save_channel_gains();
+            format!("Input gain {db:+.1} dB")
+        } else {
+            let volume = f32::from_bits(self.output_volume.load(Ordering::Relaxed));
+            let db = if volume > 0.0 {
+                20.0 * volume.log10()
+            } else {
+                MIN_NUDGE_VOLUME_DB - LEVEL_NUDGE_DB
+            };
+            let db = (db + step_db).clamp(MIN_NUDGE_VOLUME_DB, 0.0);
+            let volume = db_to_amplitude(db);
+            self.output_volume
+                .store(volume.to_bits(), Ordering::Relaxed);
+            self.config.output_volume = volume;
+            self.save_config();
+            format!("Playback volume {db:+.1} dB")
+        };
+        self.level_nudge = Some((readout, Instant::now()));
+    }
+
+    /// Drops a cue point at the current end of the captured audio.
+    fn drop_mark_impl(&mut self) {
+        if !self.recording_phase.is_recording() {
+            return;
+        }
+        let channels = self.recording_channels.max(1) as usize;
+        let captured = self.audio_data.lock().unwrap().len();
+        let mark = captured - captured % channels;
+        if self.pending_marks.last() == Some(&mark) {
+            return;
+        }
+        self.pending_marks.push(mark);
+        let secs = mark as f64 / (self.recording_sample_rate as f64 * channels as f64);
+        self.add_log(format!(
+            "Cue point {} at {:.2}s",
+            self.pending_marks.len(),
+            secs
+        ));
+    }
+
+    fn start_rename_impl(&mut self, filename: &str) {
+        // Can't rename while playing or recording
+        if self.recording_phase.is_recording() || self.playback_state != PlaybackState::Stopped {
+            return;
+        }
+
+        self.renaming_file = Some(filename.to_string());
+        self.new_name = sidecar_path(filename, "");
+    }
+
+    fn confirm_rename_impl(&mut self) {
+        if let Some(old_name) = self.renaming_file.clone() {
+            let new_filename = match rename_target(&old_name, &self.new_name) {
+                Ok(name) => name,
+                Err(e) => {
+                    self.add_log(e);
+                    return;
+                }
+            };
+
+            if new_filename != old_name && std::path::Path::new(&new_filename).exists() {
+                self.add_log("File with that name already exists.".into());
+                return;
+            }
+
+            match std::fs::rename(&old_name, &new_filename) {
+                Ok(()) => {
+                    self.add_log(format!("Renamed '{}' to '{}'", old_name, new_filename));
+                    if fs::metadata(marks_path(&old_name)).is_ok() {
+                        let _ = fs::rename(marks_path(&old_name), marks_path(&new_filename));
+                    }
+                    if fs::metadata(transcript_path(&old_name)).is_ok() {
+                        let _ =
+                            fs::rename(transcript_path(&old_name), transcript_path(&new_filename));
+                    }
+                    for sidecar in [playback_settings_path, speed_path] {
+                        if fs::metadata(sidecar(&old_name)).is_ok() {
+                            let _ = fs::rename(sidecar(&old_name), sidecar(&new_filename));
+                        }
+                    }
+                    let had_notes = self.notes.contains_key(&old_name);
+                    self.follow_rename(&old_name, &new_filename);
+                    if had_notes {
+                        self.persist_notes();
+                    }
+                    self.refresh_files();
+                    self.renaming_file = None;
+                    self.new_name.clear();
+                }
+                Err(e) => {
+                    self.add_log(format!("Error renaming file: {}", e));
+                }
+            }
+        }
+    }
+
+    /// Points everything that refers to `old_name` at `new_name`. Notes are moved but
+    /// not saved.
+    fn follow_rename(&mut self, old_name: &str, new_name: &str) {
+        if self.marker_file.as_deref() == Some(old_name) {
+            self.marker_file = Some(new_name.to_string());
+        }
+        if self.selected_file.as_deref() == Some(old_name) {
+            self.selected_file = Some(new_name.to_string());
+        }
+        if let Some(waveform) = self.waveform.as_mut()
+            && waveform.file == old_name
+        {
+            waveform.file = new_name.to_string();
+        }
+        if let Some(file_notes) = self.notes.remove(old_name) {
+            self.notes.insert(new_name.to_string(), file_notes);
+        }
+    }
+
+    /// What the batch rename would do to each file in the list (those matching the
+    /// search): the new name, or why the file can't have it.
+    fn batch_rename_plan(&self) -> Vec<(String, Result<String, String>)> {
+        let files: Vec<&String> = self
+            .files
+            .iter()
+            .filter(|f| self.file_matches_search(f))
+            .collect();
+        let mut plan: Vec<(String, Result<String, String>)> = files
+            .iter()
+            .enumerate()
+            .map(|(i, &file)| {
+                let target = expand_rename_template(&self.batch_rename_template, i + 1)
+                    .and_then(|name| rename_target(file, &name));
+                (file.clone(), target)
+            })
+            .collect();
+
+        let mut targets: HashMap<String, usize> = HashMap::new();
+        for (_, target) in &plan {
+            if let Ok(name) = target {
+                *targets.entry(name.clone()).or_default() += 1;
+            }
+        }
+        for (_, target) in &mut plan {
+            let Ok(name) = target else {
+                continue;
+            };
+            if targets[name.as_str()] > 1 {
+                *target = Err(format!("{}: same name as another file", name));
+            } else if !files.contains(&&*name) && std::path::Path::new(name.as_str()).exists() {
+                *target = Err(format!("{}: a file with that name already exists", name));
+            }
+        }
+        // A skipped file keeps its name, so no other file can take it
+        loop {
+            let kept: HashSet<String> = plan
+                .iter()
+                .filter(|(_, target)| target.is_err())
+                .map(|(from, _)| from.clone())
+                .collect();
+            let mut changed = false;
+            for (from, target) in &mut plan {
+                if let Ok(name) = target
+                    && *name != *from
+                    && kept.contains(name.as_str())
+                {
+                    *target = Err(format!("{}: kept by a file that is skipped", name));
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+        plan
+    }
+
+    /// What `kind` would do, in a line, or `None` if it would do nothing.
+    fn batch_summary(&self, kind: BatchKind) -> Option<String> {
+        let files = |n: usize| format!("{} file{}", n, if n == 1 { "" } else { "s" });
+        match kind {
+            BatchKind::Rename => {
+                let plan = self.batch_rename_plan();
+                let renamed = plan
+                    .iter()
+                    .filter(|(from, to)| to.as_ref().is_ok_and(|to| to != from))
+                    .count();
+                let skipped = plan.iter().filter(|(_, to)| to.is_err()).count();
+                (renamed > 0).then(|| match skipped {
+                    0 => format!("Rename {}.", files(renamed)),
+                    n => format!(
+                        "Rename {}, skip {} conflict{}.",
+                        files(renamed),
+                        n,
+                        if n == 1 { "" } else { "s" }
+                    ),
+                })
+            }
+            BatchKind::DeleteDuplicates => {
+                let n = self.duplicates.len();
+                (n > 0).then(|| {
+                    format!(
+                        "Delete {} duplicate{} ({}).",
+                        n,
+                        if n == 1 { "" } else { "s" },
+                        "they're kept until the app closes, so this can be undone"
+                    )
+                })
+            }
+            BatchKind::ExportAll => {
+                let outputs: Vec<String> = self
+                    .export_sources()
+                    .iter()
+                    .map(|f| export_path(f, &self.config.export))
+                    .collect();
+                let replaced = outputs
+                    .iter()
+                    .filter(|o| std::path::Path::new(o.as_str()).exists())
+                    .count();
+                (!outputs.is_empty()).then(|| match replaced {
+                    0 => format!(
+                        "Export {} as {}.",
+                        files(outputs.len()),
+                        self.config.export.format
+                    ),
+                    n => format!(
+                        "Export {} as {}, replacing {} earlier export{}.",
+                        files(outputs.len()),
+                        self.config.export.format,
+                        n,
+                        if n == 1 { "" } else { "s" }
+                    ),
+                })
+            }
+        }
+    }
+
+    /// Asks for confirmation of `kind`, showing what it will do.
+    fn request_batch_impl(&mut self, kind: BatchKind) {
+        if self.recording_phase.is_recording()
+            || self.playback_state != PlaybackState::Stopped
+            || self.background_job.is_some()
+        {
+            return;
+        }
+        match self.batch_summary(kind) {
+            Some(_) => self.pending_batch = Some(kind),
+            None => self.add_log("Nothing to do.".into()),
+        }
+    }
+
+    fn confirm_batch_impl(&mut self) -> Task<Message> {
+        match self.pending_batch.take() {
+            Some(BatchKind::Rename) => self.apply_batch_rename_impl(),
+            Some(BatchKind::DeleteDuplicates) => self.delete_duplicates_impl(),
+            Some(BatchKind::ExportAll) => return self.export_all_impl(),
+            None => {}
+        }
+        Task::none()
+    }
+
+    /// A fresh holding folder for a batch's files.
+    fn new_holding_dir(&self) -> String {
+        (1..)
+            .map(|n| format!("{}/{}", UNDO_DIR, n))
+            .find(|dir| {
+                !std::path::Path::new(dir).exists()
+                    && self.undo_history.iter().all(|entry| match &entry.undo {
+                        BatchUndo::Renamed(_) => true,
+                        BatchUndo::Deleted { held, .. } | BatchUndo::Exported { held, .. } => {
+                            held != dir
+                        }
+                    })
+            })
+            .unwrap()
+    }
+
+    fn push_undo(&mut self, entry: UndoEntry) {
+        self.undo_history.push(entry);
+        if self.undo_history.len() > UNDO_HISTORY_LEN {
+            let oldest = self.undo_history.remove(0);
+            discard_held_files(&oldest);
+        }
+    }
+
+    /// Puts back earlier exports whose replacement never got written, because the
+    /// export was cancelled or failed, and forgets outputs that weren't written.
+    /// Exports are renamed into place whole, so a missing output means that file
+    /// wasn't exported.
+    fn restore_unexported_files(&mut self) {
+        let exists = |path: &String| std::path::Path::new(path.as_str()).exists();
+        for entry in &mut self.undo_history {
+            if let BatchUndo::Exported {
+                held,
+                outputs,
+                replaced,
+            } = &mut entry.undo
+            {
+                replaced.retain(|file| {
+                    if exists(file) {
+                        return true;
+                    }
+                    match unhold_file(held, file) {
+                        Ok(()) => {
+                            outputs.retain(|output| output != file);
+                            false
+                        }
+                        Err(_) => true,
+                    }
+                });
+                outputs.retain(exists);
+            }
+        }
+        self.undo_history.retain(|entry| {
+            !matches!(&entry.undo, BatchUndo::Exported { outputs, .. } if outputs.is_empty())
+        });
+    }
+
+    /// Forgets every batch, deleting the files held for them.
+    fn clear_undo_history(&mut self) {
+        self.restore_unexported_files();
+        for entry in std::mem::take(&mut self.undo_history) {
+            discard_held_files(&entry);
+        }
+        let _ = fs::remove_dir(UNDO_DIR);
+    }
+
+    /// Reverts the last batch as a whole.
+    fn undo_batch_impl(&mut self) {
+        if self.recording_phase.is_recording()
+            || self.playback_state != PlaybackState::Stopped
+            || self.background_job.is_some()
+        {
+            self.add_log("Stop recording, playback and exports before undoing.".into());
+            return;
+        }
+        let Some(entry) = self.undo_history.pop() else {
+            return;
+        };
+        let result = match &entry.undo {
+            BatchUndo::Renamed(renames) => {
+                let back: Vec<(String, String)> = renames
+                    .iter()
+                    .map(|(old, new)| (new.clone(), old.clone()))
+                    .collect();
+                let renamed: HashSet<&String> = back.iter().map(|(from, _)| from).collect();
+                let targets = back.iter().map(|(_, to)| to);
+                match first_existing(targets.filter(|to| !renamed.contains(to))) {
+                    Some(path) => Err(format!("'{}' is in the way", path)),
+                    None => self.rename_files(&back).map_err(|e| e.to_string()),
+                }
+            }
+            BatchUndo::Deleted { held, files } => {
+                match first_existing(files.iter().map(|(file, _)| file)) {
+                    Some(path) => Err(format!("'{}' is in the way", path)),
+                    None => files
+                        .iter()
+                        .try_for_each(|(file, _)| unhold_file(held, file))
+                        .map(|()| {
+                            for (file, notes) in files {
+                                if let Some(notes) = notes {
+                                    self.notes.insert(file.clone(), notes.clone());
+                                }
+                            }
+                            self.persist_notes();
+                        })
+                        .map_err(|e| e.to_string()),
+                }
+            }
+            BatchUndo::Exported {
+                held,
+                outputs,
+                replaced,
+            } => outputs
+                .iter()
+                .filter(|output| std::path::Path::new(output.as_str()).exists())
+                .try_for_each(fs::remove_file)
+                .and_then(|()| replaced.iter().try_for_each(|file| unhold_file(held, file)))
+                .map_err(|e| e.to_string()),
+        };
+        match result {
+            Ok(()) => {
+                discard_held_files(&entry);
+                let _ = fs::remove_dir(UNDO_DIR);
+                self.refresh_files();
+                self.add_log(format!("Undid the {}.", entry.label));
+            }
+            Err(e) => {
+                self.add_log(format!("Couldn't undo the {}: {}", entry.label, e));
+                self.undo_history.push(entry);
+            }
+        }
+    }
+
+    /// Renames the files in the plan that can be renamed, skipping the conflicts.
+    fn apply_batch_rename_impl(&mut self) {
+        if self.recording_phase.is_recording() || self.playback_state != PlaybackState::Stopped {
+            return;
+        }
+        // A running job may be reading or writing any of the files in the list
+        if let Some(job) = &self.background_job {
+            self.add_log(format!(
+                "Wait for '{}' to finish before renaming.",
+                job.label
+            ));
+            return;
+        }
+        let plan = self.batch_rename_plan();
+        let skipped = plan.iter().filter(|(_, target)| target.is_err()).count();
+        let renames: Vec<(String, String)> = plan
+            .into_iter()
+            .filter_map(|(from, to)| to.ok().filter(|to| *to != from).map(|to| (from, to)))
+            .collect();
+        if renames.is_empty() {
+            return;
+        }
+        if let Err(e) = self.rename_files(&renames) {
+            self.add_log(format!(
+                "Batch rename failed, so nothing was renamed: {}",
+                e
+            ));
+            return;
+        }
+        self.refresh_files();
+        self.batch_rename_open = false;
+        let label = format!(
+            "rename of {} file{}",
+            renames.len(),
+            if renames.len() == 1 { "" } else { "s" }
+        );
+        self.push_undo(UndoEntry {
+            label,
+            undo: BatchUndo::Renamed(renames.clone()),
+        });
+        self.add_log(format!(
+            "Renamed {} file{}{}.",
+            renames.len(),
+            if renames.len() == 1 { "" } else { "s" },
+            match skipped {
+                0 => String::new(),
+                n => format!(", skipped {}", n),
+            }
+        ));
+    }
+
+    /// Renames recordings along with their sidecars and notes, all or nothing.
+    fn rename_files(&mut self, renames: &[(String, String)]) -> io::Result<()> {
+        // Sidecars go in the same all-or-nothing pass as their recordings
+        let mut pairs = renames.to_vec();
+        for (from, to) in renames {
+            for sidecar in [
+                marks_path,
+                transcript_path,
+                playback_settings_path,
+                speed_path,
+            ] {
+                if std::path::Path::new(&sidecar(from)).exists() {
+                    pairs.push((sidecar(from), sidecar(to)));
+                }
+            }
+        }
+        rename_all(&pairs)?;
+
+        // Notes move one at a time, so none lands on a name another still has
+        let moved: Vec<(String, Option<FileNotes>)> = renames
+            .iter()
+            .map(|(from, _)| (from.clone(), self.notes.remove(from)))
+            .collect();
+        for ((from, to), (_, file_notes)) in renames.iter().zip(moved) {
+            self.follow_rename(from, to);
+            if let Some(file_notes) = file_notes {
+                self.notes.insert(to.clone(), file_notes);
+            }
+        }
+        self.persist_notes();
+        Ok(())
+    }
+
+    fn cancel_rename_impl(&mut self) {
+        self.renaming_file = None;
+        self.new_name.clear();
+        self.add_log("Rename cancelled.".into());
+    }
+
+    fn play_file_impl(&mut self, filename: &str) {
+        self.play_file_from(filename, None);
+    }
+
+    fn playback_speed(&self) -> f32 {
+        f32::from_bits(self.playback_speed.load(Ordering::Relaxed))
+    }
+
+    fn set_playback_speed(&mut self, speed: f32) {
+        let speed = speed.clamp(MIN_PLAYBACK_SPEED, MAX_PLAYBACK_SPEED);
+        self.playback_speed
+            .store(speed.to_bits(), Ordering::Relaxed);
+    }
+
+    /// The file playing, if it's a file rather than the test tone.
+    fn playing_file(&self) -> Option<&String> {
+        match &self.currently_playing {
+            Some(PlaybackSource::File(file)) => Some(file),
+            _ => None,
+        }
+    }
+
+    /// The file playback settings are saved for: the one playing, or else the
+    /// selected one.
+    fn playback_settings_file(&self) -> Option<String> {
+        self.playing_file()
+            .cloned()
+            .or_else(|| self.selected_file.clone())
+    }
+
+    /// Remembers the current speed for the file, keeping its other saved settings.
+    fn save_playback_speed(&mut self) {
+        let Some(file) = self.playback_settings_file() else {
+            return;
+        };
+        let settings = FilePlayback {
+            speed: self.playback_speed(),
+            ..load_file_playback(&file).unwrap_or_default()
+        };
+        if let Err(e) = save_file_playback(&file, &settings) {
+            self.add_log(format!("Error saving playback speed: {}", e));
+        }
+    }
+
+    /// Remembers the current speed, volume and stereo width for the file.
+    fn remember_playback_settings_impl(&mut self) {
+        let Some(file) = self.playback_settings_file() else {
+            return;
+        };
+        let settings = FilePlayback {
+            speed: self.playback_speed(),
+            volume: Some(f32::from_bits(self.output_volume.load(Ordering::Relaxed))),
+            stereo_width: Some(*self.stereo_width.lock().unwrap()),
+        };
+        match save_file_playback(&file, &settings) {
+            Ok(()) => {
+                self.file_playback_applied = true;
+                self.add_log(format!("Saved playback settings for '{}'", file));
+            }
+            Err(e) => self.add_log(format!("Error saving playback settings: {}", e)),
+        }
+    }
+
+    /// Forgets the file's saved settings and goes back to the usual ones.
+    fn clear_playback_settings_impl(&mut self) {
+        let Some(file) = self.playback_settings_file() else {
+            return;
+        };
+        if let Err(e) = save_file_playback(&file, &FilePlayback::default()) {
+            self.add_log(format!("Error clearing playback settings: {}", e));
+            return;
+        }
+        self.apply_file_playback(&FilePlayback::default());
+        self.add_log(format!(
+            "Cleared the saved playback settings for '{}'",
+            file
+        ));
+    }
+
+    /// Puts a file's saved playback settings in place. Volume and width a previous
+    /// file brought with it go back to the usual ones.
+    fn apply_file_playback(&mut self, settings: &FilePlayback) {
+        self.set_playback_speed(settings.speed);
+        let has_levels = settings.volume.is_some() || settings.stereo_width.is_some();
+        if has_levels || self.file_playback_applied {
+            let volume = settings.volume.unwrap_or(self.config.output_volume);
+            self.output_volume
+                .store(volume.to_bits(), Ordering::Relaxed);
+            *self.stereo_width.lock().unwrap() = settings.stereo_width.unwrap_or(1.0);
+        }
+        self.file_playback_applied = has_levels;
+    }
+
+    /// Plays a generated tone through the normal playback path, in place of a file.
+    fn play_test_tone_impl(&mut self, tone: ToneSpec) {
+        if self.recording_phase.is_recording()
+            || self.recording_phase.is_armed()
+            || self.latency_test.is_some()
+        {
+            return;
+        }
+        self.stop_playback_impl();
+
+        let mono = test_tone(&tone, TEST_TONE_SAMPLE_RATE, TEST_TONE_SECS);
+        let samples: Vec<f32> = mono.iter().flat_map(|&s| [s, s]).collect();
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate: TEST_TONE_SAMPLE_RATE,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        let note = match tone.waveform {
+            ToneWaveform::Sine => format!(" (sine, {} Hz, {} dBFS)", tone.frequency, tone.level_db),
+            waveform => format!(
+                " ({}, {} dBFS)",
+                waveform.to_string().to_lowercase(),
+                tone.level_db
+            ),
+        };
+        self.start_playback(PlaybackSource::TestTone, spec, samples, 0, &note);
+    }
+
+    /// The tone described by the settings inputs, if they're valid.
+    fn tone_spec(&self) -> Option<ToneSpec> {
+        let frequency = self.tone_frequency_input.trim().parse::<f32>().ok()?;
+        let level_db = self.tone_level_input.trim().parse::<f32>().ok()?;
+        let nyquist = TEST_TONE_SAMPLE_RATE as f32 / 2.0;
+        ((20.0..nyquist).contains(&frequency) && level_db <= 0.0).then_some(ToneSpec {
+            waveform: self.tone_waveform,
+            frequency,
+            level_db,
+        })
+    }
+
+    /// Plays `filename` from `start`, or from where the skip/resume settings say if
+    /// `start` is `None`.
+    fn play_file_from(&mut self, filename: &str, start: Option<usize>) {
+        if self.previewing {
+            self.stop_playback_impl();
+        }
+        if self.playback_state != PlaybackState::Stopped
+            || self.recording_phase.is_recording()
+            || self.recording_phase.is_armed()
+        {
+            return;
+        }
+
+        self.stop_playback_impl();
+
+        let (spec, mut samples, damage) = match read_wav_recoverable(filename) {
+            Ok(r) => r,
+            Err(e) => {
+                self.add_log(e);
+                return;
+            }
+        };
+        if let Some(e) = damage {
+            self.add_log(format!(
+                "'{}' is damaged ({}); playing the {:.1}s that could be read.",
+                filename,
+                e,
+                samples.len() as f64 / (spec.sample_rate as f64 * spec.channels.max(1) as f64)
+            ));
+            self.damaged_files.insert(filename.to_string());
+        }
+
+        println!(
+            "File spec: channels={}, sample_rate={}, bits_per_sample={}",
+            spec.channels, spec.sample_rate, spec.bits_per_sample
+        );
+
+        if samples.is_empty() {
+            self.add_log("File contains no samples.".into());
+            return;
+        }
+
+        let (start_position, mut start_note) = match start {
+            Some(position) => (position.min(samples.len()), String::new()),
+            None => self.playback_start_position(filename, &spec, &samples),
+        };
+        let saved = load_file_playback(filename).unwrap_or_default();
+        self.apply_file_playback(&saved);
+        if saved.speed != 1.0 {
+            start_note += &format!(" at {:.2}x", saved.speed);
+        }
+        if saved.volume.is_some() || saved.stereo_width.is_some() {
+            start_note += " with its saved settings";
+        }
+        if self.config.auto_level {
+            // The waveform shows the file as stored, not the levelled buffer
+            self.show_waveform(filename, spec, &samples);
+            start_note += &self.apply_auto_level(filename, &spec, &mut samples);
+        }
+        self.start_playback(
+            PlaybackSource::File(filename.to_string()),
+            spec,
+            samples,
+            start_position,
+            &start_note,
+        );
+    }
+
+    /// Plays the first `preview_secs` of a file, then stops. Starting another preview
+    /// replaces the current one; full playback isn't interrupted.
+    fn preview_file_impl(&mut self, filename: &str) {
+        if self.previewing {
+            self.stop_playback_impl();
+        }
+        if self.playback_state != PlaybackState::Stopped
+            || self.recording_phase.is_recording()
+            || self.recording_phase.is_armed()
+        {
+            return;
+        }
+
+        let (spec, mut samples) = match read_wav_file(filename) {
+            Ok(r) => r,
+            Err(e) => {
+                self.add_log(e);
+                return;
+            }
+        };
+        if samples.is_empty() {
+            self.add_log("File contains no samples.".into());
+            return;
+        }
+
+        self.show_waveform(filename, spec, &samples);
+        let mut note = format!(" (preview, {}s)", self.config.preview_secs);
+        note += &self.apply_auto_level(filename, &spec, &mut samples);
+        let channels = spec.channels.max(1) as usize;
+        let frames = (self.config.preview_secs * spec.sample_rate as f32) as usize;
+        samples.truncate(frames * channels);
+
+        if self.start_playback(
+            PlaybackSource::File(filename.to_string()),
+            spec,
+            samples,
+            0,
+            &note,
+        ) {
+            self.previewing = true;
+        }
+    }
+
+    /// Plays a file as the current export settings would write it (resampled,
+    /// downmixed and normalised) without encoding or saving anything. Stopping it
+    /// doesn't leave a resume position, as with a preview. The processing runs in
+    /// the background and playback starts when `ExportPreviewReady` arrives.
+    fn preview_export_impl(&mut self, filename: &str) -> Task<Message> {
+        if self.previewing {
+            self.stop_playback_impl();
+        }
+        if self.playback_state != PlaybackState::Stopped
+            || self.recording_phase.is_recording()
+            || self.recording_phase.is_armed()
+        {
+            return Task::none();
+        }
+
+        self.add_log(format!("Preparing the export preview of '{}'...", filename));
+        let file = filename.to_string();
+        let settings = self.config.export.clone();
+        Task::perform(
+            run_in_background({
+                let file = file.clone();
+                move || process_for_export(&file, &settings, &JobControl::default())
+            }),
+            move |result| {
+                Message::ExportPreviewReady(
+                    file.clone(),
+                    result.unwrap_or_else(|| Err("Export preview failed unexpectedly.".into())),
+                )
+            },
+        )
+    }
+
+    fn export_preview_ready_impl(&mut self, filename: &str, audio: Result<ExportAudio, String>) {
+        // Something else may have started while the audio was being processed
+        if self.playback_state != PlaybackState::Stopped
+            || self.recording_phase.is_recording()
+            || self.recording_phase.is_armed()
+        {
+            return;
+        }
+        let audio = match audio {
+            Ok(audio) => audio,
+            Err(e) => {
+                self.add_log(e);
+                return;
+            }
+        };
+        if audio.samples.is_empty() {
+            self.add_log("File contains no samples.".into());
+            return;
+        }
+
+        let spec = WavSpec {
+            channels: audio.channels,
+            sample_rate: audio.sample_rate,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        let loudness = audio
+            .measured_lufs
+            .map(|lufs| format!(", normalised from {:.1} LUFS", lufs))
+            .unwrap_or_default();
+        let note = format!(
+            " (export preview: {} Hz, {} ch{})",
+            audio.sample_rate, audio.channels, loudness
+        );
+        // The file's own waveform stays up, with the playhead mapped onto it, so
+        // markers and peaks are still the file's
+        self.load_waveform(filename);
+        let source = PlaybackSource::File(filename.to_string());
+        if self.start_playback(source, spec, audio.samples, 0, &note) {
+            self.previewing = true;
+        }
+    }
+
+    fn show_waveform(&mut self, filename: &str, spec: WavSpec, samples: &[f32]) {
+        if self.waveform.as_ref().map(|w| w.file.as_str()) != Some(filename) {
+            self.waveform = Some(WaveformData::new(
+                filename,
+                samples,
+                spec.channels,
+                spec.sample_rate,
+            ));
+        }
+    }
+
+    /// Opens an output stream that plays `samples` from `start_position`, and marks
+    /// `filename` as the playing file. Returns whether playback started.
+    fn start_playback(
+        &mut self,
+        source: PlaybackSource,
+        spec: WavSpec,
+        samples: Vec<f32>,
+        start_position: usize,
+        start_note: &str,
+    ) -> bool {
+        // The tone leaves the selected file's waveform up
+        if let PlaybackSource::File(file) = &source {
+            self.show_waveform(file, spec, &samples);
+        }
+
+        // Store samples for pause/resume functionality
+        *self.playback_samples.lock().unwrap() = samples;
+        *self.playback_position.lock().unwrap() = start_position;
+        *self.is_stream_paused.lock().unwrap() = false;
+
+        let supported_formats = match self.backend.supported_output_formats() {
+            Ok(formats) => {
+                println!("Supported output configs:");
+                for f in &formats {
+                    println!(
+                        "  Channels: {}, Sample rate range: {}-{}, Format: {:?}",
+                        f.channels, f.min_sample_rate, f.max_sample_rate, f.sample_format
+                    );
+                }
+                formats
+            }
+            Err(e) => {
+                self.add_log(e);
+                return false;
+            }
+        };
+
+        let rank = |f: &&SupportedFormat| {
+            let format_priority = sample_format_priority(f.sample_format);
+            let rate_diff = ((f.max_sample_rate as i64) - (spec.sample_rate as i64)).abs();
+            (format_priority, rate_diff)
+        };
+        let mut channel_offset = self.config.output_channel_offset as usize;
+        let routed = if channel_offset > 0 {
+            // The fewest device channels that still reach past the file's last one
+            let needed = channel_offset + spec.channels as usize;
+            let routed = supported_formats
+                .iter()
+                .filter(|f| f.channels as usize >= needed)
+                .min_by_key(|f| (f.channels, rank(f)));
+            if routed.is_none() {
+                let most = supported_formats.iter().map(|f| f.channels).max();
+                self.add_log(format!(
+                    "Output device has {} channels, too few to play from channel {}; \
+                     using channel 1.",
+                    most.unwrap_or(0),
+                    channel_offset + 1
+                ));
+                channel_offset = 0;
+            }
+            routed
+        } else {
+            None
+        };
+        let matched = routed.or_else(|| {
+            supported_formats
+                .iter()
+                .filter(|f| f.channels == spec.channels)
+                .min_by_key(rank)
+        });
+
+        // The file's own rate where the device has it, so nothing needs resampling
+        let rate_for = |f: &SupportedFormat| {
+            if f.contains_rate(spec.sample_rate) {
+                spec.sample_rate
+            } else {
+                f.max_sample_rate
+            }
+        };
+        let chosen = match matched {
+            Some(f) => (f, f.with_sample_rate(rate_for(f))),
+            None => match supported_formats.first() {
+                Some(f) => {
+                    self.add_log(format!(
+                        "Output device can't play {} channels; mixing them {} to {}.",
+                        spec.channels,
+                        if spec.channels > f.channels {
+                            "down"
+                        } else {
+                            "up"
+                        },
+                        f.channels
+                    ));
+                    (f, f.with_sample_rate(rate_for(f)))
+                }
+                None => {
+                    self.add_log("No compatible output configuration found.".into());
+                    return false;
+                }
+            },
+        };
+
+        let (supported, mut stream_format) = chosen;
+        let low_latency = self
+            .config
+            .low_latency
+            .then(|| low_latency_buffer_size(&supported.buffer_size))
+            .flatten();
+        let latency_note = if self.config.low_latency && low_latency.is_none() {
+            LOW_LATENCY_UNSUPPORTED
+        } else {
+            ""
+        };
+        stream_format.buffer_size = low_latency.unwrap_or_else(|| {
+            playback_buffer_size(
+                self.playback_samples.lock().unwrap().len(),
+                stream_format.channels,
+                stream_format.sample_rate,
+                &supported.buffer_size,
+            )
+        });
+
+        println!(
+            "File sample rate: {}, Device will use: {}",
+            spec.sample_rate, stream_format.sample_rate
+        );
+
+        if spec.sample_rate != stream_format.sample_rate {
+            self.add_log(format!(
+                "Resampling {} Hz to the device's {} Hz ({})",
+                spec.sample_rate, stream_format.sample_rate, self.config.playback_resample_quality
+            ));
+        }
+
+        println!("Using sample format: {:?}", stream_format.sample_format);
+
+        // A warm stream is already pulling from the shared buffer, so swapping the
+        // samples above is all it takes if the formats line up
+        let key = (
+            stream_format,
+            spec.channels,
+            spec.sample_rate,
+            channel_offset,
+        );
+        let reusable = self.output_stream.is_some()
+            && self
+                .output_stream_key
+                .is_some_and(|(format, channels, rate, offset)| {
+                    format.channels == key.0.channels
+                        && format.sample_rate == key.0.sample_rate
+                        && format.sample_format == key.0.sample_format
+                        && channels == key.1
+                        && rate == key.2
+                        && offset == key.3
+                });
+        self.sync_playback_polarity(spec.channels);
+        let build_out = if reusable {
+            Ok(self.output_stream.take().unwrap())
+        } else {
+            self.output_stream = None;
+            if self.input_stream.is_none() {
+                self.reset_stream_health();
+            }
+            self.build_output_stream(stream_format, spec, channel_offset)
+        };
+
+        match build_out {
+            Ok(stream) => {
+                self.output_stream = Some(stream);
+                self.output_stream_key = Some(key);
+                self.output_idle_since = None;
+                self.playback_state = PlaybackState::Playing;
+                self.add_log(format!("Playing: {}{}{}", source, start_note, latency_note));
+                self.currently_playing = Some(source);
+                self.playback_spec = Some(spec);
+                self.open_second_output(spec);
+                let offset = Duration::from_secs_f64(
+                    start_position as f64 / (spec.sample_rate as f64 * spec.channels as f64),
+                );
+                self.start_time = Instant::now().checked_sub(offset);
+                self.elapsed_time = offset;
+                true
+            }
+            Err(e) => {
+                self.add_log(e);
+                false
+            }
+        }
+    }
+
+    /// Builds an output stream that plays whatever is in `playback_samples` from
+    /// `playback_position`, and silence once it runs out. With a `channel_offset`
+    /// the file's channels go out unmixed from that device channel on.
+    fn build_output_stream(
+        &self,
+        stream_format: StreamFormat,
+        spec: WavSpec,
+        channel_offset: usize,
+    ) -> Result<Box<dyn AudioStream>, String> {
+        let samples_arc = Arc::clone(&self.playback_samples);
+        let position_arc = Arc::clone(&self.playback_position);
+        let paused_arc = Arc::clone(&self.is_stream_paused);
+        let play_tx = self.playback_status_tx.clone();
+        let width_arc = Arc::clone(&self.stereo_width);
+        let volume_arc = Arc::clone(&self.output_volume);
+        let speed_arc = Arc::clone(&self.playback_speed);
+        let quality_arc = Arc::clone(&self.playback_resample_quality);
+        let polarity_arc = Arc::clone(&self.playback_polarity);
+        let error_tx = self.output_error_tx.clone();
+        let file_channels = spec.channels.max(1) as usize;
+        let device_channels = stream_format.channels.max(1) as usize;
+        // File frames per device frame at normal speed
+        let rate_step = spec.sample_rate as f64 / stream_format.sample_rate.max(1) as f64;
+        let is_stereo = spec.channels == 2 && (stream_format.channels == 2 || channel_offset > 0);
+        let mut fraction = 0.0f64;
+        let mut scratch: Vec<f32> = Vec::new();
+        let mut routed: Vec<f32> = Vec::new();
+        self.output_health.reset();
+        let health = Arc::clone(&self.output_health);
+        self.output_levels.reset();
+        let levels = Arc::clone(&self.output_levels);
+        let ramp_frames = self.start_ramp_frames(stream_format.sample_rate);
+        let mut ramped = 0usize;
+        self.backend.build_output_stream(
+            &stream_format,
+            Box::new(move |device_out: &mut [f32]| {
+                let started = Instant::now();
+                let frames = device_out.len() / device_channels;
+                let is_paused = *paused_arc.lock().unwrap();
+                if is_paused {
+                    device_out.fill(0.0);
+                    health.record(started, Instant::now(), frames, stream_format.sample_rate);
+                    return;
+                }
+
+                // Routed playback renders the file's own channels, then places them
+                let (out, device_channels) = if channel_offset > 0 {
+                    routed.resize(device_out.len() / device_channels * file_channels, 0.0);
+                    (&mut routed[..], file_channels)
+                } else {
+                    (&mut *device_out, device_channels)
+                };
+
+                let samples = samples_arc.lock().unwrap();
+                let mut position = position_arc.lock().unwrap();
+                let was_playing = *position < samples.len();
+
+                // The buffer can be swapped or emptied under a running stream
+                let start = (*position).min(samples.len());
+                let speed = f32::from_bits(speed_arc.load(Ordering::Relaxed)) as f64;
+                let step = speed * rate_step;
+                // Polarity is flipped per file channel, so before any remixing
+                let polarity = polarity_arc.lock().unwrap();
+                let len = if step != 1.0 {
+                    // Varispeed or another device rate: `step` file frames per output
+                    // frame, carrying the fraction of a frame over to the next callback
+                    let mut frame_position = (start / file_channels) as f64 + fraction;
+                    scratch.clear();
+                    interpolate_frames(
+                        &samples,
+                        file_channels,
+                        &mut frame_position,
+                        step,
+                        out.len() / device_channels,
+                        *quality_arc.lock().unwrap(),
+                        &mut scratch,
+                    );
+                    invert_polarity(&mut scratch, file_channels, &polarity);
+                    remix_frames(&scratch, file_channels, out, device_channels);
+                    fraction = frame_position.fract();
+                    (frame_position as usize * file_channels).min(samples.len()) - start
+                } else if file_channels == device_channels {
+                    let len = out.len().min(samples.len() - start);
+                    out[..len].copy_from_slice(&samples[start..start + len]);
+                    out[len..].fill(0.0);
+                    invert_polarity(&mut out[..len], file_channels, &polarity);
+                    len
+                } else if polarity.is_empty() {
+                    remix_frames(&samples[start..], file_channels, out, device_channels)
+                } else {
+                    let end =
+                        (start + out.len() / device_channels * file_channels).min(samples.len());
+                    scratch.clear();
+                    scratch.extend_from_slice(&samples[start..end]);
+                    invert_polarity(&mut scratch, file_channels, &polarity);
+                    remix_frames(&scratch, file_channels, out, device_channels)
+                };
+                drop(polarity);
+                *position = start + len;
+
+                // Anything past the audio is silence, so these can run over all of `out`
+                if is_stereo {
+                    let width = *width_arc.lock().unwrap();
+                    if width != 1.0 {
+                        apply_stereo_width(out, width);
+                    }
+                }
+                let volume = f32::from_bits(volume_arc.load(Ordering::Relaxed));
+                if volume != 1.0 {
+                    out.iter_mut().for_each(|s| *s *= volume);
+                }
+                apply_start_ramp(out, device_channels, &mut ramped, ramp_frames);
+                // Metered as sent, after width and volume, so it matches what's heard
+                levels.record(out);
+
+                if channel_offset > 0 {
+                    device_out.fill(0.0);
+                    for (frame, device_frame) in routed
+                        .chunks_exact(file_channels)
+                        .zip(device_out.chunks_exact_mut(stream_format.channels as usize))
+                    {
+                        device_frame[channel_offset..channel_offset + file_channels]
+                            .copy_from_slice(frame);
+                    }
+                }
+
+                if was_playing && *position >= samples.len() {
+                    let _ = play_tx.send(());
+                }
+                health.record(started, Instant::now(), frames, stream_format.sample_rate);
+            }),
+            Box::new(move |err| {
+                let _ = error_tx.send(err);
+            }),
+        )
+    }
+
+    /// Plays the playback buffer on the second output device too, following the
+    /// main output's position. That device may run at another rate, so frames are
+    /// interpolated to it; if the two drift apart by more than
+    /// `SECOND_OUTPUT_RESYNC_SECS` (or playback is moved) it jumps back in line.
+    fn open_second_output(&mut self, spec: WavSpec) {
+        self.second_output_stream = None;
+        let Some(name) = self.config.second_output.clone() else {
+            return;
+        };
+        let format = match self.backend.named_output_format(&name) {
+            Ok(format) => format,
+            Err(e) => {
+                self.add_log(format!("Couldn't also play on '{}': {}", name, e));
+                return;
+            }
+        };
+
+        let samples_arc = Arc::clone(&self.playback_samples);
+        let position_arc = Arc::clone(&self.playback_position);
+        let paused_arc = Arc::clone(&self.is_stream_paused);
+        let volume_arc = Arc::clone(&self.second_output_volume);
+        let speed_arc = Arc::clone(&self.playback_speed);
+        let quality_arc = Arc::clone(&self.playback_resample_quality);
+        let polarity_arc = Arc::clone(&self.playback_polarity);
+        let file_channels = spec.channels.max(1) as usize;
+        let device_channels = format.channels.max(1) as usize;
+        let step = spec.sample_rate as f64 / format.sample_rate.max(1) as f64;
+        let ramp_frames = self.start_ramp_frames(format.sample_rate);
+        let mut ramped = 0usize;
+        let resync = SECOND_OUTPUT_RESYNC_SECS * spec.sample_rate as f64;
+        let file_rate = spec.sample_rate as f64;
+        let mut frame_position: Option<f64> = None;
+        let mut main_moved: Option<(usize, Instant)> = None;
+        let mut scratch: Vec<f32> = Vec::new();
+        let built = self.backend.build_named_output_stream(
+            &name,
+            &format,
+            Box::new(move |out: &mut [f32]| {
+                if *paused_arc.lock().unwrap() {
+                    out.fill(0.0);
+                    main_moved = None;
+                    return;
+                }
+                let samples = samples_arc.lock().unwrap();
+                let speed = f32::from_bits(speed_arc.load(Ordering::Relaxed)) as f64;
+                // The main position moves a whole buffer at a time, so in between
+                // it's carried on from when it last moved
+                let main_position = *position_arc.lock().unwrap();
+                if main_moved.is_none_or(|(from, _)| from != main_position) {
+                    main_moved = Some((main_position, Instant::now()));
+                }
+                let main = (main_position / file_channels) as f64;
+                let position = frame_position.get_or_insert(main);
+                let moved_at = main_moved.map_or(Duration::ZERO, |(_, at)| at.elapsed());
+                let main = main + moved_at.as_secs_f64() * file_rate * speed;
+                if (*position - main).abs() > resync {
+                    *position = main;
+                }
+
+                scratch.clear();
+                interpolate_frames(
+                    &samples,
+                    file_channels,
+                    position,
+                    step * speed,
+                    out.len() / device_channels,
+                    *quality_arc.lock().unwrap(),
+                    &mut scratch,
+                );
+                invert_polarity(&mut scratch, file_channels, &polarity_arc.lock().unwrap());
+                remix_frames(&scratch, file_channels, out, device_channels);
+
+                let volume = f32::from_bits(volume_arc.load(Ordering::Relaxed));
+                if volume != 1.0 {
+                    out.iter_mut().for_each(|s| *s *= volume);
+                }
+                apply_start_ramp(out, device_channels, &mut ramped, ramp_frames);
+            }),
+            report_side_errors(&self.side_error_tx, "Second output"),
+        );
+        match built {
+            Ok(stream) => self.second_output_stream = Some(stream),
+            Err(e) => self.add_log(format!("Couldn't also play on '{}': {}", name, e)),
+        }
+    }
+
+    fn assign_ab_slot_impl(&mut self, slot: usize, filename: String) {
+        if self.ab_inactive_samples.is_some() {
+            return;
+        }
+        let other = 1 - slot;
+        if self.ab_slots[other].as_ref() == Some(&filename) {
+            self.ab_slots[other] = None;
+        }
+        self.ab_slots[slot] = Some(filename);
+        self.waveform_overlay = None;
+    }
+
+    /// Plays take A with take B loaded alongside it, so `switch_ab_take_impl` can swap
+    /// the buffer the output callback reads from without reopening the stream.
+    fn start_ab_compare_impl(&mut self) {
+        let [Some(file_a), Some(file_b)] = self.ab_slots.clone() else {
+            self.add_log("Assign takes A and B first.".into());
+            return;
+        };
+        if self.playback_state != PlaybackState::Stopped
+            || self.recording_phase.is_recording()
+            || self.recording_phase.is_armed()
+        {
+            return;
+        }
+
+        let loaded = read_wav_file(&file_a).and_then(|a| read_wav_file(&file_b).map(|b| (a, b)));
+        let ((spec, mut samples_a), (spec_b, mut samples_b)) = match loaded {
+            Ok(takes) => takes,
+            Err(e) => {
+                self.add_log(e);
+                return;
+            }
+        };
+        if spec.channels != spec_b.channels || spec.sample_rate != spec_b.sample_rate {
+            self.add_log(
+                "Takes must have the same channel count and sample rate to compare.".into(),
+            );
+            return;
+        }
+        if samples_a.is_empty() || samples_b.is_empty() {
+            self.add_log("File contains no samples.".into());
+            return;
+        }
+
+        let mut note = String::new();
+        if self.config.auto_level && !self.ab_match_loudness {
+            // Both takes land on the same reference, which matches them too
+            self.apply_auto_level(&file_a, &spec, &mut samples_a);
+            self.apply_auto_level(&file_b, &spec_b, &mut samples_b);
+            note = " (auto-level)".into();
+        } else if self.ab_match_loudness {
+            let lufs_a = integrated_loudness(&samples_a, spec.channels, spec.sample_rate);
+            let lufs_b = integrated_loudness(&samples_b, spec.channels, spec.sample_rate);
+            if let (Some(a), Some(b)) = (lufs_a, lufs_b) {
+                // Turn the louder take down rather than the quieter one up, so nothing clips
+                let (louder, difference) = if a > b {
+                    (&mut samples_a, a - b)
+                } else {
+                    (&mut samples_b, b - a)
+                };
+                let gain = 10f32.powf(-difference as f32 / 20.0);
+                for s in louder.iter_mut() {
+                    *s *= gain;
+                }
+                note = format!(" (loudness matched, {:.1} dB)", difference);
+            }
+        }
+
+        self.ab_active = 0;
+        if self.start_playback(
+            PlaybackSource::File(file_a.clone()),
+            spec,
+            samples_a,
+            0,
+            &note,
+        ) {
+            self.ab_inactive_samples = Some(samples_b);
+            self.add_log(format!("Comparing - playing take A: {}{}", file_a, note));
+        }
+    }
+
+    /// Shows take B's waveform over take A's, or hides it again.
+    fn toggle_waveform_overlay_impl(&mut self) {
+        if self.waveform_overlay.take().is_some() {
+            return;
+        }
+        let [Some(file_a), Some(file_b)] = self.ab_slots.clone() else {
+            self.add_log("Assign takes A and B first.".into());
+            return;
+        };
+
+        let loaded = read_wav_file(&file_a).and_then(|a| read_wav_file(&file_b).map(|b| (a, b)));
+        let ((spec_a, samples_a), (spec_b, samples_b)) = match loaded {
+            Ok(takes) => takes,
+            Err(e) => {
+                self.add_log(e);
+                return;
+            }
+        };
+
+        let difference = (spec_a.channels == spec_b.channels
+            && spec_a.sample_rate == spec_b.sample_rate)
+            .then(|| sample_difference(&samples_a, &samples_b, spec_a.channels));
+        if difference.is_none() {
+            self.add_log(
+                "Takes differ in channel count or sample rate; showing waveforms only.".into(),
+            );
+        }
+
+        if self.waveform.as_ref().is_none_or(|w| w.file != file_a) {
+            self.waveform = Some(WaveformData::new(
+                &file_a,
+                &samples_a,
+                spec_a.channels,
+                spec_a.sample_rate,
+            ));
+            self.waveform_zoom = 1.0;
+            self.waveform_scroll = 0.0;
+        }
+        self.waveform_overlay = Some(WaveformOverlay {
+            base: file_a,
+            data: WaveformData::new(&file_b, &samples_b, spec_b.channels, spec_b.sample_rate),
+            difference,
+            sample_rate: spec_a.sample_rate,
+        });
+    }
+
+    /// Swaps to the other take at the same relative position.
+    fn switch_ab_take_impl(&mut self) {
+        let Some(inactive) = self.ab_inactive_samples.as_mut() else {
+            return;
+        };
+        let channels = self
+            .playback_spec
+            .map_or(1, |spec| spec.channels.max(1) as usize);
+        let sample_rate = self.playback_spec.map_or(1, |spec| spec.sample_rate.max(1));
+
+        let mut samples = self.playback_samples.lock().unwrap();
+        let mut position = self.playback_position.lock().unwrap();
+        let fraction = *position as f64 / samples.len().max(1) as f64;
+        std::mem::swap(&mut *samples, inactive);
+        let frame = (fraction * (samples.len() / channels) as f64) as usize;
+        *position = (frame * channels).min(samples.len());
+        drop((samples, position));
+
+        self.elapsed_time = Duration::from_secs_f64(frame as f64 / sample_rate as f64);
+        if self.playback_state == PlaybackState::Playing {
+            self.start_time = Instant::now().checked_sub(self.elapsed_time);
+        }
+
+        self.ab_active = 1 - self.ab_active;
+        let label = if self.ab_active == 0 { "A" } else { "B" };
+        self.currently_playing = self.ab_slots[self.ab_active]
+            .clone()
+            .map(PlaybackSource::File);
+        if let (Some(file), Some(spec)) = (self.playing_file(), self.playback_spec) {
+            let samples = self.playback_samples.lock().unwrap();
+            self.waveform = Some(WaveformData::new(
+                file,
+                &samples,
+                spec.channels,
+                spec.sample_rate,
+            ));
+        }
+        self.add_log(format!(
+            "Comparing - playing take {}: {}",
+            label,
+            self.playing_file().map(String::as_str).unwrap_or_default()
+        ));
+    }
+
+    /// Converts a sample index in what's playing to one in the waveform shown.
+    /// They differ while an export preview plays at another rate or channel count.
+    fn playback_to_waveform(&self, position: usize) -> usize {
+        match (self.playback_spec, &self.waveform) {
+            (Some(spec), Some(data))
+                if self.playing_file() == Some(&data.file)
+                    && (spec.sample_rate, spec.channels) != (data.sample_rate, data.channels) =>
+            {
+                let frames = position / spec.channels.max(1) as usize;
+                let secs = frames as f64 / spec.sample_rate.max(1) as f64;
+                (secs * data.sample_rate as f64) as usize * data.channels.max(1) as usize
+            }
+            _ => position,
+        }
+    }
+
+    /// The reverse of `playback_to_waveform`.
+    fn waveform_to_playback(&self, position: usize) -> usize {
+        match (self.playback_spec, &self.waveform) {
+            (Some(spec), Some(data))
+                if self.playing_file() == Some(&data.file)
+                    && (spec.sample_rate, spec.channels) != (data.sample_rate, data.channels) =>
+            {
+                let frames = position / data.channels.max(1) as usize;
+                let secs = frames as f64 / data.sample_rate.max(1) as f64;
+                (secs * spec.sample_rate as f64) as usize * spec.channels.max(1) as usize
+            }
+            _ => position,
+        }
+    }
+
+    fn load_waveform(&mut self, filename: &str) {
+        if self.waveform.as_ref().map(|w| w.file.as_str()) == Some(filename) {
+            return;
+        }
+        match read_wav_file(filename) {
+            Ok((spec, samples)) => {
+                self.waveform = Some(WaveformData::new(
+                    filename,
+                    &samples,
+                    spec.channels,
+                    spec.sample_rate,
+                ));
+                self.waveform_zoom = 1.0;
+                self.waveform_scroll = 0.0;
+            }
+            Err(e) => self.add_log(e),
+        }
+    }
+
+    /// Saves the selected file's waveform as `name.png`, at the size and in the
+    /// colors given, which are remembered for next time.
+    fn export_waveform_image_impl(&mut self) {
+        let Some(filename) = self.selected_file.clone() else {
+            return;
+        };
+        let size = (
+            self.image_width_input.trim().parse::<u32>(),
+            self.image_height_input.trim().parse::<u32>(),
+        );
+        let (Ok(width @ 1..=MAX_IMAGE_SIDE), Ok(height @ 1..=MAX_IMAGE_SIDE)) = size else {
+            self.add_log(format!(
+                "Image size must be 1 to {} pixels each way.",
+                MAX_IMAGE_SIDE
+            ));
+            return;
+        };
+        let colors = (
+            waveform::parse_hex_color(&self.config.waveform_image_foreground),
+            waveform::parse_hex_color(&self.config.waveform_image_background),
+        );
+        let (Some(foreground), Some(background)) = colors else {
+            self.add_log("Colors must be given as #rrggbb.".into());
+            return;
+        };
+
+        self.load_waveform(&filename);
+        let Some(data) = self.waveform.as_ref().filter(|w| w.file == filename) else {
+            return;
+        };
+        let path = sidecar_path(&filename, ".png");
+        match waveform::save_png(&path, data, width, height, foreground, background) {
+            Ok(()) => {
+                self.config.waveform_image_width = width;
+                self.config.waveform_image_height = height;
+                self.save_config();
+                self.add_log(format!(
+                    "Saved waveform image '{}' ({}x{})",
+                    path, width, height
+                ));
+            }
+            Err(e) => self.add_log(format!("Error saving waveform image: {}", e)),
+        }
+    }
+
+    /// Moves playback of the waveform's file to `fraction` of the way through it,
+    /// starting playback there if that file isn't already loaded.
+    fn seek_waveform_impl(&mut self, fraction: f32) {
+        let Some(data) = &self.waveform else {
+            return;
+        };
+        if self.recording_phase.is_recording() || self.recording_phase.is_armed() {
+            return;
+        }
+        let file = data.file.clone();
+        let position = data.sample_at(fraction);
+        let frame_rate = data.sample_rate.max(1) as f64 * data.channels.max(1) as f64;
+
+        if self.playback_state == PlaybackState::Stopped || self.playing_file() != Some(&file) {
+            self.stop_playback_impl();
+            self.play_file_from(&file, Some(position));
+            return;
+        }
+
+        let len = self.playback_samples.lock().unwrap().len();
+        *self.playback_position.lock().unwrap() = self.waveform_to_playback(position).min(len);
+        self.elapsed_time = Duration::from_secs_f64(position as f64 / frame_rate);
+        if self.playback_state == PlaybackState::Playing {
+            self.start_time = Instant::now().checked_sub(self.elapsed_time);
+        }
+    }
+
+    /// Moves playback `secs` forwards (or backwards, if negative) while playing or
+    /// paused. Skips stop at the start or the last frame.
+    fn skip_impl(&mut self, secs: f32) {
+        let Some(spec) = self.playback_spec else {
+            return;
+        };
+        if self.playback_state == PlaybackState::Stopped {
+            return;
+        }
+        let channels = spec.channels.max(1) as i64;
+        let frame_rate = spec.sample_rate as f64 * channels as f64;
+        let delta = (secs as f64 * spec.sample_rate as f64) as i64 * channels;
+
+        let len = self.playback_samples.lock().unwrap().len();
+        let mut position = self.playback_position.lock().unwrap();
+        let last_frame = (len / channels as usize).saturating_sub(1) * channels as usize;
+        let target = ((*position as i64 + delta).max(0) as usize).min(last_frame);
+        *position = target;
+        drop(position);
+
+        self.elapsed_time = Duration::from_secs_f64(target as f64 / frame_rate);
+        if self.playback_state == PlaybackState::Playing {
+            self.start_time = Instant::now().checked_sub(self.elapsed_time);
+        }
+    }
+
+    fn pause_playback_impl(&mut self) {
+        if self.playback_state == PlaybackState::Playing {
+            *self.is_stream_paused.lock().unwrap() = true;
+            self.playback_state = PlaybackState::Paused;
+            self.add_log(if let Some(source) = &self.currently_playing {
+                format!("Paused: {}", source)
+            } else {
+                "Playback paused.".into()
+            });
+            self.start_time = None;
+        }
+    }
+
+    fn resume_playback_impl(&mut self) {
+        if self.playback_state == PlaybackState::Paused {
+            *self.is_stream_paused.lock().unwrap() = false;
+            self.playback_state = PlaybackState::Playing;
+            self.add_log(if let Some(source) = &self.currently_playing {
+                format!("Playing: {}", source)
+            } else {
+                "Playback resumed.".into()
+            });
+            self.start_time = Some(Instant::now());
+        }
+    }
+
+    /// Works out where playback should begin from the skip-intro and resume settings,
+    /// returning the sample index and a note for the status message.
+    fn playback_start_position(
+        &self,
+        filename: &str,
+        spec: &WavSpec,
+        samples: &[f32],
+    ) -> (usize, String) {
+        let channels = spec.channels.max(1) as usize;
+        let skip_frames = (self.config.skip_intro_secs.max(0.0) * spec.sample_rate as f32) as usize;
+        let skip = (skip_frames * channels).min(samples.len());
+        let resume = self
+            .config
+            .resume_last_position
+            .then(|| self.last_positions.get(filename).copied())
+            .flatten()
+            .filter(|&p| p < samples.len());
+
+        let to_secs =
+            |position: usize| position as f32 / (spec.sample_rate as f32 * channels as f32);
+        match (resume, skip > 0) {
+            (Some(position), false) => {
+                (position, format!(" (resumed at {:.1}s)", to_secs(position)))
+            }
+            (Some(position), true) if self.config.resume_over_skip => {
+                (position, format!(" (resumed at {:.1}s)", to_secs(position)))
+            }
+            (_, true) => (skip, format!(" (skipped {:.1}s)", to_secs(skip))),
+            (None, false) => (0, String::new()),
+        }
+    }
+
+    fn stop_playback_impl(&mut self) {
+        if self.playback_state != PlaybackState::Stopped {
+            // A preview only covers the start of the file, so it doesn't move the
+            // resume position
+            if std::mem::take(&mut self.previewing) {
+                self.currently_playing = None;
+            }
+            if let Some(PlaybackSource::File(file)) = self.currently_playing.take() {
+                let position = *self.playback_position.lock().unwrap();
+                if position < self.playback_samples.lock().unwrap().len() {
+                    self.last_positions.insert(file, position);
+                } else {
+                    self.last_positions.remove(&file);
+                }
+            }
+            if self.config.keep_output_warm {
+                // Leave the stream running on an empty buffer so the next play starts
+                // straight away
+                self.playback_samples.lock().unwrap().clear();
+                self.output_idle_since = Some(Instant::now());
+            } else {
+                self.release_output_stream();
+            }
+            self.ab_inactive_samples = None;
+            self.second_output_stream = None;
+            self.playback_state = PlaybackState::Stopped;
+            self.meter_level = 0.0;
+            self.meter_updated = None;
+            self.currently_playing = None;
+            self.add_log("Playback stopped.".into());
+            self.start_time = None;
+            self.elapsed_time = Duration::from_secs(0);
+            *self.is_stream_paused.lock().unwrap() = false;
+            *self.playback_position.lock().unwrap() = 0;
+        }
+    }
+
+    /// Handles errors reported by the output stream. When the device has gone away,
+    /// playback moves to the new default device from where it was, or stops if
+    /// there's nothing to move to. Errors from the side streams are just logged.
+    fn check_output_errors(&mut self) {
+        while let Ok((stream, err)) = self.side_error_rx.try_recv() {
+            self.add_log(match err {
+                StreamError::DeviceLost => format!("{}: output device disconnected.", stream),
+                StreamError::Other(e) => format!("{} stream error: {}", stream, e),
+            });
+        }
+        let mut device_lost = false;
+        while let Ok(err) = self.output_error_rx.try_recv() {
+            match err {
+                StreamError::DeviceLost => device_lost = true,
+                StreamError::Other(e) => self.add_log(format!("Output stream error: {}", e)),
+            }
+        }
+        if !device_lost || self.output_stream.is_none() {
+            return;
+        }
+
+        self.release_output_stream();
+        if self.playback_state == PlaybackState::Stopped {
+            // Only a warm stream was open; the next play opens the new device
+            self.add_log("Output device disconnected.".into());
+            return;
+        }
+
+        let was_paused = self.playback_state == PlaybackState::Paused;
+        let position = *self.playback_position.lock().unwrap();
+        let samples = std::mem::take(&mut *self.playback_samples.lock().unwrap());
+        let resumed = match (self.currently_playing.clone(), self.playback_spec) {
+            (Some(source), Some(spec)) => {
+                self.start_playback(source, spec, samples, position, " (output device changed)")
+            }
+            _ => false,
+        };
+        if !resumed {
+            self.stop_playback_impl();
+            self.add_log("Output device disconnected - playback stopped.".into());
+        } else if was_paused {
+            *self.is_stream_paused.lock().unwrap() = true;
+            self.playback_state = PlaybackState::Paused;
+            self.start_time = None;
+        }
+    }
+
+    /// Starts a loopback measurement: plays a click and listens for it on the input.
+    fn measure_latency_impl(&mut self) {
+        if self.recording_phase.is_recording()
+            || self.recording_phase.is_armed()
+            || self.playback_state != PlaybackState::Stopped
+            || self.latency_test.is_some()
+        {
+            return;
+        }
+        let formats = self
+            .backend
+            .default_input_format()
+            .and_then(|input| Ok((input, self.backend.default_output_format()?)));
+        let (input_format, output_format) = match formats {
+            Ok(formats) => formats,
+            Err(e) => {
+                self.add_log(e);
+                return;
+            }
+        };
+
+        let captured = Arc::new(Mutex::new(Vec::new()));
+        let click_at = Arc::new(Mutex::new(None));
+
+        let out_channels = output_format.channels.max(1) as usize;
+        let click = latency_click(output_format.sample_rate);
+        let lead_frames = (output_format.sample_rate as f32 * LATENCY_LEAD_SECS) as usize;
+        let mut frame = 0;
+        let captured_out = Arc::clone(&captured);
+        let click_at_out = Arc::clone(&click_at);
+        let output = self.backend.build_output_stream(
+            &output_format,
+            Box::new(move |out: &mut [f32]| {
+                for out_frame in out.chunks_mut(out_channels) {
+                    if frame == lead_frames {
+                        *click_at_out.lock().unwrap() = Some(captured_out.lock().unwrap().len());
+                    }
+                    let sample = frame
+                        .checked_sub(lead_frames)
+                        .and_then(|i| click.get(i))
+                        .copied()
+                        .unwrap_or(0.0);
+                    out_frame.fill(sample);
+                    frame += 1;
+                }
+            }),
+            report_side_errors(&self.side_error_tx, "Latency test"),
+        );
+
+        let in_channels = input_format.channels.max(1) as usize;
+        let max_frames =
+            (input_format.sample_rate as f32 * (LATENCY_LEAD_SECS + LATENCY_LISTEN_SECS)) as usize;
+        let captured_in = Arc::clone(&captured);
+        let input = output.and_then(|output| {
+            let input = self.backend.build_input_stream(
+                &input_format,
+                Box::new(move |data: &[f32], _| {
+                    let mut captured = captured_in.lock().unwrap();
+                    let room = max_frames.saturating_sub(captured.len());
+                    captured.extend(data.iter().step_by(in_channels).take(room));
+                }),
+            )?;
+            Ok((output, input))
+        });
+
+        match input {
+            Ok(streams) => {
+                self.latency_test = Some(LatencyTest {
+                    started: Instant::now(),
+                    input_rate: input_format.sample_rate,
+                    captured,
+                    click_at,
+                    _streams: streams,
+                });
+                self.add_log("Measuring latency - keep quiet for a second...".into());
+            }
+            Err(e) => self.add_log(e),
+        }
+    }
+
+    fn finish_latency_test(&mut self) {
+        let Some(test) = self.latency_test.take() else {
+            return;
+        };
+        let captured = test.captured.lock().unwrap();
+        let click_at = *test.click_at.lock().unwrap();
+        let found = click_at.and_then(|from| {
+            find_click(&captured, &latency_click(test.input_rate), from).map(|at| at - from)
+        });
+
+        match found {
+            Some(frames) => {
+                let ms = frames as f32 * 1000.0 / test.input_rate as f32;
+                self.config.round_trip_latency_ms = Some(ms);
+                self.add_log(format!(
+                    "Round-trip latency: {:.1} ms ({} samples at {} Hz).",
+                    ms, frames, test.input_rate
+                ));
+                self.save_config();
+            }
+            None => self.add_log(
+                "Couldn't hear the click. Connect the output back to the input (a loopback \
+                 cable, or your interface's loopback/monitor input), turn the level up and \
+                 try again."
+                    .into(),
+            ),
+        }
+    }
+
+    /// Lists the hosts and output devices offered in Settings.
+    fn enumerate_devices(&mut self) {
+        self.audio_hosts = std::iter::once(HostChoice {
+            name: None,
+            available: true,
+        })
+        .chain(
+            self.backend
+                .host_names()
+                .into_iter()
+                .map(|(name, available)| HostChoice {
+                    name: Some(name),
+                    available,
+                }),
+        )
+        .collect();
+        let default = self.backend.output_device_name().ok();
+        self.output_devices = match self.backend.output_device_names() {
+            Ok(names) => names
+                .into_iter()
+                .filter(|name| Some(name) != default.as_ref())
+                .collect(),
+            Err(e) => {
+                self.add_log(e);
+                Vec::new()
+            }
+        };
+        self.output_channel_count = self
+            .backend
+            .supported_output_formats()
+            .ok()
+            .and_then(|formats| formats.iter().map(|f| f.channels).max())
+            .unwrap_or(0);
+    }
+
+    fn select_audio_host_impl(&mut self, choice: HostChoice) {
+        if choice.name == self.config.audio_host {
+            return;
+        }
+        if self.input_stream.is_some() || self.playback_state != PlaybackState::Stopped {
+            self.add_log("Stop recording and playback before changing the audio host.".into());
+            return;
+        }
+        if let Err(e) = self.backend.set_host(choice.name.as_deref()) {
+            self.add_log(e);
+            return;
+        }
+        // A stream kept warm is on the old host's device
+        self.release_output_stream();
+        let label = choice.to_string();
+        self.config.audio_host = choice.name;
+        self.save_config();
+        self.enumerate_devices();
+        self.add_log(format!("Using audio host: {}.", label));
+    }
+
+    fn start_ramp_frames(&self, sample_rate: u32) -> usize {
+        (sample_rate as u64 * self.config.output_start_ramp_ms as u64 / 1000) as usize
+    }
+
+    fn release_output_stream(&mut self) {
+        self.output_stream = None;
+        self.second_output_stream = None;
+        self.output_stream_key = None;
+        self.output_idle_since = None;
+    }
+
+    fn delete_file_impl(&mut self, filename: &str) {
+        // Can't delete while recording, playing, or renaming
+        if self.recording_phase.is_recording()
+            || self.playback_state != PlaybackState::Stopped
+            || self.renaming_file.is_some()
+        {
+            return;
+        }
+
+        match fs::remove_file(filename) {
+            Ok(_) => {
+                self.add_log(format!("Deleted file: {}", filename));
+                let _ = fs::remove_file(marks_path(filename));
+                let _ = fs::remove_file(transcript_path(filename));
+                let _ = fs::remove_file(playback_settings_path(filename));
+                let _ = fs::remove_file(speed_path(filename));
+                if self.marker_file.as_deref() == Some(filename) {
+                    self.marker_file = None;
+                    self.markers.clear();
+                }
+                if self.selected_file.as_deref() == Some(filename) {
+                    self.selected_file = None;
+                }
+                if self.waveform.as_ref().is_some_and(|w| w.file == filename) {
+                    self.waveform = None;
+                }
+                if self.notes.remove(filename).is_some() {
+                    self.persist_notes();
+                }
+                self.refresh_files();
+            }
+            Err(e) => {
+                self.add_log(format!("Error deleting file: {}", e));
+            }
+        }
+    }
+
+    fn detect_chapters_impl(&mut self, filename: &str) {
+        let min_gap = Duration::from_secs_f32(self.config.chapter_min_gap_secs);
+
+        let (spec, samples) = match read_wav_file(filename) {
+            Ok(r) => r,
+            Err(e) => {
+                self.add_log(e);
+                return;
+            }
+        };
+
+        self.markers =
+            detect_silence_boundaries(&samples, spec.channels, spec.sample_rate, min_gap);
+        self.marker_labels.clear();
+        self.marker_file = Some(filename.to_string());
+        self.marker_samples_per_sec = spec.sample_rate as usize * spec.channels as usize;
+        self.add_log(format!(
+            "Found {} chapters in '{}'",
+            self.markers.len() + 1,
+            filename
+        ));
+    }
+
+    fn is_marker_file_playing(&self) -> bool {
+        self.playback_state != PlaybackState::Stopped
+            && self.marker_file.is_some()
+            && self.playing_file() == self.marker_file.as_ref()
+    }
+
+    fn next_chapter_impl(&mut self) {
+        if !self.is_marker_file_playing() {
+            return;
+        }
+
+        // Markers are in the file's samples, which an export preview doesn't play
+        let position = self.playback_to_waveform(*self.playback_position.lock().unwrap());
+        if let Some(&next) = self.markers.iter().find(|&&m| m > position) {
+            *self.playback_position.lock().unwrap() = self.waveform_to_playback(next);
+            let chapter = self.markers.iter().position(|&m| m == next).unwrap_or(0) + 2;
+            self.add_log(format!("Chapter {}", chapter));
+        }
+    }
+
+    fn previous_chapter_impl(&mut self) {
+        if !self.is_marker_file_playing() {
+            return;
+        }
+
+        // Jumping back from just after a marker goes to the one before it, like a media player
+        let grace = self.marker_samples_per_sec;
+        let position = self.playback_to_waveform(*self.playback_position.lock().unwrap());
+        let previous = self
+            .markers
+            .iter()
+            .rev()
+            .find(|&&m| m + grace < position)
+            .copied();
+
+        *self.playback_position.lock().unwrap() = self.waveform_to_playback(previous.unwrap_or(0));
+        let chapter = previous
+            .and_then(|p| self.markers.iter().position(|&m| m == p))
+            .map_or(1, |i| i + 2);
+        self.add_log(format!("Chapter {}", chapter));
+    }
+
+    fn export_chapters_impl(&mut self) -> Task<Message> {
+        let Some(filename) = self.marker_file.clone() else {
+            return Task::none();
+        };
+
+        let markers = self.markers.clone();
+        self.start_job(
+            Some(filename.clone()),
+            format!("Exporting chapters of '{}'", filename),
+            move |job| {
+                let (spec, samples) = read_wav_file(&filename)?;
+                match write_chapter_files(&filename, spec, &samples, &markers, &job) {
+                    Ok(count) => Ok(format!("Exported {} chapters from '{}'", count, filename)),
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => {
+                        Err("Export cancelled.".to_string())
+                    }
+                    Err(e) => Err(format!("Error exporting chapters: {}", e)),
+                }
+            },
+        )
+    }
+
+    fn split_at_markers_impl(&mut self) -> Task<Message> {
+        let Some(filename) = self.marker_file.clone() else {
+            return Task::none();
+        };
+        if self.recording_phase.is_recording() || self.markers.is_empty() {
+            return Task::none();
+        }
+
+        let markers = self.markers.clone();
+        self.start_job(
+            Some(filename.clone()),
+            format!("Splitting '{}'", filename),
+            move |job| {
+                let (spec, samples) = read_wav_file(&filename)?;
+                let segments = split_at(&samples, spec, &markers);
+                match write_split_files(&filename, spec, &segments, &job) {
+                    Ok(names) => Ok(format!("Split '{}' into {} files", filename, names.len())),
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => {
+                        Err("Export cancelled.".to_string())
+                    }
+                    Err(e) => Err(format!("Error splitting file: {}", e)),
+                }
+            },
+        )
+    }
+
+    /// Writes each channel of the selected file as a mono file of its own.
+    fn export_channels_impl(&mut self) -> Task<Message> {
+        let Some(filename) = self.selected_file.clone() else {
+            return Task::none();
+        };
+        if !self.can_interact_with_file(&filename) || self.background_job.is_some() {
+            return Task::none();
+        }
+
+        self.start_job(
+            Some(filename.clone()),
+            format!("Exporting the channels of '{}'", filename),
+            move |job| match write_channel_files(&filename, &job) {
+                Ok(names) => Ok(format!(
+                    "Wrote {} mono stems from '{}'",
+                    names.len(),
+                    filename
+                )),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {
+                    Err("Export cancelled.".to_string())
+                }
+                Err(e) => Err(format!("Error exporting channels: {}", e)),
+            },
+        )
+    }
+
+    fn export_file_impl(&mut self, filename: &str) -> Task<Message> {
+        if self.recording_phase.is_recording() {
+            return Task::none();
+        }
+
+        let filename = filename.to_string();
+        let settings = self.config.export.clone();
+        self.start_job(
+            Some(filename.clone()),
+            format!("Exporting '{}'", filename),
+            move |job| {
+                export_file(&filename, &settings, &job).map(|output| {
+                    let loudness = output
+                        .measured_lufs
+                        .map(|lufs| format!(", normalised from {:.1} LUFS", lufs))
+                        .unwrap_or_default();
+                    format!(
+                        "Exported '{}' ({} Hz, {} ch{})",
+                        output.path, output.sample_rate, output.channels, loudness
+                    )
+                })
+            },
+        )
+    }
+
+    /// Exports every file in the list (as filtered by the search) through the queue.
+    /// The files Export All works through: those the search shows, leaving out
+    /// earlier exports so they aren't exported again.
+    fn export_sources(&self) -> Vec<String> {
+        self.files
+            .iter()
+            .filter(|f| self.file_matches_search(f) && !is_export_output(f))
+            .cloned()
+            .collect()
+    }
+
+    fn export_all_impl(&mut self) -> Task<Message> {
+        if self.recording_phase.is_recording() || self.background_job.is_some() {
+            return Task::none();
+        }
+        let files: Vec<QueueItem> = self
+            .export_sources()
+            .into_iter()
+            .map(|file| QueueItem {
+                file,
+                status: QueueStatus::Waiting,
+            })
+            .collect();
+        if files.is_empty() {
+            return Task::none();
+        }
+        // Earlier exports this batch would overwrite are kept, so it can be undone
+        let held = self.new_holding_dir();
+        let outputs: Vec<String> = files
+            .iter()
+            .map(|item| export_path(&item.file, &self.config.export))
+            .collect();
+        let mut replaced = Vec::new();
+        for output in &outputs {
+            if std::path::Path::new(output).exists() {
+                match hold_file(&held, output) {
+                    Ok(()) => replaced.push(output.clone()),
+                    Err(e) => {
+                        for output in &replaced {
+                            let _ = unhold_file(&held, output);
+                        }
+                        self.add_log(format!("Couldn't set aside '{}': {}", output, e));
+                        return Task::none();
+                    }
+                }
+            }
+        }
+        self.push_undo(UndoEntry {
+            label: format!(
+                "export of {} file{}",
+                files.len(),
+                if files.len() == 1 { "" } else { "s" }
+            ),
+            undo: BatchUndo::Exported {
+                held,
+                outputs,
+                replaced,
+            },
+        });
+        *self.export_queue.lock().unwrap() = files;
+        self.run_export_queue_impl()
+    }
+
+    /// Puts items that failed (or never ran because the batch was cancelled) back in
+    /// the queue and runs it again.
+    fn retry_export_queue_impl(&mut self) -> Task<Message> {
+        if self.recording_phase.is_recording() || self.background_job.is_some() {
+            return Task::none();
+        }
+        let mut queue = self.export_queue.lock().unwrap();
+        queue.retain(|item| !matches!(item.status, QueueStatus::Done(_)));
+        for item in queue.iter_mut() {
+            item.status = QueueStatus::Waiting;
+        }
+        let empty = queue.is_empty();
+        drop(queue);
+        if empty {
+            return Task::none();
+        }
+        self.run_export_queue_impl()
+    }
+
+    fn run_export_queue_impl(&mut self) -> Task<Message> {
+        let queue = Arc::clone(&self.export_queue);
+        let total = queue.lock().unwrap().len();
+        let settings = self.config.export.clone();
+        let retries = self.config.export_retries;
+        let backoff = Duration::from_millis(self.config.export_retry_backoff_ms.into());
+        self.start_job(None, format!("Exporting {} files", total), move |job| {
+            let (done, failed) = run_export_queue(&queue, &settings, retries, backoff, &job);
+            if failed == 0 {
+                Ok(format!("Exported {} of {} files.", done, total))
+            } else {
+                Ok(format!(
+                    "Exported {} of {} files; {} failed. Use 'Retry failed' to run them again.",
+                    done, total, failed
+                ))
+            }
+        })
+    }
+
+    /// Runs processing on a background thread. Only one job runs at a time; it reports
+    /// progress and checks for cancellation through its `JobControl`, and its result
+    /// comes back as `Message::JobFinished`.
+    fn start_job(
+        &mut self,
+        file: Option<String>,
+        label: String,
+        work: impl FnOnce(JobControl) -> Result<String, String> + Send + 'static,
+    ) -> Task<Message> {
+        if let Some(job) = &self.background_job {
+            self.add_log(if job.control.is_cancelled() {
+                format!("Wait for '{}' to finish cancelling.", job.label)
+            } else {
+                format!("Wait for '{}' to finish first.", job.label)
+            });
+            return Task::none();
+        }
+
+        let id = self.next_job_id;
+        self.next_job_id += 1;
+        let control = JobControl::default();
+        self.add_log(format!("{}...", label));
+        self.background_job = Some(BackgroundJob {
+            id,
+            label,
+            control: control.clone(),
+            file,
+        });
+
+        Task::perform(run_in_background(move || work(control)), move |result| {
+            Message::JobFinished(
+                id,
+                result.unwrap_or_else(|| Err("Processing failed unexpectedly.".into())),
+            )
+        })
+    }
+
+    fn job_finished_impl(&mut self, id: u64, result: Result<String, String>) {
+        if self.background_job.as_ref().map(|job| job.id) != Some(id) {
+            return;
+        }
+
+        let job = self.background_job.take().unwrap();
+        self.restore_unexported_files();
+        let scan = self.duplicate_scan.lock().unwrap().take();
+        if job.control.is_cancelled() {
+            // Whatever it got done before noticing is on disk, so the list may be stale
+            self.add_log(format!("Cancelled: {}.", job.label));
+            self.refresh_files();
+            return;
+        }
+        match result {
+            Ok(message) => {
+                if let Some(scan) = scan {
+                    self.fingerprint_cache.extend(scan.fingerprints);
+                    for error in scan.errors {
+                        self.add_log(error);
+                    }
+                    self.duplicates = scan.duplicates;
+                }
+                self.add_log(message);
+                self.refresh_files();
+                // The job may have rewritten the file on display
+                if let Some(file) = self.waveform.take().map(|w| w.file) {
+                    self.load_waveform(&file);
+                }
+            }
+            Err(e) => {
+                self.add_log(e);
+            }
+        }
+    }
+
+    /// Asks the job to stop. It stays the running job until its worker has actually
+    /// stopped and `JobFinished` arrives, so nothing else can start on its files
+    /// in the meantime.
+    fn cancel_job_impl(&mut self) {
+        if let Some(job) = &self.background_job
+            && !job.control.is_cancelled()
+        {
+            job.control.cancel();
+            self.add_log(format!("Cancelling: {}...", job.label));
+        }
+    }
+
+    /// Switches to keeping recordings in `folder`. Settings come along but are still
+    /// saved where they were loaded from; the file list, notes and projects are the new
+    /// folder's own.
+    fn open_folder_impl(&mut self, folder: &str) {
+        if self.recording_phase != RecordingPhase::Idle
+            || self.playback_state != PlaybackState::Stopped
+            || self.background_job.is_some()
+        {
+            self.add_log("Stop recording and playback before switching folders.".into());
+            return;
+        }
+        let previous = std::env::current_dir().ok();
+        let opened = fs::canonicalize(folder.trim()).and_then(|dir| {
+            // What the undo history holds is relative to the folder being left
+            self.clear_undo_history();
+            std::env::set_current_dir(&dir).map(|()| dir)
+        });
+        let dir = match opened {
+            Ok(dir) => dir.display().to_string(),
+            Err(e) => {
+                self.add_log(format!("Couldn't open folder '{}': {}", folder.trim(), e));
+                self.config
+                    .recent_folders
+                    .retain(|f| std::path::Path::new(f).is_dir());
+                return;
+            }
+        };
+
+        let recent = &mut self.config.recent_folders;
+        for entry in previous
+            .map(|p| p.display().to_string())
+            .into_iter()
+            .chain([dir.clone()])
+        {
+            recent.retain(|f| *f != entry);
+            recent.insert(0, entry);
+        }
+        recent.retain(|f| std::path::Path::new(f).is_dir());
+        recent.truncate(RECENT_FOLDERS_LEN);
+        self.save_config();
+
+        // Everything below is about files of the folder left behind
+        self.selected_file = None;
+        self.selected_bext = None;
+        self.selected_comment = None;
+        self.waveform = None;
+        self.markers.clear();
+        self.marker_labels.clear();
+        self.marker_file = None;
+        self.ab_slots = [None, None];
+        self.active_project = None;
+        self.project_clip = None;
+        self.projects = load_projects();
+        self.last_positions.clear();
+        self.damaged_files.clear();
+        self.fingerprint_cache.clear();
+        self.analysis_cache.clear();
+        self.loudness_cache.clear();
+        self.mono_check = None;
+        self.refresh_files();
+        self.folder_input = dir.clone();
+        self.add_log(format!(
+            "Opened folder '{}' ({} recordings)",
+            dir,
+            self.files.len()
+        ));
+    }
+
+    fn refresh_files(&mut self) {
+        self.files = list_wav_files(self.config.listing_depth());
+        self.file_modified = self
+            .files
+            .iter()
+            .filter_map(|f| Some((f.clone(), fs::metadata(f).and_then(|m| m.modified()).ok()?)))
+            .collect();
+        if self.config.group_by_date {
+            // Newest first; the sort is stable, so a day's ties keep folder and name order
+            let modified = &self.file_modified;
+            self.files
+                .sort_by_key(|f| std::cmp::Reverse(modified.get(f).copied()));
+        }
+        self.notes = load_notes();
+        let files = &self.files;
+        self.duplicates.retain(|f| files.contains(f));
+    }
+
+    /// Analyses `filename` for the info panel on its own thread, unless it hasn't
+    /// changed since the last time. The result comes back as
+    /// `Message::AnalysisFinished`.
+    fn update_analysis(&mut self, filename: &str) -> Task<Message> {
+        let Ok(modified) = fs::metadata(filename).and_then(|m| m.modified()) else {
+            return Task::none();
+        };
+        if self
+            .analysis_cache
+            .get(&cache_key(filename))
+            .is_some_and(|(cached_mtime, _)| *cached_mtime == modified)
+        {
+            return Task::none();
+        }
+        let file = filename.to_string();
+        Task::perform(
+            run_in_background({
+                let file = file.clone();
+                move || analyze(&file)
+            }),
+            move |result| {
+                Message::AnalysisFinished(
+                    file.clone(),
+                    modified,
+                    result.unwrap_or_else(|| Err("Analysis failed unexpectedly.".into())),
+                )
+            },
+        )
+    }
+
+    fn cached_loudness(&mut self, filename: &str, spec: &WavSpec, samples: &[f32]) -> Option<f64> {
+        let modified = fs::metadata(filename).and_then(|m| m.modified()).ok()?;
+        if let Some(&(cached_mtime, lufs)) = self.loudness_cache.get(&cache_key(filename))
+            && cached_mtime == modified
+        {
+            return lufs;
+        }
+
+        let lufs = integrated_loudness(samples, spec.channels, spec.sample_rate);
+        self.loudness_cache
+            .insert(cache_key(filename), (modified, lufs));
+        lufs
+    }
+
+    /// Scales a playback buffer towards the auto-level reference. Like normalising,
+    /// the gain is held back if it would push peaks over the ceiling. Returns a note
+    /// for the status message.
+    fn apply_auto_level(&mut self, filename: &str, spec: &WavSpec, samples: &mut [f32]) -> String {
+        if !self.config.auto_level {
+            return String::new();
+        }
+        let Some(measured) = self.cached_loudness(filename, spec, samples) else {
+            return String::new();
+        };
+
+        let peak = samples.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+        let ceiling = db_to_amplitude(NORMALIZE_PEAK_CEILING_DB);
+        let mut gain = db_to_amplitude(self.config.auto_level_target_lufs - measured as f32);
+        if peak * gain > ceiling {
+            gain = ceiling / peak;
+        }
+        for s in samples.iter_mut() {
+            *s *= gain;
+        }
+        format!(" (auto-level {:+.1} dB)", 20.0 * gain.log10())
+    }
+
+    fn apply_fades_impl(&mut self) {
+        let Some(filename) = self.selected_file.clone() else {
+            return;
+        };
+        if !self.can_interact_with_file(&filename) {
+            return;
+        }
+
+        let (Ok(fade_in_ms), Ok(fade_out_ms)) = (
+            self.fade_in_input.trim().parse::<u32>(),
+            self.fade_out_input.trim().parse::<u32>(),
+        ) else {
+            self.add_log("Fade durations must be whole milliseconds.".into());
+            return;
+        };
+
+        let (spec, mut samples) = match read_wav_file(&filename) {
+            Ok(r) => r,
+            Err(e) => {
+                self.add_log(e);
+                return;
+            }
+        };
+
+        apply_fades(
+            &mut samples,
+            spec.channels,
+            spec.sample_rate,
+            fade_in_ms,
+            fade_out_ms,
+        );
+        match rewrite_wav_file(&filename, spec, &samples) {
+            Ok(()) => {
+                self.add_log(format!(
+                    "Applied {} ms fade-in and {} ms fade-out to '{}'",
+                    fade_in_ms, fade_out_ms, filename
+                ));
+            }
+            Err(e) => {
+                self.add_log(format!("Error applying fades: {}", e));
+            }
+        }
+    }
+
+    /// Inserts a pause into the selected file at the waveform cursor, i.e. where its
+    /// playback is. Playback is stopped so the file can be rewritten.
+    fn insert_silence_impl(&mut self) {
+        let Some(filename) = self.selected_file.clone() else {
+            return;
+        };
+        if self.recording_phase.is_recording() {
+            return;
+        }
+        let Ok(secs) = self.silence_input.trim().parse::<f32>() else {
+            self.add_log("Silence length must be a number of seconds.".into());
+            return;
+        };
+        if !secs.is_finite() || secs <= 0.0 || secs > MAX_SILENCE_SECS {
+            self.add_log(format!(
+                "Silence length must be more than 0 and at most {} seconds.",
+                MAX_SILENCE_SECS
+            ));
+            return;
+        }
+        if self.file_in_job(&filename) {
+            return;
+        }
+        if self.playback_state == PlaybackState::Stopped || self.playing_file() != Some(&filename) {
+            self.add_log("Click the waveform where the silence should go.".into());
+            return;
+        }
+        let position = self.playback_to_waveform(*self.playback_position.lock().unwrap());
+        self.stop_playback_impl();
+        if !self.can_interact_with_file(&filename) {
+            return;
+        }
+
+        let (spec, samples) = match read_wav_file(&filename) {
+            Ok(r) => r,
+            Err(e) => {
+                self.add_log(e);
+                return;
+            }
+        };
+        let frames = (secs * spec.sample_rate as f32).round() as usize;
+        let edited = insert_silence(&samples, spec.channels, position, frames);
+        match rewrite_wav_file(&filename, spec, &edited) {
+            Ok(()) => {
+                let at = position as f64 / (spec.sample_rate as f64 * spec.channels.max(1) as f64);
+                self.add_log(format!(
+                    "Inserted {:.2}s of silence into '{}' at {:.2}s",
+                    secs, filename, at
+                ));
+                if self.waveform.as_ref().is_some_and(|w| w.file == filename) {
+                    self.waveform = None;
+                    self.load_waveform(&filename);
+                }
+                let inserted = frames as f64 / spec.sample_rate.max(1) as f64;
+                self.remap_file_marks(&filename, spec.sample_rate, |mark| {
+                    Some(if mark >= at { mark + inserted } else { mark })
+                });
+            }
+            Err(e) => {
+                self.add_log(format!("Error inserting silence: {}", e));
+            }
+        }
+    }
+
+    /// Rewrites a damaged file as a clean WAV holding just the audio that can still
+    /// be read from it.
+    fn repair_file_impl(&mut self) {
+        let Some(filename) = self.selected_file.clone() else {
+            return;
+        };
+        if !self.can_interact_with_file(&filename) {
+            return;
+        }
+        let (spec, samples, damage) = match read_wav_recoverable(&filename) {
+            Ok(r) => r,
+            Err(e) => {
+                self.add_log(e);
+                return;
+            }
+        };
+        if damage.is_none() {
+            self.damaged_files.remove(&filename);
+            self.add_log(format!("'{}' reads cleanly; nothing to repair.", filename));
+            return;
+        }
+        match rewrite_wav_file(&filename, spec, &samples) {
+            Ok(()) => {
+                self.damaged_files.remove(&filename);
+                let secs =
+                    samples.len() as f64 / (spec.sample_rate as f64 * spec.channels.max(1) as f64);
+                self.add_log(format!(
+                    "Repaired '{}', keeping {:.1}s of audio",
+                    filename, secs
+                ));
+                if self.waveform.as_ref().is_some_and(|w| w.file == filename) {
+                    self.waveform = None;
+                    self.load_waveform(&filename);
+                }
+            }
+            Err(e) => self.add_log(format!("Error repairing file: {}", e)),
+        }
+    }
+
+    fn mono_check_impl(&mut self) {
+        let Some(filename) = self.selected_file.clone() else {
+            return;
+        };
+        let (spec, samples) = match read_wav_file(&filename) {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                self.add_log(e);
+                return;
+            }
+        };
+        if spec.channels != 2 {
+            self.add_log("Mono check is for stereo files.".into());
+            return;
+        }
+        match mono_compatibility(&samples, spec.sample_rate) {
+            Some(check) => self.mono_check = Some((filename, check)),
+            None => self.add_log("File is too short or quiet to check.".into()),
+        }
+    }
+
+    /// Which of a file's `channels` the polarity tools flip.
+    fn polarity_flags(&self, channels: u16) -> Vec<bool> {
+        (0..channels as usize)
+            .map(|c| !self.polarity_skipped.contains(&c))
+            .collect()
+    }
+
+    /// Hands the playback callbacks the channels to flip for a file of `channels`.
+    fn sync_playback_polarity(&self, channels: u16) {
+        let flags = if self.invert_playback {
+            self.polarity_flags(channels)
+        } else {
+            Vec::new()
+        };
+        *self.playback_polarity.lock().unwrap() = flags;
+    }
+
+    /// Rewrites the selected file with the chosen channels negated.
+    fn invert_polarity_impl(&mut self) {
+        let Some(filename) = self.selected_file.clone() else {
+            return;
+        };
+        if !self.can_interact_with_file(&filename) {
+            return;
+        }
+        let (spec, mut samples) = match read_wav_file(&filename) {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                self.add_log(e);
+                return;
+            }
+        };
+        let flags = self.polarity_flags(spec.channels);
+        if !flags.contains(&true) {
+            self.add_log("Pick at least one channel to invert.".into());
+            return;
+        }
+        invert_polarity(&mut samples, spec.channels as usize, &flags);
+        match rewrite_wav_file(&filename, spec, &samples) {
+            Ok(()) => {
+                let which = if flags.len() > 1 && flags.contains(&false) {
+                    let numbers: Vec<String> = (0..flags.len())
+                        .filter(|&c| flags[c])
+                        .map(|c| (c + 1).to_string())
+                        .collect();
+                    format!(" on channel(s) {}", numbers.join(", "))
+                } else {
+                    String::new()
+                };
+                self.add_log(format!("Inverted the polarity of '{}'{}", filename, which));
+                if self.waveform.as_ref().is_some_and(|w| w.file == filename) {
+                    self.waveform = None;
+                    self.load_waveform(&filename);
+                }
+            }
+            Err(e) => self.add_log(format!("Error inverting polarity: {}", e)),
+        }
+    }
+
+    fn normalize_file_impl(&mut self) -> Task<Message> {
+        let Some(filename) = self.selected_file.clone() else {
+            return Task::none();
+        };
+        if !self.can_interact_with_file(&filename) {
+            return Task::none();
+        }
+        let Ok(target) = self.normalize_input.trim().parse::<f32>() else {
+            self.add_log("Normalize target must be a number in LUFS.".into());
+            return Task::none();
+        };
+
+        self.start_job(
+            Some(filename.clone()),
+            format!("Normalizing '{}'", filename),
+            move |job| {
+                let (spec, mut samples) = read_wav_file(&filename)?;
+                let samples_len = samples.len().max(1);
+                job.set_progress(0.3);
+                if job.is_cancelled() {
+                    return Err("Normalize cancelled.".into());
+                }
+                let (measured, gain) =
+                    loudness_gain(&samples, spec.channels, spec.sample_rate, target).ok_or_else(
+                        || format!("'{}' is too quiet or short to measure.", filename),
+                    )?;
+                let chunk = PROGRESS_INTERVAL_FRAMES * spec.channels.max(1) as usize;
+                for (i, block) in samples.chunks_mut(chunk).enumerate() {
+                    if job.is_cancelled() {
+                        return Err("Normalize cancelled.".into());
+                    }
+                    job.set_progress(0.3 + 0.4 * (i * chunk) as f32 / samples_len as f32);
+                    for s in block {
+                        *s *= gain;
+                    }
+                }
+                job.set_progress(0.7);
+                rewrite_wav_file(&filename, spec, &samples)
+                    .map_err(|e| format!("Error normalizing file: {}", e))?;
+                job.set_progress(1.0);
+                Ok(format!(
+                    "Normalized '{}' towards {:.1} LUFS (was {:.1} LUFS)",
+                    filename, target, measured
+                ))
+            },
+        )
+    }
+
+    fn trim_silence_impl(&mut self) {
+        let Some(filename) = self.selected_file.clone() else {
+            return;
+        };
+        if !self.can_interact_with_file(&filename) {
+            return;
+        }
+
+        let (spec, samples) = match read_wav_file(&filename) {
+            Ok(r) => r,
+            Err(e) => {
+                self.add_log(e);
+                return;
+            }
+        };
+
+        let Some(range) = silence_trim_range(
+            &samples,
+            spec.channels,
+            spec.sample_rate,
+            db_to_amplitude(self.config.processing.trim_threshold_db),
+            self.config.processing.trim_padding_ms,
+        ) else {
+            self.add_log(format!("'{}' is silent; nothing to keep.", filename));
+            return;
+        };
+        if range.len() == samples.len() {
+            self.add_log(format!("No silence to trim in '{}'", filename));
+            return;
+        }
+
+        let removed = samples.len() - range.len();
+        match rewrite_wav_file(&filename, spec, &samples[range.clone()]) {
+            Ok(()) => {
+                let secs = removed as f32 / (spec.sample_rate as f32 * spec.channels as f32);
+                self.add_log(format!(
+                    "Trimmed {:.2}s of silence from '{}'",
+                    secs, filename
+                ));
+                if self.waveform.as_ref().is_some_and(|w| w.file == filename) {
+                    self.waveform = None;
+                    self.load_waveform(&filename);
+                }
+                let samples_per_sec = spec.sample_rate as f64 * spec.channels.max(1) as f64;
+                let (start, end) = (
+                    range.start as f64 / samples_per_sec,
+                    range.end as f64 / samples_per_sec,
+                );
+                self.remap_file_marks(&filename, spec.sample_rate, |mark| {
+                    (start..end).contains(&mark).then_some(mark - start)
+                });
+            }
+            Err(e) => {
+                self.add_log(format!("Error trimming file: {}", e));
+            }
+        }
+    }
+
+    /// Copies the selected file and trims silence from the copy, leaving the
+    /// original as it was. The copy is selected so further edits go to it too.
+    fn duplicate_and_trim_impl(&mut self) -> Task<Message> {
+        let Some(original) = self.selected_file.clone() else {
+            return Task::none();
+        };
+        if !self.can_interact_with_file(&original) {
+            return Task::none();
+        }
+
+        let copy = trimmed_copy_name(&original);
+        if let Err(e) = fs::copy(&original, &copy) {
+            self.add_log(format!("Error copying '{}': {}", original, e));
+            return Task::none();
+        }
+        // Notes and tags still apply; marks and transcripts are timed to the original
+        for sidecar in [playback_settings_path, speed_path] {
+            if fs::metadata(sidecar(&original)).is_ok() {
+                let _ = fs::copy(sidecar(&original), sidecar(&copy));
+            }
+        }
+        if let Some(file_notes) = self.notes.get(&original).cloned() {
+            self.notes.insert(copy.clone(), file_notes);
+            self.persist_notes();
+        }
+        self.refresh_files();
+        self.add_log(format!("Copied '{}' to '{}'", original, copy));
+
+        self.selected_file = Some(copy.clone());
+        // The copy is analysed once it's trimmed
+        let _ = self.select_file(copy.clone());
+        self.trim_silence_impl();
+        self.update_analysis(&copy)
+    }
+
+    /// Searches the list for recordings with the same audio as a background job;
+    /// what it finds is picked up from `duplicate_scan` when the job finishes.
+    fn find_duplicates_impl(&mut self) -> Task<Message> {
+        let files = self.files.clone();
+        let fingerprints = self.fingerprint_cache.clone();
+        let found = Arc::clone(&self.duplicate_scan);
+        self.start_job(None, "Finding duplicates".into(), move |job| {
+            let scan = find_duplicates(&files, fingerprints, &job)?;
+            let message = match (scan.duplicates.len(), scan.errors.len()) {
+                (0, 0) => "No duplicate recordings found.".into(),
+                (n, 0) => format!("Found {} duplicate recording(s).", n),
+                (n, u) => format!(
+                    "Found {} duplicate recording(s); {} file(s) unreadable.",
+                    n, u
+                ),
+            };
+            *found.lock().unwrap() = Some(scan);
+            Ok(message)
+        })
+    }
+
+    fn delete_duplicates_impl(&mut self) {
+        if self.recording_phase.is_recording() || self.playback_state != PlaybackState::Stopped {
+            return;
+        }
+
+        let held = self.new_holding_dir();
+        let mut duplicates: Vec<String> =
+            std::mem::take(&mut self.duplicates).into_iter().collect();
+        duplicates.sort();
+        let mut deleted = Vec::new();
+        let mut failed = 0;
+        for file in duplicates {
+            match hold_file(&held, &file) {
+                Ok(()) => {
+                    self.fingerprint_cache.remove(&cache_key(&file));
+                    let notes = self.notes.remove(&file);
+                    if notes.is_some() {
+                        self.persist_notes();
+                    }
+                    deleted.push((file, notes));
+                }
+                Err(e) => {
+                    self.add_log(format!("Error deleting '{}': {}", file, e));
+                    failed += 1;
+                }
+            }
+        }
+        let count = deleted.len();
+        if count > 0 {
+            self.push_undo(UndoEntry {
+                label: format!(
+                    "deletion of {} duplicate{}",
+                    count,
+                    if count == 1 { "" } else { "s" }
+                ),
+                undo: BatchUndo::Deleted {
+                    held,
+                    files: deleted,
+                },
+            });
+        }
+
+        self.refresh_files();
+        self.add_log(if failed == 0 {
+            format!("Deleted {} duplicate(s).", count)
+        } else {
+            format!(
+                "Deleted {} duplicate(s); {} could not be deleted.",
+                count, failed
+            )
+        });
+    }
+
+    fn persist_notes(&mut self) {
+        self.notes.retain(|_, n| !n.is_empty());
+        if let Err(e) = save_notes(&self.notes) {
+            self.add_log(format!("Error saving notes: {}", e));
+        }
+    }
+
+    fn update_notes_impl(&mut self, text: String) {
+        if let Some(file) = &self.selected_file {
+            self.notes.entry(file.clone()).or_default().notes = text;
+            self.persist_notes();
+        }
+    }
+
+    fn set_label_impl(&mut self, label: Option<ColorLabel>) {
+        if let Some(file) = &self.selected_file {
+            self.notes.entry(file.clone()).or_default().label = label;
+            self.persist_notes();
+        }
+    }
+
+    fn add_tag_impl(&mut self) {
+        let tag = self.tag_input.trim().to_string();
+        let Some(file) = &self.selected_file else {
+            return;
+        };
+        if tag.is_empty() {
+            return;
+        }
+
+        let file_notes = self.notes.entry(file.clone()).or_default();
+        if !file_notes.tags.contains(&tag) {
+            file_notes.tags.push(tag);
+            self.persist_notes();
+        }
+        self.tag_input.clear();
+    }
+
+    fn remove_tag_impl(&mut self, tag: &str) {
+        if let Some(file) = &self.selected_file
+            && let Some(file_notes) = self.notes.get_mut(file)
+        {
+            file_notes.tags.retain(|t| t != tag);
+            self.persist_notes();
+        }
+    }
+
+    fn file_matches_search(&self, filename: &str) -> bool {
+        let query = self.search_query.trim().to_lowercase();
+        if query.is_empty() {
+            return true;
+        }
+
+        filename.to_lowercase().contains(&query)
+            || self.notes.get(filename).is_some_and(|n| n.matches(&query))
+    }
+
+    /// Built-in presets followed by the user's own.
+    fn export_presets(&self) -> Vec<ExportPreset> {
+        let mut presets = builtin_presets();
+        presets.extend(self.config.export_presets.iter().cloned());
+        presets
+    }
+
+    fn save_export_preset_impl(&mut self) {
+        let name = self.preset_name.trim().to_string();
+        if name.is_empty() {
+            self.add_log("Enter a name for the preset.".into());
+            return;
+        }
+        if builtin_presets().iter().any(|p| p.name == name) {
+            self.add_log(format!("'{}' is a built-in preset.", name));
+            return;
+        }
+
+        let preset = ExportPreset {
+            name: name.clone(),
+            settings: self.config.export.clone(),
+        };
+        match self
+            .config
+            .export_presets
+            .iter_mut()
+            .find(|p| p.name == name)
+        {
+            Some(existing) => *existing = preset,
+            None => self.config.export_presets.push(preset),
+        }
+        self.preset_name.clear();
+        self.add_log(format!("Saved export preset '{}'", name));
+        self.save_config();
+    }
+
+    /// Shows `message` in the status line and appends it to the log.
+    fn add_log(&mut self, message: String) {
+        if self.status_log.len() == STATUS_LOG_LEN {
+            self.status_log.pop_front();
+        }
+        self.status_log
+            .push_back((self.launched_at.elapsed(), message.clone()));
+        self.status_message = message;
+    }
+
+    fn update_tray(&self) {
+        #[cfg(feature = "tray")]
+        if let Some(tray) = &self.tray {
+            tray.set_recording(self.recording_phase.is_recording());
+        }
+    }
+
+    #[cfg(feature = "tray")]
+    fn with_tray(mut self) -> Self {
+        match tray::Tray::new() {
+            Ok(tray) => self.tray = Some(tray),
+            Err(e) => self.add_log(e),
+        }
+        self
+    }
+
+    #[cfg(feature = "tray")]
+    fn tray_action_impl(&mut self, action: tray::TrayAction) -> Task<Message> {
+        match action {
+            tray::TrayAction::ToggleRecording => {
+                self.toggle_recording_impl();
+                Task::none()
+            }
+            tray::TrayAction::ShowWindow => iced::window::get_latest().and_then(|id| {
+                Task::batch([
+                    iced::window::minimize(id, false),
+                    iced::window::gain_focus(id),
+                ])
+            }),
+        }
+    }
+
+    fn reset_processing_impl(&mut self) {
+        self.config.processing = ProcessingChain::default();
+        let mut gains = self.channel_gains.lock().unwrap();
+        let channels = gains.len();
+        *gains = self.config.processing.input_gains(channels);
+        drop(gains);
+        self.trim_threshold_input = self.config.processing.trim_threshold_db.to_string();
+        self.trim_padding_input = self.config.processing.trim_padding_ms.to_string();
+        self.normalize_input = self.config.processing.normalize_target_lufs.to_string();
+        self.save_config();
+        self.add_log("Processing settings reset to defaults.".into());
+    }
+
+    fn save_config(&mut self) {
+        // Settings that failed to load are left for the user to fix, not replaced
+        if self.config_unreadable {
+            return;
+        }
+        if let Err(e) = self.config.save(&self.config_path) {
+            self.add_log(format!("Error saving settings: {}", e));
+        }
+    }
+
+    fn can_interact_with_file(&self, filename: &str) -> bool {
+        // Can't interact if recording or if this file is currently playing
+        if self.recording_phase.is_recording() {
+            return false;
+        }
+
+        if let Some(playing_file) = self.playing_file()
+            && playing_file == filename
+            && self.playback_state != PlaybackState::Stopped
+        {
+            return false;
+        }
+
+        // Can't interact if any file is being renamed
+        if self.renaming_file.is_some() {
+            return false;
+        }
+
+        // Or while a background job is rewriting it
+        !self.file_in_job(filename)
+    }
+
+    /// Whether the running background job is working on `filename`.
+    fn file_in_job(&self, filename: &str) -> bool {
+        self.background_job
+            .as_ref()
+            .is_some_and(|job| job.file.as_deref() == Some(filename))
+    }
+
+    fn update(&mut self, message: Message) -> Task<Message> {
+        match message {
+            Message::StartRecording => self.start_recording_impl(),
+            Message::StopRecording => self.stop_recording_impl(),
+            Message::ArmRecording => self.arm_recording_impl(),
+            Message::DisarmRecording => self.disarm_recording_impl(),
+            Message::RerecordLastTake => self.rerecord_last_take_impl(),
+            Message::StartMonitorOnly => self.start_monitor_only_impl(),
+            Message::PlayFile(fname) => self.play_file_impl(&fname),
+            Message::PreviewFile(fname) => self.preview_file_impl(&fname),
+            Message::PreviewSelected => {
+                if let Some(fname) = self.selected_file.clone() {
+                    self.preview_file_impl(&fname);
+                }
+            }
+            Message::PausePlayback => self.pause_playback_impl(),
+            Message::SkipBackward => self.skip_impl(-self.config.skip_back_secs),
+            Message::SkipForward => self.skip_impl(self.config.skip_forward_secs),
+            Message::UpdateSkipBack(value) => {
+                if let Ok(secs) = value.trim().parse::<f32>()
+                    && secs.is_finite()
+                    && secs > 0.0
+                    && secs <= MAX_SKIP_SECS
+                {
+                    self.config.skip_back_secs = secs;
+                    self.save_config();
+                }
+                self.skip_back_input = value;
+            }
+            Message::UpdateSkipForward(value) => {
+                if let Ok(secs) = value.trim().parse::<f32>()
+                    && secs.is_finite()
+                    && secs > 0.0
+                    && secs <= MAX_SKIP_SECS
+                {
+                    self.config.skip_forward_secs = secs;
+                    self.save_config();
+                }
+                self.skip_forward_input = value;
+            }
+            Message::ResumePlayback => self.resume_playback_impl(),
+            Message::StopPlayback => self.stop_playback_impl(),
+            Message::DeleteFile(fname) => self.delete_file_impl(&fname),
+            Message::StartRename(filename) => self.start_rename_impl(&filename),
+            Message::UpdateRenameName(name) => {
+                self.new_name = name;
+            }
+            Message::ConfirmRename => self.confirm_rename_impl(),
+            Message::ToggleBatchRename => self.batch_rename_open = !self.batch_rename_open,
+            Message::UpdateBatchRenameTemplate(template) => self.batch_rename_template = template,
+            Message::UpdateOpenFile(path) => self.open_file_input = path,
+            Message::OpenForEditing(path) => return self.open_for_editing_impl(&path),
+            Message::ApplyBatchRename => self.request_batch_impl(BatchKind::Rename),
+            Message::ConfirmBatch => return self.confirm_batch_impl(),
+            Message::CancelBatch => self.pending_batch = None,
+            Message::UndoBatch => self.undo_batch_impl(),
+            Message::CancelRename => self.cancel_rename_impl(),
+            Message::FinalizeRecording => return self.finalize_recording(),
+            Message::DiscardRecording => self.discard_recording_impl(),
+            Message::UpdateTakeName(name) => {
+                if self.take_name.is_some() {
+                    self.take_name = Some(name);
+                }
+            }
+            Message::TakeNameSuggested(result) => match result {
+                // Only taken while the name offered hasn't been touched, and isn't in use
+                Ok(Some(name)) => {
+                    if self.recording_phase == RecordingPhase::Finalizing
+                        && self.take_name.as_ref() == Some(&self.default_take_name())
+                        && !std::path::Path::new(&name).exists()
+                    {
+                        self.take_name = Some(name);
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => self.add_log(format!("Couldn't name the take from speech: {}.", e)),
+            },
+            Message::RecoverInterruptedTake => self.recover_interrupted_take_impl(),
+            Message::DiscardInterruptedTake => self.discard_interrupted_take_impl(),
+            Message::ToggleAutoSave(enabled) => {
+                self.config.auto_save_recordings = enabled;
+                self.save_config();
+            }
+            Message::MeasureLatency => self.measure_latency_impl(),
+            Message::ToggleLiveCaptions(enabled) => {
+                self.config.live_captions = enabled;
+                self.save_config();
+            }
+            Message::ToggleNameBySpeech(enabled) => {
+                self.config.name_by_speech = enabled;
+                self.save_config();
+            }
+            Message::UpdatePostSaveCommand(command) => {
+                self.config.post_save_command = command;
+                self.save_config();
+            }
+            Message::PostSaveCommandFinished(file, result) => match result {
+                Ok(message) => self.add_log(format!("'{}': {}", file, message)),
+                Err(e) => self.add_log(format!("'{}': {}", file, e)),
+            },
+            Message::TranscriptSaved(result) => {
+                if let Err(e) = result {
+                    self.add_log(format!("Error saving transcript: {}", e));
+                }
+            }
+            Message::UpdateFolder(folder) => self.folder_input = folder,
+            Message::OpenFolder(folder) => self.open_folder_impl(&folder),
+            Message::ToggleRecursiveListing(enabled) => {
+                self.config.recursive_listing = enabled;
+                self.save_config();
+                self.refresh_files();
+            }
+            Message::ToggleGroupByDate(enabled) => {
+                self.config.group_by_date = enabled;
+                self.save_config();
+                self.refresh_files();
+            }
+            Message::UpdateListingDepth(value) => {
+                if let Ok(depth) = value.trim().parse::<usize>()
+                    && depth > 0
+                {
+                    self.config.listing_depth = depth;
+                    self.save_config();
+                    self.refresh_files();
+                }
+                self.listing_depth_input = value;
+            }
+            Message::ToggleLowLatency(enabled) => {
+                self.config.low_latency = enabled;
+                self.save_config();
+            }
+            Message::ToggleAutoLevel(enabled) => {
+                self.config.auto_level = enabled;
+                self.save_config();
+            }
+            Message::UpdateAutoLevelTarget(value) => {
+                if let Ok(lufs) = value.trim().parse::<f32>()
+                    && (-70.0..0.0).contains(&lufs)
+                {
+                    self.config.auto_level_target_lufs = lufs;
+                    self.save_config();
+                }
+                self.auto_level_input = value;
+            }
+            Message::ToggleKeepOutputWarm(enabled) => {
+                self.config.keep_output_warm = enabled;
+                self.save_config();
+                if !enabled && self.playback_state == PlaybackState::Stopped {
+                    self.release_output_stream();
+                }
+            }
+            Message::ToggleOutputDither(enabled) => {
+                self.config.output_dither = enabled;
+                self.backend.set_output_dither(enabled);
+                self.save_config();
+            }
+            Message::UpdateWarmOutputIdle(value) => {
+                if let Ok(secs) = value.trim().parse::<u32>() {
+                    self.config.warm_output_idle_secs = secs;
+                    self.save_config();
+                }
+                self.warm_output_idle_input = value;
+            }
+            Message::UpdateStartRamp(value) => {
+                if let Ok(ms) = value.trim().parse::<u32>()
+                    && ms <= MAX_START_RAMP_MS
+                {
+                    self.config.output_start_ramp_ms = ms;
+                    self.save_config();
+                }
+                self.start_ramp_input = value;
+            }
+            Message::TogglePreserveSourceFormat(enabled) => {
+                self.config.preserve_source_format = enabled;
+                self.save_config();
+            }
+            Message::Tick(now) => {
+                if let Some(start) = self.start_time {
+                    self.elapsed_time = now - start;
+                }
+                // Off normal speed the clock runs apart from the file, so go by position
+                if self.playback_state == PlaybackState::Playing
+                    && self.playback_speed() != 1.0
+                    && let Some(spec) = self.playback_spec
+                {
+                    let position = *self.playback_position.lock().unwrap();
+                    self.elapsed_time = Duration::from_secs_f64(
+                        position as f64 / (spec.sample_rate as f64 * spec.channels.max(1) as f64),
+                    );
+                }
+
+                if matches!(
+                    self.recording_phase,
+                    RecordingPhase::Recording | RecordingPhase::Stopping { .. }
+                ) {
+                    self.feed_captions();
+                    self.journal_recording(now, false);
+                }
+
+                if self.recording_phase.is_recording()
+                    && let Some(limit) = self.max_recording_samples()
+                {
+                    // Stop early enough that the stop tail still fits under the cap
+                    let tail = stop_flush_delay(
+                        self.recording_buffer_size,
+                        self.recording_sample_rate,
+                        self.config.stop_tail_ms,
+                    );
+                    let tail_samples = (tail.as_secs_f64()
+                        * self.recording_sample_rate as f64
+                        * self.recording_channels as f64)
+                        as usize;
+                    if self.audio_data.lock().unwrap().len() + tail_samples >= limit {
+                        self.stop_recording_impl();
+                        self.add_log(format!(
+                            "Recording stopped: the file reached the {} MB size limit.",
+                            self.config.max_file_size_mb
+                        ));
+                    }
+                }
+
+                if self.input_stream.is_some() || self.output_stream.is_some() {
+                    self.update_stream_health(now);
+                }
+
+                if self.recording_phase.is_recording()
+                    || self.recording_phase.is_armed()
+                    || self.playback_state == PlaybackState::Playing
+                {
+                    self.update_meter(now);
+                } else if self.clip_until.is_some_and(|until| now >= until) {
+                    self.clip_until = None;
+                }
+
+                if let Some((flash_start, _)) = self.cue_flash
+                    && now.duration_since(flash_start) >= Duration::from_millis(CUE_FLASH_MS)
+                {
+                    self.cue_flash = None;
+                    self.cue_stream = None;
+                }
+                if let Some((_, shown_at)) = self.take_summary
+                    && now.duration_since(shown_at) >= Duration::from_secs(TAKE_SUMMARY_SECS)
+                {
+                    self.take_summary = None;
+                }
+                if let Some((_, shown_at)) = self.level_nudge
+                    && now.duration_since(shown_at) >= Duration::from_millis(LEVEL_NUDGE_SHOW_MS)
+                {
+                    self.level_nudge = None;
+                }
+
+                if let RecordingPhase::Stopping { since } = self.recording_phase
+                    && now.duration_since(since)
+                        >= stop_flush_delay(
+                            self.recording_buffer_size,
+                            self.recording_sample_rate,
+                            self.config.stop_tail_ms,
+                        )
+                {
+                    // Dropping the stream stops the callback, so the buffer is complete
+                    self.input_stream = None;
+                    self.stop_monitor();
+                    self.input_capturing.store(false, Ordering::Relaxed);
+                    self.recording_phase = RecordingPhase::Finalizing;
+                    let naming = self.offer_take_name();
+                    if self.config.auto_save_recordings {
+                        return naming
+                            .chain(Task::perform(async {}, |_| Message::FinalizeRecording));
+                    }
+                    self.add_log("Recording stopped. Save or discard it?".into());
+                    return naming;
+                }
+
+                self.check_output_errors();
+
+                if let Some(test) = &self.latency_test
+                    && now.duration_since(test.started)
+                        >= Duration::from_secs_f32(LATENCY_LEAD_SECS + LATENCY_LISTEN_SECS)
+                {
+                    self.finish_latency_test();
+                }
+
+                if self.playback_status_rx.try_recv().is_ok() {
+                    self.stop_playback_impl();
+                    self.add_log("Playback finished.".into());
+                }
+            }
+            Message::CheckIdleOutput(now) => {
+                let idle_limit = Duration::from_secs(self.config.warm_output_idle_secs.into());
+                if self.config.warm_output_idle_secs > 0
+                    && self
+                        .output_idle_since
+                        .is_some_and(|idle| now.duration_since(idle) >= idle_limit)
+                {
+                    self.release_output_stream();
+                }
+            }
+            #[cfg(feature = "tray")]
+            Message::Tray(action) => return self.tray_action_impl(action),
+            Message::Toggle => match self.config.space_action {
+                SpaceAction::Context => {
+                    if self.recording_phase.is_recording() {
+                        self.stop_recording_impl();
+                    } else if self.playback_state == PlaybackState::Playing {
+                        self.pause_playback_impl();
+                    } else if self.playback_state == PlaybackState::Paused {
+                        self.resume_playback_impl();
+                    } else {
+                        self.start_recording_impl();
+                    }
+                }
+                SpaceAction::Record => self.toggle_recording_impl(),
+                SpaceAction::PlayPause => self.toggle_playback_impl(),
+            },
+            Message::ToggleRecording => self.toggle_recording_impl(),
+            Message::TogglePlayback => self.toggle_playback_impl(),
+            Message::SelectSpaceAction(action) => {
+                self.config.space_action = action;
+                self.save_config();
+            }
+            Message::PlayTestTone(tone) => self.play_test_tone_impl(tone),
+            Message::SelectToneWaveform(waveform) => self.tone_waveform = waveform,
+            Message::UpdateToneFrequency(value) => self.tone_frequency_input = value,
+            Message::UpdateToneLevel(value) => self.tone_level_input = value,
+            Message::SelectTimerPrecision(precision) => {
+                self.config.timer_precision = precision;
+                self.save_config();
+            }
+            Message::ToggleTimerHours(enabled) => {
+                self.config.timer_always_hours = enabled;
+                self.save_config();
+            }
+            Message::SelectMeterMode(mode) => {
+                self.config.meter_mode = mode;
+                self.save_config();
+            }
+            Message::UpdateMeterAttack(value) => {
+                if let Ok(ms) = value.trim().parse::<f32>()
+                    && ms >= 0.0
+                {
+                    self.config.meter_attack_ms = ms;
+                    self.save_config();
+                }
+                self.meter_attack_input = value;
+            }
+            Message::UpdateMeterFall(value) => {
+                if let Ok(rate) = value.trim().parse::<f32>()
+                    && rate > 0.0
+                {
+                    self.config.meter_fall_db_per_sec = rate;
+                    self.save_config();
+                }
+                self.meter_fall_input = value;
+            }
+            Message::UpdateMeterTarget(value) => {
+                if value.trim().is_empty() {
+                    self.config.meter_target_db = None;
+                    self.save_config();
+                } else if let Ok(db) = value.trim().parse::<f32>()
+                    && db <= 0.0
+                {
+                    self.config.meter_target_db = Some(db);
+                    self.save_config();
+                }
+                self.meter_target_input = value;
+            }
+            Message::UpdateMeterTolerance(value) => {
+                if let Ok(db) = value.trim().parse::<f32>()
+                    && db.is_finite()
+                    && db > 0.0
+                    && db <= MAX_METER_TOLERANCE_DB
+                {
+                    self.config.meter_target_tolerance_db = db;
+                    self.save_config();
+                }
+                self.meter_tolerance_input = value;
+            }
+            Message::DetectChapters(fname) => self.detect_chapters_impl(&fname),
+            Message::UpdateChapterMinGap(value) => {
+                if let Ok(secs) = value.trim().parse::<f32>()
+                    && secs > 0.0
+                {
+                    self.config.chapter_min_gap_secs = secs;
+                    self.save_config();
+                }
+                self.chapter_min_gap = value;
+            }
+            Message::UpdateSkipIntro(value) => {
+                if let Ok(secs) = value.trim().parse::<f32>()
+                    && secs >= 0.0
+                {
+                    self.config.skip_intro_secs = secs;
+                    self.save_config();
+                }
+                self.skip_intro_input = value;
+            }
+            Message::UpdatePreviewLength(value) => {
+                if let Ok(secs) = value.trim().parse::<f32>()
+                    && secs.is_finite()
+                    && secs > 0.0
+                    && secs <= MAX_PREVIEW_SECS
+                {
+                    self.config.preview_secs = secs;
+                    self.save_config();
+                }
+                self.preview_input = value;
+            }
+            Message::UpdateStopTail(value) => {
+                if let Ok(ms) = value.trim().parse::<u32>() {
+                    self.config.stop_tail_ms = ms;
+                    self.save_config();
+                }
+                self.stop_tail_input = value;
+            }
+            Message::UpdatePreRoll(value) => {
+                if let Ok(secs) = value.trim().parse::<f32>()
+                    && (0.0..=MAX_PRE_ROLL_SECS).contains(&secs)
+                {
+                    self.config.pre_roll_secs = secs;
+                    self.save_config();
+                }
+                self.pre_roll_input = value;
+            }
+            Message::UpdateMaxFileSize(value) => {
+                if let Ok(mb) = value.trim().parse::<f32>()
+                    && mb.is_finite()
+                    && mb >= 0.0
+                {
+                    self.config.max_file_size_mb = mb;
+                    self.save_config();
+                }
+                self.max_file_size_input = value;
+            }
+            Message::ToggleResumeLastPosition(enabled) => {
+                self.config.resume_last_position = enabled;
+                self.save_config();
+            }
+            Message::ToggleResumeOverSkip(enabled) => {
+                self.config.resume_over_skip = enabled;
+                self.save_config();
+            }
+            Message::ToggleDcBlock(enabled) => {
+                self.config.processing.dc_block_enabled = enabled;
+                self.save_config();
+            }
+            Message::NextChapter => self.next_chapter_impl(),
+            Message::PreviousChapter => self.previous_chapter_impl(),
+            Message::ExportChapters => return self.export_chapters_impl(),
+            Message::SplitAtMarkers => return self.split_at_markers_impl(),
+            Message::ExportChannels => return self.export_channels_impl(),
+            Message::AssignAbSlot(slot, fname) => self.assign_ab_slot_impl(slot, fname),
+            Message::ToggleAbLoudnessMatch(enabled) => self.ab_match_loudness = enabled,
+            Message::StartAbCompare => self.start_ab_compare_impl(),
+            Message::ToggleWaveformOverlay => self.toggle_waveform_overlay_impl(),
+            Message::SwitchAbTake => self.switch_ab_take_impl(),
+            Message::ToggleLog => self.show_log = !self.show_log,
+            Message::OpenDeviceInspector => {
+                self.device_reports = vec![
+                    DeviceReport::input(self.backend.as_ref()),
+                    DeviceReport::output(self.backend.as_ref()),
+                ];
+                self.screen = Screen::Devices;
+            }
+            Message::CloseDeviceInspector => {
+                self.device_reports.clear();
+                self.screen = Screen::Settings;
+            }
+            Message::SeekWaveform(fraction) => self.seek_waveform_impl(fraction),
+            Message::JumpToSpeech(fraction) => {
+                if let Some(start) = self.waveform.as_ref().and_then(|w| w.speech_from(fraction)) {
+                    self.seek_waveform_impl(start);
+                }
+            }
+            Message::ZoomWaveform(zoom) => {
+                self.waveform_zoom = zoom.clamp(1.0, waveform::MAX_ZOOM);
+            }
+            Message::ScrollWaveform(scroll) => self.waveform_scroll = scroll,
+            Message::SeekOverview(fraction) => {
+                // Bring the spot into the zoomed view as well
+                let len = waveform::View::new(self.waveform_zoom, 0.0).len;
+                if len < 1.0 {
+                    self.waveform_scroll = ((fraction - len / 2.0) / (1.0 - len)).clamp(0.0, 1.0);
+                }
+                self.seek_waveform_impl(fraction);
+            }
+            Message::ToggleWaveformOverview(enabled) => {
+                self.config.waveform_overview = enabled;
+                self.save_config();
+            }
+            Message::ClearLog => self.status_log.clear(),
+            Message::ToggleAudioCues(enabled) => {
+                self.config.audio_cues_enabled = enabled;
+                self.save_config();
+            }
+            Message::ToggleDarkTheme(enabled) => {
+                self.config.dark_theme = enabled;
+                self.save_config();
+            }
+            Message::ExportFile(fname) => return self.export_file_impl(&fname),
+            Message::PreviewExport(fname) => return self.preview_export_impl(&fname),
+            Message::ExportPreviewReady(fname, audio) => {
+                self.export_preview_ready_impl(&fname, audio)
+            }
+            Message::JobFinished(id, result) => self.job_finished_impl(id, result),
+            Message::CancelJob => self.cancel_job_impl(),
+            Message::SelectExportSampleRate(rate) => {
+                self.config.export.sample_rate = rate;
+                self.save_config();
+            }
+            Message::SelectExportResampleQuality(quality) => {
+                self.config.export.resample_quality = quality;
+                self.save_config();
+            }
+            Message::SelectPlaybackResampleQuality(quality) => {
+                *self.playback_resample_quality.lock().unwrap() = quality;
+                self.config.playback_resample_quality = quality;
+                self.save_config();
+            }
+            Message::UpdateMp3Title(value) => {
+                self.config.export.mp3_tags.title = value;
+                self.save_config();
+            }
+            Message::UpdateMp3Artist(value) => {
+                self.config.export.mp3_tags.artist = value;
+                self.save_config();
+            }
+            Message::UpdateMp3Album(value) => {
+                self.config.export.mp3_tags.album = value;
+                self.save_config();
+            }
+            Message::UpdateMp3CoverArt(value) => {
+                // Kept as a full path once it names a file, so opening another
+                // folder doesn't lose it
+                let path = value.trim();
+                if path.is_empty() {
+                    self.config.export.mp3_tags.cover_art.clear();
+                    self.save_config();
+                } else if let Ok(full) = fs::canonicalize(path)
+                    && full.is_file()
+                {
+                    self.config.export.mp3_tags.cover_art = full.to_string_lossy().into_owned();
+                    self.save_config();
+                }
+                self.cover_art_input = value;
+            }
+            Message::ToggleExportMono(enabled) => {
+                self.config.export.mono = enabled;
+                self.save_config();
+            }
+            Message::SelectExportPreset(preset) => {
+                if let Some(target) = preset.settings.normalize_lufs {
+                    self.export_lufs_input = target.to_string();
+                }
+                let tags = std::mem::take(&mut self.config.export.mp3_tags);
+                self.config.export = preset.settings;
+                // Presets without tags of their own keep the ones already filled in
+                if self.config.export.mp3_tags == Mp3Tags::default() {
+                    self.config.export.mp3_tags = tags;
+                }
+                self.cover_art_input = self.config.export.mp3_tags.cover_art.clone();
+                self.save_config();
+            }
+            Message::SelectExportFormat(format) => {
+                self.config.export.format = format;
+                self.save_config();
+            }
+            Message::ToggleExportNormalize(enabled) => {
+                self.config.export.normalize_lufs = if enabled {
+                    Some(self.export_lufs_input.trim().parse().unwrap_or(-16.0))
+                } else {
+                    None
+                };
+                self.save_config();
+            }
+            Message::UpdateExportLufs(value) => {
+                if let Ok(target) = value.trim().parse::<f32>()
+                    && (-70.0..0.0).contains(&target)
+                    && self.config.export.normalize_lufs.is_some()
+                {
+                    self.config.export.normalize_lufs = Some(target);
+                    self.save_config();
+                }
+                self.export_lufs_input = value;
+            }
+            Message::ExportAll => self.request_batch_impl(BatchKind::ExportAll),
+            Message::RetryExportQueue => return self.retry_export_queue_impl(),
+            Message::ClearExportQueue => {
+                if self.background_job.is_none() {
+                    self.export_queue.lock().unwrap().clear();
+                }
+            }
+            Message::UpdateExportRetries(value) => {
+                if let Ok(retries) = value.trim().parse::<u32>() {
+                    self.config.export_retries = retries;
+                    self.save_config();
+                }
+                self.export_retries_input = value;
+            }
+            Message::UpdateExportBackoff(value) => {
+                if let Ok(ms) = value.trim().parse::<u32>() {
+                    self.config.export_retry_backoff_ms = ms;
+                    self.save_config();
+                }
+                self.export_backoff_input = value;
+            }
+            Message::UpdatePresetName(name) => self.preset_name = name,
+            Message::SaveExportPreset => self.save_export_preset_impl(),
+            Message::SelectFile(fname) => return self.select_file_impl(fname),
+            Message::AnalysisFinished(fname, modified, result) => {
+                self.analysis_cache
+                    .insert(cache_key(&fname), (modified, result));
+            }
+            Message::UpdateNotes(text) => self.update_notes_impl(text),
+            Message::SetLabel(label) => self.set_label_impl(label),
+            Message::UpdateTagInput(value) => {
+                self.tag_input = value;
+            }
+            Message::AddTag => self.add_tag_impl(),
+            Message::RemoveTag(tag) => self.remove_tag_impl(&tag),
+            Message::UpdateSearch(query) => {
+                self.search_query = query;
+            }
+            Message::FindDuplicates => return self.find_duplicates_impl(),
+            Message::UpdateFadeIn(value) => {
+                self.fade_in_input = value;
+            }
+            Message::UpdateFadeOut(value) => {
+                self.fade_out_input = value;
+            }
+            Message::ApplyFades => self.apply_fades_impl(),
+            Message::RepairFile => self.repair_file_impl(),
+            Message::TrimSilence => self.trim_silence_impl(),
+            Message::DuplicateAndTrim => return self.duplicate_and_trim_impl(),
+            Message::UpdateSilenceLength(value) => {
+                self.silence_input = value;
+            }
+            Message::InsertSilence => self.insert_silence_impl(),
+            Message::UpdateNormalizeTarget(value) => {
+                if let Ok(target) = value.trim().parse::<f32>()
+                    && (-70.0..0.0).contains(&target)
+                {
+                    self.config.processing.normalize_target_lufs = target;
+                    self.save_config();
+                }
+                self.normalize_input = value;
+            }
+            Message::NormalizeFile => return self.normalize_file_impl(),
+            Message::TogglePolarityChannel(channel, inverted) => {
+                if inverted {
+                    self.polarity_skipped.remove(&channel);
+                } else {
+                    self.polarity_skipped.insert(channel);
+                }
+                if let Some(spec) = self.playback_spec {
+                    self.sync_playback_polarity(spec.channels);
+                }
+            }
+            Message::ToggleInvertPlayback(enabled) => {
+                self.invert_playback = enabled;
+                if let Some(spec) = self.playback_spec {
+                    self.sync_playback_polarity(spec.channels);
+                }
+            }
+            Message::InvertPolarity => self.invert_polarity_impl(),
+            Message::ToggleMetronome => self.toggle_metronome_impl(),
+            Message::ToggleInputMonitor(enabled) => {
+                self.config.monitor_input = enabled;
+                self.save_config();
+                if !enabled {
+                    self.stop_monitor();
+                } else if self.input_stream.is_some() && self.monitor_stream.is_none() {
+                    self.start_monitor();
+                }
+            }
+            Message::ToggleMonitorProcessed(processed) => {
+                self.config.monitor_processed = processed;
+                self.monitor_processed.store(processed, Ordering::Relaxed);
+                self.save_config();
+            }
+            Message::UpdateMetronomeBpm(value) => {
+                if let Ok(bpm) = value.trim().parse::<f32>()
+                    && (MIN_METRONOME_BPM..=MAX_METRONOME_BPM).contains(&bpm)
+                {
+                    self.set_metronome_bpm(bpm);
+                }
+                self.metronome_input = value;
+            }
+            Message::TapTempo(at) => self.tap_tempo_impl(at),
+            Message::KeyPressed(key, modifiers) => return self.key_pressed_impl(key, modifiers),
+            Message::NudgeLevel(step_db) => self.nudge_level_impl(step_db),
+            Message::DismissTakeSummary => self.take_summary = None,
+            Message::CloseRequested(id) => {
+                self.shutdown();
+                return iced::window::close(id);
+            }
+            Message::RemapShortcut(action) => self.remapping_shortcut = Some(action),
+            Message::ResetShortcuts => {
+                self.remapping_shortcut = None;
+                self.config.keymap.clear();
+                self.save_config();
+                self.add_log("Keyboard shortcuts reset to the defaults.".into());
+            }
+            Message::SelectMarkerFormat(format) => self.marker_format = format,
+            Message::ExportMarkers => self.export_markers_impl(),
+            Message::ImportMarkers => return self.import_markers_impl(),
+            Message::UpdateImageWidth(value) => self.image_width_input = value,
+            Message::UpdateImageHeight(value) => self.image_height_input = value,
+            Message::UpdateImageForeground(value) => self.config.waveform_image_foreground = value,
+            Message::UpdateImageBackground(value) => self.config.waveform_image_background = value,
+            Message::ExportWaveformImage => self.export_waveform_image_impl(),
+            Message::MonoCheck => self.mono_check_impl(),
+            Message::DropMark => self.drop_mark_impl(),
+            Message::ToggleBwfMetadata(enabled) => {
+                self.config.bwf_metadata = enabled;
+                self.save_config();
+            }
+            Message::ToggleProcessingNote(enabled) => {
+                self.config.processing_note = enabled;
+                self.save_config();
+            }
+            Message::UpdateRecordingDescription(value) => {
+                self.recording_description = value;
+            }
+            Message::SelectProject(project) => self.select_project_impl(project),
+            Message::CloseProject => {
+                self.active_project = None;
+                self.project_clip = None;
+            }
+            Message::SelectProjectClip(clip) => self.project_clip = Some(clip),
+            Message::UpdateProjectName(value) => self.project_name_input = value,
+            Message::UpdateProjectClips(value) => self.project_clips_input = value,
+            Message::CreateProject => self.create_project_impl(),
+            Message::ToggleAutoTrim(enabled) => {
+                self.config.processing.auto_trim_on_save = enabled;
+                self.save_config();
+            }
+            Message::UpdateTrimThreshold(value) => {
+                if let Ok(db) = value.trim().parse::<f32>()
+                    && (-96.0..0.0).contains(&db)
+                {
+                    self.config.processing.trim_threshold_db = db;
+                    self.save_config();
+                }
+                self.trim_threshold_input = value;
+            }
+            Message::UpdateTrimPadding(value) => {
+                if let Ok(ms) = value.trim().parse::<u32>() {
+                    self.config.processing.trim_padding_ms = ms;
+                    self.save_config();
+                }
+                self.trim_padding_input = value;
+            }
+            Message::DeleteDuplicates => self.request_batch_impl(BatchKind::DeleteDuplicates),
+            Message::SetPlaybackSpeed(speed) => self.set_playback_speed(speed),
+            Message::SavePlaybackSpeed => self.save_playback_speed(),
+            Message::ResetPlaybackSpeed => {
+                self.set_playback_speed(1.0);
+                self.save_playback_speed();
+            }
+            Message::RememberPlaybackSettings => self.remember_playback_settings_impl(),
+            Message::ClearPlaybackSettings => self.clear_playback_settings_impl(),
+            Message::SetStereoWidth(width) => {
+                *self.stereo_width.lock().unwrap() = width;
+            }
+            Message::SetChannelGain(channel, db) => {
+                if let Some(gain) = self.channel_gains.lock().unwrap().get_mut(channel) {
+                    *gain = db_to_amplitude(db);
+                }
+            }
+            Message::SaveChannelGains => self.save_channel_gains(),
+            Message::ResetProcessing => self.reset_processing_impl(),
+            Message::SelectFirstOutputChannel(channel) => {
+                self.config.output_channel_offset = channel.saturating_sub(1);
+                self.save_config();
+            }
+            Message::SelectSecondOutput(name) => {
+                self.config.second_output = name;
+                self.save_config();
+            }
+            Message::SetOutputVolume(volume) => {
+                self.output_volume
+                    .store(volume.to_bits(), Ordering::Relaxed);
+            }
+            Message::SetSecondOutputVolume(volume) => {
+                self.second_output_volume
+                    .store(volume.to_bits(), Ordering::Relaxed);
+            }
+            Message::SaveOutputVolumes => {
+                self.config.output_volume =
+                    f32::from_bits(self.output_volume.load(Ordering::Relaxed));
+                self.config.second_output_volume =
+                    f32::from_bits(self.second_output_volume.load(Ordering::Relaxed));
+                self.save_config();
+            }
+            Message::ShowHelp => {
+                if self.screen == Screen::Main {
+                    self.screen = Screen::Help;
+                }
+            }
+            Message::CloseHelp => {
+                if self.screen == Screen::Help {
+                    self.screen = Screen::Main;
+                }
+            }
+            Message::OpenSettings => {
+                if self.renaming_file.is_none() {
+                    self.enumerate_devices();
+                    self.screen = Screen::Settings;
+                }
+            }
+            Message::SelectAudioHost(choice) => self.select_audio_host_impl(choice),
+            Message::CloseSettings => {
+                self.chapter_min_gap = self.config.chapter_min_gap_secs.to_string();
+                self.skip_intro_input = self.config.skip_intro_secs.to_string();
+                self.preview_input = self.config.preview_secs.to_string();
+                self.skip_back_input = self.config.skip_back_secs.to_string();
+                self.skip_forward_input = self.config.skip_forward_secs.to_string();
+                self.meter_attack_input = self.config.meter_attack_ms.to_string();
+                self.meter_fall_input = self.config.meter_fall_db_per_sec.to_string();
+                self.meter_target_input = self
+                    .config
+                    .meter_target_db
+                    .map_or(String::new(), |db| db.to_string());
+                self.meter_tolerance_input = self.config.meter_target_tolerance_db.to_string();
+                self.stop_tail_input = self.config.stop_tail_ms.to_string();
+                self.pre_roll_input = self.config.pre_roll_secs.to_string();
+                self.max_file_size_input = self.config.max_file_size_mb.to_string();
+                self.warm_output_idle_input = self.config.warm_output_idle_secs.to_string();
+                self.start_ramp_input = self.config.output_start_ramp_ms.to_string();
+                self.auto_level_input = self.config.auto_level_target_lufs.to_string();
+                self.trim_threshold_input = self.config.processing.trim_threshold_db.to_string();
+                self.trim_padding_input = self.config.processing.trim_padding_ms.to_string();
+                self.remapping_shortcut = None;
+                self.screen = Screen::Main;
+            }
+        }
+        Task::none()
+    }
+
+    fn subscription(&self) -> Subscription<Message> {
+        let tick = if self.recording_phase.input_open()
+            || self.playback_state != PlaybackState::Stopped
+            || self.cue_flash.is_some()
+            || self.level_nudge.is_some()
+            || self.take_summary.is_some()
+            || self.clip_until.is_some()
+            || self.background_job.is_some()
+            || self.latency_test.is_some()
+            || self.metronome_stream.is_some()
+        {
+            time::every(Duration::from_millis(16)).map(Message::Tick)
+        } else {
+            Subscription::none()
+        };
+
+        // An idle warm stream is released on a coarse timer of its own
+        let idle_output =
+            if self.output_idle_since.is_some() && self.config.warm_output_idle_secs > 0 {
+                time::every(Duration::from_secs(1)).map(Message::CheckIdleOutput)
+            } else {
+                Subscription::none()
+            };
+
+        // Keys are looked up in the keymap when they arrive, so remapping takes
+        // effect without rebuilding the subscription
+        let keyboard =
+            keyboard::on_key_press(|key, modifiers| Some(Message::KeyPressed(key, modifiers)));
+
+        #[cfg(feature = "tray")]
+        let keyboard = Subscription::batch([keyboard, tray::subscription().map(Message::Tray)]);
+
+        // Closing is handled in `update`, so a take in progress is saved first
+        let close = iced::window::close_requests().map(Message::CloseRequested);
+
+        // A WAV dropped on the window is opened for editing
+        let dropped = iced::event::listen_with(|event, _status, _window| match event {
+            iced::Event::Window(iced::window::Event::FileDropped(path)) => {
+                Some(Message::OpenForEditing(path.display().to_string()))
+            }
+            _ => None,
+        });
+
+        Subscription::batch(vec![tick, idle_output, keyboard, close, dropped])
+    }
+
+    fn view(&self) -> Element<'_, Message> {
+        let content = match self.screen {
+            Screen::Main => self.view_main(),
+            Screen::Settings => self.view_settings(),
+            Screen::Help => self.view_help(),
+            Screen::Devices => self.view_devices(),
+        };
+
+        let flash = self.cue_flash.map(|(_, color)| color);
+        center(content)
+            .style(move |_theme| container::Style {
+                background: flash.map(iced::Background::Color),
+                ..container::Style::default()
+            })
+            .into()
+    }
+
+    fn view_help(&self) -> Column<'_, Message> {
+        let mut shortcuts = column![].spacing(8);
+        let keys = ShortcutAction::ALL
+            .into_iter()
+            .map(|action| {
+                (
+                    key_label(shortcut_key(&self.config.keymap, action)),
+                    action.description(),
+                )
+            })
+            .chain(std::iter::once((
+                "Double-click".to_string(),
+                "Rename a recording",
+            )));
+        for (key, action) in keys {
+            shortcuts = shortcuts
+                .push(row![text(key).width(Length::Fixed(80.0)), text(action)].spacing(16));
+        }
+
+        column![
+            text("Keyboard Shortcuts").size(30),
+            shortcuts,
+            button(text("Close")).on_press(Message::CloseHelp),
+        ]
+        .spacing(16)
+        .align_x(iced::Alignment::Center)
+    }
+
+    fn view_devices(&self) -> Column<'_, Message> {
+        let cell = |value: String, width: f32| text(value).size(14).width(Length::Fixed(width));
+        let mut reports = column![].spacing(16);
+        for report in &self.device_reports {
+            let name = match &report.name {
+                Ok(name) => name.clone(),
+                Err(e) => e.clone(),
+            };
+            let default_format = match &report.default_format {
+                Ok(f) => format!(
+                    "Default: {} ch, {} Hz, {:?}",
+                    f.channels, f.sample_rate, f.sample_format
+                ),
+                Err(e) => e.clone(),
+            };
+
+            let mut table = column![
+                row![
+                    cell("Channels".into(), 80.0),
+                    cell("Sample rates (Hz)".into(), 160.0),
+                    cell("Format".into(), 80.0),
+                    cell("Buffer".into(), 140.0),
+                ]
+                .spacing(8)
+            ]
+            .spacing(4);
+            match &report.formats {
+                Ok(formats) if formats.is_empty() => {
+                    table = table.push(text("No supported configurations reported.").size(14));
+                }
+                Ok(formats) => {
+                    for f in formats {
+                        table = table.push(
+                            row![
+                                cell(f.channels.to_string(), 80.0),
+                                cell(
+                                    format!("{}-{}", f.min_sample_rate, f.max_sample_rate),
+                                    160.0
+                                ),
+                                cell(format!("{:?}", f.sample_format), 80.0),
+                                cell(describe_buffer_size(&f.buffer_size), 140.0),
+                            ]
+                            .spacing(8),
+                        );
+                    }
+                }
+                Err(e) => table = table.push(text(e.clone()).size(14)),
+            }
+
+            reports = reports.push(
+                column![
+                    text(format!("{}: {}", report.role, name)).size(20),
+                    text(default_format).size(14),
+                    table,
+                ]
+                .spacing(6),
+            );
+        }
+
+        column![
+            text("Audio Devices").size(30),
+            scrollable(reports).height(Length::Fixed(420.0)),
+            row![
+                button(text("Refresh")).on_press(Message::OpenDeviceInspector),
+                button(text("Back")).on_press(Message::CloseDeviceInspector),
+            ]
+            .spacing(16),
+        ]
+        .spacing(16)
+        .align_x(iced::Alignment::Center)
+    }
+
+    fn view_settings(&self) -> Column<'_, Message> {
+        let presets = self.export_presets();
+        let current_preset = presets
+            .iter()
+            .find(|p| p.matches(&self.config.export))
+            .cloned();
+        let output_volume = f32::from_bits(self.output_volume.load(Ordering::Relaxed));
+        let second_volume = f32::from_bits(self.second_output_volume.load(Ordering::Relaxed));
+        let second_outputs: Vec<SecondOutput> = std::iter::once(None)
+            .chain(self.output_devices.iter().cloned().map(Some))
+            .map(SecondOutput)
+            .collect();
+
+        let mut shortcuts = column![text(
+            "Keyboard shortcuts (click one, then press its new key; Esc cancels):"
+        )]
+        .spacing(4);
+        for action in ShortcutAction::ALL {
+            let key = if self.remapping_shortcut == Some(action) {
+                "Press a key...".to_string()
+            } else {
+                key_label(shortcut_key(&self.config.keymap, action))
+            };
+            shortcuts = shortcuts.push(
+                row![
+                    button(text(key).size(14))
+                        .width(Length::Fixed(120.0))
+                        .on_press(Message::RemapShortcut(action)),
+                    text(action.description()).size(14),
+                ]
+                .spacing(8)
+                .align_y(iced::Alignment::Center),
+            );
+        }
+        shortcuts =
+            shortcuts.push(button(text("Reset shortcuts")).on_press(Message::ResetShortcuts));
+
+        column![
+            text("Settings").size(30),
+            text(&self.status_message).size(16),
+            checkbox(
+                "Play a beep when recording starts/stops (may be heard in the take)",
+                self.config.audio_cues_enabled
+            )
+            .on_toggle(Message::ToggleAudioCues),
+            checkbox("Dark theme", self.config.dark_theme).on_toggle(Message::ToggleDarkTheme),
+            row![
+                text("Timer shows:"),
+                pick_list(
+                    TimerPrecision::ALL,
+                    Some(self.config.timer_precision),
+                    Message::SelectTimerPrecision,
+                ),
+                checkbox("Always show hours", self.config.timer_always_hours)
+                    .on_toggle(Message::ToggleTimerHours),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center),
+            row![
+                text("Meter:"),
+                pick_list(
+                    MeterMode::ALL,
+                    Some(self.config.meter_mode),
+                    Message::SelectMeterMode,
+                ),
+                text("attack (ms):"),
+                text_input("0", &self.meter_attack_input)
+                    .on_input(Message::UpdateMeterAttack)
+                    .width(Length::Fixed(60.0)),
+                text("fall (dB/s):"),
+                text_input("60", &self.meter_fall_input)
+                    .on_input(Message::UpdateMeterFall)
+                    .width(Length::Fixed(60.0)),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center),
+            text("VU-style: RMS, 300 ms attack, 20 dB/s fall. PPM-style: Peak, 10 ms attack, 12 dB/s fall.")
+                .size(14),
+            row![
+                text("Target level (dB, blank for none):"),
+                text_input("-18", &self.meter_target_input)
+                    .on_input(Message::UpdateMeterTarget)
+                    .width(Length::Fixed(60.0)),
+                text("within ± (dB):"),
+                text_input("3", &self.meter_tolerance_input)
+                    .on_input(Message::UpdateMeterTolerance)
+                    .width(Length::Fixed(60.0)),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center),
+            row![
+                text("Space key:"),
+                pick_list(
+                    SpaceAction::ALL,
+                    Some(self.config.space_action),
+                    Message::SelectSpaceAction,
+                ),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center),
+            checkbox(
+                "Save recordings automatically when stopped",
+                self.config.auto_save_recordings
+            )
+            .on_toggle(Message::ToggleAutoSave),
+            row![
+                text("Keep recording after Stop (extra ms):"),
+                text_input("0", &self.stop_tail_input)
+                    .on_input(Message::UpdateStopTail)
+                    .width(Length::Fixed(60.0)),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center),
+            row![
+                text("Pre-roll: keep input from before Record while armed (s, 0 = off):"),
+                text_input("0", &self.pre_roll_input)
+                    .on_input(Message::UpdatePreRoll)
+                    .width(Length::Fixed(60.0)),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center),
+            row![
+                text("Stop recording at file size (MB, 0 = no limit):"),
+                text_input("0", &self.max_file_size_input)
+                    .on_input(Message::UpdateMaxFileSize)
+                    .width(Length::Fixed(60.0)),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center),
+            checkbox("Low latency mode", self.config.low_latency)
+                .on_toggle(Message::ToggleLowLatency),
+            row![
+                text(match self.config.round_trip_latency_ms {
+                    Some(ms) => format!("Round-trip latency: {:.1} ms", ms),
+                    None => "Round-trip latency: not measured".into(),
+                }),
+                if self.latency_test.is_none()
+                    && !self.recording_phase.is_recording()
+                    && !self.recording_phase.is_armed()
+                    && self.playback_state == PlaybackState::Stopped
+                {
+                    button(text("Measure latency")).on_press(Message::MeasureLatency)
+                } else {
+                    button(text("Measure latency"))
+                },
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center),
+            text(
+                "Smaller audio buffers reduce monitoring delay, but may cause clicks or \
+                 dropouts on a busy system. Devices that can't do it use the default buffer."
+            )
+            .size(14),
+            row![
+                text("Test tone:"),
+                pick_list(
+                    ToneWaveform::ALL,
+                    Some(self.tone_waveform),
+                    Message::SelectToneWaveform
+                ),
+                text_input("1000", &self.tone_frequency_input)
+                    .on_input(Message::UpdateToneFrequency)
+                    .width(Length::Fixed(70.0)),
+                text("Hz"),
+                text_input("-18", &self.tone_level_input)
+                    .on_input(Message::UpdateToneLevel)
+                    .width(Length::Fixed(60.0)),
+                text("dBFS"),
+                match self.tone_spec() {
+                    Some(tone) if self.recording_phase == RecordingPhase::Idle => {
+                        button(text("Play")).on_press(Message::PlayTestTone(tone))
+                    }
+                    _ => button(text("Play")),
+                },
+                if self.currently_playing == Some(PlaybackSource::TestTone) {
+                    button(text("Stop")).on_press(Message::StopPlayback)
+                } else {
+                    button(text("Stop"))
+                },
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center),
+            row![
+                text("Audio host:"),
+                pick_list(
+                    self.audio_hosts.as_slice(),
+                    self.audio_hosts
+                        .iter()
+                        .find(|host| host.name == self.config.audio_host)
+                        .cloned(),
+                    Message::SelectAudioHost,
+                ),
+                text("e.g. ASIO for low latency, JACK for routing").size(14),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center),
+            row![
+                text("Output volume:"),
+                slider(0.0..=1.0, output_volume, Message::SetOutputVolume)
+                    .step(0.01)
+                    .on_release(Message::SaveOutputVolumes)
+                    .width(Length::Fixed(120.0)),
+                text(format!("{:.0}%", output_volume * 100.0)).size(14),
+                text("Also play on:"),
+                pick_list(
+                    second_outputs,
+                    Some(SecondOutput(self.config.second_output.clone())),
+                    |choice| Message::SelectSecondOutput(choice.0),
+                ),
+                slider(0.0..=1.0, second_volume, Message::SetSecondOutputVolume)
+                    .step(0.01)
+                    .on_release(Message::SaveOutputVolumes)
+                    .width(Length::Fixed(120.0)),
+                text(format!("{:.0}%", second_volume * 100.0)).size(14),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center),
+            row![
+                text("Play from output channel:"),
+                pick_list(
+                    (1..=self
+                        .output_channel_count
+                        .max(self.config.output_channel_offset + 1))
+                        .collect::<Vec<u16>>(),
+                    Some(self.config.output_channel_offset + 1),
+                    Message::SelectFirstOutputChannel,
+                ),
+                text(format!(
+                    "of {} on the output device (later channels carry the rest of the file's)",
+                    self.output_channel_count
+                ))
+                .size(14),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center),
+            row![
+                checkbox("Auto-level playback to", self.config.auto_level)
+                    .on_toggle(Message::ToggleAutoLevel),
+                text_input("-20", &self.auto_level_input)
+                    .on_input(Message::UpdateAutoLevelTarget)
+                    .width(Length::Fixed(60.0)),
+                text("LUFS"),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center),
+            checkbox(
+                "Keep output stream open between plays",
+                self.config.keep_output_warm
+            )
+            .on_toggle(Message::ToggleKeepOutputWarm),
+            row![
+                text("Close it after idle (s, 0 = never):"),
+                text_input("60", &self.warm_output_idle_input)
+                    .on_input(Message::UpdateWarmOutputIdle)
+                    .width(Length::Fixed(60.0)),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center),
+            text(
+                "Playback starts faster and without the pop of the device waking up, \
+                 at the cost of keeping the audio device busy."
+            )
+            .size(14),
+            row![
+                text("Fade in each new output stream over (ms, 0 = off):"),
+                text_input("20", &self.start_ramp_input)
+                    .on_input(Message::UpdateStartRamp)
+                    .width(Length::Fixed(60.0)),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center),
+            row![
+                text("Playback resampling:"),
+                pick_list(
+                    ResampleQuality::ALL,
+                    Some(self.config.playback_resample_quality),
+                    Message::SelectPlaybackResampleQuality,
+                ),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center),
+            text(
+                "Used when a file's rate differs from the device's, or at another speed. \
+                 Fast (linear) is lightest on the CPU; High (windowed sinc) sounds cleaner, \
+                 especially on high frequencies. Export has its own setting, High by default."
+            )
+            .size(14),
+            checkbox(
+                "Dither playback on 8/16-bit output devices",
+                self.config.output_dither
+            )
+            .on_toggle(Message::ToggleOutputDither),
+            text(
+                "Keeps fades and quiet passages smooth instead of gritty, \
+                 for the price of a faint, steady hiss."
+            )
+            .size(14),
+            row![
+                checkbox(
+                    "List recordings in subfolders",
+                    self.config.recursive_listing
+                )
+                .on_toggle(Message::ToggleRecursiveListing),
+                text("Levels deep:"),
+                text_input("3", &self.listing_depth_input)
+                    .on_input(Message::UpdateListingDepth)
+                    .width(Length::Fixed(50.0)),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center),
+            checkbox(
+                "Live captions while recording (saved as .transcript.txt)",
+                self.config.live_captions
+            )
+            .on_toggle(Message::ToggleLiveCaptions),
+            checkbox(
+                "Name takes after their first spoken words",
+                self.config.name_by_speech
+            )
+            .on_toggle(Message::ToggleNameBySpeech),
+            row![
+                text("After saving, run:"),
+                text_input("command (gets the take's path)", &self.config.post_save_command)
+                    .on_input(Message::UpdatePostSaveCommand)
+                    .width(Length::Fixed(320.0)),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center),
+            checkbox(
+                "Preserve source format (save 16-bit input as 16-bit WAV)",
+                self.config.preserve_source_format
+            )
+            .on_toggle(Message::TogglePreserveSourceFormat),
+            checkbox(
+                "Write Broadcast Wave metadata (start time, description)",
+                self.config.bwf_metadata
+            )
+            .on_toggle(Message::ToggleBwfMetadata),
+            checkbox(
+                "Note input processing (gain, filters, trim) in the WAV",
+                self.config.processing_note
+            )
+            .on_toggle(Message::ToggleProcessingNote),
+            checkbox(
+                "Trim leading/trailing silence when saving",
+                self.config.processing.auto_trim_on_save
+            )
+            .on_toggle(Message::ToggleAutoTrim),
+            row![
+                text("Silence below (dBFS):"),
+                text_input("-34", &self.trim_threshold_input)
+                    .on_input(Message::UpdateTrimThreshold)
+                    .width(Length::Fixed(60.0)),
+                text("Keep padding (ms):"),
+                text_input("750", &self.trim_padding_input)
+                    .on_input(Message::UpdateTrimPadding)
+                    .width(Length::Fixed(60.0)),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center),
+            text("Input processing").size(20),
+            checkbox(
+                "Remove DC offset (5 Hz high-pass)",
+                self.config.processing.dc_block_enabled
+            )
+            .on_toggle(Message::ToggleDcBlock),
+            text("Input gains, trimming and the Normalize target are remembered too.").size(14),
+            button(text("Reset processing to defaults")).on_press(Message::ResetProcessing),
+            row![
+                text("Chapter detection min silence gap (s):"),
+                text_input("2.0", &self.chapter_min_gap)
+                    .on_input(Message::UpdateChapterMinGap)
+                    .width(Length::Fixed(60.0)),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center),
+            row![
+                text("Skip first (s) on playback:"),
+                text_input("0", &self.skip_intro_input)
+                    .on_input(Message::UpdateSkipIntro)
+                    .width(Length::Fixed(60.0)),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center),
+            row![
+                text("Arrow keys skip back (s):"),
+                text_input("5", &self.skip_back_input)
+                    .on_input(Message::UpdateSkipBack)
+                    .width(Length::Fixed(60.0)),
+                text("forward (s):"),
+                text_input("10", &self.skip_forward_input)
+                    .on_input(Message::UpdateSkipForward)
+                    .width(Length::Fixed(60.0)),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center),
+            row![
+                text("Preview length (s):"),
+                text_input("5", &self.preview_input)
+                    .on_input(Message::UpdatePreviewLength)
+                    .width(Length::Fixed(60.0)),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center),
+            checkbox(
+                "Resume playback where it was stopped",
+                self.config.resume_last_position
+            )
+            .on_toggle(Message::ToggleResumeLastPosition),
+            checkbox(
+                "Resume position takes precedence over skip",
+                self.config.resume_over_skip
+            )
+            .on_toggle(Message::ToggleResumeOverSkip),
+            text("Export").size(20),
+            row![
+                text("Preset:"),
+                pick_list(presets, current_preset, Message::SelectExportPreset)
+                    .placeholder("Custom"),
+                text_input("Preset name", &self.preset_name)
+                    .on_input(Message::UpdatePresetName)
+                    .on_submit(Message::SaveExportPreset)
+                    .width(Length::Fixed(140.0)),
+                button(text("Save as preset")).on_press(Message::SaveExportPreset),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center),
+            row![
+                text("Format:"),
+                pick_list(
+                    ExportFormat::ALL,
+                    Some(self.config.export.format),
+                    Message::SelectExportFormat,
+                ),
+                text("Sample rate:"),
+                pick_list(
+                    ExportSampleRate::ALL,
+                    Some(self.config.export.sample_rate),
+                    Message::SelectExportSampleRate,
+                ),
+                checkbox("Downmix to mono", self.config.export.mono)
+                    .on_toggle(Message::ToggleExportMono),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center),
+            row![
+                text("Resampling:"),
+                pick_list(
+                    ResampleQuality::ALL,
+                    Some(self.config.export.resample_quality),
+                    Message::SelectExportResampleQuality,
+                ),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center),
+            row![
+                checkbox(
+                    "Normalize loudness to (LUFS):",
+                    self.config.export.normalize_lufs.is_some()
+                )
+                .on_toggle(Message::ToggleExportNormalize),
+                text_input("-16", &self.export_lufs_input)
+                    .on_input(Message::UpdateExportLufs)
+                    .width(Length::Fixed(60.0)),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center),
+            row![
+                text("MP3 tags:"),
+                text_input("Title (the recording's name)", &self.config.export.mp3_tags.title)
+                    .on_input(Message::UpdateMp3Title)
+                    .width(Length::Fixed(200.0)),
+                text_input("Artist", &self.config.export.mp3_tags.artist)
+                    .on_input(Message::UpdateMp3Artist)
+                    .width(Length::Fixed(140.0)),
+                text_input("Album or show", &self.config.export.mp3_tags.album)
+                    .on_input(Message::UpdateMp3Album)
+                    .width(Length::Fixed(140.0)),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center),
+            row![
+                text("Cover art:"),
+                text_input(
+                    "Path to a PNG or JPEG image",
+                    &self.cover_art_input
+                )
+                .on_input(Message::UpdateMp3CoverArt)
+                .width(Length::Fixed(360.0)),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center),
+            row![
+                text("Batch export retries per file:"),
+                text_input("2", &self.export_retries_input)
+                    .on_input(Message::UpdateExportRetries)
+                    .width(Length::Fixed(60.0)),
+                text("First retry after (ms):"),
+                text_input("500", &self.export_backoff_input)
+                    .on_input(Message::UpdateExportBackoff)
+                    .width(Length::Fixed(70.0)),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center),
+            shortcuts,
+            row![
+                button(text("Inspect devices")).on_press(Message::OpenDeviceInspector),
+                button(text("Back")).on_press(Message::CloseSettings),
+            ]
+            .spacing(16),
+        ]
+        .spacing(16)
+        .align_x(iced::Alignment::Center)
+    }
+
+    fn view_main(&self) -> Column<'_, Message> {
+        let formatted = format_timer(
+            self.elapsed_time,
+            self.config.timer_precision,
+            self.config.timer_always_hours,
+        );
+
+        let timer_text = if self.recording_phase == RecordingPhase::Monitoring {
+            text("MONITOR ONLY - NOT RECORDING")
+                .size(40)
+                .color(iced::Color::from_rgb(0.2, 0.6, 1.0))
+        } else if self.recording_phase.is_armed() {
+            text("ARMED")
+                .size(40)
+                .color(iced::Color::from_rgb(1.0, 0.6, 0.0))
+        } else {
+            text(formatted).size(40)
+        };
+        let take_summary: Element<Message> = match &self.take_summary {
+            Some((summary, _)) => row![
+                text(format!("Saved {}", summary))
+                    .size(20)
+                    .color(iced::Color::from_rgb(0.2, 0.7, 0.3)),
+                button(text("Dismiss")).on_press(Message::DismissTakeSummary),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center)
+            .into(),
+            None => row![].into(),
+        };
+        // Blank rather than absent so the layout doesn't jump when it clears
+        let nudge_text = text(
+            self.level_nudge
+                .as_ref()
+                .map_or("", |(readout, _)| readout.as_str()),
+        )
+        .size(16);
+
+        // Single record button that shows current state
+        let record_button = if self.recording_phase.is_recording() {
+            button(text("Stop Recording")).on_press(Message::StopRecording)
+        } else if self.playback_state == PlaybackState::Stopped && self.recording_phase.can_start()
+        {
+            button(text("Record")).on_press(Message::StartRecording)
+        } else {
+            button(text("Record")) // Disabled when playing
+        };
+
+        let monitor_only_button = if self.recording_phase == RecordingPhase::Monitoring {
+            button(text("Stop monitoring")).on_press(Message::DisarmRecording)
+        } else if self.recording_phase != RecordingPhase::Idle
+            || self.playback_state != PlaybackState::Stopped
+        {
+            button(text("Monitor only"))
+        } else {
+            button(text("Monitor only")).on_press(Message::StartMonitorOnly)
+        };
+
+        let arm_button = if self.recording_phase == RecordingPhase::Monitoring {
+            button(text("Arm"))
+        } else if self.recording_phase.is_armed() {
+            button(text("Disarm")).on_press(Message::DisarmRecording)
+        } else if self.recording_phase != RecordingPhase::Idle
+            || self.playback_state != PlaybackState::Stopped
+        {
+            button(text("Arm"))
+        } else {
+            button(text("Arm")).on_press(Message::ArmRecording)
+        };
+
+        let mut meter_row = row![].spacing(8).align_y(iced::Alignment::Center);
+        if self.recording_phase.is_recording()
+            || self.recording_phase.is_armed()
+            || self.playback_state != PlaybackState::Stopped
+            || self.clip_until.is_some()
+        {
+            let on_target = self.meter_on_target();
+            let source = if self.recording_phase.input_open() {
+                "Input"
+            } else {
+                "Output"
+            };
+            meter_row = meter_row.push(text(source)).push(
+                canvas(LevelMeter {
+                    level: self.meter_level,
+                    target: self.config.meter_target_db.map(db_to_amplitude),
+                    on_target: on_target == Some(std::cmp::Ordering::Equal),
+                })
+                .width(Length::Fixed(300.0))
+                .height(Length::Fixed(10.0)),
+            );
+            meter_row = meter_row.push(match on_target {
+                Some(std::cmp::Ordering::Less) => text("Too quiet").size(14),
+                Some(std::cmp::Ordering::Equal) => text("In range")
+                    .size(14)
+                    .color(iced::Color::from_rgb(0.2, 0.8, 0.3)),
+                Some(std::cmp::Ordering::Greater) => text("Too loud")
+                    .size(14)
+                    .color(iced::Color::from_rgb(1.0, 0.75, 0.0)),
+                None => text(""),
+            });
+            if self.clip_until.is_some() {
+                meter_row =
+                    meter_row.push(text("CLIP").color(iced::Color::from_rgb(1.0, 0.2, 0.2)));
             }
         }
-    }
-
-    fn can_interact_with_file(&self, filename: &str) -> bool {
-        // Can't interact if recording or if this file is currently playing
-        if self.is_recording {
-            return false;
+        if self.recording_phase.is_recording() {
+            meter_row = meter_row.push(
+                canvas(LevelHistory {
+                    levels: &self.level_history,
+                    capacity: LEVEL_HISTORY_LEN,
+                })
+                .width(Length::Fixed(240.0))
+                .height(Length::Fixed(30.0)),
+            );
         }
 
-        if let Some(playing_file) = &self.currently_playing_file {
-            if playing_file == filename && self.playback_state != PlaybackState::Stopped {
-                return false;
+        let mut health_row = row![].spacing(8).align_y(iced::Alignment::Center);
+        if self.input_stream.is_some() || self.playback_state != PlaybackState::Stopped {
+            let (color, label) = if self.dropout_until.is_some() {
+                (iced::Color::from_rgb(1.0, 0.2, 0.2), "Audio dropping out")
+            } else if self.stream_load >= CALLBACK_STRAIN_LOAD {
+                (iced::Color::from_rgb(1.0, 0.75, 0.0), "Audio strained")
+            } else {
+                (iced::Color::from_rgb(0.2, 0.8, 0.3), "Audio OK")
+            };
+            health_row = health_row.push(text("\u{25CF}").color(color)).push(
+                text(format!(
+                    "{}: {:.0}% load, {} dropout{}",
+                    label,
+                    self.stream_load * 100.0,
+                    self.dropouts,
+                    if self.dropouts == 1 { "" } else { "s" }
+                ))
+                .size(14),
+            );
+            if self.dropout_until.is_some() {
+                health_row =
+                    health_row.push(text("Try a larger buffer size in Settings.").size(14));
             }
         }
 
-        // Can't interact if any file is being renamed
-        if self.renaming_file.is_some() {
-            return false;
+        // Per-channel input gain, for devices with more than one input
+        let mut gains_row = row![].spacing(8).align_y(iced::Alignment::Center);
+        let gains = self.channel_gains.lock().unwrap().clone();
+        if gains.len() > 1 {
+            gains_row = gains_row.push(text("Input gain:"));
+            for (channel, gain) in gains.iter().enumerate() {
+                let db = 20.0 * gain.log10();
+                gains_row = gains_row
+                    .push(text(format!("Ch {}", channel + 1)))
+                    .push(
+                        slider(-INPUT_GAIN_RANGE_DB..=INPUT_GAIN_RANGE_DB, db, move |db| {
+                            Message::SetChannelGain(channel, db)
+                        })
+                        .step(0.5)
+                        .on_release(Message::SaveChannelGains)
+                        .width(Length::Fixed(100.0)),
+                    )
+                    .push(text(format!("{:+.1} dB", db)).size(14));
+            }
         }
 
-        true
-    }
+        let monitor_row = row![
+            checkbox("Monitor input", self.config.monitor_input)
+                .on_toggle(Message::ToggleInputMonitor),
+            checkbox(
+                "Hear it processed (gain, DC block), as it will be recorded",
+                self.config.monitor_processed
+            )
+            .on_toggle_maybe(
+                self.config
+                    .monitor_input
+                    .then_some(Message::ToggleMonitorProcessed)
+            ),
+        ]
+        .spacing(16)
+        .align_y(iced::Alignment::Center);
 
-    fn update(&mut self, message: Message) -> Task<Message> {
-        match message {
-            Message::StartRecording => self.start_recording_impl(),
-            Message::StopRecording => self.stop_recording_impl(),
-            Message::PlayFile(fname) => self.play_file_impl(&fname),
-            Message::PausePlayback => self.pause_playback_impl(),
-            Message::ResumePlayback => self.resume_playback_impl(),
-            Message::StopPlayback => self.stop_playback_impl(),
-            Message::DeleteFile(fname) => self.delete_file_impl(&fname),
-            Message::StartRename(filename) => self.start_rename_impl(&filename),
-            Message::UpdateRenameName(name) => {
-                self.new_name = name;
-            }
-            Message::ConfirmRename => self.confirm_rename_impl(),
-            Message::CancelRename => self.cancel_rename_impl(),
-            Message::FinalizeRecording => self.finalize_recording(),
-            Message::Tick(now) => {
-                if let Some(start) = self.start_time {
-                    self.elapsed_time = now - start;
-                }
+        let metronome_row = row![
+            button(text(if self.metronome_stream.is_some() {
+                "Metronome off"
+            } else {
+                "Metronome"
+            }))
+            .on_press(Message::ToggleMetronome),
+            text_input("120", &self.metronome_input)
+                .on_input(Message::UpdateMetronomeBpm)
+                .width(Length::Fixed(60.0)),
+            text("BPM"),
+            button(text("Tap")).on_press_with(|| Message::TapTempo(Instant::now())),
+            text(match self.tempo_taps.len() {
+                0 => String::new(),
+                1 => "Keep tapping...".to_string(),
+                taps => format!("{} taps: {} BPM", taps, self.config.metronome_bpm),
+            })
+            .size(14),
+        ]
+        .spacing(8)
+        .align_y(iced::Alignment::Center);
 
-                if let Some(stop_time) = self.stopping_time {
-                    if now.duration_since(stop_time) >= Duration::from_millis(200) {
-                        return Task::perform(async {}, |_| Message::FinalizeRecording);
-                    }
-                }
+        let mut pending_row = row![].spacing(8).align_y(iced::Alignment::Center);
+        if self.recording_phase == RecordingPhase::Finalizing {
+            pending_row = pending_row.push(text("Unsaved take:"));
+            if let Some(name) = &self.take_name {
+                pending_row = pending_row.push(
+                    text_input("Filename", name)
+                        .on_input(Message::UpdateTakeName)
+                        .on_submit(Message::FinalizeRecording)
+                        .width(Length::Fixed(240.0)),
+                );
+            }
+            pending_row = pending_row
+                .push(button(text("Save")).on_press(Message::FinalizeRecording))
+                .push(button(text("Discard")).on_press(Message::DiscardRecording));
+        }
+        if let Some(take) = &self.interrupted_take {
+            let (note, recover) = match take {
+                InterruptedTake::Readable(secs) => (
+                    format!("A recording was interrupted ({:.1}s captured):", secs),
+                    "Recover",
+                ),
+                InterruptedTake::Unreadable(e) => (
+                    format!("An interrupted recording couldn't be read ({}):", e),
+                    "Try again",
+                ),
+            };
+            pending_row = pending_row
+                .push(text(note))
+                .push(button(text(recover)).on_press(Message::RecoverInterruptedTake))
+                .push(button(text("Discard")).on_press(Message::DiscardInterruptedTake));
+        }
 
-                if self.playback_status_rx.try_recv().is_ok() {
-                    self.stop_playback_impl();
-                    self.status_message = "Playback finished.".into();
-                }
+        let mut waveform_panel = column![].spacing(4).align_x(iced::Alignment::Center);
+        if let Some(data) = &self.waveform {
+            let playhead = (self.playing_file() == Some(&data.file)
+                && self.playback_state != PlaybackState::Stopped)
+                .then(|| {
+                    let position = *self.playback_position.lock().unwrap();
+                    self.playback_to_waveform(position) as f32 / data.total_samples.max(1) as f32
+                });
+            let markers: Vec<f32> = if self.marker_file.as_ref() == Some(&data.file) {
+                self.markers
+                    .iter()
+                    .map(|&m| m as f32 / data.total_samples.max(1) as f32)
+                    .collect()
+            } else {
+                Vec::new()
+            };
+            let overlay = self
+                .waveform_overlay
+                .as_ref()
+                .filter(|o| o.base == data.file);
+            let zoom = self.waveform_zoom;
+            let view = waveform::View::new(zoom, self.waveform_scroll);
+            if self.config.waveform_overview {
+                waveform_panel = waveform_panel.push(
+                    canvas(Waveform {
+                        data,
+                        view: waveform::View::new(1.0, 0.0),
+                        playhead,
+                        markers: markers.clone(),
+                        overlay: None,
+                        highlight: (zoom > 1.0).then_some(view),
+                        on_seek: Message::SeekOverview,
+                    })
+                    .width(Length::Fixed(600.0))
+                    .height(Length::Fixed(28.0)),
+                );
             }
-            Message::Toggle => {
-                if self.is_recording {
-                    self.stop_recording_impl();
-                } else if self.playback_state == PlaybackState::Playing {
-                    self.pause_playback_impl();
-                } else if self.playback_state == PlaybackState::Paused {
-                    self.resume_playback_impl();
-                } else {
-                    self.start_recording_impl();
-                }
+            let mut controls = row![
+                text(&data.file).size(14),
+                button(text("-")).on_press(Message::ZoomWaveform(zoom / 2.0)),
+                text(format!("{}x", zoom)).size(14),
+                button(text("+")).on_press(Message::ZoomWaveform(zoom * 2.0)),
+                checkbox("Overview", self.config.waveform_overview)
+                    .on_toggle(Message::ToggleWaveformOverview),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center);
+            if zoom > 1.0 {
+                controls = controls.push(
+                    slider(0.0..=1.0, self.waveform_scroll, Message::ScrollWaveform)
+                        .step(0.001)
+                        .width(Length::Fixed(200.0)),
+                );
+            }
+            waveform_panel = waveform_panel.push(controls).push(
+                canvas(Waveform {
+                    data,
+                    view,
+                    playhead,
+                    markers,
+                    overlay: overlay.map(|o| &o.data),
+                    highlight: None,
+                    on_seek: Message::SeekWaveform,
+                })
+                .width(Length::Fixed(600.0))
+                .height(Length::Fixed(80.0)),
+            );
+            let speech: f32 = data.speech.iter().map(|s| s.end - s.start).sum();
+            waveform_panel = waveform_panel
+                .push(
+                    canvas(SpeechTimeline {
+                        data,
+                        playhead,
+                        on_seek: Message::JumpToSpeech,
+                    })
+                    .width(Length::Fixed(600.0))
+                    .height(Length::Fixed(8.0)),
+                )
+                .push(
+                    text(format!(
+                        "Speech: {} segment{}, {:.0}% of the file (click the strip to jump to speech)",
+                        data.speech.len(),
+                        if data.speech.len() == 1 { "" } else { "s" },
+                        speech * 100.0
+                    ))
+                    .size(12),
+                );
+            if let Some(overlay) = overlay {
+                waveform_panel = waveform_panel.push(
+                    text(format!(
+                        "Overlay: {}  {}",
+                        overlay.data.file,
+                        overlay.readout()
+                    ))
+                    .size(14),
+                );
             }
-            Message::Reset => {}
         }
-        Task::none()
-    }
-
-    fn subscription(&self) -> Subscription<Message> {
-        let tick = if self.is_recording
-            || self.playback_state != PlaybackState::Stopped
-            || self.stopping_time.is_some()
-        {
-            time::every(Duration::from_millis(16)).map(Message::Tick)
-        } else {
-            Subscription::none()
-        };
-
-        let keyboard = keyboard::on_key_press(|key, _modifiers| match key {
-            Key::Named(keyboard::key::Named::Space) => Some(Message::Toggle),
-            Key::Character(ref c) if c == "p" => Some(Message::StopPlayback),
-            _ => None,
-        });
-
-        Subscription::batch(vec![tick, keyboard])
-    }
-
-    fn view(&self) -> Element<'_, Message> {
-        let secs = self.elapsed_time.as_secs();
-        let cs = (self.elapsed_time.subsec_millis() / 10) as u64;
-        let formatted = format!("{:02}:{:02}.{:02}", secs / 60, secs % 60, cs);
 
-        let timer_text = text(formatted).size(40);
+        let mut log_panel = column![].spacing(4);
+        if self.show_log {
+            let mut entries = column![].spacing(2);
+            for (at, message) in &self.status_log {
+                let secs = at.as_secs();
+                entries = entries.push(
+                    text(format!(
+                        "[{:02}:{:02}:{:02}] {}",
+                        secs / 3600,
+                        secs / 60 % 60,
+                        secs % 60,
+                        message
+                    ))
+                    .size(13),
+                );
+            }
+            log_panel = log_panel
+                .push(
+                    row![
+                        text("Recent activity (time since launch)").size(14),
+                        button(text("Clear")).on_press(Message::ClearLog),
+                    ]
+                    .spacing(8)
+                    .align_y(iced::Alignment::Center),
+                )
+                .push(
+                    scrollable(entries)
+                        .anchor_bottom()
+                        .height(Length::Fixed(120.0))
+                        .width(Length::Fixed(500.0)),
+                );
+        }
 
-        // Single record button that shows current state
-        let record_button = if self.is_recording {
-            button(text("Stop Recording")).on_press(Message::StopRecording)
-        } else if self.playback_state == PlaybackState::Stopped && self.stopping_time.is_none() {
-            button(text("Record")).on_press(Message::StartRecording)
+        let slot_name = |slot: usize| self.ab_slots[slot].as_deref().unwrap_or("-");
+        let mut compare_row = row![text(format!(
+            "Compare  A: {}  B: {}",
+            slot_name(0),
+            slot_name(1)
+        ))]
+        .spacing(8)
+        .align_y(iced::Alignment::Center);
+        compare_row = if self.ab_inactive_samples.is_some() {
+            compare_row.push(
+                button(text(if self.ab_active == 0 {
+                    "Switch to B"
+                } else {
+                    "Switch to A"
+                }))
+                .on_press(Message::SwitchAbTake),
+            )
         } else {
-            button(text("Record")) // Disabled when playing
+            compare_row
+                .push(
+                    checkbox("Match loudness", self.ab_match_loudness)
+                        .on_toggle(Message::ToggleAbLoudnessMatch),
+                )
+                .push(
+                    if self.ab_slots.iter().all(Option::is_some)
+                        && self.playback_state == PlaybackState::Stopped
+                        && !self.recording_phase.is_recording()
+                        && !self.recording_phase.is_armed()
+                    {
+                        button(text("Compare")).on_press(Message::StartAbCompare)
+                    } else {
+                        button(text("Compare"))
+                    },
+                )
+                .push(if self.ab_slots.iter().all(Option::is_some) {
+                    button(text(if self.waveform_overlay.is_some() {
+                        "Hide overlay"
+                    } else {
+                        "Overlay"
+                    }))
+                    .on_press(Message::ToggleWaveformOverlay)
+                } else {
+                    button(text("Overlay"))
+                })
         };
 
         let files_content = if self.files.is_empty() {
             column![text("No recordings found.")]
         } else {
             let mut files_col = column![];
-            for file_name in &self.files {
-                let is_currently_playing = self.currently_playing_file.as_ref() == Some(file_name)
+            let mut current_folder = None;
+            let mut current_day = None;
+            let now = SystemTime::now();
+            for file_name in self.files.iter().filter(|f| self.file_matches_search(f)) {
+                // Files come sorted by date or by folder, so a heading starts each group
+                let folder = file_folder(file_name);
+                if self.config.group_by_date {
+                    let day = self
+                        .file_modified
+                        .get(file_name)
+                        .map_or("Unknown date".to_string(), |&modified| {
+                            date_heading(modified, now)
+                        });
+                    if current_day.as_ref() != Some(&day) {
+                        files_col = files_col.push(text(day.clone()).size(14));
+                        current_day = Some(day);
+                    }
+                } else if self.config.recursive_listing && current_folder != Some(folder) {
+                    files_col = files_col.push(
+                        text(if folder.is_empty() {
+                            "(top level)".to_string()
+                        } else {
+                            format!("{}/", folder)
+                        })
+                        .size(14),
+                    );
+                    current_folder = Some(folder);
+                }
+                let is_currently_playing = self.playing_file() == Some(file_name)
                     && self.playback_state != PlaybackState::Stopped;
                 let can_interact = self.can_interact_with_file(file_name);
                 let is_being_renamed = self.renaming_file.as_ref() == Some(file_name);
@@ -901,12 +11473,18 @@ impl VoiceRecorder {
                             }
                             _ => button(text("Play")),
                         }
-                    } else if can_interact {
+                    } else if can_interact && !self.recording_phase.is_armed() {
                         button(text("Play")).on_press(Message::PlayFile(file_name.clone()))
                     } else {
                         button(text("Play")) // Disabled
                     };
 
+                    let preview_button = if can_interact && !self.recording_phase.is_armed() {
+                        button(text("Preview")).on_press(Message::PreviewFile(file_name.clone()))
+                    } else {
+                        button(text("Preview")) // Disabled
+                    };
+
                     let stop_button = if is_currently_playing {
                         button(text("Stop")).on_press(Message::StopPlayback)
                     } else {
@@ -919,23 +11497,69 @@ impl VoiceRecorder {
                         button(text("Rename")) // Disabled
                     };
 
+                    let chapters_button = if can_interact {
+                        button(text("Chapters"))
+                            .on_press(Message::DetectChapters(file_name.clone()))
+                    } else {
+                        button(text("Chapters")) // Disabled
+                    };
+
+                    let export_button = if can_interact && self.background_job.is_none() {
+                        button(text("Export")).on_press(Message::ExportFile(file_name.clone()))
+                    } else {
+                        button(text("Export")) // Disabled
+                    };
+
+                    let preview_export_button = if can_interact && !self.recording_phase.is_armed()
+                    {
+                        button(text("Preview export"))
+                            .on_press(Message::PreviewExport(file_name.clone()))
+                    } else {
+                        button(text("Preview export")) // Disabled
+                    };
+
                     let delete_button = if can_interact {
                         button(text("Delete")).on_press(Message::DeleteFile(file_name.clone()))
                     } else {
                         button(text("Delete")) // Disabled
                     };
 
-                    let file_display = if is_currently_playing {
-                        text(format!("[PLAYING] {}", file_name)).width(Length::Fill)
-                    } else {
-                        text(file_name).width(Length::Fill)
+                    let is_selected = self.selected_file.as_ref() == Some(file_name);
+                    let mut label = match (is_currently_playing, is_selected) {
+                        (true, _) => format!("[PLAYING] {}", file_name),
+                        (false, true) => format!("> {}", file_name),
+                        (false, false) => file_name.clone(),
                     };
+                    if self.duplicates.contains(file_name) {
+                        label = format!("[DUP] {}", label);
+                    }
+                    if let Some(slot) = self
+                        .ab_slots
+                        .iter()
+                        .position(|s| s.as_ref() == Some(file_name))
+                    {
+                        label = format!("[{}] {}", if slot == 0 { "A" } else { "B" }, label);
+                    }
+                    let file_display = button(text(label))
+                        .style(button::text)
+                        .on_press(Message::SelectFile(file_name.clone()))
+                        .width(Length::Fill);
+                    let color_label = self.notes.get(file_name).and_then(|n| n.label);
+                    let label_dot = text("\u{25CF}")
+                        .color(color_label.map_or(iced::Color::TRANSPARENT, ColorLabel::color));
 
                     row![
+                        label_dot,
                         file_display,
                         primary_button,
+                        preview_button,
                         stop_button,
                         edit_button,
+                        chapters_button,
+                        export_button,
+                        preview_export_button,
+                        button(text("A")).on_press(Message::AssignAbSlot(0, file_name.clone())),
+                        button(text("B")).on_press(Message::AssignAbSlot(1, file_name.clone())),
                         delete_button,
                     ]
                     .spacing(8)
@@ -947,28 +11571,738 @@ impl VoiceRecorder {
 
         let files_scroll = scrollable(files_content).height(Length::Fixed(220.0));
 
-        let content = column![
+        let busy = self.recording_phase.is_recording()
+            || self.recording_phase.is_armed()
+            || self.playback_state != PlaybackState::Stopped;
+        let folder_row = row![
+            text("Folder:"),
+            text_input("Recordings folder", &self.folder_input)
+                .on_input(Message::UpdateFolder)
+                .on_submit(Message::OpenFolder(self.folder_input.clone()))
+                .width(Length::Fixed(320.0)),
+            if busy {
+                button(text("Open"))
+            } else {
+                button(text("Open")).on_press(Message::OpenFolder(self.folder_input.clone()))
+            },
+            pick_list(
+                self.config.recent_folders.as_slice(),
+                None::<String>,
+                Message::OpenFolder,
+            )
+            .placeholder("Recent folders"),
+        ]
+        .spacing(8)
+        .align_y(iced::Alignment::Center);
+
+        // Playback is stopped to open a file, so only recording holds it back
+        let can_open = !self.recording_phase.is_recording()
+            && !self.recording_phase.is_armed()
+            && !self.open_file_input.trim().is_empty();
+        let open_row = row![
+            text_input(
+                "WAV file to open for editing (or drop one on the window)",
+                &self.open_file_input,
+            )
+            .on_input(Message::UpdateOpenFile)
+            .on_submit(Message::OpenForEditing(self.open_file_input.clone()))
+            .width(Length::Fixed(320.0)),
+            button(text("Open for editing")).on_press_maybe(
+                can_open.then(|| Message::OpenForEditing(self.open_file_input.clone()))
+            ),
+        ]
+        .spacing(8)
+        .align_y(iced::Alignment::Center);
+
+        let search_input = text_input(
+            "Search names, notes, tags, label:red...",
+            &self.search_query,
+        )
+        .on_input(Message::UpdateSearch)
+        .width(Length::Fixed(320.0));
+        let search_row = row![
+            search_input,
+            checkbox("Group by date", self.config.group_by_date)
+                .on_toggle(Message::ToggleGroupByDate),
+        ]
+        .spacing(16)
+        .align_y(iced::Alignment::Center);
+
+        let mut batch_rename_panel = column![].spacing(4);
+        if self.batch_rename_open {
+            let plan = self.batch_rename_plan();
+            let can_apply = plan
+                .iter()
+                .any(|(from, target)| target.as_ref().is_ok_and(|to| to != from))
+                && !self.recording_phase.is_recording()
+                && self.playback_state == PlaybackState::Stopped
+                && self.background_job.is_none();
+            batch_rename_panel = batch_rename_panel.push(
+                row![
+                    text(format!("Rename the {} files listed to:", plan.len())),
+                    text_input("recording_{n}", &self.batch_rename_template)
+                        .on_input(Message::UpdateBatchRenameTemplate)
+                        .width(Length::Fixed(200.0)),
+                    button(text("Rename"))
+                        .on_press_maybe(can_apply.then_some(Message::ApplyBatchRename)),
+                    button(text("Cancel")).on_press(Message::ToggleBatchRename),
+                ]
+                .spacing(8)
+                .align_y(iced::Alignment::Center),
+            );
+            batch_rename_panel = batch_rename_panel.push(
+                text("{n} is the file's number in the list; {n:3} pads it to 3 digits.").size(14),
+            );
+            const PREVIEW_LEN: usize = 20;
+            for (from, target) in plan.iter().take(PREVIEW_LEN) {
+                batch_rename_panel = batch_rename_panel.push(match target {
+                    Ok(to) => text(format!("{} -> {}", from, to)).size(14),
+                    Err(e) => text(format!("{} -> {}", from, e))
+                        .size(14)
+                        .color(iced::Color::from_rgb(1.0, 0.3, 0.3)),
+                });
+            }
+            if plan.len() > PREVIEW_LEN {
+                batch_rename_panel = batch_rename_panel
+                    .push(text(format!("...and {} more", plan.len() - PREVIEW_LEN)).size(14));
+            }
+        }
+
+        let mut confirm_row = row![].spacing(8).align_y(iced::Alignment::Center);
+        if let Some(summary) = self.pending_batch.and_then(|kind| self.batch_summary(kind)) {
+            confirm_row = confirm_row
+                .push(text(summary))
+                .push(button(text("Confirm")).on_press(Message::ConfirmBatch))
+                .push(button(text("Cancel")).on_press(Message::CancelBatch));
+        }
+        let undo_button: Element<Message> = match self.undo_history.last() {
+            Some(entry) => button(text(format!("Undo {}", entry.label)))
+                .on_press_maybe(
+                    (!self.recording_phase.is_recording()
+                        && self.playback_state == PlaybackState::Stopped
+                        && self.background_job.is_none())
+                    .then_some(Message::UndoBatch),
+                )
+                .into(),
+            None => row![].into(),
+        };
+
+        let mut notes_panel = column![].spacing(8);
+        if let Some(selected) = &self.selected_file {
+            let file_notes = self.notes.get(selected).cloned().unwrap_or_default();
+
+            let mut tags_row = row![text("Tags:")]
+                .spacing(6)
+                .align_y(iced::Alignment::Center);
+            for tag in file_notes.tags {
+                tags_row = tags_row.push(
+                    button(text(format!("{} x", tag))).on_press(Message::RemoveTag(tag.clone())),
+                );
+            }
+            tags_row = tags_row
+                .push(
+                    text_input("New tag", &self.tag_input)
+                        .on_input(Message::UpdateTagInput)
+                        .on_submit(Message::AddTag)
+                        .width(Length::Fixed(120.0)),
+                )
+                .push(button(text("Add tag")).on_press(Message::AddTag));
+
+            let mut label_row = row![text("Label:")]
+                .spacing(6)
+                .align_y(iced::Alignment::Center);
+            for color in ColorLabel::ALL {
+                let name = if file_notes.label == Some(color) {
+                    format!("[{}]", color)
+                } else {
+                    color.to_string()
+                };
+                label_row = label_row.push(
+                    button(text(name).color(color.color()))
+                        .style(button::text)
+                        .on_press(Message::SetLabel(Some(color))),
+                );
+            }
+            label_row = label_row.push(button(text("None")).on_press(Message::SetLabel(None)));
+
+            if let Some(bext) = &self.selected_bext {
+                let description = if bext.description.is_empty() {
+                    String::new()
+                } else {
+                    format!(" - {}", bext.description)
+                };
+                notes_panel = notes_panel.push(text(format!(
+                    "Recorded {} {} UTC{}",
+                    bext.origination_date, bext.origination_time, description
+                )));
+            }
+            if let Some(comment) = &self.selected_comment {
+                notes_panel = notes_panel.push(text(comment).size(14));
+            }
+            match self.analysis_cache.get(&cache_key(selected)) {
+                Some((_, Ok(analysis))) => {
+                    notes_panel = notes_panel.push(
+                        text(format!(
+                            "{} - {}",
+                            format_timer(
+                                analysis.duration,
+                                self.config.timer_precision,
+                                self.config.timer_always_hours
+                            ),
+                            analysis.readout()
+                        ))
+                        .size(14),
+                    );
+                }
+                Some((_, Err(e))) => {
+                    notes_panel =
+                        notes_panel.push(text(format!("Couldn't analyse: {}", e)).size(14));
+                }
+                None => {
+                    notes_panel = notes_panel.push(text("Analysing...").size(14));
+                }
+            }
+            let mut polarity_row = row![text("Polarity:")]
+                .spacing(8)
+                .align_y(iced::Alignment::Center);
+            let channels = self
+                .waveform
+                .as_ref()
+                .filter(|w| w.file == *selected)
+                .map_or(1, |w| w.channels);
+            if channels > 1 {
+                for c in 0..channels as usize {
+                    polarity_row = polarity_row.push(
+                        checkbox(format!("Ch {}", c + 1), !self.polarity_skipped.contains(&c))
+                            .on_toggle(move |on| Message::TogglePolarityChannel(c, on)),
+                    );
+                }
+            }
+            polarity_row = polarity_row
+                .push(
+                    checkbox("Invert on playback", self.invert_playback)
+                        .on_toggle(Message::ToggleInvertPlayback),
+                )
+                .push(if self.can_interact_with_file(selected) {
+                    button(text("Invert file")).on_press(Message::InvertPolarity)
+                } else {
+                    button(text("Invert file"))
+                });
+            notes_panel = notes_panel
+                .push(text(format!("Notes for {}", selected)).size(18))
+                .push(
+                    text_input("Add notes...", &file_notes.notes)
+                        .on_input(Message::UpdateNotes)
+                        .width(Length::Fixed(480.0)),
+                )
+                .push(tags_row)
+                .push(label_row)
+                .push(
+                    row![
+                        text("Fade in (ms):"),
+                        text_input("500", &self.fade_in_input)
+                            .on_input(Message::UpdateFadeIn)
+                            .width(Length::Fixed(70.0)),
+                        text("Fade out (ms):"),
+                        text_input("1000", &self.fade_out_input)
+                            .on_input(Message::UpdateFadeOut)
+                            .width(Length::Fixed(70.0)),
+                        if self.can_interact_with_file(selected) {
+                            button(text("Apply fades")).on_press(Message::ApplyFades)
+                        } else {
+                            button(text("Apply fades"))
+                        },
+                        if self.can_interact_with_file(selected) {
+                            button(text("Trim silence")).on_press(Message::TrimSilence)
+                        } else {
+                            button(text("Trim silence"))
+                        },
+                        if self.can_interact_with_file(selected) {
+                            button(text("Trim a copy")).on_press(Message::DuplicateAndTrim)
+                        } else {
+                            button(text("Trim a copy"))
+                        },
+                    ]
+                    .spacing(8)
+                    .align_y(iced::Alignment::Center),
+                )
+                .push(
+                    row![
+                        text("Silence (s):"),
+                        text_input("1.0", &self.silence_input)
+                            .on_input(Message::UpdateSilenceLength)
+                            .width(Length::Fixed(70.0)),
+                        if !self.recording_phase.is_recording()
+                            && self.playback_state != PlaybackState::Stopped
+                            && self.playing_file() == Some(selected)
+                            && !self.file_in_job(selected)
+                        {
+                            button(text("Insert at cursor")).on_press(Message::InsertSilence)
+                        } else {
+                            button(text("Insert at cursor"))
+                        },
+                    ]
+                    .spacing(8)
+                    .align_y(iced::Alignment::Center),
+                )
+                .push(
+                    row![
+                        text("Loudness (LUFS):"),
+                        text_input("-16", &self.normalize_input)
+                            .on_input(Message::UpdateNormalizeTarget)
+                            .width(Length::Fixed(70.0)),
+                        if self.can_interact_with_file(selected) && self.background_job.is_none() {
+                            button(text("Normalize")).on_press(Message::NormalizeFile)
+                        } else {
+                            button(text("Normalize"))
+                        },
+                    ]
+                    .spacing(8)
+                    .align_y(iced::Alignment::Center),
+                )
+                .push(polarity_row)
+                .push(
+                    row![
+                        text("Markers:"),
+                        pick_list(
+                            MarkerFormat::ALL,
+                            Some(self.marker_format),
+                            Message::SelectMarkerFormat
+                        ),
+                        if self.marker_file.as_ref() == Some(selected) && !self.markers.is_empty() {
+                            button(text("Export")).on_press(Message::ExportMarkers)
+                        } else {
+                            button(text("Export"))
+                        },
+                        button(text("Import")).on_press(Message::ImportMarkers),
+                    ]
+                    .spacing(8)
+                    .align_y(iced::Alignment::Center),
+                )
+                .push(
+                    row![
+                        text("Waveform image:"),
+                        text_input("1200", &self.image_width_input)
+                            .on_input(Message::UpdateImageWidth)
+                            .width(Length::Fixed(60.0)),
+                        text("x"),
+                        text_input("200", &self.image_height_input)
+                            .on_input(Message::UpdateImageHeight)
+                            .width(Length::Fixed(60.0)),
+                        text_input("#3070d0", &self.config.waveform_image_foreground)
+                            .on_input(Message::UpdateImageForeground)
+                            .width(Length::Fixed(80.0)),
+                        text("on"),
+                        text_input("#ffffff", &self.config.waveform_image_background)
+                            .on_input(Message::UpdateImageBackground)
+                            .width(Length::Fixed(80.0)),
+                        button(text("Export waveform image"))
+                            .on_press(Message::ExportWaveformImage),
+                    ]
+                    .spacing(8)
+                    .align_y(iced::Alignment::Center),
+                );
+
+            let multichannel = self
+                .waveform
+                .as_ref()
+                .is_some_and(|w| &w.file == selected && w.channels > 1);
+            let mut mono_row = row![
+                button(text("Export channels separately")).on_press_maybe(
+                    (multichannel
+                        && self.background_job.is_none()
+                        && self.can_interact_with_file(selected))
+                    .then_some(Message::ExportChannels)
+                ),
+                button(text("Mono check")).on_press(Message::MonoCheck),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center);
+            if let Some((file, check)) = &self.mono_check
+                && file == selected
+            {
+                let difference = match check.difference_db {
+                    Some(db) => format!("{:+.1} dB", db),
+                    None => "cancels out".into(),
+                };
+                let summary = text(format!(
+                    "Mono fold-down: {}, L/R correlation {:.2}",
+                    difference, check.correlation
+                ));
+                mono_row = mono_row.push(if check.cancels() {
+                    summary.color(iced::Color::from_rgb(1.0, 0.4, 0.2))
+                } else {
+                    summary
+                });
+                if check.cancels() {
+                    mono_row = mono_row.push(
+                        text("Phase cancellation - check polarity and stereo effects")
+                            .color(iced::Color::from_rgb(1.0, 0.4, 0.2)),
+                    );
+                }
+            }
+            notes_panel = notes_panel.push(mono_row);
+
+            if self.damaged_files.contains(selected) {
+                notes_panel = notes_panel.push(
+                    row![
+                        text("This file is damaged; only part of it could be read.")
+                            .color(iced::Color::from_rgb(1.0, 0.4, 0.2)),
+                        if self.can_interact_with_file(selected) {
+                            button(text("Repair")).on_press(Message::RepairFile)
+                        } else {
+                            button(text("Repair"))
+                        },
+                    ]
+                    .spacing(8)
+                    .align_y(iced::Alignment::Center),
+                );
+            }
+        }
+
+        let chapter_nav_enabled = self.is_marker_file_playing();
+        let mut chapters_row = row![].spacing(8).align_y(iced::Alignment::Center);
+
+        if let Some(marker_file) = &self.marker_file {
+            chapters_row = chapters_row
+                .push(text(format!(
+                    "{}: {} chapters",
+                    marker_file,
+                    self.markers.len() + 1
+                )))
+                .push(if chapter_nav_enabled {
+                    button(text("Prev")).on_press(Message::PreviousChapter)
+                } else {
+                    button(text("Prev")) // Disabled unless this file is playing
+                })
+                .push(if chapter_nav_enabled {
+                    button(text("Next")).on_press(Message::NextChapter)
+                } else {
+                    button(text("Next")) // Disabled unless this file is playing
+                })
+                .push(
+                    if self.recording_phase.is_recording() || self.background_job.is_some() {
+                        button(text("Export chapters"))
+                    } else {
+                        button(text("Export chapters")).on_press(Message::ExportChapters)
+                    },
+                )
+                .push(
+                    if self.recording_phase.is_recording()
+                        || self.background_job.is_some()
+                        || self.markers.is_empty()
+                    {
+                        button(text("Split at markers"))
+                    } else {
+                        button(text("Split at markers")).on_press(Message::SplitAtMarkers)
+                    },
+                );
+        }
+
+        let stereo_width = *self.stereo_width.lock().unwrap();
+        let speed = self.playback_speed();
+        let width_row = row![
+            text("Stereo width:"),
+            slider(0.0..=2.0, stereo_width, Message::SetStereoWidth)
+                .step(0.05)
+                .width(Length::Fixed(200.0)),
+            text(format!("{:.2} (stereo files only)", stereo_width)),
+            text("Speed:"),
+            slider(
+                MIN_PLAYBACK_SPEED..=MAX_PLAYBACK_SPEED,
+                speed,
+                Message::SetPlaybackSpeed
+            )
+            .step(0.05)
+            .on_release(Message::SavePlaybackSpeed)
+            .width(Length::Fixed(150.0)),
+            text(format!("{:.2}x", speed)),
+            if speed != 1.0 {
+                button(text("1x")).on_press(Message::ResetPlaybackSpeed)
+            } else {
+                button(text("1x"))
+            },
+            button(text("Remember for this file")).on_press_maybe(
+                self.playback_settings_file()
+                    .map(|_| Message::RememberPlaybackSettings)
+            ),
+            button(text("Clear saved settings")).on_press_maybe(
+                self.playback_settings_file()
+                    .map(|_| Message::ClearPlaybackSettings)
+            ),
+        ]
+        .spacing(8)
+        .align_y(iced::Alignment::Center);
+
+        let mut description_row = row![].spacing(8).align_y(iced::Alignment::Center);
+        if self.config.bwf_metadata {
+            description_row = description_row.push(text("Description:")).push(
+                text_input("Saved in the next recording", &self.recording_description)
+                    .on_input(Message::UpdateRecordingDescription)
+                    .width(Length::Fixed(360.0)),
+            );
+        }
+
+        let mut project_panel = column![].spacing(8).align_x(iced::Alignment::Center);
+        let mut project_row = row![
+            text("Project:"),
+            pick_list(
+                self.projects.as_slice(),
+                self.active_project.clone(),
+                Message::SelectProject
+            )
+            .placeholder("None"),
+        ]
+        .spacing(8)
+        .align_y(iced::Alignment::Center);
+        if let Some(project) = &self.active_project {
+            project_row = project_row.push(button(text("Close")).on_press(Message::CloseProject));
+
+            // Recorded clips are ticked off; the one the next take becomes is marked
+            let next = self.next_project_clip();
+            let mut clips_row = row![].spacing(8).align_y(iced::Alignment::Center);
+            for clip in &project.clips {
+                let clip_button = if self.files.contains(&project.clip_filename(clip)) {
+                    button(text(format!("[x] {}", clip))).style(button::secondary)
+                } else if next.as_ref() == Some(clip) {
+                    button(text(format!("> {}", clip)))
+                } else {
+                    button(text(format!("[ ] {}", clip)))
+                        .style(button::secondary)
+                        .on_press(Message::SelectProjectClip(clip.clone()))
+                };
+                clips_row = clips_row.push(clip_button);
+            }
+            project_panel = project_panel.push(project_row).push(clips_row);
+        } else {
+            project_row = project_row
+                .push(
+                    text_input("New project", &self.project_name_input)
+                        .on_input(Message::UpdateProjectName)
+                        .width(Length::Fixed(140.0)),
+                )
+                .push(
+                    text_input("Clips, comma separated", &self.project_clips_input)
+                        .on_input(Message::UpdateProjectClips)
+                        .on_submit(Message::CreateProject)
+                        .width(Length::Fixed(220.0)),
+                )
+                .push(button(text("Create")).on_press(Message::CreateProject));
+            project_panel = project_panel.push(project_row);
+        }
+
+        let mut export_row = row![].spacing(8).align_y(iced::Alignment::Center);
+        if let Some(job) = &self.background_job {
+            let progress = job.control.progress();
+            let cancelling = job.control.is_cancelled();
+            export_row = export_row
+                .push(text(if cancelling {
+                    format!("{} (cancelling)...", job.label)
+                } else {
+                    format!("{}...", job.label)
+                }))
+                .push(progress_bar(0.0..=1.0, progress).width(Length::Fixed(200.0)))
+                .push(text(format!("{:.0}%", progress * 100.0)))
+                .push(
+                    button(text("Cancel"))
+                        .on_press_maybe((!cancelling).then_some(Message::CancelJob)),
+                );
+        }
+
+        let mut captions_panel = column![].spacing(2);
+        if let Some(captions) = &self.captions {
+            let lines = captions.lines.lock().unwrap();
+            let mut entries = column![].spacing(2);
+            for line in lines.iter() {
+                entries = entries.push(text(line.clone()).size(14));
+            }
+            if lines.is_empty() && self.recording_phase.is_recording() {
+                entries = entries.push(text("Listening...").size(14));
+            }
+            captions_panel = captions_panel.push(
+                scrollable(entries)
+                    .anchor_bottom()
+                    .height(Length::Fixed(80.0))
+                    .width(Length::Fixed(500.0)),
+            );
+        }
+
+        let mut queue_panel = column![].spacing(4).align_x(iced::Alignment::Center);
+        let queue = self.export_queue.lock().unwrap();
+        if !queue.is_empty() {
+            let mut items = column![].spacing(2);
+            for item in queue.iter() {
+                items = items.push(text(format!("{} - {}", item.file, item.status)).size(13));
+            }
+            let idle = self.background_job.is_none();
+            let has_failed = queue
+                .iter()
+                .any(|item| !matches!(item.status, QueueStatus::Done(_)));
+            queue_panel = queue_panel
+                .push(
+                    scrollable(items)
+                        .height(Length::Fixed(90.0))
+                        .width(Length::Fixed(500.0)),
+                )
+                .push(
+                    row![
+                        if idle && has_failed {
+                            button(text("Retry failed")).on_press(Message::RetryExportQueue)
+                        } else {
+                            button(text("Retry failed"))
+                        },
+                        if idle {
+                            button(text("Clear")).on_press(Message::ClearExportQueue)
+                        } else {
+                            button(text("Clear"))
+                        },
+                    ]
+                    .spacing(8),
+                );
+        }
+        drop(queue);
+
+        column![
             text("Voice Recorder").size(30),
             text(&self.status_message).size(16),
+            take_summary,
+            export_row,
+            queue_panel,
             timer_text,
-            record_button,
-            text("Recorded Files").size(22),
-            files_scroll
+            nudge_text,
+            meter_row,
+            health_row,
+            gains_row,
+            monitor_row,
+            metronome_row,
+            row![
+                monitor_only_button,
+                arm_button,
+                record_button,
+                button(text("Re-record")).on_press_maybe(
+                    (self.last_take.is_some()
+                        && self.recording_phase == RecordingPhase::Idle
+                        && self.playback_state == PlaybackState::Stopped)
+                        .then_some(Message::RerecordLastTake)
+                ),
+                if self.recording_phase.is_recording() {
+                    button(text("Mark")).on_press(Message::DropMark)
+                } else {
+                    button(text("Mark")) // Only while recording
+                },
+                button(text("Settings")).on_press(Message::OpenSettings),
+                button(text(if self.show_log { "Hide log" } else { "Log" }))
+                    .on_press(Message::ToggleLog),
+                button(text("?")).on_press(Message::ShowHelp),
+            ]
+            .spacing(16),
+            description_row,
+            captions_panel,
+            project_panel,
+            log_panel,
+            pending_row,
+            chapters_row,
+            width_row,
+            waveform_panel,
+            compare_row,
+            row![
+                text("Recorded Files").size(22),
+                button(text("Find duplicates")).on_press_maybe(
+                    (!self.recording_phase.is_recording() && self.background_job.is_none())
+                        .then_some(Message::FindDuplicates)
+                ),
+                button(text("Rename all...")).on_press(Message::ToggleBatchRename),
+                if self.recording_phase.is_recording()
+                    || self.background_job.is_some()
+                    || self.files.is_empty()
+                {
+                    button(text("Export all"))
+                } else {
+                    button(text("Export all")).on_press(Message::ExportAll)
+                },
+                if self.duplicates.is_empty()
+                    || self.recording_phase.is_recording()
+                    || self.playback_state != PlaybackState::Stopped
+                {
+                    button(text("Delete duplicates"))
+                } else {
+                    button(text("Delete duplicates")).on_press(Message::DeleteDuplicates)
+                },
+                undo_button,
+            ]
+            .spacing(16)
+            .align_y(iced::Alignment::Center),
+            confirm_row,
+            folder_row,
+            open_row,
+            search_row,
+            batch_rename_panel,
+            files_scroll,
+            notes_panel,
         ]
         .spacing(16)
-        .align_x(iced::Alignment::Center);
-
-        center(content).into()
+        .align_x(iced::Alignment::Center)
     }
 
     fn theme(&self) -> Theme {
-        Theme::Dark
+        if self.config.dark_theme {
+            Theme::Dark
+        } else {
+            Theme::Light
+        }
+    }
+}
+
+/// Windows starts a GUI-subsystem program without a console, so command-line output
+/// (help and errors included) would go nowhere. Borrows the console of the shell it
+/// was run from, if there is one.
+#[cfg(windows)]
+fn attach_parent_console() {
+    const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn AttachConsole(process_id: u32) -> i32;
+    }
+    // SAFETY: takes no pointers; failing (no parent console) leaves things as they were
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+pub fn main() -> ExitCode {
+    #[cfg(windows)]
+    if std::env::args_os().len() > 1 {
+        attach_parent_console();
+    }
+    let Some(command) = cli::Cli::parse().command else {
+        return match run_gui() {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("{}", e);
+                ExitCode::FAILURE
+            }
+        };
+    };
+
+    let mut recorder = VoiceRecorder::default();
+    match cli::run(&mut recorder, command) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
     }
 }
 
-pub fn main() -> iced::Result {
+fn run_gui() -> iced::Result {
     iced::application("Voice Recorder", VoiceRecorder::update, VoiceRecorder::view)
         .subscription(VoiceRecorder::subscription)
         .theme(VoiceRecorder::theme)
-        .run()
+        .exit_on_close_request(false)
+        .run_with(|| {
+            let recorder = VoiceRecorder::default();
+            #[cfg(feature = "tray")]
+            let recorder = recorder.with_tray();
+            (recorder, Task::none())
+        })
 }
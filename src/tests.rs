@@ -0,0 +1,3737 @@
+use super::*;
+use crate::audio::mock::MockBackend;
+
+use std::path::{Path, PathBuf};
+use std::sync::MutexGuard;
+
+/// File operations use the working directory, so tests that touch files run one at a
+/// time inside their own temporary directory.
+static CWD_LOCK: Mutex<()> = Mutex::new(());
+
+struct TempDir {
+    path: PathBuf,
+    previous: PathBuf,
+    _guard: MutexGuard<'static, ()>,
+}
+
+impl TempDir {
+    fn enter(name: &str) -> Self {
+        let guard = CWD_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let path = std::env::temp_dir().join(format!("rust_voice_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        let previous = std::env::current_dir().unwrap();
+        std::env::set_current_dir(&path).unwrap();
+        Self {
+            path,
+            previous,
+            _guard: guard,
+        }
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::env::set_current_dir(&self.previous);
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+fn sine(frequency: f32, sample_rate: u32, channels: u16, secs: f32) -> Vec<f32> {
+    let frames = (sample_rate as f32 * secs) as usize;
+    (0..frames)
+        .flat_map(|i| {
+            let v = (i as f32 / sample_rate as f32 * frequency * std::f32::consts::TAU).sin() * 0.5;
+            std::iter::repeat_n(v, channels as usize)
+        })
+        .collect()
+}
+
+fn recorder_with(backend: MockBackend) -> VoiceRecorder {
+    let mut recorder = VoiceRecorder::with_backend(Box::new(backend));
+    recorder.config.audio_cues_enabled = false;
+    recorder
+}
+
+fn send(recorder: &mut VoiceRecorder, message: Message) {
+    let _ = recorder.update(message);
+}
+
+#[test]
+fn records_synthetic_tone_to_wav() {
+    let _dir = TempDir::enter("record");
+    let tone = sine(440.0, 48000, 2, 0.5);
+    let mut backend = MockBackend::new(2, 48000);
+    backend.input_signal = tone.clone();
+
+    let mut recorder = recorder_with(backend);
+    send(&mut recorder, Message::StartRecording);
+    assert!(recorder.recording_phase.is_recording());
+    send(&mut recorder, Message::StopRecording);
+    send(&mut recorder, Message::FinalizeRecording);
+
+    assert_eq!(recorder.files, vec!["recording_1.wav".to_string()]);
+    let (spec, samples) = read_wav_file("recording_1.wav").unwrap();
+    assert_eq!(spec.channels, 2);
+    assert_eq!(spec.sample_rate, 48000);
+    assert_eq!(samples, tone);
+}
+
+#[test]
+fn empty_recording_is_not_saved() {
+    let _dir = TempDir::enter("empty");
+    let mut recorder = recorder_with(MockBackend::new(1, 48000));
+    send(&mut recorder, Message::StartRecording);
+    send(&mut recorder, Message::StopRecording);
+    send(&mut recorder, Message::FinalizeRecording);
+
+    assert!(recorder.files.is_empty());
+    assert!(recorder.status_message.contains("No audio data"));
+}
+
+#[test]
+fn plays_file_through_output_stream() {
+    let _dir = TempDir::enter("play");
+    let tone = sine(220.0, 44100, 1, 0.25);
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate: 44100,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    write_wav_file_f32("tone.wav", spec, &tone).unwrap();
+
+    let mut backend = MockBackend::new(1, 44100);
+    backend.max_render_samples = tone.len() + 1024;
+    let rendered = Arc::clone(&backend.rendered);
+
+    let mut recorder = recorder_with(backend);
+    recorder.config.output_start_ramp_ms = 0;
+    send(&mut recorder, Message::PlayFile("tone.wav".into()));
+    assert_eq!(recorder.playback_state, PlaybackState::Playing);
+
+    let rendered = rendered.lock().unwrap();
+    assert_eq!(&rendered[..tone.len()], &tone[..]);
+    assert!(rendered[tone.len()..].iter().all(|&s| s == 0.0));
+    assert!(recorder.playback_status_rx.try_recv().is_ok());
+}
+
+#[test]
+fn dc_blocker_removes_offset_per_channel() {
+    let sample_rate = 48000;
+    let mut samples: Vec<f32> = (0..sample_rate).flat_map(|_| [0.5, -0.25]).collect();
+    DcBlocker::new(2, sample_rate).process(&mut samples);
+
+    let tail = &samples[samples.len() - 200..];
+    assert!(tail.iter().all(|s| s.abs() < 0.01));
+}
+
+#[test]
+fn stereo_width_zero_collapses_to_mono() {
+    let mut samples = vec![1.0, 0.0, 0.2, -0.2];
+    apply_stereo_width(&mut samples, 0.0);
+    assert_eq!(samples, vec![0.5, 0.5, 0.0, 0.0]);
+}
+
+#[test]
+fn fades_are_symmetric_across_channels() {
+    let mut samples = vec![1.0; 2 * 1000];
+    apply_fades(&mut samples, 2, 1000, 100, 200);
+
+    assert_eq!(samples[0], 0.0);
+    assert_eq!(samples[1], 0.0);
+    assert_eq!(samples[2 * 500], 1.0);
+    assert!(samples.chunks(2).all(|f| f[0] == f[1]));
+    assert!(samples[2 * 999] < 0.01);
+}
+
+#[test]
+fn discarded_recording_is_not_saved() {
+    let _dir = TempDir::enter("discard");
+    let mut backend = MockBackend::new(1, 48000);
+    backend.input_signal = sine(440.0, 48000, 1, 0.1);
+
+    let mut recorder = recorder_with(backend);
+    send(&mut recorder, Message::StartRecording);
+    send(&mut recorder, Message::StopRecording);
+    let RecordingPhase::Stopping { since } = recorder.recording_phase else {
+        panic!("not stopping: {:?}", recorder.recording_phase);
+    };
+    send(&mut recorder, Message::Tick(since + Duration::from_secs(1)));
+    assert_eq!(recorder.recording_phase, RecordingPhase::Finalizing);
+
+    send(&mut recorder, Message::StartRecording);
+    assert!(!recorder.recording_phase.is_recording());
+
+    send(&mut recorder, Message::DiscardRecording);
+    assert_eq!(recorder.recording_phase, RecordingPhase::Idle);
+    assert!(recorder.audio_data.lock().unwrap().is_empty());
+    assert!(list_wav_files(None).is_empty());
+}
+
+#[test]
+fn integrated_loudness_of_reference_sine() {
+    // A 997 Hz sine at 0 dBFS peak reads -3.01 LUFS; `sine` is 6 dB below that
+    let tone = sine(997.0, 48000, 1, 3.0);
+    let lufs = integrated_loudness(&tone, 1, 48000).unwrap();
+    assert!((lufs + 9.03).abs() < 0.1, "measured {lufs}");
+}
+
+#[test]
+fn voicemail_preset_exports_8k_mono_pcm() {
+    let _dir = TempDir::enter("preset");
+    let spec = WavSpec {
+        channels: 2,
+        sample_rate: 48000,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    write_wav_file_f32("take.wav", spec, &sine(440.0, 48000, 2, 0.5)).unwrap();
+
+    let preset = builtin_presets()
+        .into_iter()
+        .find(|p| p.name == "Voicemail")
+        .unwrap();
+    let output = export_file("take.wav", &preset.settings, &JobControl::default()).unwrap();
+
+    assert_eq!(output.path, "take_export.wav");
+    let reader = WavReader::open("take_export.wav").unwrap();
+    assert_eq!(reader.spec().channels, 1);
+    assert_eq!(reader.spec().sample_rate, 8000);
+    assert_eq!(reader.spec().bits_per_sample, 16);
+    assert_eq!(reader.len(), 4000);
+}
+
+#[test]
+fn exports_to_aiff_and_caf_keep_the_source_format() {
+    let _dir = TempDir::enter("apple_formats");
+    let spec = WavSpec {
+        channels: 2,
+        sample_rate: 44100,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let samples: Vec<i16> = vec![16384, -16384, 32767, -32768, 0, 1];
+    write_wav_file_i16("take.wav", spec, &samples).unwrap();
+    let source = fs::read("take.wav").unwrap();
+
+    let preset = builtin_presets()
+        .into_iter()
+        .find(|p| p.name == "Logic / Final Cut")
+        .unwrap();
+    assert_eq!(preset.settings.format, ExportFormat::Aiff);
+    let settings = ExportSettings {
+        format: ExportFormat::Aiff,
+        ..ExportSettings::default()
+    };
+    let output = export_file("take.wav", &settings, &JobControl::default()).unwrap();
+    assert_eq!(output.path, "take_export.aiff");
+    let aiff = fs::read("take_export.aiff").unwrap();
+    assert_eq!(&aiff[..4], b"FORM");
+    assert_eq!(
+        u32::from_be_bytes(aiff[4..8].try_into().unwrap()) as usize,
+        aiff.len() - 8
+    );
+    assert_eq!(&aiff[8..16], b"AIFFCOMM");
+    // 2 channels, 3 frames, 16 bits, 44100 Hz as an 80-bit float
+    assert_eq!(&aiff[20..28], &[0, 2, 0, 0, 0, 3, 0, 16]);
+    assert_eq!(&aiff[28..32], &[0x40, 0x0E, 0xAC, 0x44]);
+    assert_eq!(&aiff[38..42], b"SSND");
+    assert_eq!(
+        &aiff[54..],
+        &[0x40, 0x00, 0xC0, 0x00, 0x7F, 0xFF, 0x80, 0x01, 0, 0, 0, 1]
+    );
+
+    let float_spec = WavSpec {
+        channels: 1,
+        sample_rate: 48000,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    write_wav_file_f32("float.wav", float_spec, &[0.5, -0.25]).unwrap();
+    let settings = ExportSettings {
+        format: ExportFormat::Caf,
+        ..ExportSettings::default()
+    };
+    export_file("float.wav", &settings, &JobControl::default()).unwrap();
+    let caf = fs::read("float_export.caf").unwrap();
+    assert_eq!(&caf[..8], &[b'c', b'a', b'f', b'f', 0, 1, 0, 0]);
+    assert_eq!(&caf[8..12], b"desc");
+    assert_eq!(f64::from_be_bytes(caf[20..28].try_into().unwrap()), 48000.0);
+    assert_eq!(&caf[28..32], b"lpcm");
+    // Float flag, 4 bytes per packet, 1 frame per packet, 1 channel, 32 bits
+    assert_eq!(
+        &caf[32..52],
+        &[0, 0, 0, 1, 0, 0, 0, 4, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 32]
+    );
+    assert_eq!(&caf[52..56], b"data");
+    assert_eq!(i64::from_be_bytes(caf[56..64].try_into().unwrap()), 12);
+    assert_eq!(f32::from_be_bytes(caf[68..72].try_into().unwrap()), 0.5);
+    assert_eq!(f32::from_be_bytes(caf[72..76].try_into().unwrap()), -0.25);
+
+    assert_eq!(fs::read("take.wav").unwrap(), source);
+}
+
+#[test]
+fn split_at_snaps_cuts_to_frames() {
+    let spec = WavSpec {
+        channels: 2,
+        sample_rate: 1000,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let samples: Vec<f32> = (0..20).map(|i| i as f32).collect();
+    let segments = split_at(&samples, spec, &[7, 7, 12, 40]);
+
+    assert_eq!(segments.len(), 3);
+    assert_eq!(segments[0], &samples[..6]);
+    assert_eq!(segments[1], &samples[6..12]);
+    assert_eq!(segments[2], &samples[12..]);
+}
+
+#[test]
+fn low_latency_recording_falls_back_without_buffer_range() {
+    let _dir = TempDir::enter("low_latency");
+    let mut backend = MockBackend::new(1, 48000);
+    backend.input_formats[0].buffer_size = cpal::SupportedBufferSize::Unknown;
+
+    let mut recorder = recorder_with(backend);
+    recorder.config.low_latency = true;
+    send(&mut recorder, Message::StartRecording);
+    assert!(recorder.recording_phase.is_recording());
+    assert!(
+        recorder
+            .status_message
+            .contains("low latency not supported")
+    );
+}
+
+#[test]
+fn armed_input_is_metered_but_not_captured() {
+    let _dir = TempDir::enter("armed");
+    let mut backend = MockBackend::new(1, 48000);
+    backend.input_signal = sine(440.0, 48000, 1, 0.1);
+
+    let mut recorder = recorder_with(backend);
+    send(&mut recorder, Message::ArmRecording);
+    assert!(recorder.recording_phase.is_armed());
+    assert!(recorder.audio_data.lock().unwrap().is_empty());
+    assert!(recorder.input_levels.take().0 > 0.4);
+
+    send(&mut recorder, Message::StartRecording);
+    assert!(recorder.recording_phase.is_recording());
+    assert!(!recorder.recording_phase.is_armed());
+    assert!(recorder.input_capturing.load(Ordering::Relaxed));
+}
+
+#[test]
+fn saved_takes_are_summarised_until_the_next_one() {
+    let _dir = TempDir::enter("take_summary");
+    let mut backend = MockBackend::new(1, 48000);
+    backend.input_signal = sine(440.0, 48000, 1, 1.0);
+    backend.input_signal[100] = 1.0;
+    backend.input_signal[200] = -1.0;
+
+    let mut recorder = recorder_with(backend);
+    send(&mut recorder, Message::StartRecording);
+    send(&mut recorder, Message::StopRecording);
+    send(&mut recorder, Message::FinalizeRecording);
+    let (summary, saved_at) = recorder.take_summary.clone().unwrap();
+    assert_eq!(
+        summary,
+        "'recording_1.wav': 00:01.0, peak 0.0 dBFS, 2 clipped samples, 0.2 MB"
+    );
+
+    send(
+        &mut recorder,
+        Message::Tick(saved_at + Duration::from_secs(TAKE_SUMMARY_SECS)),
+    );
+    assert!(recorder.take_summary.is_none());
+
+    recorder.take_summary = Some((summary, saved_at));
+    send(&mut recorder, Message::StartRecording);
+    assert!(recorder.take_summary.is_none());
+}
+
+#[test]
+fn meter_reports_where_the_level_sits_against_the_target() {
+    let _dir = TempDir::enter("meter_target");
+    let mut recorder = recorder_with(MockBackend::new(1, 48000));
+    recorder.meter_level = db_to_amplitude(-20.0);
+    assert_eq!(recorder.meter_on_target(), None);
+
+    send(&mut recorder, Message::UpdateMeterTarget("-18".into()));
+    send(&mut recorder, Message::UpdateMeterTolerance("1.5".into()));
+    assert_eq!(recorder.meter_on_target(), Some(std::cmp::Ordering::Less));
+    recorder.meter_level = db_to_amplitude(-17.0);
+    assert_eq!(recorder.meter_on_target(), Some(std::cmp::Ordering::Equal));
+    recorder.meter_level = db_to_amplitude(-12.0);
+    assert_eq!(
+        recorder.meter_on_target(),
+        Some(std::cmp::Ordering::Greater)
+    );
+    recorder.meter_level = 0.0;
+    assert_eq!(recorder.meter_on_target(), Some(std::cmp::Ordering::Less));
+
+    // Positive targets are refused; the config keeps the last good one
+    send(&mut recorder, Message::UpdateMeterTarget("6".into()));
+    assert_eq!(Config::load().unwrap().meter_target_db, Some(-18.0));
+    assert_eq!(Config::load().unwrap().meter_target_tolerance_db, 1.5);
+    send(&mut recorder, Message::UpdateMeterTarget(" ".into()));
+    assert_eq!(Config::load().unwrap().meter_target_db, None);
+}
+
+#[test]
+fn audio_cues_are_off_by_default_and_report_failures_in_the_log() {
+    let _dir = TempDir::enter("audio_cues");
+    assert!(!Config::default().audio_cues_enabled);
+
+    let mut backend = MockBackend::new(1, 48000);
+    backend.input_signal = sine(440.0, 48000, 1, 0.1);
+    let present = Arc::clone(&backend.output_present);
+    let mut recorder = recorder_with(backend);
+    recorder.config.audio_cues_enabled = true;
+    *present.lock().unwrap() = false;
+    send(&mut recorder, Message::StartRecording);
+    assert!(recorder.recording_phase.is_recording());
+    assert!(
+        recorder
+            .status_log
+            .iter()
+            .any(|(_, line)| line == "Couldn't play the cue: No output device found.")
+    );
+}
+
+#[test]
+fn bad_settings_are_reset_or_left_alone_instead_of_overwritten() {
+    let _dir = TempDir::enter("bad_settings");
+    // Hand-edited numbers out of range are put back to their defaults
+    fs::write(
+        CONFIG_PATH,
+        r#"{"chapter_min_gap_secs": -1.0, "preview_secs": 3.0, "output_volume": 4.0}"#,
+    )
+    .unwrap();
+    let recorder = recorder_with(MockBackend::new(1, 48000));
+    assert_eq!(recorder.config.chapter_min_gap_secs, 2.0);
+    assert_eq!(recorder.config.output_volume, 1.0);
+    assert_eq!(recorder.config.preview_secs, 3.0);
+    assert!(
+        recorder
+            .status_message
+            .contains("chapter_min_gap_secs, output_volume")
+    );
+
+    // A file that doesn't parse at all isn't replaced by the defaults
+    let broken = r#"{"preview_secs": "three", "dark_theme": false}"#;
+    fs::write(CONFIG_PATH, broken).unwrap();
+    assert!(Config::load().is_err());
+    let mut recorder = recorder_with(MockBackend::new(1, 48000));
+    assert!(recorder.config.dark_theme);
+    assert!(recorder.status_message.starts_with("Couldn't read"));
+    send(&mut recorder, Message::ToggleGroupByDate(true));
+    assert_eq!(fs::read_to_string(CONFIG_PATH).unwrap(), broken);
+}
+
+#[test]
+fn closing_the_window_saves_the_take_and_releases_streams() {
+    let _dir = TempDir::enter("close_window");
+    let mut backend = MockBackend::new(1, 48000);
+    backend.input_signal = sine(440.0, 48000, 1, 0.5);
+
+    let mut recorder = recorder_with(backend);
+    send(&mut recorder, Message::StartRecording);
+    send(&mut recorder, Message::ToggleMetronome);
+    assert!(recorder.metronome_stream.is_some());
+    send(
+        &mut recorder,
+        Message::CloseRequested(iced::window::Id::unique()),
+    );
+    assert_eq!(recorder.recording_phase, RecordingPhase::Idle);
+    assert!(recorder.input_stream.is_none());
+    assert!(recorder.metronome_stream.is_none());
+    assert!(recorder.output_stream.is_none());
+    let (_, samples) = read_wav_file("recording_1.wav").unwrap();
+    assert_eq!(samples.len(), 24000);
+
+    // Nothing is written for an armed input
+    send(&mut recorder, Message::ArmRecording);
+    send(
+        &mut recorder,
+        Message::CloseRequested(iced::window::Id::unique()),
+    );
+    assert_eq!(recorder.recording_phase, RecordingPhase::Idle);
+    assert!(recorder.input_stream.is_none());
+    assert!(!std::path::Path::new("recording_2.wav").exists());
+}
+
+#[test]
+fn stop_flush_delay_follows_buffer_size() {
+    assert_eq!(
+        stop_flush_delay(BufferSize::Fixed(1024), 48000, 0),
+        Duration::from_secs_f64(2048.0 / 48000.0)
+    );
+    assert_eq!(
+        stop_flush_delay(BufferSize::Fixed(480), 48000, 50),
+        Duration::from_millis(70)
+    );
+}
+
+#[test]
+fn ab_switch_keeps_relative_position() {
+    let _dir = TempDir::enter("ab");
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate: 8000,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    write_wav_file_f32("a.wav", spec, &sine(200.0, 8000, 1, 1.0)).unwrap();
+    write_wav_file_f32("b.wav", spec, &sine(200.0, 8000, 1, 2.0)).unwrap();
+
+    let mut recorder = recorder_with(MockBackend::new(1, 8000));
+    send(&mut recorder, Message::AssignAbSlot(0, "a.wav".into()));
+    send(&mut recorder, Message::AssignAbSlot(1, "b.wav".into()));
+    send(&mut recorder, Message::StartAbCompare);
+    assert_eq!(recorder.playback_state, PlaybackState::Playing);
+
+    *recorder.playback_position.lock().unwrap() = 2000;
+    send(&mut recorder, Message::SwitchAbTake);
+    assert_eq!(recorder.playing_file().map(String::as_str), Some("b.wav"));
+    assert_eq!(recorder.playback_samples.lock().unwrap().len(), 16000);
+    assert_eq!(*recorder.playback_position.lock().unwrap(), 4000);
+
+    send(&mut recorder, Message::SwitchAbTake);
+    assert_eq!(recorder.playing_file().map(String::as_str), Some("a.wav"));
+    assert_eq!(*recorder.playback_position.lock().unwrap(), 2000);
+}
+
+#[test]
+fn i16_input_is_saved_bit_exact() {
+    let _dir = TempDir::enter("i16");
+    let native: Vec<i16> = vec![i16::MIN, -12345, -1, 0, 1, 777, i16::MAX];
+    let mut backend = MockBackend::new(1, 48000);
+    backend.input_format.sample_format = SampleFormat::I16;
+    backend.input_signal = native.iter().map(|&s| s as f32 / i16::MAX as f32).collect();
+
+    let mut recorder = recorder_with(backend);
+    recorder.config.preserve_source_format = true;
+    send(&mut recorder, Message::StartRecording);
+    send(&mut recorder, Message::StopRecording);
+    send(&mut recorder, Message::FinalizeRecording);
+
+    let mut reader = WavReader::open("recording_1.wav").unwrap();
+    assert_eq!(reader.spec().bits_per_sample, 16);
+    let saved: Vec<i16> = reader.samples::<i16>().map(Result::unwrap).collect();
+    assert_eq!(saved, native);
+
+    // Unsigned 16-bit devices are kept as 16-bit too
+    let mut backend = MockBackend::new(1, 48000);
+    backend.input_format.sample_format = SampleFormat::U16;
+    backend.input_signal = native.iter().map(|&s| s as f32 / i16::MAX as f32).collect();
+    let mut recorder = recorder_with(backend);
+    recorder.config.preserve_source_format = true;
+    send(&mut recorder, Message::StartRecording);
+    send(&mut recorder, Message::StopRecording);
+    send(&mut recorder, Message::FinalizeRecording);
+    let mut reader = WavReader::open("recording_2.wav").unwrap();
+    assert_eq!(reader.spec().bits_per_sample, 16);
+    let saved: Vec<i16> = reader.samples::<i16>().map(Result::unwrap).collect();
+    assert_eq!(saved, native);
+}
+
+#[test]
+fn status_log_keeps_most_recent_messages() {
+    let mut recorder = recorder_with(MockBackend::new(1, 48000));
+    for i in 0..STATUS_LOG_LEN + 5 {
+        recorder.add_log(format!("message {}", i));
+    }
+
+    assert_eq!(recorder.status_log.len(), STATUS_LOG_LEN);
+    assert_eq!(recorder.status_log.front().unwrap().1, "message 5");
+    assert_eq!(
+        recorder.status_message,
+        format!("message {}", STATUS_LOG_LEN + 4)
+    );
+
+    send(&mut recorder, Message::ClearLog);
+    assert!(recorder.status_log.is_empty());
+}
+
+#[test]
+fn waveform_click_seeks_and_starts_playback() {
+    let _dir = TempDir::enter("seek");
+    let spec = WavSpec {
+        channels: 2,
+        sample_rate: 8000,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    write_wav_file_f32("take.wav", spec, &sine(200.0, 8000, 2, 1.0)).unwrap();
+
+    let mut recorder = recorder_with(MockBackend::new(2, 8000));
+    send(&mut recorder, Message::SelectFile("take.wav".into()));
+    send(&mut recorder, Message::ZoomWaveform(2.0));
+    send(&mut recorder, Message::ScrollWaveform(1.0));
+
+    // Right half of the file is visible, so a click at the middle lands at 75%
+    let view = waveform::View::new(recorder.waveform_zoom, recorder.waveform_scroll);
+    send(
+        &mut recorder,
+        Message::SeekWaveform(view.fraction_at(300.0, 600.0)),
+    );
+    assert_eq!(recorder.playback_state, PlaybackState::Playing);
+    assert_eq!(*recorder.playback_position.lock().unwrap(), 6000 * 2);
+
+    send(&mut recorder, Message::SeekWaveform(2.0));
+    assert_eq!(*recorder.playback_position.lock().unwrap(), 7999 * 2);
+}
+
+#[test]
+fn silence_trim_keeps_padding_around_sound() {
+    let mut samples = vec![0.0; 2 * 1000];
+    samples.extend(vec![0.5; 2 * 500]);
+    samples.extend(vec![0.0; 2 * 1000]);
+
+    let range = silence_trim_range(&samples, 2, 1000, 0.1, 200).unwrap();
+    assert_eq!(range, 2 * 800..2 * 1700);
+    assert!(silence_trim_range(&[0.0; 100], 2, 1000, 0.1, 200).is_none());
+}
+
+#[test]
+fn cancelled_job_stops_resampling() {
+    let samples = vec![0.25f32; 48000];
+    let job = JobControl::default();
+    let mut calls = 0;
+    let mut progress = job.stage(0.0, 1.0);
+    let out = resample_with_progress(
+        &samples,
+        1,
+        48000,
+        44100,
+        ResampleQuality::High,
+        &mut |fraction| {
+            calls += 1;
+            progress(fraction)
+        },
+    );
+    assert!(out.is_some());
+    assert!(calls > 1);
+
+    job.cancel();
+    let out = resample_with_progress(
+        &samples,
+        1,
+        48000,
+        44100,
+        ResampleQuality::High,
+        &mut job.stage(0.0, 1.0),
+    );
+    assert!(out.is_none());
+    assert!(job.progress() < 0.01);
+}
+
+#[test]
+fn failed_chapter_and_export_writes_leave_nothing_behind() {
+    let _dir = TempDir::enter("partial_outputs");
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate: 8000,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let samples = vec![0.25; 300];
+    write_wav_file_f32("take.wav", spec, &samples).unwrap();
+
+    // The second chapter can't be written, so the first mustn't be left either
+    fs::create_dir("take_ch02.wav.tmp").unwrap();
+    let job = JobControl::default();
+    assert!(write_chapter_files("take.wav", spec, &samples, &[100, 200], &job).is_err());
+    fs::remove_dir("take_ch02.wav.tmp").unwrap();
+    assert!(!std::path::Path::new("take_ch01.wav").exists());
+    assert!(!std::path::Path::new("take_ch01.wav.tmp").exists());
+
+    let cancelled = JobControl::default();
+    cancelled.cancel();
+    let result = write_chapter_files("take.wav", spec, &samples, &[100, 200], &cancelled);
+    assert_eq!(result.unwrap_err().kind(), io::ErrorKind::Interrupted);
+
+    // Nor when one can't be renamed into place after the first already was
+    fs::create_dir_all("take_ch02.wav/in_the_way").unwrap();
+    assert!(write_chapter_files("take.wav", spec, &samples, &[100, 200], &job).is_err());
+    fs::remove_dir_all("take_ch02.wav").unwrap();
+    for leftover in ["take_ch01.wav", "take_ch02.wav.tmp", "take_ch03.wav.tmp"] {
+        assert!(!std::path::Path::new(leftover).exists(), "{}", leftover);
+    }
+
+    assert_eq!(
+        write_chapter_files("take.wav", spec, &samples, &[100, 200], &job).unwrap(),
+        3
+    );
+    assert_eq!(read_wav_file("take_ch03.wav").unwrap().1.len(), 100);
+
+    fs::create_dir("take_export.wav.tmp").unwrap();
+    let settings = ExportSettings::default();
+    assert!(export_file("take.wav", &settings, &job).is_err());
+    assert!(!std::path::Path::new("take_export.wav").exists());
+    fs::remove_dir("take_export.wav.tmp").unwrap();
+    assert!(export_file("take.wav", &settings, &job).is_ok());
+    assert!(!std::path::Path::new("take_export.wav.tmp").exists());
+}
+
+#[test]
+fn duplicates_are_confirmed_by_their_samples_off_the_ui_thread() {
+    let _dir = TempDir::enter("find_duplicates");
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate: 8000,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let take = sine(300.0, 8000, 1, 0.2);
+    let mut near = take.clone();
+    near[100] += 0.01;
+    write_wav_file_f32("a.wav", spec, &take).unwrap();
+    write_wav_file_f32("b_copy.wav", spec, &take).unwrap();
+    write_wav_file_f32("c_near.wav", spec, &near).unwrap();
+
+    let mut recorder = recorder_with(MockBackend::new(1, 8000));
+    // Pretend the near-duplicate's fingerprint collides with the take's
+    let hash = audio_fingerprint("a.wav").unwrap();
+    let modified = fs::metadata("c_near.wav").unwrap().modified().unwrap();
+    recorder
+        .fingerprint_cache
+        .insert(cache_key("c_near.wav"), (modified, hash));
+
+    send(&mut recorder, Message::FindDuplicates);
+    let id = recorder.background_job.as_ref().unwrap().id;
+    let started = Instant::now();
+    while recorder.duplicate_scan.lock().unwrap().is_none() {
+        assert!(started.elapsed() < Duration::from_secs(10));
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    send(&mut recorder, Message::JobFinished(id, Ok("Found.".into())));
+    assert_eq!(recorder.duplicates, ["b_copy.wav".to_string()].into());
+    assert!(recorder.background_job.is_none());
+}
+
+#[test]
+fn cancelled_job_blocks_new_jobs_until_its_worker_finishes() {
+    let _dir = TempDir::enter("cancel_job");
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate: 48000,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    write_wav_file_f32("take.wav", spec, &[0.25; 480]).unwrap();
+    let mut recorder = recorder_with(MockBackend::new(1, 48000));
+
+    send(&mut recorder, Message::ExportFile("take.wav".into()));
+    let id = recorder.background_job.as_ref().unwrap().id;
+    send(&mut recorder, Message::CancelJob);
+    let job = recorder.background_job.as_ref().unwrap();
+    assert_eq!(job.id, id);
+    assert!(job.control.is_cancelled());
+
+    // The worker may still be writing, so nothing else starts yet
+    send(&mut recorder, Message::ExportFile("take.wav".into()));
+    assert_eq!(recorder.background_job.as_ref().unwrap().id, id);
+    assert!(recorder.status_message.contains("finish cancelling"));
+
+    send(
+        &mut recorder,
+        Message::JobFinished(id, Err("Export cancelled.".into())),
+    );
+    assert!(recorder.background_job.is_none());
+    assert!(recorder.status_message.starts_with("Cancelled: Exporting"));
+    send(&mut recorder, Message::ExportFile("take.wav".into()));
+    assert_ne!(recorder.background_job.as_ref().unwrap().id, id);
+}
+
+#[test]
+fn bext_chunk_is_added_before_data_and_survives_rewrite() {
+    let _dir = TempDir::enter("bext");
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate: 48000,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let samples = sine(440.0, 48000, 1, 0.1);
+    write_wav_file_like("take.wav", spec, &samples).unwrap();
+
+    // 2024-02-29 12:00:01 UTC
+    let started = std::time::UNIX_EPOCH + Duration::from_secs(1_709_208_001);
+    let bext = bwf::Bext::new("Interview", "take.wav", started, 48000);
+    assert_eq!(bext.origination_date, "2024-02-29");
+    assert_eq!(bext.origination_time, "12:00:01");
+    assert_eq!(bext.time_reference, 43_201 * 48000);
+    bwf::write_chunk("take.wav", bwf::BEXT, &bext.to_bytes()).unwrap();
+
+    let (read_spec, read_samples) = read_wav_file("take.wav").unwrap();
+    assert_eq!(read_spec, spec);
+    assert_eq!(read_samples.len(), samples.len());
+
+    rewrite_wav_file("take.wav", spec, &read_samples[..4800]).unwrap();
+    let data = bwf::read_chunk("take.wav", bwf::BEXT).unwrap().unwrap();
+    assert_eq!(bwf::Bext::from_bytes(&data), Some(bext));
+    assert_eq!(read_wav_file("take.wav").unwrap().1.len(), 4800);
+
+    // The comment joins an INFO list already there, and an adtl list is left alone
+    let adtl = b"adtllabl\x08\0\0\0\x01\0\0\0In\0\0".to_vec();
+    let mut info = b"INFOIART\x04\0\0\0Ana\0".to_vec();
+    bwf::write_chunks(
+        "take.wav",
+        &[(bwf::LIST, adtl.clone()), (bwf::LIST, info.clone())],
+    )
+    .unwrap();
+    info = bwf::info_comment(bwf::read_info("take.wav").unwrap().as_deref(), "Trimmed");
+    bwf::write_chunk("take.wav", bwf::LIST, &info).unwrap();
+    rewrite_wav_file("take.wav", spec, &read_samples[..4800]).unwrap();
+    let lists = bwf::read_chunks("take.wav", &[bwf::LIST]).unwrap();
+    assert_eq!(lists.len(), 2);
+    assert!(lists.contains(&(bwf::LIST, adtl)));
+    let info = bwf::read_info("take.wav").unwrap().unwrap();
+    assert!(info.starts_with(b"INFOIART\x04\0\0\0Ana\0"));
+    assert_eq!(bwf::read_info_comment(&info).as_deref(), Some("Trimmed"));
+}
+
+#[test]
+fn double_click_on_filename_starts_rename() {
+    let _dir = TempDir::enter("double_click");
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate: 8000,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    write_wav_file_like("take.wav", spec, &sine(200.0, 8000, 1, 0.2)).unwrap();
+
+    let mut recorder = recorder_with(MockBackend::new(1, 8000));
+    send(&mut recorder, Message::SelectFile("take.wav".into()));
+    assert_eq!(recorder.renaming_file, None);
+    send(&mut recorder, Message::SelectFile("take.wav".into()));
+    assert_eq!(recorder.renaming_file.as_deref(), Some("take.wav"));
+    assert_eq!(recorder.selected_file.as_deref(), Some("take.wav"));
+    assert_eq!(recorder.new_name, "take");
+}
+
+#[test]
+fn sidecars_are_named_after_the_wav_whatever_the_case_of_its_extension() {
+    assert_eq!(marks_path("take.WAV"), "take.marks.json");
+    assert_eq!(transcript_path("dir/take.Wav"), "dir/take.transcript.txt");
+    assert_eq!(playback_settings_path("v1.2.wav"), "v1.2.playback.json");
+    assert_eq!(
+        export_path("take.WAV", &ExportSettings::default()),
+        "take_export.wav"
+    );
+    assert_eq!(
+        MarkerFormat::Csv.path("take.WAV").as_deref(),
+        Some("take.markers.csv")
+    );
+}
+
+#[test]
+fn cue_points_are_saved_beside_the_recording() {
+    let _dir = TempDir::enter("marks");
+    let tone = sine(440.0, 48000, 2, 0.5);
+    let mut backend = MockBackend::new(2, 48000);
+    backend.input_signal = tone.clone();
+
+    let mut recorder = recorder_with(backend);
+    send(&mut recorder, Message::DropMark);
+    assert!(recorder.pending_marks.is_empty());
+
+    send(&mut recorder, Message::StartRecording);
+    send(&mut recorder, Message::DropMark);
+    recorder.audio_data.lock().unwrap().extend_from_slice(&tone);
+    send(&mut recorder, Message::StopRecording);
+    send(&mut recorder, Message::FinalizeRecording);
+
+    assert_eq!(
+        load_marks("recording_1.wav"),
+        Some(vec![(0.5, String::new())])
+    );
+    assert_eq!(recorder.markers, vec![tone.len()]);
+
+    let mut reopened = recorder_with(MockBackend::new(2, 48000));
+    send(&mut reopened, Message::SelectFile("recording_1.wav".into()));
+    assert_eq!(reopened.marker_file.as_deref(), Some("recording_1.wav"));
+    assert_eq!(reopened.markers, vec![tone.len()]);
+}
+
+#[test]
+fn warm_output_stream_is_reused_between_plays() {
+    let _dir = TempDir::enter("warm_output");
+    let spec = WavSpec {
+        channels: 2,
+        sample_rate: 48000,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    write_wav_file_f32("take.wav", spec, &sine(440.0, 48000, 2, 0.2)).unwrap();
+
+    let backend = MockBackend::new(2, 48000);
+    let built = Arc::clone(&backend.output_streams_built);
+    let mut recorder = recorder_with(backend);
+    recorder.config.keep_output_warm = true;
+
+    send(&mut recorder, Message::PlayFile("take.wav".into()));
+    send(&mut recorder, Message::StopPlayback);
+    assert!(recorder.output_stream.is_some());
+    assert!(recorder.playback_samples.lock().unwrap().is_empty());
+
+    send(&mut recorder, Message::PlayFile("take.wav".into()));
+    assert_eq!(recorder.playback_state, PlaybackState::Playing);
+    assert_eq!(*built.lock().unwrap(), 1);
+
+    // A file at another rate needs a stream resampling at another step
+    let cd = WavSpec {
+        sample_rate: 44100,
+        ..spec
+    };
+    write_wav_file_f32("cd.wav", cd, &sine(440.0, 44100, 2, 0.2)).unwrap();
+    send(&mut recorder, Message::StopPlayback);
+    send(&mut recorder, Message::PlayFile("cd.wav".into()));
+    assert_eq!(*built.lock().unwrap(), 2);
+    send(&mut recorder, Message::StopPlayback);
+    send(&mut recorder, Message::PlayFile("take.wav".into()));
+    assert_eq!(*built.lock().unwrap(), 3);
+
+    // Released once it has sat idle for the configured time
+    send(&mut recorder, Message::StopPlayback);
+    send(&mut recorder, Message::CheckIdleOutput(Instant::now()));
+    assert!(recorder.output_stream.is_some());
+    let later = Instant::now() + Duration::from_secs(60);
+    send(&mut recorder, Message::CheckIdleOutput(later));
+    assert!(recorder.output_stream.is_none());
+
+    send(&mut recorder, Message::PlayFile("take.wav".into()));
+    send(&mut recorder, Message::StopPlayback);
+    send(&mut recorder, Message::ToggleKeepOutputWarm(false));
+    assert!(recorder.output_stream.is_none());
+}
+
+#[test]
+fn auto_level_brings_quiet_file_up_to_reference() {
+    let _dir = TempDir::enter("auto_level");
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate: 48000,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let quiet: Vec<f32> = sine(1000.0, 48000, 1, 1.0)
+        .iter()
+        .map(|s| s * 0.1)
+        .collect();
+    write_wav_file_f32("quiet.wav", spec, &quiet).unwrap();
+
+    let mut recorder = recorder_with(MockBackend::new(1, 48000));
+    recorder.config.auto_level = true;
+    send(&mut recorder, Message::PlayFile("quiet.wav".into()));
+
+    let levelled = recorder.playback_samples.lock().unwrap().clone();
+    let lufs = integrated_loudness(&levelled, 1, 48000).unwrap();
+    assert!((lufs - recorder.config.auto_level_target_lufs as f64).abs() < 0.1);
+    assert!(
+        recorder
+            .loudness_cache
+            .contains_key(&cache_key("quiet.wav"))
+    );
+    // The waveform still shows the file as stored
+    let peak = recorder.waveform.as_ref().unwrap().peaks[0];
+    assert!(peak <= 0.051);
+}
+
+#[test]
+fn cli_records_and_converts_headlessly() {
+    let _dir = TempDir::enter("cli");
+    let tone = sine(440.0, 48000, 2, 0.5);
+    let mut backend = MockBackend::new(2, 48000);
+    backend.input_signal = tone.clone();
+    let mut recorder = recorder_with(backend);
+
+    let record = cli::Cli::try_parse_from([
+        "rust_voice",
+        "record",
+        "--seconds",
+        "0",
+        "--out",
+        "take.wav",
+    ])
+    .unwrap()
+    .command
+    .unwrap();
+    cli::run(&mut recorder, record).unwrap();
+    assert_eq!(read_wav_file("take.wav").unwrap().1, tone);
+
+    let convert = cli::Cli::try_parse_from([
+        "rust_voice",
+        "convert",
+        "take.wav",
+        "take.flac",
+        "--mono",
+        "--lufs",
+        "-20",
+    ])
+    .unwrap()
+    .command
+    .unwrap();
+    cli::run(&mut recorder, convert).unwrap();
+    assert!(fs::metadata("take.flac").unwrap().len() > 0);
+
+    // Rates the encoders can't take are refused before anything is written
+    let zero_rate = [
+        "rust_voice",
+        "convert",
+        "take.wav",
+        "out.wav",
+        "--sample-rate",
+        "0",
+    ];
+    assert!(cli::Cli::try_parse_from(zero_rate).is_err());
+    let mp3_rate = [
+        "rust_voice",
+        "convert",
+        "take.wav",
+        "out.mp3",
+        "--sample-rate",
+        "7",
+    ];
+    let convert = cli::Cli::try_parse_from(mp3_rate).unwrap().command.unwrap();
+    let error = cli::run(&mut recorder, convert).unwrap_err();
+    assert!(
+        error.starts_with("MP3 export supports sample rates of 8000"),
+        "{}",
+        error
+    );
+    assert!(!std::path::Path::new("out.mp3").exists());
+
+    assert!(
+        cli::Cli::try_parse_from(["rust_voice"])
+            .unwrap()
+            .command
+            .is_none()
+    );
+}
+
+#[test]
+fn channel_gain_is_applied_per_interleaved_channel() {
+    let _dir = TempDir::enter("channel_gain");
+    let tone = sine(440.0, 48000, 2, 0.2);
+    let mut backend = MockBackend::new(2, 48000);
+    backend.input_signal = tone.clone();
+
+    let mut recorder = recorder_with(backend);
+    assert_eq!(recorder.channel_gains.lock().unwrap().len(), 2);
+    send(&mut recorder, Message::SetChannelGain(1, -6.0));
+    send(&mut recorder, Message::StartRecording);
+
+    let captured = recorder.audio_data.lock().unwrap().clone();
+    let gain = db_to_amplitude(-6.0);
+    for (got, want) in captured.chunks(2).zip(tone.chunks(2)) {
+        assert_eq!(got[0], want[0]);
+        assert!((got[1] - want[1] * gain).abs() < 1e-6);
+    }
+}
+
+#[test]
+fn preview_plays_only_the_start_and_keeps_resume_position() {
+    let _dir = TempDir::enter("preview");
+    let spec = WavSpec {
+        channels: 2,
+        sample_rate: 8000,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    write_wav_file_f32("take.wav", spec, &sine(200.0, 8000, 2, 4.0)).unwrap();
+
+    let mut recorder = recorder_with(MockBackend::new(2, 8000));
+    recorder.config.preview_secs = 1.5;
+    recorder.last_positions.insert("take.wav".into(), 16000);
+    send(&mut recorder, Message::PreviewFile("take.wav".into()));
+
+    assert_eq!(recorder.playback_state, PlaybackState::Playing);
+    assert_eq!(recorder.playback_samples.lock().unwrap().len(), 12000 * 2);
+    assert_eq!(recorder.waveform.as_ref().unwrap().total_samples, 32000 * 2);
+
+    send(&mut recorder, Message::StopPlayback);
+    assert!(!recorder.previewing);
+    assert_eq!(recorder.last_positions.get("take.wav"), Some(&16000));
+}
+
+#[test]
+fn preview_length_rejects_values_the_config_cannot_hold() {
+    let _dir = TempDir::enter("preview_length");
+    let mut recorder = recorder_with(MockBackend::new(1, 48000));
+    for value in ["inf", "1e30", "NaN", "0"] {
+        send(&mut recorder, Message::UpdatePreviewLength(value.into()));
+    }
+    assert_eq!(recorder.config.preview_secs, Config::default().preview_secs);
+
+    send(&mut recorder, Message::UpdatePreviewLength("20".into()));
+    assert_eq!(Config::load().unwrap().preview_secs, 20.0);
+}
+
+#[test]
+fn export_preview_plays_the_processed_audio_without_writing_it() {
+    let _dir = TempDir::enter("export_preview");
+    let spec = WavSpec {
+        channels: 2,
+        sample_rate: 48000,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    write_wav_file_f32("take.wav", spec, &sine(440.0, 48000, 2, 1.0)).unwrap();
+
+    let mut backend = MockBackend::new(2, 16000);
+    backend.max_render_samples = 4000;
+    let mut recorder = recorder_with(backend);
+    recorder.config.export = ExportSettings {
+        format: ExportFormat::Mp3,
+        sample_rate: ExportSampleRate::Hz(16000),
+        mono: true,
+        normalize_lufs: Some(-16.0),
+        ..ExportSettings::default()
+    };
+    send(&mut recorder, Message::PreviewExport("take.wav".into()));
+    assert_eq!(recorder.playback_state, PlaybackState::Stopped);
+    // The processing task doesn't run here, so do its work in place
+    let expected =
+        process_for_export("take.wav", &recorder.config.export, &JobControl::default()).unwrap();
+    send(
+        &mut recorder,
+        Message::ExportPreviewReady("take.wav".into(), Ok(expected.clone())),
+    );
+
+    assert_eq!(recorder.playback_state, PlaybackState::Playing);
+    let playback_spec = recorder.playback_spec.unwrap();
+    assert_eq!(
+        (playback_spec.sample_rate, playback_spec.channels),
+        (16000, 1)
+    );
+    assert_eq!(*recorder.playback_samples.lock().unwrap(), expected.samples);
+    assert!(expected.measured_lufs.is_some());
+    assert!(!std::path::Path::new("take_export.mp3").exists());
+
+    // The file's own waveform stays up, with the playhead mapped onto it
+    let data = recorder.waveform.as_ref().unwrap();
+    assert_eq!((data.sample_rate, data.channels), (48000, 2));
+    assert_eq!(recorder.playback_to_waveform(8000), 24000 * 2);
+    assert_eq!(recorder.waveform_to_playback(24000 * 2), 8000);
+
+    send(&mut recorder, Message::StopPlayback);
+    assert!(!recorder.previewing);
+    assert!(!recorder.last_positions.contains_key("take.wav"));
+}
+
+#[test]
+fn outside_wav_opens_for_editing_converted_and_paused() {
+    let dir = TempDir::enter("open_for_editing");
+    // A 24-bit take with a cue point, outside the recordings folder
+    let spec = WavSpec {
+        channels: 2,
+        sample_rate: 44100,
+        bits_per_sample: 24,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create("outside.wav", spec).unwrap();
+    for s in sine(440.0, 44100, 2, 1.0) {
+        writer.write_sample((s * 8388607.0) as i32).unwrap();
+    }
+    writer.finalize().unwrap();
+    bwf::write_chunk("outside.wav", bwf::CUE, &bwf::cue_points(&[22050])).unwrap();
+    let source = dir.path.join("outside.wav");
+    fs::create_dir("library").unwrap();
+    std::env::set_current_dir("library").unwrap();
+
+    let mut recorder = recorder_with(MockBackend::new(2, 44100));
+    recorder.screen = Screen::Settings;
+    send(
+        &mut recorder,
+        Message::OpenForEditing(source.to_str().unwrap().into()),
+    );
+
+    let (spec, samples) = read_wav_file("outside.wav").unwrap();
+    assert_eq!(spec.sample_format, hound::SampleFormat::Float);
+    assert_eq!(samples.len(), 44100 * 2);
+    assert!((samples[2] - sine(440.0, 44100, 2, 1.0)[2]).abs() < 1e-4);
+    assert!(recorder.files.contains(&"outside.wav".to_string()));
+    assert_eq!(recorder.selected_file.as_deref(), Some("outside.wav"));
+    assert_eq!(recorder.screen, Screen::Main);
+    assert_eq!(recorder.waveform.as_ref().unwrap().file, "outside.wav");
+    assert_eq!(recorder.markers, vec![22050 * 2]);
+    assert_eq!(recorder.playback_state, PlaybackState::Paused);
+    assert_eq!(*recorder.playback_position.lock().unwrap(), 0);
+
+    // Opening it again from inside the folder uses it where it is
+    send(&mut recorder, Message::OpenForEditing("outside.wav".into()));
+    assert_eq!(recorder.files.len(), 1);
+    assert_eq!(recorder.playback_state, PlaybackState::Paused);
+}
+
+#[test]
+fn post_save_command_gets_the_take_and_reports_how_it_went() {
+    let _dir = TempDir::enter("post_save_command");
+    fs::write("hook.sh", "echo \"$1\" > hooked.txt\n").unwrap();
+    fs::write(
+        "fail.sh",
+        "echo starting\necho upload refused >&2\nexit 3\n",
+    )
+    .unwrap();
+
+    fs::rename("hook.sh", "my hook.sh").unwrap();
+    assert_eq!(
+        split_command(r#"sh 'my hook.sh' --title "Take 'one'" """#),
+        Ok(vec![
+            "sh".to_string(),
+            "my hook.sh".into(),
+            "--title".into(),
+            "Take 'one'".into(),
+            String::new(),
+        ])
+    );
+    assert!(split_command("sh 'my hook.sh").is_err());
+
+    let ok = run_post_save_command("sh 'my hook.sh'", "/recordings/take one.wav").unwrap();
+    assert!(ok.contains("exit status: 0"), "{}", ok);
+    assert_eq!(
+        fs::read_to_string("hooked.txt").unwrap(),
+        "/recordings/take one.wav\n"
+    );
+
+    let failed = run_post_save_command("sh fail.sh", "take.wav").unwrap_err();
+    assert!(failed.contains("exit status: 3"), "{}", failed);
+    assert!(failed.ends_with("upload refused"), "{}", failed);
+    let missing = run_post_save_command("no_such_program_here", "take.wav").unwrap_err();
+    assert!(
+        missing.starts_with("Couldn't run post-save command"),
+        "{}",
+        missing
+    );
+
+    let mut recorder = recorder_with(MockBackend::new(2, 48000));
+    send(
+        &mut recorder,
+        Message::PostSaveCommandFinished("take.wav".into(), Err(failed)),
+    );
+    assert!(
+        recorder
+            .status_message
+            .starts_with("'take.wav': Post-save command failed")
+    );
+}
+
+#[test]
+fn lost_output_device_moves_playback_or_stops_cleanly() {
+    let _dir = TempDir::enter("output_device_lost");
+    let spec = WavSpec {
+        channels: 2,
+        sample_rate: 48000,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    write_wav_file_f32("take.wav", spec, &sine(440.0, 48000, 2, 1.0)).unwrap();
+
+    let mut backend = MockBackend::new(2, 48000);
+    backend.max_render_samples = 4096;
+    let built = Arc::clone(&backend.output_streams_built);
+    let error = Arc::clone(&backend.output_error);
+    let present = Arc::clone(&backend.output_present);
+    let mut recorder = recorder_with(backend);
+
+    // Unplugged mid-play with another device to fall back to
+    *error.lock().unwrap() = Some(StreamError::DeviceLost);
+    send(&mut recorder, Message::PlayFile("take.wav".into()));
+    let position = *recorder.playback_position.lock().unwrap();
+    assert!(position > 0);
+    send(&mut recorder, Message::Tick(Instant::now()));
+    assert_eq!(*built.lock().unwrap(), 2);
+    assert_eq!(recorder.playback_state, PlaybackState::Playing);
+    assert_eq!(*recorder.playback_position.lock().unwrap(), position);
+
+    // And with nothing left to play on
+    *error.lock().unwrap() = Some(StreamError::DeviceLost);
+    send(&mut recorder, Message::StopPlayback);
+    send(&mut recorder, Message::PlayFile("take.wav".into()));
+    *present.lock().unwrap() = false;
+    send(&mut recorder, Message::Tick(Instant::now()));
+    assert_eq!(recorder.playback_state, PlaybackState::Stopped);
+    assert!(recorder.output_stream.is_none());
+    assert!(
+        recorder
+            .status_message
+            .contains("Output device disconnected")
+    );
+}
+
+#[test]
+fn project_takes_are_named_after_the_next_missing_clip() {
+    let _dir = TempDir::enter("projects");
+    let mut backend = MockBackend::new(1, 48000);
+    backend.input_signal = sine(440.0, 48000, 1, 0.2);
+    let mut recorder = recorder_with(backend);
+
+    send(&mut recorder, Message::UpdateProjectName("Show 12".into()));
+    send(
+        &mut recorder,
+        Message::UpdateProjectClips("intro, body, outro".into()),
+    );
+    send(&mut recorder, Message::CreateProject);
+    assert_eq!(
+        load_projects(),
+        vec![recorder.active_project.clone().unwrap()]
+    );
+
+    let take = |recorder: &mut VoiceRecorder| {
+        send(recorder, Message::StartRecording);
+        send(recorder, Message::StopRecording);
+        send(recorder, Message::FinalizeRecording);
+    };
+    take(&mut recorder);
+    assert!(recorder.files.contains(&"Show 12_intro.wav".to_string()));
+
+    // Skipping ahead, then the gap is filled in afterwards
+    send(&mut recorder, Message::SelectProjectClip("outro".into()));
+    take(&mut recorder);
+    assert!(recorder.files.contains(&"Show 12_outro.wav".to_string()));
+    assert_eq!(recorder.next_project_clip().as_deref(), Some("body"));
+    take(&mut recorder);
+    assert_eq!(recorder.next_project_clip(), None);
+    assert!(recorder.status_message.contains("All clips"));
+
+    // Once the project is done, takes go back to the usual names
+    take(&mut recorder);
+    assert!(recorder.files.contains(&"recording_4.wav".to_string()));
+}
+
+#[test]
+fn four_channel_file_is_mixed_down_for_a_stereo_device() {
+    let _dir = TempDir::enter("multichannel");
+    let spec = WavSpec {
+        channels: 4,
+        sample_rate: 48000,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let frames = 4800;
+    let samples: Vec<f32> = [0.1, 0.2, 0.3, 0.4].repeat(frames);
+    write_wav_file_f32("quad.wav", spec, &samples).unwrap();
+
+    let mut backend = MockBackend::new(2, 48000);
+    backend.max_render_samples = 1024;
+    let rendered = Arc::clone(&backend.rendered);
+    let mut recorder = recorder_with(backend);
+    recorder.config.output_start_ramp_ms = 0;
+    send(&mut recorder, Message::PlayFile("quad.wav".into()));
+
+    let rendered = rendered.lock().unwrap();
+    for frame in rendered.chunks_exact(2) {
+        assert!((frame[0] - 0.2).abs() < 1e-6 && (frame[1] - 0.3).abs() < 1e-6);
+    }
+    // Position still counts the file's own samples
+    assert_eq!(
+        *recorder.playback_position.lock().unwrap(),
+        rendered.len() * 2
+    );
+    assert!(
+        recorder
+            .status_log
+            .iter()
+            .any(|(_, line)| line.contains("mixing them down to 2"))
+    );
+}
+
+#[test]
+fn surround_files_are_folded_down_with_itu_coefficients() {
+    let g = std::f32::consts::FRAC_1_SQRT_2;
+    // 5.1: L R C LFE Ls Rs
+    let frame = [0.1, 0.2, 0.4, 1.0, 0.3, 0.5];
+    let mut out = [0.0; 2];
+    assert_eq!(remix_frames(&frame, 6, &mut out, 2), 6);
+    assert!(
+        (out[0] - (0.1 + 0.4 * g + 0.3 * g)).abs() < 1e-6,
+        "{:?}",
+        out
+    );
+    assert!(
+        (out[1] - (0.2 + 0.4 * g + 0.5 * g)).abs() < 1e-6,
+        "{:?}",
+        out
+    );
+
+    // 7.1 adds a second surround pair; a mono device gets the average
+    let frame = [0.1, 0.2, 0.4, 1.0, 0.3, 0.5, 0.2, 0.1];
+    let mut mono = [0.0; 1];
+    remix_frames(&frame, 8, &mut mono, 1);
+    let left = 0.1 + (0.4 + 0.3 + 0.2) * g;
+    let right = 0.2 + (0.4 + 0.5 + 0.1) * g;
+    assert!((mono[0] - (left + right) * 0.5).abs() < 1e-6);
+}
+
+#[test]
+fn dedicated_keys_only_touch_playback_or_recording() {
+    let _dir = TempDir::enter("dedicated_keys");
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate: 48000,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    write_wav_file_f32("take.wav", spec, &sine(440.0, 48000, 1, 0.5)).unwrap();
+    let mut recorder = recorder_with(MockBackend::new(1, 48000));
+
+    send(&mut recorder, Message::SelectFile("take.wav".into()));
+    send(&mut recorder, Message::TogglePlayback);
+    assert_eq!(recorder.playback_state, PlaybackState::Playing);
+    send(&mut recorder, Message::TogglePlayback);
+    assert_eq!(recorder.playback_state, PlaybackState::Paused);
+    send(&mut recorder, Message::ToggleRecording);
+    assert!(!recorder.recording_phase.is_recording());
+    send(&mut recorder, Message::StopPlayback);
+
+    // Space can be pinned to recording only
+    recorder.config.space_action = SpaceAction::Record;
+    send(&mut recorder, Message::Toggle);
+    assert!(recorder.recording_phase.is_recording());
+    send(&mut recorder, Message::TogglePlayback);
+    assert_eq!(recorder.playback_state, PlaybackState::Stopped);
+    send(&mut recorder, Message::Toggle);
+    assert!(!recorder.recording_phase.is_recording());
+}
+
+#[test]
+fn export_queue_retries_then_reports_failures() {
+    let _dir = TempDir::enter("export_queue");
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate: 48000,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    write_wav_file_f32("good.wav", spec, &sine(440.0, 48000, 1, 0.1)).unwrap();
+    let queue = Mutex::new(
+        ["good.wav", "missing.wav"]
+            .map(|file| QueueItem {
+                file: file.into(),
+                status: QueueStatus::Waiting,
+            })
+            .to_vec(),
+    );
+
+    let started = Instant::now();
+    let counts = run_export_queue(
+        &queue,
+        &ExportSettings::default(),
+        2,
+        Duration::from_millis(20),
+        &JobControl::default(),
+    );
+    assert_eq!(counts, (1, 1));
+    // Two retries, backing off 20 ms then 40 ms
+    assert!(started.elapsed() >= Duration::from_millis(60));
+
+    let items = queue.lock().unwrap();
+    assert_eq!(items[0].status, QueueStatus::Done("good_export.wav".into()));
+    assert!(matches!(items[1].status, QueueStatus::Failed(_)));
+    assert!(fs::metadata("good_export.wav").is_ok());
+}
+
+#[test]
+fn processing_used_is_noted_in_the_wav() {
+    let _dir = TempDir::enter("processing_note");
+    let mut backend = MockBackend::new(2, 48000);
+    backend.input_signal = sine(440.0, 48000, 2, 0.2);
+    let mut recorder = recorder_with(backend);
+    recorder.config.processing.dc_block_enabled = true;
+    recorder.config.bwf_metadata = true;
+    recorder.recording_description = "Interview".into();
+    send(&mut recorder, Message::SetChannelGain(1, -6.0));
+
+    send(&mut recorder, Message::StartRecording);
+    send(&mut recorder, Message::StopRecording);
+    send(&mut recorder, Message::FinalizeRecording);
+    send(&mut recorder, Message::SelectFile("recording_1.wav".into()));
+    // The description goes into this take's bext chunk and no further
+    let bext = bwf::read_chunk("recording_1.wav", bwf::BEXT)
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        bwf::Bext::from_bytes(&bext).unwrap().description,
+        "Interview"
+    );
+    assert!(recorder.recording_description.is_empty());
+
+    let comment = recorder.selected_comment.clone().unwrap();
+    assert!(comment.contains("ch1 +0.0 dB, ch2 -6.0 dB"), "{}", comment);
+    assert!(comment.contains("DC block"), "{}", comment);
+    // Readers that don't know the chunk still see a normal WAV
+    assert!(read_wav_file("recording_1.wav").is_ok());
+}
+
+#[test]
+fn latency_is_measured_from_the_looped_back_click() {
+    let _dir = TempDir::enter("latency");
+    let delay = 960;
+    let mut input = vec![0.0; 48000];
+    let click = latency_click(48000);
+    input[delay..delay + click.len()].copy_from_slice(&click);
+
+    let mut backend = MockBackend::new(1, 48000);
+    backend.input_signal = input;
+    backend.max_render_samples = 48000;
+    let mut recorder = recorder_with(backend);
+
+    send(&mut recorder, Message::MeasureLatency);
+    send(
+        &mut recorder,
+        Message::Tick(Instant::now() + Duration::from_secs(2)),
+    );
+    assert!(recorder.latency_test.is_none());
+    assert_eq!(recorder.config.round_trip_latency_ms, Some(20.0));
+
+    // A take played along to the metronome is moved back onto the beat
+    for metronome in [false, true] {
+        if metronome {
+            send(&mut recorder, Message::ToggleMetronome);
+        }
+        send(&mut recorder, Message::StartRecording);
+        send(&mut recorder, Message::StopRecording);
+        send(&mut recorder, Message::FinalizeRecording);
+    }
+    let (_, plain) = read_wav_file("recording_1.wav").unwrap();
+    let (_, aligned) = read_wav_file("recording_2.wav").unwrap();
+    assert_eq!(aligned, plain[delay..]);
+
+    // With nothing looped back, the user is told how to route it
+    let mut backend = MockBackend::new(1, 48000);
+    backend.input_signal = vec![0.0; 48000];
+    backend.max_render_samples = 48000;
+    let mut recorder = recorder_with(backend);
+    send(&mut recorder, Message::MeasureLatency);
+    send(
+        &mut recorder,
+        Message::Tick(Instant::now() + Duration::from_secs(2)),
+    );
+    assert!(recorder.status_message.contains("loopback"));
+}
+
+#[test]
+fn color_labels_persist_and_filter_the_list() {
+    let _dir = TempDir::enter("labels");
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate: 48000,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    for name in ["keep.wav", "maybe.wav"] {
+        write_wav_file_f32(name, spec, &sine(440.0, 48000, 1, 0.1)).unwrap();
+    }
+    let mut recorder = recorder_with(MockBackend::new(1, 48000));
+    send(&mut recorder, Message::SelectFile("keep.wav".into()));
+    send(&mut recorder, Message::SetLabel(Some(ColorLabel::Green)));
+
+    let reopened = recorder_with(MockBackend::new(1, 48000));
+    assert_eq!(
+        reopened.notes.get("keep.wav").and_then(|n| n.label),
+        Some(ColorLabel::Green)
+    );
+
+    recorder.search_query = "label:green".into();
+    assert!(recorder.file_matches_search("keep.wav"));
+    assert!(!recorder.file_matches_search("maybe.wav"));
+    recorder.search_query = "label:red".into();
+    assert!(!recorder.file_matches_search("keep.wav"));
+
+    send(&mut recorder, Message::SetLabel(None));
+    assert!(!recorder.notes.contains_key("keep.wav"));
+}
+
+/// "Hears" how many samples it was given, and "done" at the end.
+struct CountingTranscriber;
+
+impl transcribe::Transcriber for CountingTranscriber {
+    fn accept(&mut self, samples: &[f32]) -> Option<String> {
+        Some(format!("{} samples", samples.len()))
+    }
+
+    fn finish(&mut self) -> Option<String> {
+        Some("done".into())
+    }
+}
+
+#[test]
+fn live_captions_are_saved_beside_the_take() {
+    let _dir = TempDir::enter("live_captions");
+    let mut backend = MockBackend::new(2, 48000);
+    backend.input_signal = sine(440.0, 48000, 2, 0.2);
+    let mut recorder = recorder_with(backend);
+    recorder.config.live_captions = true;
+    recorder.captions_engine = |_| Ok(Box::new(CountingTranscriber));
+
+    send(&mut recorder, Message::StartRecording);
+    send(&mut recorder, Message::Tick(Instant::now()));
+    send(&mut recorder, Message::StopRecording);
+    send(&mut recorder, Message::FinalizeRecording);
+
+    // Written once the engine has caught up, off the UI thread
+    let started = Instant::now();
+    while !fs::read_to_string("recording_1.transcript.txt").is_ok_and(|t| t.ends_with("done\n")) {
+        assert!(started.elapsed() < Duration::from_secs(10));
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    // Fed as mono: 0.2 s at 48 kHz
+    assert_eq!(
+        fs::read_to_string("recording_1.transcript.txt").unwrap(),
+        "9600 samples\ndone\n"
+    );
+
+    // Without an engine the take is still recorded
+    recorder.captions_engine = |_| Err("no model".into());
+    send(&mut recorder, Message::StartRecording);
+    assert!(recorder.recording_phase.is_recording());
+    // The engine is built on the caption thread, so the failure shows up on a tick
+    let started = Instant::now();
+    while !recorder
+        .status_message
+        .contains("Live captions unavailable")
+    {
+        assert!(started.elapsed() < Duration::from_secs(10));
+        std::thread::sleep(Duration::from_millis(5));
+        send(&mut recorder, Message::Tick(Instant::now()));
+    }
+    assert!(recorder.captions.is_none());
+    assert!(recorder.recording_phase.is_recording());
+}
+
+#[test]
+fn timer_format_follows_precision_and_hours_settings() {
+    let elapsed = Duration::from_millis(83_456);
+    assert_eq!(
+        format_timer(elapsed, TimerPrecision::Hundredths, false),
+        "01:23.45"
+    );
+    assert_eq!(
+        format_timer(elapsed, TimerPrecision::Tenths, false),
+        "01:23.4"
+    );
+    assert_eq!(
+        format_timer(elapsed, TimerPrecision::Seconds, true),
+        "00:01:23"
+    );
+}
+
+#[test]
+fn timer_rolls_minutes_over_into_hours() {
+    let hour = Duration::from_secs(3600);
+    assert_eq!(
+        format_timer(hour, TimerPrecision::Hundredths, false),
+        "01:00:00.00"
+    );
+    assert_eq!(
+        format_timer(
+            hour * 2 + Duration::from_millis(61_230),
+            TimerPrecision::Hundredths,
+            false
+        ),
+        "02:01:01.23"
+    );
+    assert_eq!(
+        format_timer(
+            hour - Duration::from_millis(10),
+            TimerPrecision::Hundredths,
+            false
+        ),
+        "59:59.99"
+    );
+}
+
+#[test]
+fn mono_check_flags_out_of_phase_channels() {
+    let centred = sine(440.0, 48000, 2, 1.0);
+    let check = mono_compatibility(&centred, 48000).unwrap();
+    assert!(check.difference_db.unwrap().abs() < 0.1);
+    assert!(check.correlation > 0.99 && !check.cancels());
+
+    // Right channel inverted: the fold-down is silence
+    let inverted: Vec<f32> = centred
+        .chunks_exact(2)
+        .flat_map(|f| [f[0], -f[1]])
+        .collect();
+    let check = mono_compatibility(&inverted, 48000).unwrap();
+    assert_eq!(check.difference_db, None);
+    assert!(check.correlation < -0.99 && check.cancels());
+}
+
+#[test]
+fn recursive_listing_walks_subfolders_to_the_set_depth() {
+    let _dir = TempDir::enter("recursive_listing");
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate: 48000,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    fs::create_dir_all("show/ep1/raw").unwrap();
+    for path in [
+        "top.wav",
+        "show/intro.wav",
+        "show/ep1/take.wav",
+        "show/ep1/raw/deep.wav",
+    ] {
+        write_wav_file_f32(path, spec, &sine(440.0, 48000, 1, 0.05)).unwrap();
+    }
+
+    assert_eq!(list_wav_files(None), vec!["top.wav"]);
+    assert_eq!(
+        list_wav_files(Some(2)),
+        vec!["top.wav", "show/intro.wav", "show/ep1/take.wav"]
+    );
+
+    // Renaming keeps a file in its folder
+    let mut recorder = recorder_with(MockBackend::new(1, 48000));
+    send(&mut recorder, Message::ToggleRecursiveListing(true));
+    assert_eq!(recorder.files.len(), 4);
+    send(&mut recorder, Message::StartRename("show/intro.wav".into()));
+    send(&mut recorder, Message::UpdateRenameName("opening".into()));
+    send(&mut recorder, Message::ConfirmRename);
+    assert!(recorder.files.contains(&"show/opening.wav".to_string()));
+}
+
+#[test]
+fn processing_chain_is_restored_and_can_be_reset() {
+    let _dir = TempDir::enter("processing_chain");
+    // Settings files from before the chain was grouped still load
+    fs::write(
+        CONFIG_PATH,
+        r#"{"dc_block_enabled": true, "trim_threshold_db": -40.0}"#,
+    )
+    .unwrap();
+    let mut recorder = recorder_with(MockBackend::new(2, 48000));
+    assert!(recorder.config.processing.dc_block_enabled);
+    assert_eq!(recorder.config.processing.trim_threshold_db, -40.0);
+
+    send(&mut recorder, Message::SetChannelGain(1, -6.0));
+    send(&mut recorder, Message::SaveChannelGains);
+    send(&mut recorder, Message::UpdateNormalizeTarget("-19".into()));
+
+    let mut reopened = recorder_with(MockBackend::new(2, 48000));
+    let gains = reopened.channel_gains.lock().unwrap().clone();
+    assert!((gains[1] - db_to_amplitude(-6.0)).abs() < 1e-4);
+    assert_eq!(reopened.normalize_input, "-19");
+
+    send(&mut reopened, Message::ResetProcessing);
+    assert_eq!(reopened.config.processing, ProcessingChain::default());
+    assert_eq!(*reopened.channel_gains.lock().unwrap(), vec![1.0, 1.0]);
+    assert_eq!(
+        Config::load().unwrap().processing,
+        ProcessingChain::default()
+    );
+}
+
+#[test]
+fn arrow_keys_skip_within_the_playing_file() {
+    let _dir = TempDir::enter("skip");
+    let spec = WavSpec {
+        channels: 2,
+        sample_rate: 8000,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    write_wav_file_f32("take.wav", spec, &sine(440.0, 8000, 2, 30.0)).unwrap();
+
+    let mut backend = MockBackend::new(2, 8000);
+    backend.max_render_samples = 0;
+    let mut recorder = recorder_with(backend);
+    send(&mut recorder, Message::PlayFile("take.wav".into()));
+    send(&mut recorder, Message::PausePlayback);
+    let spec = recorder.playback_spec.unwrap();
+    let second = spec.sample_rate as usize * spec.channels as usize;
+    let len = recorder.playback_samples.lock().unwrap().len();
+    let position = Arc::clone(&recorder.playback_position);
+    let position = || *position.lock().unwrap();
+
+    send(&mut recorder, Message::SkipForward);
+    assert_eq!(position(), 10 * second);
+    send(&mut recorder, Message::SkipBackward);
+    assert_eq!(position(), 5 * second);
+    assert_eq!(recorder.elapsed_time, Duration::from_secs(5));
+    assert_eq!(recorder.playback_state, PlaybackState::Paused);
+
+    // Clamped at the start and at the last frame
+    send(&mut recorder, Message::SkipBackward);
+    send(&mut recorder, Message::SkipBackward);
+    assert_eq!(position(), 0);
+    send(&mut recorder, Message::UpdateSkipForward("60".into()));
+    assert!(len < 60 * second);
+    send(&mut recorder, Message::SkipForward);
+    assert_eq!(position(), len - 2);
+    assert_eq!(recorder.playback_state, PlaybackState::Paused);
+}
+
+#[test]
+fn takes_can_be_named_after_their_first_words() {
+    assert_eq!(
+        spoken_title_filename("  Meeting: agenda for Q3! and more").as_deref(),
+        Some("meeting_agenda_for_q3_and.wav")
+    );
+    assert_eq!(spoken_title_filename(" ?! "), None);
+    let started = std::time::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    let name = timestamp_filename(started);
+    assert!(name.starts_with("recording_2023-11-1"), "{}", name);
+    assert_eq!(name.len(), "recording_2023-11-14_22-13-20.wav".len());
+
+    let _dir = TempDir::enter("name_by_speech");
+    let mut backend = MockBackend::new(1, 48000);
+    backend.input_signal = sine(440.0, 48000, 1, 0.2);
+    let mut recorder = recorder_with(backend);
+    recorder.config.name_by_speech = true;
+
+    let stop_take = |recorder: &mut VoiceRecorder| {
+        send(recorder, Message::StartRecording);
+        send(recorder, Message::StopRecording);
+        let RecordingPhase::Stopping { since } = recorder.recording_phase else {
+            panic!("not stopping: {:?}", recorder.recording_phase);
+        };
+        send(recorder, Message::Tick(since + Duration::from_secs(1)));
+        assert_eq!(recorder.recording_phase, RecordingPhase::Finalizing);
+        recorder.take_name.clone().unwrap()
+    };
+    let offered = stop_take(&mut recorder);
+    assert!(offered.starts_with("recording_20"), "{}", offered);
+    let spoken = Message::TakeNameSuggested(Ok(Some("meeting_agenda_for_q3_part.wav".into())));
+    send(&mut recorder, spoken.clone());
+    send(&mut recorder, Message::FinalizeRecording);
+    assert!(fs::metadata("meeting_agenda_for_q3_part.wav").is_ok());
+
+    // The same words again would clash, so the time stays on offer
+    let offered = stop_take(&mut recorder);
+    send(&mut recorder, spoken.clone());
+    assert_eq!(recorder.take_name.as_ref(), Some(&offered));
+
+    // A name typed over the offer is kept, whatever comes back later
+    send(&mut recorder, Message::UpdateTakeName("Intro".into()));
+    send(
+        &mut recorder,
+        Message::TakeNameSuggested(Ok(Some("other.wav".into()))),
+    );
+    send(&mut recorder, Message::FinalizeRecording);
+    assert!(fs::metadata("Intro.wav").is_ok());
+    assert!(fs::metadata(&offered).is_err());
+}
+
+#[test]
+fn silence_is_inserted_at_the_waveform_cursor() {
+    assert_eq!(
+        insert_silence(&[1.0, 2.0, 3.0, 4.0], 2, 3, 1),
+        vec![1.0, 2.0, 0.0, 0.0, 3.0, 4.0]
+    );
+    assert_eq!(insert_silence(&[1.0], 1, 5, 2), vec![1.0, 0.0, 0.0]);
+
+    let _dir = TempDir::enter("insert_silence");
+    let spec = WavSpec {
+        channels: 2,
+        sample_rate: 8000,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let original = sine(440.0, 8000, 2, 1.0);
+    write_wav_file_f32("take.wav", spec, &original).unwrap();
+    save_marks("take.wav", &[(0.25, String::new()), (0.75, "b".into())]).unwrap();
+    bwf::write_chunk("take.wav", bwf::CUE, &bwf::cue_points(&[2000, 6000])).unwrap();
+
+    let mut backend = MockBackend::new(2, 8000);
+    backend.max_render_samples = 0;
+    let mut recorder = recorder_with(backend);
+    send(&mut recorder, Message::SelectFile("take.wav".into()));
+    send(&mut recorder, Message::InsertSilence);
+    assert!(recorder.status_message.contains("Click the waveform"));
+
+    send(&mut recorder, Message::SeekWaveform(0.5));
+    send(&mut recorder, Message::UpdateSilenceLength("0.25".into()));
+    send(&mut recorder, Message::InsertSilence);
+    assert_eq!(recorder.playback_state, PlaybackState::Stopped);
+
+    let (_, edited) = read_wav_file("take.wav").unwrap();
+    assert_eq!(edited.len(), original.len() + 4000);
+    assert_eq!(&edited[..8000], &original[..8000]);
+    assert!(edited[8000..12000].iter().all(|&s| s == 0.0));
+    assert_eq!(&edited[12000..], &original[8000..]);
+
+    // Markers after the cursor move with the audio, in the sidecar and the cue chunk
+    assert_eq!(
+        load_marks("take.wav").unwrap(),
+        vec![(0.25, String::new()), (1.0, "b".into())]
+    );
+    let cue = bwf::read_chunk("take.wav", bwf::CUE).unwrap().unwrap();
+    assert_eq!(bwf::read_cue_points(&cue), vec![2000, 8000]);
+    assert_eq!(recorder.markers, vec![4000, 16000]);
+}
+
+#[test]
+fn speech_timeline_finds_speech_and_jumps_to_it() {
+    let floor = |secs: f32| -> Vec<f32> {
+        (0..(secs * 8000.0) as usize)
+            .map(|i| if i % 2 == 0 { 0.002 } else { -0.002 })
+            .collect()
+    };
+    let mut samples = floor(1.0);
+    samples.extend(sine(300.0, 8000, 1, 1.0));
+    // A short pause is part of the same stretch of speech
+    samples.extend(floor(0.2));
+    samples.extend(sine(300.0, 8000, 1, 0.6));
+    samples.extend(floor(1.2));
+    samples.extend(sine(300.0, 8000, 1, 1.0));
+    // A click is too short to count
+    samples.extend(floor(0.5));
+    samples.extend(sine(300.0, 8000, 1, 0.04));
+    samples.extend(floor(0.5));
+    let total = samples.len() as f32 / 8000.0;
+
+    let speech = waveform::speech_segments(&samples, 1, 8000);
+    assert_eq!(speech.len(), 2, "{:?}", speech);
+    assert!((speech[0].start - 1.0 / total).abs() < 0.005);
+    assert!((speech[0].end - 2.8 / total).abs() < 0.005);
+    assert!((speech[1].start - 4.0 / total).abs() < 0.005);
+    assert!(waveform::speech_segments(&floor(2.0), 1, 8000).is_empty());
+
+    let _dir = TempDir::enter("speech_timeline");
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate: 8000,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    write_wav_file_f32("meeting.wav", spec, &samples).unwrap();
+    let mut backend = MockBackend::new(1, 8000);
+    backend.max_render_samples = 0;
+    let mut recorder = recorder_with(backend);
+    send(&mut recorder, Message::SelectFile("meeting.wav".into()));
+
+    // Clicking in a pause jumps to the next speech; in speech, to where it starts
+    send(&mut recorder, Message::JumpToSpeech(3.5 / total));
+    assert_eq!(recorder.playback_state, PlaybackState::Playing);
+    let position = *recorder.playback_position.lock().unwrap() as f32 / 8000.0;
+    assert!((position - 4.0).abs() < 0.05, "{}", position);
+    send(&mut recorder, Message::JumpToSpeech(2.0 / total));
+    let position = *recorder.playback_position.lock().unwrap() as f32 / 8000.0;
+    assert!((position - 1.0).abs() < 0.05, "{}", position);
+
+    // Nothing after the last speech
+    send(&mut recorder, Message::JumpToSpeech(0.99));
+    let position = *recorder.playback_position.lock().unwrap() as f32 / 8000.0;
+    assert!((position - 1.0).abs() < 0.05, "{}", position);
+}
+
+#[test]
+fn level_history_keeps_one_peak_per_interval() {
+    let _dir = TempDir::enter("level_history");
+    let mut backend = MockBackend::new(1, 48000);
+    backend.input_signal = sine(440.0, 48000, 1, 0.2);
+    let mut recorder = recorder_with(backend);
+
+    send(&mut recorder, Message::StartRecording);
+    let start = Instant::now();
+    let interval = Duration::from_millis(LEVEL_HISTORY_INTERVAL_MS);
+    send(&mut recorder, Message::Tick(start));
+    assert!(recorder.level_history.is_empty());
+    send(&mut recorder, Message::Tick(start + interval));
+    send(&mut recorder, Message::Tick(start + interval * 2));
+    assert_eq!(recorder.level_history.len(), 2);
+    assert!(
+        (recorder.level_history[0] + 6.0).abs() < 0.1,
+        "{:?}",
+        recorder.level_history
+    );
+    assert!(recorder.level_history[1] < -60.0);
+
+    // Only the last 30 seconds are kept
+    for i in 3..LEVEL_HISTORY_LEN as u32 + 10 {
+        send(&mut recorder, Message::Tick(start + interval * i));
+    }
+    assert_eq!(recorder.level_history.len(), LEVEL_HISTORY_LEN);
+
+    send(&mut recorder, Message::StopRecording);
+    send(&mut recorder, Message::FinalizeRecording);
+    send(&mut recorder, Message::StartRecording);
+    assert!(recorder.level_history.is_empty());
+}
+
+#[test]
+fn recording_prefers_48k_when_the_device_supports_it() {
+    let _dir = TempDir::enter("preferred_rate");
+    let mut recorder = recorder_with(MockBackend::new(1, 44100));
+    send(&mut recorder, Message::StartRecording);
+    assert_eq!(recorder.recording_sample_rate, 48000);
+    assert_eq!(recorder.status_message, "Recording...");
+    send(&mut recorder, Message::StopRecording);
+    send(&mut recorder, Message::FinalizeRecording);
+
+    // A device fixed at 44.1 kHz records at its own rate, and says so
+    let mut backend = MockBackend::new(1, 44100);
+    backend.input_formats[0].min_sample_rate = 44100;
+    backend.input_formats[0].max_sample_rate = 44100;
+    let mut recorder = recorder_with(backend);
+    send(&mut recorder, Message::StartRecording);
+    assert_eq!(recorder.recording_sample_rate, 44100);
+    assert!(
+        recorder
+            .status_message
+            .contains("device can't record at 48000 Hz, using 44100 Hz")
+    );
+}
+
+#[test]
+fn test_tone_plays_through_the_output() {
+    for waveform in ToneWaveform::ALL {
+        let tone = ToneSpec {
+            waveform,
+            frequency: 1000.0,
+            level_db: -6.0,
+        };
+        let samples = test_tone(&tone, 48000, 0.5);
+        assert_eq!(samples.len(), 24000);
+        let peak = samples.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+        assert!(
+            (peak - 0.501).abs() < 0.01,
+            "{} peaks at {}",
+            waveform,
+            peak
+        );
+    }
+
+    let _dir = TempDir::enter("test_tone");
+    let mut backend = MockBackend::new(2, 48000);
+    backend.max_render_samples = 1024;
+    let rendered = Arc::clone(&backend.rendered);
+    let mut recorder = recorder_with(backend);
+    send(&mut recorder, Message::UpdateToneFrequency("440".into()));
+    let tone = recorder.tone_spec().unwrap();
+    assert_eq!(tone.frequency, 440.0);
+    assert_eq!(tone.level_db, -18.0);
+
+    send(&mut recorder, Message::PlayTestTone(tone));
+    assert_eq!(recorder.playback_state, PlaybackState::Playing);
+    assert!(
+        recorder
+            .status_message
+            .contains("Test tone (sine, 440 Hz, -18 dBFS)")
+    );
+    let rendered = rendered.lock().unwrap().clone();
+    assert_eq!(rendered.len(), 1024);
+    assert_eq!(rendered[2], rendered[3]);
+    assert!(rendered.iter().any(|&s| s != 0.0));
+
+    send(&mut recorder, Message::StopPlayback);
+    assert!(recorder.last_positions.is_empty());
+
+    send(&mut recorder, Message::UpdateToneLevel("3".into()));
+    assert!(recorder.tone_spec().is_none());
+}
+
+#[test]
+fn truncated_file_plays_what_it_can_and_can_be_repaired() {
+    let _dir = TempDir::enter("damaged_wav");
+    let spec = WavSpec {
+        channels: 2,
+        sample_rate: 8000,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let original = sine(440.0, 8000, 2, 1.0);
+    write_wav_file_f32("take.wav", spec, &original).unwrap();
+    // Cut the data off part way through a frame
+    let bytes = fs::read("take.wav").unwrap();
+    fs::write("take.wav", &bytes[..bytes.len() - 4000 * 4 - 6]).unwrap();
+    assert!(read_wav_file("take.wav").is_err());
+
+    let mut backend = MockBackend::new(2, 8000);
+    backend.max_render_samples = 0;
+    let mut recorder = recorder_with(backend);
+    send(&mut recorder, Message::SelectFile("take.wav".into()));
+    send(&mut recorder, Message::PlayFile("take.wav".into()));
+    assert_eq!(recorder.playback_state, PlaybackState::Playing);
+    assert!(
+        recorder
+            .status_log
+            .iter()
+            .any(|(_, m)| m.contains("'take.wav' is damaged"))
+    );
+    let readable = original.len() - 4000 - 2;
+    assert_eq!(recorder.playback_samples.lock().unwrap().len(), readable);
+
+    send(&mut recorder, Message::StopPlayback);
+    send(&mut recorder, Message::RepairFile);
+    assert!(recorder.status_message.contains("Repaired 'take.wav'"));
+    let (_, repaired) = read_wav_file("take.wav").unwrap();
+    assert_eq!(repaired, original[..readable]);
+    assert!(recorder.damaged_files.is_empty());
+}
+
+#[test]
+fn meter_follows_the_chosen_mode_and_ballistics() {
+    let tick = Duration::from_millis(100);
+    assert_eq!(meter_ballistics(0.1, 0.5, tick, 0.0, 20.0), 0.5);
+    // One attack time constant covers ~63% of the rise
+    let risen = meter_ballistics(0.0, 1.0, tick, 100.0, 20.0);
+    assert!((risen - 0.632).abs() < 0.001);
+    // 20 dB/s for 100 ms is a 2 dB fall
+    let fallen = meter_ballistics(1.0, 0.0, tick, 0.0, 20.0);
+    assert!((fallen - db_to_amplitude(-2.0)).abs() < 1e-6);
+    assert_eq!(meter_ballistics(1.0, 0.9, tick, 0.0, 20.0), 0.9);
+
+    let _dir = TempDir::enter("meter_mode");
+    let mut backend = MockBackend::new(1, 48000);
+    backend.input_signal = sine(440.0, 48000, 1, 0.2);
+    let mut recorder = recorder_with(backend);
+    send(&mut recorder, Message::SelectMeterMode(MeterMode::Rms));
+    send(&mut recorder, Message::ArmRecording);
+    send(&mut recorder, Message::Tick(Instant::now()));
+    // A 0.5 peak sine has an RMS of 0.5 / sqrt(2)
+    assert!((recorder.meter_level - 0.3536).abs() < 0.001);
+}
+
+#[test]
+fn second_output_follows_playback_at_its_own_rate() {
+    let _dir = TempDir::enter("second_output");
+    let spec = WavSpec {
+        channels: 2,
+        sample_rate: 48000,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let original = sine(440.0, 48000, 2, 1.0);
+    write_wav_file_f32("take.wav", spec, &original).unwrap();
+
+    let mut backend = MockBackend::new(2, 48000);
+    backend.max_render_samples = 1024;
+    // All in one callback, as the main output doesn't move while it's pulled
+    backend.block_frames = 512;
+    let headphones = StreamFormat {
+        channels: 2,
+        sample_rate: 24000,
+        sample_format: SampleFormat::F32,
+        buffer_size: BufferSize::Default,
+    };
+    backend.named_outputs = vec![("Headphones".into(), headphones)];
+    let second = Arc::clone(&backend.named_rendered);
+    let mut recorder = recorder_with(backend);
+    recorder.config.output_start_ramp_ms = 0;
+
+    send(&mut recorder, Message::OpenSettings);
+    assert_eq!(recorder.output_devices, vec!["Headphones".to_string()]);
+    send(
+        &mut recorder,
+        Message::SelectSecondOutput(Some("Headphones".into())),
+    );
+    send(&mut recorder, Message::SetSecondOutputVolume(0.5));
+    send(&mut recorder, Message::SaveOutputVolumes);
+    send(&mut recorder, Message::CloseSettings);
+    send(&mut recorder, Message::PlayFile("take.wav".into()));
+    assert!(recorder.second_output_stream.is_some());
+
+    // Picks up where the main output is (512 frames in), at half the rate
+    let second = second.lock().unwrap();
+    assert_eq!(second.len(), 1024);
+    for (k, frame) in second.chunks(2).enumerate() {
+        let source = (512 + 2 * k) * 2;
+        assert!((frame[0] - original[source] * 0.5).abs() < 1e-6);
+        assert!((frame[1] - original[source + 1] * 0.5).abs() < 1e-6);
+    }
+    assert_eq!(recorder.config.second_output_volume, 0.5);
+
+    send(&mut recorder, Message::StopPlayback);
+    assert!(recorder.second_output_stream.is_none());
+}
+
+#[test]
+fn recent_folders_switch_the_library_and_forget_missing_ones() {
+    let dir = TempDir::enter("recent_folders");
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate: 8000,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    for folder in ["a", "b"] {
+        fs::create_dir(folder).unwrap();
+        write_wav_file_f32(&format!("{}/{}.wav", folder, folder), spec, &[0.0; 8]).unwrap();
+    }
+    let path = |folder: &str| fs::canonicalize(dir.path.join(folder)).unwrap();
+    let root = fs::canonicalize(&dir.path).unwrap().display().to_string();
+
+    // b keeps settings of its own
+    fs::write(format!("b/{}", CONFIG_PATH), "{}").unwrap();
+
+    let mut recorder = recorder_with(MockBackend::new(1, 48000));
+    send(&mut recorder, Message::OpenFolder("a".into()));
+    assert_eq!(recorder.files, vec!["a.wav".to_string()]);
+    assert_eq!(std::env::current_dir().unwrap(), path("a"));
+    send(&mut recorder, Message::AssignAbSlot(0, "a.wav".into()));
+    recorder.markers = vec![4];
+    recorder.marker_file = Some("a.wav".into());
+    let journal = recorder.recovery_path.clone();
+    let b = path("b").display().to_string();
+    send(&mut recorder, Message::OpenFolder(b.clone()));
+    assert_eq!(recorder.files, vec!["b.wav".to_string()]);
+    // Nothing about a's files is carried over, but the crash journal stays put
+    assert_eq!(recorder.ab_slots, [None, None]);
+    assert!(recorder.markers.is_empty() && recorder.marker_file.is_none());
+    assert_eq!(recorder.recovery_path, journal);
+    assert_eq!(
+        std::path::Path::new(&journal),
+        std::path::Path::new(&root).join(RECOVERY_PATH)
+    );
+    let a = path("a").display().to_string();
+    assert_eq!(
+        recorder.config.recent_folders,
+        vec![b.clone(), a.clone(), root]
+    );
+
+    // The settings went along with us, but are saved where they came from
+    assert_eq!(fs::read_to_string(CONFIG_PATH).unwrap(), "{}");
+    assert!(fs::metadata(path("a").join(CONFIG_PATH)).is_err());
+    let saved: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(dir.path.join(CONFIG_PATH)).unwrap()).unwrap();
+    assert_eq!(saved["recent_folders"][0], b.as_str());
+
+    send(&mut recorder, Message::OpenFolder(a.clone()));
+    fs::remove_dir_all(&b).unwrap();
+    send(&mut recorder, Message::OpenFolder(b.clone()));
+    assert!(recorder.status_message.contains("Couldn't open folder"));
+    assert!(!recorder.config.recent_folders.contains(&b));
+    assert_eq!(recorder.config.recent_folders[0], a);
+}
+
+#[test]
+fn playback_speed_is_remembered_per_file() {
+    let _dir = TempDir::enter("playback_speed");
+    let spec = WavSpec {
+        channels: 2,
+        sample_rate: 48000,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let original = sine(440.0, 48000, 2, 1.0);
+    write_wav_file_f32("take.wav", spec, &original).unwrap();
+    write_wav_file_f32("other.wav", spec, &original).unwrap();
+
+    let mut backend = MockBackend::new(2, 48000);
+    backend.max_render_samples = 1024;
+    let rendered = Arc::clone(&backend.rendered);
+    let mut recorder = recorder_with(backend);
+    recorder.config.output_start_ramp_ms = 0;
+    send(&mut recorder, Message::SelectFile("take.wav".into()));
+    send(&mut recorder, Message::SetPlaybackSpeed(1.5));
+    send(&mut recorder, Message::SavePlaybackSpeed);
+
+    // Another file plays at normal speed...
+    send(&mut recorder, Message::PlayFile("other.wav".into()));
+    assert_eq!(recorder.playback_speed(), 1.0);
+    send(&mut recorder, Message::StopPlayback);
+
+    // ...and this one comes back at 1.5x: 512 output frames cover 768 of the file
+    rendered.lock().unwrap().clear();
+    send(&mut recorder, Message::PlayFile("take.wav".into()));
+    assert_eq!(recorder.playback_speed(), 1.5);
+    assert!(recorder.status_message.contains("at 1.50x"));
+    assert_eq!(*recorder.playback_position.lock().unwrap(), 768 * 2);
+    let rendered = rendered.lock().unwrap().clone();
+    let between = (original[2] + original[4]) / 2.0;
+    assert!((rendered[2] - between).abs() < 1e-6);
+
+    send(&mut recorder, Message::ResetPlaybackSpeed);
+    assert_eq!(recorder.playback_speed(), 1.0);
+    assert!(fs::metadata(speed_path("take.wav")).is_err());
+}
+
+#[test]
+fn recording_phase_guards_against_double_starts_and_saves() {
+    let _dir = TempDir::enter("phase");
+    let mut backend = MockBackend::new(1, 48000);
+    backend.input_signal = sine(440.0, 48000, 1, 0.1);
+
+    let mut recorder = recorder_with(backend);
+    send(&mut recorder, Message::StartRecording);
+    send(&mut recorder, Message::StopRecording);
+    let RecordingPhase::Stopping { since } = recorder.recording_phase else {
+        panic!("not stopping: {:?}", recorder.recording_phase);
+    };
+
+    send(&mut recorder, Message::StartRecording);
+    send(&mut recorder, Message::ArmRecording);
+    assert_eq!(recorder.recording_phase, RecordingPhase::Stopping { since });
+
+    send(&mut recorder, Message::FinalizeRecording);
+    assert_eq!(recorder.recording_phase, RecordingPhase::Idle);
+    send(&mut recorder, Message::FinalizeRecording);
+    send(&mut recorder, Message::Tick(since + Duration::from_secs(1)));
+    assert_eq!(recorder.recording_phase, RecordingPhase::Idle);
+    assert_eq!(list_wav_files(None).len(), 1);
+}
+
+#[test]
+fn overlay_compares_takes_a_and_b() {
+    let _dir = TempDir::enter("overlay");
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate: 1000,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let original = sine(50.0, 1000, 1, 1.0);
+    let mut processed: Vec<f32> = original.iter().map(|s| s * 0.5).collect();
+    processed.truncate(800);
+    write_wav_file_f32("original.wav", spec, &original).unwrap();
+    write_wav_file_f32("processed.wav", spec, &processed).unwrap();
+
+    let diff = sample_difference(&original, &processed, 1);
+    assert_eq!(diff.compared_frames, 800);
+    assert_eq!(diff.extra_frames, -200);
+    assert!((diff.max - 0.25).abs() < 1e-3, "max {}", diff.max);
+
+    let mut recorder = recorder_with(MockBackend::new(1, 48000));
+    send(
+        &mut recorder,
+        Message::AssignAbSlot(0, "original.wav".into()),
+    );
+    send(
+        &mut recorder,
+        Message::AssignAbSlot(1, "processed.wav".into()),
+    );
+    send(&mut recorder, Message::ToggleWaveformOverlay);
+
+    assert_eq!(recorder.waveform.as_ref().unwrap().file, "original.wav");
+    let overlay = recorder.waveform_overlay.as_ref().unwrap();
+    assert_eq!(overlay.data.file, "processed.wav");
+    assert_eq!(overlay.difference, Some(diff));
+    assert!(
+        overlay.readout().contains("B is 0.20s shorter"),
+        "{}",
+        overlay.readout()
+    );
+
+    send(&mut recorder, Message::ToggleWaveformOverlay);
+    assert!(recorder.waveform_overlay.is_none());
+}
+
+#[test]
+fn recording_stops_at_the_file_size_cap() {
+    let _dir = TempDir::enter("size_cap");
+    let mut backend = MockBackend::new(1, 48000);
+    backend.input_signal = sine(440.0, 48000, 1, 2.0);
+
+    let mut recorder = recorder_with(backend);
+    recorder.config.max_file_size_mb = 0.1;
+    recorder.config.auto_save_recordings = true;
+    send(&mut recorder, Message::StartRecording);
+    send(&mut recorder, Message::Tick(Instant::now()));
+    assert!(matches!(
+        recorder.recording_phase,
+        RecordingPhase::Stopping { .. }
+    ));
+    assert!(recorder.status_message.contains("0.1 MB size limit"));
+
+    send(&mut recorder, Message::FinalizeRecording);
+    let files = list_wav_files(None);
+    assert_eq!(files.len(), 1);
+    let size = fs::metadata(&files[0]).unwrap().len();
+    assert!(size <= 100_000, "{} bytes", size);
+    assert!(size > 90_000, "{} bytes", size);
+}
+
+#[test]
+fn playback_can_be_routed_to_later_output_channels() {
+    let _dir = TempDir::enter("channel_offset");
+    let spec = WavSpec {
+        channels: 2,
+        sample_rate: 48000,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let original = sine(440.0, 48000, 2, 0.5);
+    write_wav_file_f32("take.wav", spec, &original).unwrap();
+
+    let mut backend = MockBackend::new(2, 48000);
+    backend.max_render_samples = 2048;
+    let mut interface = backend.output_formats[0];
+    interface.channels = 4;
+    backend.output_formats.push(interface);
+    let rendered = Arc::clone(&backend.rendered);
+    let mut recorder = recorder_with(backend);
+    recorder.config.output_start_ramp_ms = 0;
+
+    send(&mut recorder, Message::OpenSettings);
+    assert_eq!(recorder.output_channel_count, 4);
+    send(&mut recorder, Message::SelectFirstOutputChannel(3));
+    send(&mut recorder, Message::CloseSettings);
+    assert_eq!(recorder.config.output_channel_offset, 2);
+    send(&mut recorder, Message::PlayFile("take.wav".into()));
+
+    let out = rendered.lock().unwrap().clone();
+    assert_eq!(out.len(), 2048);
+    for (k, frame) in out.chunks(4).enumerate() {
+        assert_eq!(&frame[..2], &[0.0, 0.0]);
+        assert_eq!(&frame[2..], &original[k * 2..k * 2 + 2]);
+    }
+    send(&mut recorder, Message::StopPlayback);
+
+    // A device without enough channels falls back to the first ones
+    recorder.config.output_channel_offset = 3;
+    rendered.lock().unwrap().clear();
+    send(&mut recorder, Message::PlayFile("take.wav".into()));
+    assert!(
+        recorder
+            .status_log
+            .iter()
+            .any(|(_, line)| line.contains("too few to play from channel 4"))
+    );
+    assert_eq!(&rendered.lock().unwrap()[..4], &original[..4]);
+}
+
+#[test]
+fn take_interrupted_by_a_crash_can_be_recovered() {
+    let _dir = TempDir::enter("recovery");
+    let mut backend = MockBackend::new(1, 48000);
+    backend.input_signal = sine(440.0, 48000, 1, 0.5);
+
+    let mut recorder = recorder_with(backend);
+    send(&mut recorder, Message::StartRecording);
+    send(&mut recorder, Message::Tick(Instant::now()));
+    let captured = recorder.audio_data.lock().unwrap().clone();
+    // A crash skips the writer's own cleanup; only what was flushed survives
+    std::mem::forget(recorder.recovery_writer.take());
+    drop(recorder);
+
+    let mut recorder = recorder_with(MockBackend::new(1, 48000));
+    assert_eq!(
+        recorder.interrupted_take,
+        Some(InterruptedTake::Readable(0.5))
+    );
+    assert!(list_wav_files(None).is_empty());
+    send(&mut recorder, Message::RecoverInterruptedTake);
+    assert_eq!(recorder.files, vec!["recovered_1.wav".to_string()]);
+    let (spec, samples) = read_wav_file("recovered_1.wav").unwrap();
+    assert_eq!(spec.sample_rate, 48000);
+    assert_eq!(samples, captured);
+    assert!(!Path::new(RECOVERY_PATH).exists());
+
+    // A take that's saved normally leaves no journal behind
+    let mut backend = MockBackend::new(1, 48000);
+    backend.input_signal = sine(440.0, 48000, 1, 0.1);
+    let mut recorder = recorder_with(backend);
+    recorder.config.auto_save_recordings = true;
+    send(&mut recorder, Message::StartRecording);
+    send(&mut recorder, Message::StopRecording);
+    send(&mut recorder, Message::FinalizeRecording);
+    assert!(!Path::new(RECOVERY_PATH).exists());
+    assert_eq!(recorder.interrupted_take, None);
+
+    // A journal that can't be read is kept, and blocks recording over it, until
+    // it's discarded
+    fs::write(RECOVERY_PATH, "not a wav").unwrap();
+    let mut recorder = recorder_with(MockBackend::new(1, 48000));
+    assert!(matches!(
+        recorder.interrupted_take,
+        Some(InterruptedTake::Unreadable(_))
+    ));
+    send(&mut recorder, Message::StartRecording);
+    assert!(!recorder.recording_phase.is_recording());
+    assert_eq!(fs::read_to_string(RECOVERY_PATH).unwrap(), "not a wav");
+    assert!(recorder.interrupted_take.is_some());
+    send(&mut recorder, Message::DiscardInterruptedTake);
+    assert!(!Path::new(RECOVERY_PATH).exists());
+    send(&mut recorder, Message::StartRecording);
+    assert!(recorder.recording_phase.is_recording());
+}
+
+#[test]
+fn clicking_the_overview_seeks_and_scrolls_the_zoomed_view() {
+    let _dir = TempDir::enter("overview");
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate: 8000,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    write_wav_file_f32("take.wav", spec, &sine(440.0, 8000, 1, 2.0)).unwrap();
+
+    let mut recorder = recorder_with(MockBackend::new(1, 8000));
+    send(&mut recorder, Message::SelectFile("take.wav".into()));
+    assert!(recorder.config.waveform_overview);
+    send(&mut recorder, Message::ZoomWaveform(4.0));
+    send(&mut recorder, Message::SeekOverview(0.75));
+
+    assert_eq!(recorder.playback_state, PlaybackState::Playing);
+    assert_eq!(
+        recorder.playing_file().map(String::as_str),
+        Some("take.wav")
+    );
+    // The quarter-width view is centred on the spot clicked
+    let view = waveform::View::new(4.0, recorder.waveform_scroll);
+    assert!((view.start + view.len / 2.0 - 0.75).abs() < 1e-6);
+
+    send(&mut recorder, Message::ToggleWaveformOverview(false));
+    assert!(!recorder.config.waveform_overview);
+}
+
+#[test]
+fn duplicate_and_trim_leaves_the_original_alone() {
+    let _dir = TempDir::enter("duplicate_trim");
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate: 8000,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let mut samples = vec![0.0; 8000];
+    samples.extend(sine(440.0, 8000, 1, 1.0));
+    samples.extend(vec![0.0; 8000]);
+    write_wav_file_f32("take.wav", spec, &samples).unwrap();
+    let cue = bwf::cue_points(&[100, 12000, 23000]);
+    bwf::write_chunk("take.wav", bwf::CUE, &cue).unwrap();
+
+    let mut recorder = recorder_with(MockBackend::new(1, 8000));
+    send(&mut recorder, Message::SelectFile("take.wav".into()));
+    send(&mut recorder, Message::UpdateTagInput("keeper".into()));
+    send(&mut recorder, Message::AddTag);
+    send(&mut recorder, Message::DuplicateAndTrim);
+
+    assert_eq!(recorder.selected_file.as_deref(), Some("take_trim.wav"));
+    assert_eq!(read_wav_file("take.wav").unwrap().1, samples);
+    let trimmed = read_wav_file("take_trim.wav").unwrap().1;
+    // One second of tone with the default 750 ms padding either side
+    assert!(
+        (19990..=20000).contains(&trimmed.len()),
+        "{} samples",
+        trimmed.len()
+    );
+    assert_eq!(
+        recorder.notes["take_trim.wav"].tags,
+        vec!["keeper".to_string()]
+    );
+    // Cue points in the trimmed-off silence go; the one in the tone moves with it
+    let trimmed_cue = bwf::read_chunk("take_trim.wav", bwf::CUE).unwrap().unwrap();
+    let points = bwf::read_cue_points(&trimmed_cue);
+    assert_eq!(points.len(), 1, "{:?}", points);
+    assert!((9990..=10010).contains(&points[0]), "{:?}", points);
+    assert_eq!(bwf::read_chunk("take.wav", bwf::CUE).unwrap(), Some(cue));
+
+    // Not a double-click on the original
+    recorder.last_file_click = None;
+    send(&mut recorder, Message::SelectFile("take.wav".into()));
+    send(&mut recorder, Message::DuplicateAndTrim);
+    assert_eq!(recorder.selected_file.as_deref(), Some("take_trim_2.wav"));
+}
+
+#[test]
+fn tpdf_dither_keeps_detail_below_one_step() {
+    let mut tpdf = audio::Tpdf::new();
+    let noise: Vec<f32> = (0..10000).map(|_| tpdf.sample()).collect();
+    assert!(noise.iter().all(|n| (-1.0..=1.0).contains(n)));
+    assert!((noise.iter().sum::<f32>() / noise.len() as f32).abs() < 0.02);
+
+    // A level a third of a step high vanishes when truncated, but survives on
+    // average once dithered
+    assert_eq!(audio::quantize(0.3, None), 0.0);
+    let dithered: f32 = (0..10000)
+        .map(|_| audio::quantize(0.3, Some(&mut tpdf)))
+        .sum::<f32>()
+        / 10000.0;
+    assert!((dithered - 0.3).abs() < 0.03, "{}", dithered);
+
+    let _dir = TempDir::enter("dither");
+    let backend = MockBackend::new(1, 48000);
+    let dither = Arc::clone(&backend.output_dither);
+    let mut recorder = recorder_with(backend);
+    assert!(*dither.lock().unwrap());
+    send(&mut recorder, Message::ToggleOutputDither(false));
+    assert!(!*dither.lock().unwrap());
+    assert!(!recorder.config.output_dither);
+}
+
+#[test]
+fn waveform_image_is_saved_next_to_the_file() {
+    let _dir = TempDir::enter("waveform_png");
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate: 8000,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    // Loud first half, silent second half
+    let mut samples = vec![0.9; 4000];
+    samples.extend(vec![0.0; 4000]);
+    write_wav_file_f32("take.wav", spec, &samples).unwrap();
+
+    assert_eq!(waveform::parse_hex_color("#ff8000"), Some([255, 128, 0]));
+    assert_eq!(waveform::parse_hex_color("red"), None);
+
+    let mut recorder = recorder_with(MockBackend::new(1, 8000));
+    send(&mut recorder, Message::SelectFile("take.wav".into()));
+    send(&mut recorder, Message::UpdateImageWidth("40".into()));
+    send(&mut recorder, Message::UpdateImageHeight("10".into()));
+    send(
+        &mut recorder,
+        Message::UpdateImageForeground("#ff0000".into()),
+    );
+    send(
+        &mut recorder,
+        Message::UpdateImageBackground("#000000".into()),
+    );
+    send(&mut recorder, Message::ExportWaveformImage);
+    assert_eq!(recorder.config.waveform_image_width, 40);
+
+    let decoder = png::Decoder::new(fs::File::open("take.png").unwrap());
+    let mut reader = decoder.read_info().unwrap();
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels).unwrap();
+    assert_eq!((info.width, info.height), (40, 10));
+    let pixel = |x: usize, y: usize| &pixels[(y * 40 + x) * 3..(y * 40 + x) * 3 + 3];
+    assert_eq!(pixel(5, 1), &[255, 0, 0]);
+    assert_eq!(pixel(30, 1), &[0, 0, 0]);
+    assert_eq!(pixel(30, 5), &[255, 0, 0]);
+}
+
+#[test]
+fn markers_round_trip_through_cue_csv_and_audacity_labels() {
+    let _dir = TempDir::enter("marker_exchange");
+    let spec = WavSpec {
+        channels: 2,
+        sample_rate: 8000,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    write_wav_file_f32("take.wav", spec, &sine(440.0, 8000, 2, 3.0)).unwrap();
+    assert_eq!(
+        parse_marks_text("time,label\n2.5,\"b, \"\"then\"\" c\"\n0.5,a\n"),
+        Ok(vec![(0.5, "a".into()), (2.5, "b, \"then\" c".into())])
+    );
+    assert!(parse_marks_text("1.0\nsoon\n").is_err());
+
+    // Audacity regions come in at their start; anything past the end is dropped
+    fs::write(
+        "take.labels.txt",
+        "0.5\t0.5\tIntro\n1.25\t2.0\tVerse\n9.0\t9.0\tLate\n",
+    )
+    .unwrap();
+    let mut recorder = recorder_with(MockBackend::new(2, 8000));
+    send(&mut recorder, Message::SelectFile("take.wav".into()));
+    send(
+        &mut recorder,
+        Message::SelectMarkerFormat(MarkerFormat::Audacity),
+    );
+    send(&mut recorder, Message::ImportMarkers);
+    assert_eq!(recorder.marker_file.as_deref(), Some("take.wav"));
+    assert_eq!(recorder.markers, vec![8000, 20000]);
+
+    send(
+        &mut recorder,
+        Message::SelectMarkerFormat(MarkerFormat::Csv),
+    );
+    send(&mut recorder, Message::ExportMarkers);
+    // Labels that came in with the markers go back out with them
+    assert_eq!(
+        fs::read_to_string("take.markers.csv").unwrap(),
+        "time,label\n0.500000,Intro\n1.250000,Verse\n"
+    );
+    assert_eq!(
+        load_marks("take.wav").unwrap()[1],
+        (1.25, "Verse".to_string())
+    );
+
+    send(
+        &mut recorder,
+        Message::SelectMarkerFormat(MarkerFormat::Cue),
+    );
+    send(&mut recorder, Message::ExportMarkers);
+    let cue = bwf::read_chunk("take.wav", bwf::CUE).unwrap().unwrap();
+    assert_eq!(bwf::read_cue_points(&cue), vec![4000, 10000]);
+
+    // Without the sidecar the markers are read back from the cue chunk
+    fs::remove_file(marks_path("take.wav")).unwrap();
+    let mut recorder = recorder_with(MockBackend::new(2, 8000));
+    send(&mut recorder, Message::SelectFile("take.wav".into()));
+    assert_eq!(recorder.markers, vec![8000, 20000]);
+}
+
+#[test]
+fn tap_tempo_sets_the_metronome_from_recent_taps() {
+    let _dir = TempDir::enter("tap_tempo");
+    let mut backend = MockBackend::new(1, 48000);
+    backend.max_render_samples = 100_000;
+    let rendered = Arc::clone(&backend.rendered);
+    let mut recorder = recorder_with(backend);
+
+    // A slow start, then steady taps every half second: only the latest count
+    let start = Instant::now();
+    send(&mut recorder, Message::TapTempo(start));
+    assert_eq!(recorder.config.metronome_bpm, 120.0);
+    let steady = start + Duration::from_secs(3);
+    send(
+        &mut recorder,
+        Message::TapTempo(steady - Duration::from_millis(1000)),
+    );
+    for i in 0..7 {
+        send(
+            &mut recorder,
+            Message::TapTempo(steady + Duration::from_millis(i * 400)),
+        );
+    }
+    assert_eq!(recorder.tempo_taps.len(), TAP_TEMPO_TAPS);
+    assert_eq!(recorder.config.metronome_bpm, 150.0);
+    assert_eq!(recorder.metronome_input, "150");
+
+    // A long pause starts over
+    send(
+        &mut recorder,
+        Message::TapTempo(steady + Duration::from_secs(10)),
+    );
+    assert_eq!(recorder.tempo_taps.len(), 1);
+
+    send(&mut recorder, Message::ToggleMetronome);
+    assert!(recorder.metronome_stream.is_some());
+    // The mock output runs at 96 kHz: a beat every 38400 frames at 150 BPM
+    let out = rendered.lock().unwrap().clone();
+    let clicks: Vec<usize> = (1..out.len())
+        .filter(|&i| out[i - 1] == 0.0 && out[i] != 0.0)
+        .collect();
+    assert_eq!(clicks, vec![1, 38401, 76801]);
+
+    send(&mut recorder, Message::ToggleMetronome);
+    assert!(recorder.metronome_stream.is_none());
+}
+
+#[test]
+fn side_stream_errors_are_logged() {
+    let _dir = TempDir::enter("side_stream_errors");
+    let backend = MockBackend::new(1, 48000);
+    let error = Arc::clone(&backend.output_error);
+    let mut recorder = recorder_with(backend);
+
+    *error.lock().unwrap() = Some(StreamError::Other("buffer underrun".into()));
+    send(&mut recorder, Message::ToggleMetronome);
+    assert!(recorder.metronome_stream.is_some());
+    send(&mut recorder, Message::Tick(Instant::now()));
+    assert_eq!(
+        recorder.status_message,
+        "Metronome stream error: buffer underrun"
+    );
+    // Playback's own stream is left alone
+    assert!(recorder.output_stream.is_none());
+}
+
+#[test]
+fn shortcuts_can_be_remapped_without_conflicts() {
+    let _dir = TempDir::enter("keymap");
+    let mut recorder = recorder_with(MockBackend::new(1, 48000));
+    let key = |c: &str| Key::Character(c.into());
+    let escape = Key::Named(keyboard::key::Named::Escape);
+    let none = Modifiers::default();
+
+    send(
+        &mut recorder,
+        Message::KeyPressed(key("?"), Modifiers::SHIFT),
+    );
+    assert_eq!(recorder.screen, Screen::Help);
+    send(&mut recorder, Message::KeyPressed(escape.clone(), none));
+    assert_eq!(recorder.screen, Screen::Main);
+
+    // Shift doesn't matter for letters, and a key already in use is refused
+    send(
+        &mut recorder,
+        Message::RemapShortcut(ShortcutAction::ShowHelp),
+    );
+    send(
+        &mut recorder,
+        Message::KeyPressed(key("R"), Modifiers::SHIFT),
+    );
+    assert_eq!(recorder.remapping_shortcut, Some(ShortcutAction::ShowHelp));
+    assert!(recorder.status_message.contains("already the shortcut"));
+    send(&mut recorder, Message::KeyPressed(key("H"), none));
+    assert_eq!(recorder.remapping_shortcut, None);
+    assert_eq!(
+        recorder
+            .config
+            .keymap
+            .get(&ShortcutAction::ShowHelp)
+            .map(String::as_str),
+        Some("h")
+    );
+    assert_eq!(
+        Config::load()
+            .unwrap()
+            .keymap
+            .get(&ShortcutAction::ShowHelp)
+            .map(String::as_str),
+        Some("h")
+    );
+
+    send(&mut recorder, Message::KeyPressed(key("?"), none));
+    assert_eq!(recorder.screen, Screen::Main);
+    send(&mut recorder, Message::KeyPressed(key("h"), none));
+    assert_eq!(recorder.screen, Screen::Help);
+    send(&mut recorder, Message::KeyPressed(escape.clone(), none));
+
+    // Esc cancels a remap; reset brings the defaults back
+    send(
+        &mut recorder,
+        Message::RemapShortcut(ShortcutAction::StopPlayback),
+    );
+    send(&mut recorder, Message::KeyPressed(escape, none));
+    assert_eq!(recorder.remapping_shortcut, None);
+    assert_eq!(
+        shortcut_key(&recorder.config.keymap, ShortcutAction::StopPlayback),
+        "p"
+    );
+
+    send(&mut recorder, Message::ResetShortcuts);
+    assert!(recorder.config.keymap.is_empty());
+    send(&mut recorder, Message::KeyPressed(key("?"), none));
+    assert_eq!(recorder.screen, Screen::Help);
+}
+
+#[test]
+fn shift_arrows_nudge_the_input_gain_or_playback_volume() {
+    let _dir = TempDir::enter("level_nudge");
+    let mut recorder = recorder_with(MockBackend::new(1, 48000));
+    let up = Key::Named(keyboard::key::Named::ArrowUp);
+    let down = Key::Named(keyboard::key::Named::ArrowDown);
+
+    // Idle, the playback volume moves, and can't go above full
+    send(
+        &mut recorder,
+        Message::KeyPressed(up.clone(), Modifiers::SHIFT),
+    );
+    assert_eq!(recorder.config.output_volume, 1.0);
+    send(
+        &mut recorder,
+        Message::KeyPressed(down.clone(), Modifiers::SHIFT),
+    );
+    send(
+        &mut recorder,
+        Message::KeyPressed(down.clone(), Modifiers::SHIFT),
+    );
+    assert!((recorder.config.output_volume - db_to_amplitude(-2.0)).abs() < 1e-5);
+    assert_eq!(
+        recorder
+            .level_nudge
+            .as_ref()
+            .map(|(readout, _)| readout.as_str()),
+        Some("Playback volume -2.0 dB")
+    );
+    // Without Shift the arrow is a different key
+    send(
+        &mut recorder,
+        Message::KeyPressed(down, Modifiers::default()),
+    );
+    assert!((recorder.config.output_volume - db_to_amplitude(-2.0)).abs() < 1e-5);
+
+    // Armed, the input gain moves instead, clamped to the slider's range
+    send(&mut recorder, Message::ArmRecording);
+    for _ in 0..30 {
+        send(
+            &mut recorder,
+            Message::KeyPressed(up.clone(), Modifiers::SHIFT),
+        );
+    }
+    assert_eq!(recorder.config.processing.input_gains_db.len(), 1);
+    assert!((recorder.config.processing.input_gains_db[0] - INPUT_GAIN_RANGE_DB).abs() < 1e-3);
+    assert!((recorder.config.output_volume - db_to_amplitude(-2.0)).abs() < 1e-5);
+    assert_eq!(
+        recorder
+            .level_nudge
+            .as_ref()
+            .map(|(readout, _)| readout.as_str()),
+        Some("Input gain +24.0 dB")
+    );
+
+    let shown_at = recorder.level_nudge.as_ref().unwrap().1;
+    send(
+        &mut recorder,
+        Message::Tick(shown_at + Duration::from_millis(LEVEL_NUDGE_SHOW_MS)),
+    );
+    assert!(recorder.level_nudge.is_none());
+    send(&mut recorder, Message::DisarmRecording);
+}
+
+#[test]
+fn monitoring_can_tap_the_processed_input() {
+    let _dir = TempDir::enter("monitor_processed");
+    let mut backend = MockBackend::new(1, 48000);
+    backend.input_signal = sine(440.0, 48000, 1, 0.5);
+    backend.output_formats[0].channels = 2;
+    backend.max_render_samples = 2 * 4800;
+    let rendered = Arc::clone(&backend.rendered);
+    let mut recorder = recorder_with(backend);
+    let peak = |samples: &[f32]| samples.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+
+    send(&mut recorder, Message::ToggleInputMonitor(true));
+    send(&mut recorder, Message::ArmRecording);
+    assert!(recorder.monitor_stream.is_some());
+    send(&mut recorder, Message::SetChannelGain(0, 6.0));
+    // Mono input reaches both output channels, as it arrived
+    let raw = std::mem::take(&mut *rendered.lock().unwrap());
+    assert!((peak(&raw) - 0.5).abs() < 0.01);
+    assert!(raw.chunks(2).all(|frame| frame[0] == frame[1]));
+
+    send(&mut recorder, Message::DisarmRecording);
+    assert!(recorder.monitor_stream.is_none());
+
+    send(&mut recorder, Message::ToggleMonitorProcessed(true));
+    send(&mut recorder, Message::ArmRecording);
+    let processed = std::mem::take(&mut *rendered.lock().unwrap());
+    assert!((peak(&processed) - 0.5 * db_to_amplitude(6.0)).abs() < 0.01);
+
+    send(&mut recorder, Message::ToggleInputMonitor(false));
+    assert!(recorder.monitor_stream.is_none());
+    assert!(!recorder.monitor_active.load(Ordering::Relaxed));
+}
+
+#[test]
+fn late_callbacks_turn_the_health_indicator_red() {
+    let health = CallbackHealth::new();
+    let t0 = Instant::now();
+    let ms = Duration::from_millis;
+    // 480 frames at 48 kHz: a callback every 10 ms
+    health.record(t0, t0 + ms(2), 480, 48000);
+    health.record(t0 + ms(10), t0 + ms(12), 480, 48000);
+    let (late, load) = health.take();
+    assert_eq!(late, 0);
+    assert!((load - 0.2).abs() < 1e-3);
+    health.record(t0 + ms(40), t0 + ms(49), 480, 48000);
+    let (late, load) = health.take();
+    assert_eq!(late, 1);
+    assert!((load - 0.9).abs() < 1e-3);
+    assert_eq!(health.take(), (0, 0.0));
+
+    let _dir = TempDir::enter("stream_health");
+    let mut backend = MockBackend::new(1, 48000);
+    backend.input_signal = sine(440.0, 48000, 1, 0.1);
+    let mut recorder = recorder_with(backend);
+    send(&mut recorder, Message::ArmRecording);
+    let now = Instant::now();
+    recorder.input_health.record(now, now, 480, 48000);
+    recorder
+        .input_health
+        .record(now + ms(50), now + ms(50), 480, 48000);
+    send(&mut recorder, Message::Tick(now + ms(60)));
+    assert_eq!(recorder.dropouts, 1);
+    assert!(recorder.dropout_until.is_some());
+    send(&mut recorder, Message::Tick(now + ms(60 + DROPOUT_HOLD_MS)));
+    assert!(recorder.dropout_until.is_none());
+    assert_eq!(recorder.dropouts, 1);
+}
+
+#[test]
+fn pre_roll_puts_audio_from_before_record_at_the_start() {
+    let _dir = TempDir::enter("pre_roll");
+    let mut backend = MockBackend::new(1, 48000);
+    let signal = sine(440.0, 48000, 1, 1.0);
+    backend.input_signal = signal.clone();
+    let mut recorder = recorder_with(backend);
+
+    // Off by default: an armed take starts empty
+    send(&mut recorder, Message::ArmRecording);
+    send(&mut recorder, Message::StartRecording);
+    assert!(recorder.audio_data.lock().unwrap().is_empty());
+    send(&mut recorder, Message::StopRecording);
+    recorder.recording_phase = RecordingPhase::Idle;
+
+    send(&mut recorder, Message::UpdatePreRoll("45".into()));
+    assert_eq!(recorder.config.pre_roll_secs, 0.0);
+    send(&mut recorder, Message::UpdatePreRoll("0.5".into()));
+    assert_eq!(recorder.config.pre_roll_secs, 0.5);
+
+    send(&mut recorder, Message::ArmRecording);
+    assert!(recorder.status_message.contains("keeping the last 0.5 s"));
+    assert_eq!(recorder.pre_roll.lock().unwrap().len(), 24000);
+    send(&mut recorder, Message::StartRecording);
+    assert!(recorder.pre_roll.lock().unwrap().is_empty());
+    assert_eq!(*recorder.audio_data.lock().unwrap(), signal[24000..]);
+    assert_eq!(recorder.elapsed_time, Duration::from_millis(500));
+}
+
+#[test]
+fn batch_rename_previews_checks_and_renames_together() {
+    let _dir = TempDir::enter("batch_rename");
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate: 8000,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    write_wav_file_f32("rec_2.wav", spec, &[0.1; 10]).unwrap();
+    write_wav_file_f32("rec_3.wav", spec, &[0.2; 20]).unwrap();
+    write_wav_file_f32("interview_01.wav", spec, &[0.3; 30]).unwrap();
+    fs::write("rec_3.marks.json", "[8]").unwrap();
+    let mut recorder = recorder_with(MockBackend::new(1, 8000));
+    recorder.notes.insert(
+        "rec_3.wav".into(),
+        FileNotes {
+            notes: "second take".into(),
+            ..FileNotes::default()
+        },
+    );
+    recorder.persist_notes();
+    send(&mut recorder, Message::UpdateSearch("rec_".into()));
+    send(&mut recorder, Message::ToggleBatchRename);
+
+    // Names already on disk, or shared within the batch, are refused up front
+    send(
+        &mut recorder,
+        Message::UpdateBatchRenameTemplate("interview_{n:2}".into()),
+    );
+    let plan = recorder.batch_rename_plan();
+    assert!(plan[0].1.as_ref().unwrap_err().contains("already exists"));
+    assert_eq!(plan[1].1, Ok("interview_02.wav".to_string()));
+    send(
+        &mut recorder,
+        Message::UpdateBatchRenameTemplate("take".into()),
+    );
+    assert!(
+        recorder
+            .batch_rename_plan()
+            .iter()
+            .all(|(_, to)| to.as_ref().is_err_and(|e| e.contains("same name")))
+    );
+    send(&mut recorder, Message::ApplyBatchRename);
+    assert!(std::path::Path::new("rec_2.wav").exists());
+    assert!(recorder.batch_rename_open);
+    assert_eq!(
+        expand_rename_template("{n:x}", 1).unwrap_err(),
+        "Unknown placeholder {n:x}; use {n} or {n:3}."
+    );
+
+    // rec_2 -> rec_1 and rec_3 -> rec_2: names can pass along the batch
+    send(
+        &mut recorder,
+        Message::UpdateBatchRenameTemplate("rec_{n}".into()),
+    );
+    send(&mut recorder, Message::ApplyBatchRename);
+    assert_eq!(recorder.pending_batch, Some(BatchKind::Rename));
+    send(&mut recorder, Message::ConfirmBatch);
+    assert!(!recorder.batch_rename_open);
+    assert_eq!(
+        recorder.files,
+        ["interview_01.wav", "rec_1.wav", "rec_2.wav"]
+    );
+    let len = |path: &str| hound::WavReader::open(path).unwrap().duration();
+    assert_eq!((len("rec_1.wav"), len("rec_2.wav")), (10, 20));
+    assert_eq!(fs::read_to_string("rec_2.marks.json").unwrap(), "[8]");
+    assert!(!std::path::Path::new("rec_3.marks.json").exists());
+    assert_eq!(recorder.notes["rec_2.wav"].notes, "second take");
+    assert!(!recorder.notes.contains_key("rec_3.wav"));
+}
+
+#[test]
+fn batches_are_confirmed_and_undone_as_a_whole() {
+    let _dir = TempDir::enter("batch_undo");
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate: 8000,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    for (i, name) in ["a.wav", "k_1.wav", "k_2.wav", "z.wav"].iter().enumerate() {
+        write_wav_file_f32(name, spec, &[0.1 * (i + 1) as f32; 10]).unwrap();
+    }
+    fs::write("z.marks.json", "[4]").unwrap();
+    let mut recorder = recorder_with(MockBackend::new(1, 8000));
+    for file in ["a.wav", "k_1.wav", "z.wav"] {
+        recorder.notes.insert(
+            file.into(),
+            FileNotes {
+                notes: "group".into(),
+                ..FileNotes::default()
+            },
+        );
+    }
+    recorder.persist_notes();
+    send(&mut recorder, Message::UpdateSearch("group".into()));
+
+    // k_1 can't take k_2's name, so it keeps its own, and a can't have that either
+    recorder.batch_rename_template = "k_{n}".into();
+    assert_eq!(
+        recorder.batch_summary(BatchKind::Rename).unwrap(),
+        "Rename 1 file, skip 2 conflicts."
+    );
+    send(&mut recorder, Message::ApplyBatchRename);
+    assert_eq!(recorder.pending_batch, Some(BatchKind::Rename));
+    send(&mut recorder, Message::CancelBatch);
+    assert_eq!(recorder.pending_batch, None);
+    assert!(std::path::Path::new("z.wav").exists());
+    send(&mut recorder, Message::ApplyBatchRename);
+    send(&mut recorder, Message::ConfirmBatch);
+    assert_eq!(recorder.files, ["a.wav", "k_1.wav", "k_2.wav", "k_3.wav"]);
+    assert!(std::path::Path::new("k_3.marks.json").exists());
+    assert_eq!(recorder.notes["k_3.wav"].notes, "group");
+
+    send(&mut recorder, Message::UndoBatch);
+    assert_eq!(recorder.files, ["a.wav", "k_1.wav", "k_2.wav", "z.wav"]);
+    assert_eq!(fs::read_to_string("z.marks.json").unwrap(), "[4]");
+    assert_eq!(recorder.notes["z.wav"].notes, "group");
+    assert!(recorder.undo_history.is_empty());
+
+    // Deleted duplicates are held until the batch is undone
+    recorder.duplicates = ["a.wav".to_string(), "z.wav".to_string()].into();
+    send(&mut recorder, Message::DeleteDuplicates);
+    send(&mut recorder, Message::ConfirmBatch);
+    assert_eq!(recorder.files, ["k_1.wav", "k_2.wav"]);
+    assert!(!recorder.notes.contains_key("z.wav"));
+    assert_eq!(recorder.undo_history[0].label, "deletion of 2 duplicates");
+    send(&mut recorder, Message::UndoBatch);
+    assert_eq!(recorder.files, ["a.wav", "k_1.wav", "k_2.wav", "z.wav"]);
+    assert_eq!(read_wav_file("z.wav").unwrap().1, vec![0.4; 10]);
+    assert_eq!(recorder.notes["z.wav"].notes, "group");
+
+    // Exports that replace earlier ones put those back on undo
+    fs::write("a_export.wav", "earlier").unwrap();
+    assert_eq!(
+        recorder.batch_summary(BatchKind::ExportAll).unwrap(),
+        "Export 3 files as WAV (32-bit float), replacing 1 earlier export."
+    );
+    send(&mut recorder, Message::ExportAll);
+    send(&mut recorder, Message::ConfirmBatch);
+    // The job's task doesn't run here, so do its work in place
+    let settings = recorder.config.export.clone();
+    let queue = Arc::clone(&recorder.export_queue);
+    run_export_queue(&queue, &settings, 0, Duration::ZERO, &JobControl::default());
+    let id = recorder.background_job.as_ref().unwrap().id;
+    send(
+        &mut recorder,
+        Message::JobFinished(id, Ok("Exported.".into())),
+    );
+    assert!(read_wav_file("a_export.wav").is_ok());
+    assert!(std::path::Path::new("z_export.wav").exists());
+    // The exports are listed, but aren't exported again
+    recorder.refresh_files();
+    assert!(recorder.files.contains(&"z_export.wav".to_string()));
+    assert_eq!(
+        recorder.batch_summary(BatchKind::ExportAll).unwrap(),
+        "Export 3 files as WAV (32-bit float), replacing 3 earlier exports."
+    );
+    send(&mut recorder, Message::UndoBatch);
+    assert_eq!(fs::read_to_string("a_export.wav").unwrap(), "earlier");
+    assert!(!std::path::Path::new("z_export.wav").exists());
+    assert!(!std::path::Path::new(UNDO_DIR).exists());
+}
+
+#[test]
+fn a_cancelled_export_batch_puts_earlier_exports_back() {
+    let _dir = TempDir::enter("batch_export_cancel");
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate: 8000,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    write_wav_file_f32("a.wav", spec, &[0.1; 10]).unwrap();
+    fs::write("a_export.wav", "earlier").unwrap();
+    fs::create_dir_all(format!("{}/1", UNDO_DIR)).unwrap();
+    let mut recorder = recorder_with(MockBackend::new(1, 8000));
+    // Holding folders a crash left behind are cleared at startup
+    assert!(!std::path::Path::new(UNDO_DIR).exists());
+
+    send(&mut recorder, Message::ExportAll);
+    send(&mut recorder, Message::ConfirmBatch);
+    assert!(!std::path::Path::new("a_export.wav").exists());
+    send(&mut recorder, Message::CancelJob);
+    let id = recorder.background_job.as_ref().unwrap().id;
+    send(
+        &mut recorder,
+        Message::JobFinished(id, Err("Export cancelled.".into())),
+    );
+    assert_eq!(fs::read_to_string("a_export.wav").unwrap(), "earlier");
+    assert!(recorder.undo_history.is_empty());
+}
+
+#[test]
+fn audio_host_can_be_chosen_and_falls_back_when_unavailable() {
+    let _dir = TempDir::enter("audio_host");
+    let hosts = vec![("ALSA".to_string(), true), ("JACK".to_string(), false)];
+    let mut backend = MockBackend::new(1, 48000);
+    backend.hosts = hosts.clone();
+    let host = Arc::clone(&backend.host);
+    let mut recorder = recorder_with(backend);
+    send(&mut recorder, Message::OpenSettings);
+    assert_eq!(recorder.audio_hosts.len(), 3);
+    let choices = recorder.audio_hosts.clone();
+    assert_eq!(choices[2].to_string(), "JACK (unavailable)");
+
+    // Compiled in but not running: refused, and nothing changes
+    send(&mut recorder, Message::SelectAudioHost(choices[2].clone()));
+    assert!(recorder.status_message.contains("unavailable"));
+    assert_eq!(recorder.config.audio_host, None);
+
+    send(&mut recorder, Message::SelectAudioHost(choices[1].clone()));
+    assert_eq!(recorder.config.audio_host.as_deref(), Some("ALSA"));
+    assert_eq!(host.lock().unwrap().as_deref(), Some("ALSA"));
+    assert_eq!(recorder.status_message, "Using audio host: ALSA.");
+
+    // Not while a stream is open on the current one
+    send(&mut recorder, Message::ArmRecording);
+    send(&mut recorder, Message::SelectAudioHost(choices[0].clone()));
+    assert_eq!(recorder.config.audio_host.as_deref(), Some("ALSA"));
+    send(&mut recorder, Message::DisarmRecording);
+
+    // A saved host that has gone away at startup falls back to the default
+    recorder.config.audio_host = Some("JACK".into());
+    recorder.save_config();
+    let mut backend = MockBackend::new(1, 48000);
+    backend.hosts = hosts;
+    let host = Arc::clone(&backend.host);
+    let recorder = recorder_with(backend);
+    assert_eq!(*host.lock().unwrap(), None);
+    assert!(
+        recorder
+            .status_message
+            .contains("Using the default audio host")
+    );
+}
+
+#[test]
+fn selected_file_gets_an_analysis_readout() {
+    // A quarter-rate sine sampled 45 degrees off its crests: samples reach only
+    // -3 dBFS, but the waveform between them reaches full scale
+    let between: Vec<f32> = (0..4800)
+        .map(|n| (std::f32::consts::FRAC_PI_2 * n as f32 + std::f32::consts::FRAC_PI_4).sin())
+        .collect();
+    assert!((true_peak(&between, 1) - 1.0).abs() < 0.03);
+
+    let _dir = TempDir::enter("analysis");
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate: 48000,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let mut tone = sine(1000.0, 48000, 1, 1.0);
+    write_wav_file_f32("tone.wav", spec, &tone).unwrap();
+    let mut recorder = recorder_with(MockBackend::new(1, 48000));
+    // The task that analyses in the background doesn't run here, so do its work
+    let finish_analysis = |recorder: &mut VoiceRecorder| {
+        let modified = fs::metadata("tone.wav").unwrap().modified().unwrap();
+        send(
+            recorder,
+            Message::AnalysisFinished("tone.wav".into(), modified, analyze("tone.wav")),
+        );
+    };
+    send(&mut recorder, Message::SelectFile("tone.wav".into()));
+    assert!(!recorder.analysis_cache.contains_key(&cache_key("tone.wav")));
+    finish_analysis(&mut recorder);
+    let (_, analysis) = &recorder.analysis_cache[&cache_key("tone.wav")];
+    let analysis = analysis.as_ref().unwrap();
+    assert_eq!(analysis.duration, Duration::from_secs(1));
+    assert!((analysis.peak - 0.5).abs() < 1e-3);
+    assert!((analysis.true_peak - 0.5).abs() < 0.01);
+    assert!((analysis.rms - 0.5 / 2f32.sqrt()).abs() < 1e-3);
+    assert!(analysis.lufs.is_some());
+    assert_eq!(analysis.clipped_samples, 0);
+    assert!(analysis.readout().starts_with("Peak -6.0 dBFS"));
+
+    // A changed file is analysed again the next time it's selected
+    tone[100] = 1.0;
+    tone[200] = -1.0;
+    std::thread::sleep(Duration::from_millis(20));
+    write_wav_file_f32("tone.wav", spec, &tone).unwrap();
+    recorder.last_file_click = None;
+    send(&mut recorder, Message::SelectFile("tone.wav".into()));
+    recorder.last_file_click = None;
+    send(&mut recorder, Message::SelectFile("tone.wav".into()));
+    assert!(recorder.renaming_file.is_none());
+    finish_analysis(&mut recorder);
+    let (_, analysis) = &recorder.analysis_cache[&cache_key("tone.wav")];
+    assert_eq!(analysis.as_ref().unwrap().clipped_samples, 2);
+}
+
+#[test]
+fn channels_export_as_mono_stems_in_the_source_format() {
+    let _dir = TempDir::enter("stems");
+    let spec = WavSpec {
+        channels: 3,
+        sample_rate: 44100,
+        bits_per_sample: 24,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let frames: Vec<[i32; 3]> = (0..1000).map(|i| [i, -i * 100, 8_000_000 - i]).collect();
+    let mut writer = hound::WavWriter::create("session.wav", spec).unwrap();
+    for frame in &frames {
+        for &s in frame {
+            writer.write_sample(s).unwrap();
+        }
+    }
+    writer.finalize().unwrap();
+
+    let names = write_channel_files("session.wav", &JobControl::default()).unwrap();
+    assert_eq!(
+        names,
+        ["session_ch1.wav", "session_ch2.wav", "session_ch3.wav"]
+    );
+    for (ch, name) in names.iter().enumerate() {
+        let mut reader = hound::WavReader::open(name).unwrap();
+        assert_eq!(
+            reader.spec(),
+            WavSpec {
+                channels: 1,
+                ..spec
+            }
+        );
+        let samples: Vec<i32> = reader.samples::<i32>().map(Result::unwrap).collect();
+        let expected: Vec<i32> = frames.iter().map(|frame| frame[ch]).collect();
+        assert_eq!(samples, expected);
+    }
+
+    // Existing stems aren't overwritten, and a mono file has nothing to split
+    let err = write_channel_files("session.wav", &JobControl::default()).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+    let err = write_channel_files("session_ch1.wav", &JobControl::default()).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn new_output_streams_fade_up_from_silence() {
+    let _dir = TempDir::enter("start_ramp");
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate: 44100,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    write_wav_file_f32("level.wav", spec, &[0.5; 4410]).unwrap();
+    let mut backend = MockBackend::new(1, 44100);
+    backend.max_render_samples = 4410;
+    let rendered = Arc::clone(&backend.rendered);
+    let mut recorder = recorder_with(backend);
+    send(&mut recorder, Message::UpdateStartRamp("900".into()));
+    assert_eq!(recorder.config.output_start_ramp_ms, 20);
+    send(&mut recorder, Message::UpdateStartRamp("10".into()));
+    send(&mut recorder, Message::PlayFile("level.wav".into()));
+
+    let rendered = rendered.lock().unwrap();
+    assert_eq!(rendered[0], 0.0);
+    assert!((rendered[220] - 0.25).abs() < 1e-3);
+    assert!(rendered[..441].windows(2).all(|w| w[0] < w[1]));
+    assert!(rendered[441..4410].iter().all(|&s| s == 0.5));
+
+    // The ramp carries on across callbacks and then leaves the audio alone
+    let mut ramped = 0;
+    let mut first = [1.0; 6];
+    apply_start_ramp(&mut first, 2, &mut ramped, 4);
+    assert_eq!(first, [0.0, 0.0, 0.25, 0.25, 0.5, 0.5]);
+    let mut second = [1.0; 6];
+    apply_start_ramp(&mut second, 2, &mut ramped, 4);
+    assert_eq!(second, [0.75, 0.75, 1.0, 1.0, 1.0, 1.0]);
+}
+
+#[test]
+fn playback_meters_the_output_after_the_volume() {
+    let _dir = TempDir::enter("output_meter");
+    let tone = sine(440.0, 48000, 1, 0.25);
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate: 48000,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    write_wav_file_f32("tone.wav", spec, &tone).unwrap();
+
+    let mut backend = MockBackend::new(1, 48000);
+    backend.max_render_samples = tone.len() / 2;
+    let mut recorder = recorder_with(backend);
+    recorder.config.output_start_ramp_ms = 0;
+    recorder.config.meter_mode = MeterMode::Peak;
+    recorder.config.meter_attack_ms = 0.0;
+    send(&mut recorder, Message::SetOutputVolume(0.5));
+    send(&mut recorder, Message::PlayFile("tone.wav".into()));
+    assert_eq!(recorder.playback_state, PlaybackState::Playing);
+
+    // The sine peaks at 0.5; at half volume the meter reads what is heard
+    send(&mut recorder, Message::Tick(Instant::now()));
+    assert!((recorder.meter_level - 0.25).abs() < 0.01);
+    assert!(recorder.clip_until.is_none());
+
+    send(&mut recorder, Message::StopPlayback);
+    assert_eq!(recorder.meter_level, 0.0);
+}
+
+#[test]
+fn resample_quality_is_chosen_per_operation() {
+    // A 15 kHz tone has no place under 22.05 kHz's Nyquist: the sinc filters it
+    // out, while linear interpolation folds it back as an alias
+    let tone = sine(15000.0, 48000, 1, 0.5);
+    let rms = |s: &[f32]| (s.iter().map(|x| x * x).sum::<f32>() / s.len() as f32).sqrt();
+    let high = resample_with_progress(&tone, 1, 48000, 22050, ResampleQuality::High, &mut |_| true)
+        .unwrap();
+    let fast = resample_with_progress(&tone, 1, 48000, 22050, ResampleQuality::Fast, &mut |_| true)
+        .unwrap();
+    assert_eq!(high.len(), fast.len());
+    let middle = high.len() / 4..high.len() * 3 / 4;
+    assert!(rms(&high[middle.clone()]) < 0.01);
+    assert!(rms(&fast[middle]) > 0.1);
+
+    // Playback is light by default, export is careful
+    let _dir = TempDir::enter("resample_quality");
+    assert_eq!(
+        ExportSettings::default().resample_quality,
+        ResampleQuality::High
+    );
+    let file_tone = sine(1000.0, 24000, 1, 0.1);
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate: 24000,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    write_wav_file_f32("tone.wav", spec, &file_tone).unwrap();
+    let expected = sine(1000.0, 48000, 1, 0.1);
+
+    let mut backend = MockBackend::new(1, 48000);
+    backend.output_formats[0].min_sample_rate = 48000;
+    backend.output_formats[0].max_sample_rate = 48000;
+    backend.max_render_samples = expected.len();
+    let rendered = Arc::clone(&backend.rendered);
+    let mut recorder = recorder_with(backend);
+    recorder.config.output_start_ramp_ms = 0;
+    assert_eq!(
+        recorder.config.playback_resample_quality,
+        ResampleQuality::Fast
+    );
+
+    // The 24 kHz file plays at its own pitch on the 48 kHz device, either way
+    for quality in ResampleQuality::ALL {
+        send(
+            &mut recorder,
+            Message::SelectPlaybackResampleQuality(quality),
+        );
+        rendered.lock().unwrap().clear();
+        send(&mut recorder, Message::PlayFile("tone.wav".into()));
+        let rendered = rendered.lock().unwrap();
+        let error = rendered[500..4000]
+            .iter()
+            .zip(&expected[500..4000])
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0f32, f32::max);
+        assert!(error < 0.01, "{quality}: {error}");
+        drop(rendered);
+        send(&mut recorder, Message::StopPlayback);
+    }
+    assert_eq!(
+        recorder.config.playback_resample_quality,
+        ResampleQuality::High
+    );
+}
+
+#[test]
+fn files_can_be_grouped_under_their_recording_date() {
+    let _dir = TempDir::enter("group_by_date");
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate: 8000,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let now = SystemTime::now();
+    let day = Duration::from_secs(86_400);
+    // Local midday, whatever the time zone
+    let midday_utc = 1_709_251_200 + 43_200;
+    let leap_day = std::time::UNIX_EPOCH
+        + Duration::from_secs((midday_utc - local_offset_secs(midday_utc as u64)) as u64);
+    for (name, modified) in [("a.wav", now - day), ("b.wav", leap_day), ("c.wav", now)] {
+        write_wav_file_i16(name, spec, &[0; 8]).unwrap();
+        fs::File::options()
+            .write(true)
+            .open(name)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+    }
+
+    let mut recorder = recorder_with(MockBackend::new(1, 48000));
+    recorder.refresh_files();
+    assert_eq!(recorder.files, ["a.wav", "b.wav", "c.wav"]);
+
+    send(&mut recorder, Message::ToggleGroupByDate(true));
+    assert!(recorder.config.group_by_date);
+    assert_eq!(recorder.files, ["c.wav", "a.wav", "b.wav"]);
+    let headings: Vec<String> = recorder
+        .files
+        .iter()
+        .map(|f| date_heading(recorder.file_modified[f], now))
+        .collect();
+    assert_eq!(headings, ["Today", "Yesterday", "2024-03-01"]);
+
+    send(&mut recorder, Message::ToggleGroupByDate(false));
+    assert_eq!(recorder.files, ["a.wav", "b.wav", "c.wav"]);
+}
+
+#[test]
+fn polarity_flips_chosen_channels_on_playback_or_in_the_file() {
+    let _dir = TempDir::enter("polarity");
+    let spec = WavSpec {
+        channels: 2,
+        sample_rate: 48000,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    write_wav_file_f32("stereo.wav", spec, &[0.1, 0.2].repeat(4800)).unwrap();
+
+    let mut backend = MockBackend::new(2, 48000);
+    backend.max_render_samples = 1024;
+    let rendered = Arc::clone(&backend.rendered);
+    let mut recorder = recorder_with(backend);
+    recorder.config.output_start_ramp_ms = 0;
+
+    // Only the right channel is flipped, and only in what's heard
+    send(&mut recorder, Message::TogglePolarityChannel(0, false));
+    send(&mut recorder, Message::ToggleInvertPlayback(true));
+    send(&mut recorder, Message::PlayFile("stereo.wav".into()));
+    for frame in rendered.lock().unwrap().chunks_exact(2) {
+        assert_eq!(frame, [0.1, -0.2]);
+    }
+    send(&mut recorder, Message::StopPlayback);
+    assert_eq!(read_wav_file("stereo.wav").unwrap().1[..2], [0.1, 0.2]);
+
+    // Inverting the file rewrites it the same way
+    send(&mut recorder, Message::SelectFile("stereo.wav".into()));
+    send(&mut recorder, Message::InvertPolarity);
+    let (_, samples) = read_wav_file("stereo.wav").unwrap();
+    assert_eq!(samples.len(), 9600);
+    assert!(samples.chunks_exact(2).all(|frame| frame == [0.1, -0.2]));
+
+    // With no channel chosen there's nothing to do
+    send(&mut recorder, Message::TogglePolarityChannel(1, false));
+    send(&mut recorder, Message::InvertPolarity);
+    assert_eq!(read_wav_file("stereo.wav").unwrap().1[..2], [0.1, -0.2]);
+}
+
+#[test]
+fn playback_settings_are_saved_per_file_and_cleared() {
+    let _dir = TempDir::enter("file_playback");
+    let spec = WavSpec {
+        channels: 2,
+        sample_rate: 48000,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let tone = sine(440.0, 48000, 2, 0.5);
+    write_wav_file_f32("interview.wav", spec, &tone).unwrap();
+    write_wav_file_f32("other.wav", spec, &tone).unwrap();
+    // A speed saved by older versions still applies
+    fs::write("other.speed.json", "0.75").unwrap();
+
+    let mut recorder = recorder_with(MockBackend::new(2, 48000));
+    let volume = |r: &VoiceRecorder| f32::from_bits(r.output_volume.load(Ordering::Relaxed));
+    send(&mut recorder, Message::SelectFile("interview.wav".into()));
+    send(&mut recorder, Message::SetPlaybackSpeed(1.25));
+    send(&mut recorder, Message::SetOutputVolume(0.3));
+    send(&mut recorder, Message::SetStereoWidth(0.5));
+    send(&mut recorder, Message::RememberPlaybackSettings);
+    assert!(fs::metadata(playback_settings_path("interview.wav")).is_ok());
+
+    // Another file gets the usual volume and width back
+    send(&mut recorder, Message::PlayFile("other.wav".into()));
+    assert_eq!(recorder.playback_speed(), 0.75);
+    assert_eq!(volume(&recorder), 1.0);
+    assert_eq!(*recorder.stereo_width.lock().unwrap(), 1.0);
+    send(&mut recorder, Message::StopPlayback);
+
+    send(&mut recorder, Message::PlayFile("interview.wav".into()));
+    assert!(recorder.status_message.contains("with its saved settings"));
+    assert_eq!(recorder.playback_speed(), 1.25);
+    assert_eq!(volume(&recorder), 0.3);
+    assert_eq!(*recorder.stereo_width.lock().unwrap(), 0.5);
+
+    send(&mut recorder, Message::ClearPlaybackSettings);
+    assert!(fs::metadata(playback_settings_path("interview.wav")).is_err());
+    assert_eq!(recorder.playback_speed(), 1.0);
+    assert_eq!(volume(&recorder), 1.0);
+    assert_eq!(*recorder.stereo_width.lock().unwrap(), 1.0);
+
+    // Saving just a speed moves an old sidecar over to the new one
+    send(&mut recorder, Message::StopPlayback);
+    send(&mut recorder, Message::SelectFile("other.wav".into()));
+    send(&mut recorder, Message::SetPlaybackSpeed(1.5));
+    send(&mut recorder, Message::SavePlaybackSpeed);
+    assert!(fs::metadata("other.speed.json").is_err());
+    assert_eq!(load_file_playback("other.wav").unwrap().speed, 1.5);
+}
+
+#[test]
+fn monitor_only_meters_the_input_but_never_records() {
+    let _dir = TempDir::enter("monitor_only");
+    let mut backend = MockBackend::new(1, 48000);
+    backend.input_signal = sine(440.0, 48000, 1, 0.1);
+    let mut recorder = recorder_with(backend);
+    recorder.config.pre_roll_secs = 1.0;
+
+    send(&mut recorder, Message::StartMonitorOnly);
+    assert_eq!(recorder.recording_phase, RecordingPhase::Monitoring);
+    assert!(recorder.recording_phase.input_open());
+    assert!(recorder.status_message.contains("nothing is recorded"));
+    assert!(recorder.input_levels.take().0 > 0.4);
+    // Not even a pre-roll is kept
+    assert!(recorder.audio_data.lock().unwrap().is_empty());
+    assert!(recorder.pre_roll.lock().unwrap().is_empty());
+
+    send(&mut recorder, Message::StartRecording);
+    assert!(!recorder.recording_phase.is_recording());
+    assert!(!recorder.input_capturing.load(Ordering::Relaxed));
+    assert!(
+        recorder
+            .status_message
+            .contains("stop monitoring to record")
+    );
+
+    send(&mut recorder, Message::DisarmRecording);
+    assert_eq!(recorder.recording_phase, RecordingPhase::Idle);
+    assert!(recorder.input_stream.is_none());
+    assert!(list_wav_files(None).is_empty());
+}
+
+#[test]
+fn mp3_exports_carry_id3_tags_and_cover_art() {
+    let _dir = TempDir::enter("id3");
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate: 44100,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    write_wav_file_f32("episode.wav", spec, &sine(440.0, 44100, 1, 0.2)).unwrap();
+    let cover = [b"\x89PNG\r\n\x1a\n".as_slice(), &[1, 2, 3]].concat();
+    fs::write("cover.png", &cover).unwrap();
+
+    let mut settings = ExportSettings {
+        format: ExportFormat::Mp3,
+        mp3_tags: Mp3Tags {
+            title: String::new(),
+            artist: "Ana & Bo".into(),
+            album: "Show \u{9031}".into(),
+            cover_art: "cover.png".into(),
+        },
+        ..ExportSettings::default()
+    };
+    export_file("episode.wav", &settings, &JobControl::default()).unwrap();
+    let mp3 = fs::read("episode_export.mp3").unwrap();
+    assert_eq!(&mp3[..6], b"ID3\x03\x00\x00");
+    let size = mp3[6..10]
+        .iter()
+        .fold(0usize, |size, &b| (size << 7) | b as usize);
+
+    let mut frames = HashMap::new();
+    let mut pos = 10;
+    while pos < 10 + size {
+        let len = u32::from_be_bytes(mp3[pos + 4..pos + 8].try_into().unwrap()) as usize;
+        frames.insert(
+            mp3[pos..pos + 4].to_vec(),
+            mp3[pos + 10..pos + 10 + len].to_vec(),
+        );
+        pos += 10 + len;
+    }
+    assert_eq!(pos, 10 + size);
+    // A blank title becomes the recording's name
+    assert_eq!(frames[&b"TIT2".to_vec()], b"\x00episode");
+    assert_eq!(frames[&b"TPE1".to_vec()], b"\x00Ana & Bo");
+    // Text outside Latin-1 goes in as UTF-16
+    assert_eq!(
+        frames[&b"TALB".to_vec()],
+        [&[1, 0xff, 0xfe][..], b"S\0h\0o\0w\0 \0\x31\x90"].concat()
+    );
+    assert_eq!(
+        frames[&b"APIC".to_vec()],
+        [b"\x00image/png\x00\x03\x00".as_slice(), &cover].concat()
+    );
+    // The MP3 frames follow straight after
+    assert_eq!(mp3[pos], 0xff);
+    assert_eq!(mp3[pos + 1] & 0xe0, 0xe0);
+
+    // Cover art that isn't there stops the export before anything is written
+    fs::remove_file("episode_export.mp3").unwrap();
+    settings.mp3_tags.cover_art = "missing.png".into();
+    let error = export_file("episode.wav", &settings, &JobControl::default())
+        .err()
+        .unwrap();
+    assert!(error.contains("Couldn't read cover art 'missing.png'"));
+    assert!(fs::metadata("episode_export.mp3").is_err());
+
+    // The cover picked in settings is kept as a full path once it names a file
+    let mut recorder = recorder_with(MockBackend::new(1, 44100));
+    send(&mut recorder, Message::UpdateMp3CoverArt("cov".into()));
+    assert_eq!(recorder.config.export.mp3_tags.cover_art, "");
+    send(
+        &mut recorder,
+        Message::UpdateMp3CoverArt("cover.png".into()),
+    );
+    assert_eq!(
+        recorder.config.export.mp3_tags.cover_art,
+        fs::canonicalize("cover.png").unwrap().display().to_string()
+    );
+    assert_eq!(recorder.cover_art_input, "cover.png");
+}
+
+#[test]
+fn rerecord_replaces_the_last_take_under_its_name() {
+    let _dir = TempDir::enter("rerecord");
+    let mut backend = MockBackend::new(1, 48000);
+    backend.input_signal = sine(440.0, 48000, 1, 0.5);
+    let mut recorder = recorder_with(backend);
+    let take = |recorder: &mut VoiceRecorder, mark: bool| {
+        send(recorder, Message::StartRecording);
+        if mark {
+            send(recorder, Message::DropMark);
+        }
+        send(recorder, Message::StopRecording);
+        send(recorder, Message::FinalizeRecording);
+    };
+
+    // Nothing to re-record before a take is saved
+    send(&mut recorder, Message::RerecordLastTake);
+    assert_eq!(recorder.recording_phase, RecordingPhase::Idle);
+
+    take(&mut recorder, false);
+    take(&mut recorder, true);
+    assert_eq!(recorder.last_take.as_deref(), Some("recording_2.wav"));
+    assert!(fs::metadata(marks_path("recording_2.wav")).is_ok());
+
+    send(&mut recorder, Message::RerecordLastTake);
+    assert!(recorder.recording_phase.is_recording());
+    assert!(
+        recorder
+            .status_message
+            .contains("Re-recording 'recording_2.wav'")
+    );
+    assert_eq!(recorder.files, ["recording_1.wav"]);
+    assert!(fs::metadata(marks_path("recording_2.wav")).is_err());
+    // The old take is held rather than deleted, its cue points along with it
+    assert!(fs::metadata(format!("{}/1/recording_2.wav", UNDO_DIR)).is_ok());
+    assert!(fs::metadata(format!("{}/1/recording_2.marks.json", UNDO_DIR)).is_ok());
+
+    send(&mut recorder, Message::StopRecording);
+    send(&mut recorder, Message::FinalizeRecording);
+    assert_eq!(recorder.files, ["recording_1.wav", "recording_2.wav"]);
+    assert_eq!(recorder.last_take.as_deref(), Some("recording_2.wav"));
+
+    // A discarded retake doesn't leave its name behind for the next one
+    recorder.config.auto_save_recordings = false;
+    send(&mut recorder, Message::RerecordLastTake);
+    send(&mut recorder, Message::StopRecording);
+    let RecordingPhase::Stopping { since } = recorder.recording_phase else {
+        panic!("not stopping: {:?}", recorder.recording_phase);
+    };
+    send(&mut recorder, Message::Tick(since + Duration::from_secs(1)));
+    send(&mut recorder, Message::DiscardRecording);
+    assert!(recorder.rerecording.is_none());
+    assert_eq!(recorder.files, ["recording_1.wav"]);
+
+    // Undo brings back the first retake, and then the original with its marks
+    send(&mut recorder, Message::UndoBatch);
+    assert_eq!(recorder.files, ["recording_1.wav", "recording_2.wav"]);
+    assert!(fs::metadata(marks_path("recording_2.wav")).is_err());
+    fs::remove_file("recording_2.wav").unwrap();
+    send(&mut recorder, Message::UndoBatch);
+    assert_eq!(recorder.files, ["recording_1.wav", "recording_2.wav"]);
+    assert!(fs::metadata(marks_path("recording_2.wav")).is_ok());
+}
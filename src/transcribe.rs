@@ -0,0 +1,151 @@
+//! Live captions while recording. Audio is fed to a speech-to-text engine on a worker
+//! thread and finished phrases come back as lines of text. The Vosk engine is only
+//! built with the `transcribe` feature; without it (or without a model) captions are
+//! simply unavailable and recording carries on as normal.
+
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+/// Where to look for a Vosk model when `VOSK_MODEL` isn't set.
+#[cfg(feature = "transcribe")]
+const DEFAULT_MODEL_DIR: &str = "vosk-model";
+
+/// How much audio `transcribe` hands the engine at a time.
+const CLIP_CHUNK: usize = 4096;
+
+/// A streaming speech-to-text engine.
+pub trait Transcriber: Send {
+    /// Takes the next stretch of mono audio and returns a phrase once the engine
+    /// considers one finished.
+    fn accept(&mut self, samples: &[f32]) -> Option<String>;
+    /// Whatever is left once the audio has ended.
+    fn finish(&mut self) -> Option<String>;
+}
+
+/// Builds a transcriber for mono audio at `sample_rate`.
+pub type Engine = fn(u32) -> Result<Box<dyn Transcriber>, String>;
+
+#[cfg(feature = "transcribe")]
+struct Vosk(vosk::Recognizer);
+
+#[cfg(feature = "transcribe")]
+impl Vosk {
+    fn text(result: vosk::CompleteResult) -> Option<String> {
+        let text = result.single()?.text.trim();
+        (!text.is_empty()).then(|| text.to_string())
+    }
+}
+
+#[cfg(feature = "transcribe")]
+impl Transcriber for Vosk {
+    fn accept(&mut self, samples: &[f32]) -> Option<String> {
+        let ints: Vec<i16> = samples
+            .iter()
+            .map(|&s| (s.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)
+            .collect();
+        match self.0.accept_waveform(&ints) {
+            Ok(vosk::DecodingState::Finalized) => Self::text(self.0.result()),
+            _ => None,
+        }
+    }
+
+    fn finish(&mut self) -> Option<String> {
+        Self::text(self.0.final_result())
+    }
+}
+
+/// The Vosk engine, using the model in `VOSK_MODEL` or `./vosk-model`.
+#[cfg(feature = "transcribe")]
+pub fn default_engine(sample_rate: u32) -> Result<Box<dyn Transcriber>, String> {
+    let path = std::env::var("VOSK_MODEL").unwrap_or_else(|_| DEFAULT_MODEL_DIR.into());
+    let model = vosk::Model::new(path.as_str()).ok_or_else(|| {
+        format!(
+            "no speech model found at '{}' (set VOSK_MODEL to a Vosk model folder)",
+            path
+        )
+    })?;
+    let recognizer = vosk::Recognizer::new(&model, sample_rate as f32)
+        .ok_or_else(|| "the speech model couldn't be started".to_string())?;
+    Ok(Box::new(Vosk(recognizer)))
+}
+
+#[cfg(not(feature = "transcribe"))]
+pub fn default_engine(_sample_rate: u32) -> Result<Box<dyn Transcriber>, String> {
+    Err("this build doesn't include speech recognition (build with --features transcribe)".into())
+}
+
+/// Transcribes a short clip in one go, returning all of its phrases joined by spaces.
+pub fn transcribe(mut engine: Box<dyn Transcriber>, samples: &[f32]) -> String {
+    let mut phrases: Vec<String> = samples
+        .chunks(CLIP_CHUNK)
+        .filter_map(|chunk| engine.accept(chunk))
+        .collect();
+    phrases.extend(engine.finish());
+    phrases.join(" ")
+}
+
+/// A transcriber running on its own thread, fed through `feed` and read through `lines`.
+pub struct LiveCaptions {
+    tx: Option<mpsc::Sender<Vec<f32>>>,
+    worker: Option<JoinHandle<()>>,
+    pub lines: Arc<Mutex<Vec<String>>>,
+    error: Arc<Mutex<Option<String>>>,
+}
+
+impl LiveCaptions {
+    /// Builds the engine on the worker thread, since loading a model can take a
+    /// while; audio fed in the meantime waits for it. If it can't be built, `error`
+    /// says why.
+    pub fn start(engine: Engine, sample_rate: u32) -> Self {
+        let (tx, rx) = mpsc::channel::<Vec<f32>>();
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let error = Arc::new(Mutex::new(None));
+        let out = Arc::clone(&lines);
+        let failed = Arc::clone(&error);
+        let worker = thread::spawn(move || {
+            let mut engine = match engine(sample_rate) {
+                Ok(engine) => engine,
+                Err(e) => {
+                    *failed.lock().unwrap() = Some(e);
+                    return;
+                }
+            };
+            for chunk in rx {
+                if let Some(line) = engine.accept(&chunk) {
+                    out.lock().unwrap().push(line);
+                }
+            }
+            if let Some(line) = engine.finish() {
+                out.lock().unwrap().push(line);
+            }
+        });
+        Self {
+            tx: Some(tx),
+            worker: Some(worker),
+            lines,
+            error,
+        }
+    }
+
+    pub fn feed(&self, samples: Vec<f32>) {
+        if let Some(tx) = &self.tx {
+            let _ = tx.send(samples);
+        }
+    }
+
+    /// Why the engine couldn't be started, once the worker has found out.
+    pub fn error(&self) -> Option<String> {
+        self.error.lock().unwrap().clone()
+    }
+
+    /// Ends the audio and waits for the engine to catch up, returning every line.
+    /// This blocks until the engine is done, so call it off the UI thread.
+    pub fn finish(&mut self) -> Vec<String> {
+        self.tx = None;
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+        self.lines.lock().unwrap().clone()
+    }
+}
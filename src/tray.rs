@@ -0,0 +1,170 @@
+//! System tray icon (the `tray` feature): red while recording, grey otherwise, with a
+//! menu to start/stop recording and bring the window back. Menu clicks arrive as
+//! `TrayAction`s through `subscription()`.
+//!
+//! The tray has to live on a thread running the platform's event loop. On Windows and
+//! macOS that's the iced main thread; on Linux it's a GTK loop on its own thread.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use iced::Subscription;
+use iced::futures::SinkExt;
+use tray_icon::menu::{Menu, MenuEvent, MenuItem};
+use tray_icon::{Icon, TrayIcon, TrayIconBuilder};
+
+const TOGGLE_ID: &str = "toggle-recording";
+const SHOW_ID: &str = "show-window";
+const ICON_SIZE: u32 = 32;
+
+#[derive(Debug, Clone, Copy)]
+pub enum TrayAction {
+    ToggleRecording,
+    ShowWindow,
+}
+
+struct Handle {
+    icon: TrayIcon,
+    toggle: MenuItem,
+}
+
+impl Handle {
+    fn build() -> Result<Self, String> {
+        let toggle = MenuItem::with_id(TOGGLE_ID, "Start recording", true, None);
+        let show = MenuItem::with_id(SHOW_ID, "Open Voice Recorder", true, None);
+        let menu = Menu::new();
+        menu.append(&toggle)
+            .and_then(|()| menu.append(&show))
+            .map_err(|e| format!("Error building tray menu: {}", e))?;
+
+        let icon = TrayIconBuilder::new()
+            .with_menu(Box::new(menu))
+            .with_tooltip("Voice Recorder - idle")
+            .with_icon(dot_icon(false)?)
+            .build()
+            .map_err(|e| format!("Error creating tray icon: {}", e))?;
+        Ok(Self { icon, toggle })
+    }
+
+    fn show_recording(&self, recording: bool) {
+        let (label, tooltip) = if recording {
+            ("Stop recording", "Voice Recorder - recording")
+        } else {
+            ("Start recording", "Voice Recorder - idle")
+        };
+        self.toggle.set_text(label);
+        let _ = self.icon.set_tooltip(Some(tooltip));
+        if let Ok(icon) = dot_icon(recording) {
+            let _ = self.icon.set_icon(Some(icon));
+        }
+    }
+}
+
+/// A filled circle: red while recording, grey when idle.
+fn dot_icon(recording: bool) -> Result<Icon, String> {
+    let color = if recording {
+        [220, 40, 40]
+    } else {
+        [140, 140, 140]
+    };
+    let center = (ICON_SIZE as f32 - 1.0) / 2.0;
+    let radius = ICON_SIZE as f32 / 2.0 - 2.0;
+    let rgba = (0..ICON_SIZE * ICON_SIZE)
+        .flat_map(|i| {
+            let (x, y) = ((i % ICON_SIZE) as f32, (i / ICON_SIZE) as f32);
+            let inside = (x - center).hypot(y - center) <= radius;
+            [color[0], color[1], color[2], if inside { 255 } else { 0 }]
+        })
+        .collect();
+    Icon::from_rgba(rgba, ICON_SIZE, ICON_SIZE)
+        .map_err(|e| format!("Error drawing tray icon: {}", e))
+}
+
+pub struct Tray {
+    recording: Arc<AtomicBool>,
+    #[cfg(not(target_os = "linux"))]
+    handle: Handle,
+}
+
+impl Tray {
+    #[cfg(not(target_os = "linux"))]
+    pub fn new() -> Result<Self, String> {
+        Ok(Self {
+            recording: Arc::new(AtomicBool::new(false)),
+            handle: Handle::build()?,
+        })
+    }
+
+    /// Starts the GTK loop that owns the icon. It polls the recording flag, since
+    /// GTK objects can't be touched from the iced thread.
+    #[cfg(target_os = "linux")]
+    pub fn new() -> Result<Self, String> {
+        let recording = Arc::new(AtomicBool::new(false));
+        let shown = Arc::clone(&recording);
+        let (ready_tx, ready_rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            if let Err(e) = gtk::init() {
+                let _ = ready_tx.send(Err(format!("Error starting GTK: {}", e)));
+                return;
+            }
+            let handle = match Handle::build() {
+                Ok(handle) => handle,
+                Err(e) => {
+                    let _ = ready_tx.send(Err(e));
+                    return;
+                }
+            };
+            let _ = ready_tx.send(Ok(()));
+
+            let mut last = false;
+            gtk::glib::timeout_add_local(std::time::Duration::from_millis(100), move || {
+                let now = shown.load(Ordering::Relaxed);
+                if now != last {
+                    handle.show_recording(now);
+                    last = now;
+                }
+                gtk::glib::ControlFlow::Continue
+            });
+            gtk::main();
+        });
+
+        ready_rx
+            .recv()
+            .map_err(|_| "Tray thread exited.".to_string())??;
+        Ok(Self { recording })
+    }
+
+    pub fn set_recording(&self, recording: bool) {
+        if self.recording.swap(recording, Ordering::Relaxed) != recording {
+            #[cfg(not(target_os = "linux"))]
+            self.handle.show_recording(recording);
+        }
+    }
+}
+
+/// Menu clicks, forwarded from the tray's event channel.
+pub fn subscription() -> Subscription<TrayAction> {
+    Subscription::run(|| {
+        iced::stream::channel(16, |mut output| async move {
+            let (tx, mut rx) = iced::futures::channel::mpsc::unbounded();
+            // The menu channel blocks, so it gets a thread of its own
+            std::thread::spawn(move || {
+                while let Ok(event) = MenuEvent::receiver().recv() {
+                    let action = if event.id == TOGGLE_ID {
+                        TrayAction::ToggleRecording
+                    } else if event.id == SHOW_ID {
+                        TrayAction::ShowWindow
+                    } else {
+                        continue;
+                    };
+                    if tx.unbounded_send(action).is_err() {
+                        break;
+                    }
+                }
+            });
+            while let Some(action) = iced::futures::StreamExt::next(&mut rx).await {
+                let _ = output.send(action).await;
+            }
+        })
+    })
+}
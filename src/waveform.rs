@@ -0,0 +1,491 @@
+//! Waveform display. Peaks are computed once per file and drawn on a canvas showing
+//! the visible part of the file; clicks are reported as a position in the whole file.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::ops::Range;
+
+use iced::mouse;
+use iced::widget::canvas::{self, Event, Frame, Geometry};
+use iced::{Color, Point, Rectangle, Renderer, Size, Theme};
+
+/// Peak buckets kept per file, enough for a few thousand pixels at full zoom-out.
+const PEAK_BUCKETS: usize = 4000;
+pub const MAX_ZOOM: f32 = 32.0;
+
+/// Voice activity is judged over short windows: a window is speech when its RMS is
+/// well above the file's noise floor (its quietest tenth of windows) and above an
+/// absolute minimum, so a file that is all room tone has no speech.
+const VAD_WINDOW_MS: u32 = 20;
+const VAD_FLOOR_MARGIN: f32 = 4.0;
+const VAD_MIN_LEVEL: f32 = 0.01;
+/// Pauses up to this long count as part of the surrounding speech, and bursts
+/// shorter than the minimum (clicks, bumps) are dropped.
+const VAD_HANGOVER_MS: u32 = 300;
+const VAD_MIN_SPEECH_MS: u32 = 100;
+
+/// Per-file waveform summary: the absolute peak of each bucket across all channels.
+#[derive(Debug, Clone)]
+pub struct WaveformData {
+    pub file: String,
+    pub peaks: Vec<f32>,
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub total_samples: usize,
+    /// Where speech was detected, as fractions of the whole file.
+    pub speech: Vec<Range<f32>>,
+}
+
+impl WaveformData {
+    pub fn new(file: &str, samples: &[f32], channels: u16, sample_rate: u32) -> Self {
+        let channels_usize = channels.max(1) as usize;
+        let frames = samples.len() / channels_usize;
+        let buckets = PEAK_BUCKETS.min(frames).max(1);
+        let peaks = (0..buckets)
+            .map(|b| {
+                let start = b * frames / buckets * channels_usize;
+                let end = (b + 1) * frames / buckets * channels_usize;
+                samples[start..end]
+                    .iter()
+                    .fold(0.0f32, |m, s| m.max(s.abs()))
+            })
+            .collect();
+
+        Self {
+            file: file.to_string(),
+            peaks,
+            channels,
+            sample_rate,
+            total_samples: samples.len(),
+            speech: speech_segments(samples, channels, sample_rate),
+        }
+    }
+
+    /// Start of the speech segment at `fraction`, or else of the next one after it.
+    pub fn speech_from(&self, fraction: f32) -> Option<f32> {
+        self.speech
+            .iter()
+            .find(|segment| segment.end > fraction)
+            .map(|segment| segment.start)
+    }
+
+    /// Interleaved sample index for a position in the file (0.0 to 1.0), on a frame
+    /// boundary and clamped to the last frame.
+    pub fn sample_at(&self, fraction: f32) -> usize {
+        let channels = self.channels.max(1) as usize;
+        let frames = self.total_samples / channels;
+        let frame = (fraction.clamp(0.0, 1.0) as f64 * frames as f64) as usize;
+        frame.min(frames.saturating_sub(1)) * channels
+    }
+
+    pub fn duration_secs(&self) -> f32 {
+        let frames = self.total_samples / self.channels.max(1) as usize;
+        frames as f32 / self.sample_rate.max(1) as f32
+    }
+
+    /// Peak over a span of the file given as fractions, or `None` past its end.
+    fn peak_between(&self, from: f32, to: f32) -> Option<f32> {
+        let peaks = &self.peaks;
+        if peaks.is_empty() || from >= 1.0 {
+            return None;
+        }
+        let first = ((from * peaks.len() as f32) as usize).min(peaks.len() - 1);
+        let last = ((to * peaks.len() as f32).ceil() as usize).clamp(first + 1, peaks.len());
+        Some(
+            peaks[first..last]
+                .iter()
+                .fold(0.0f32, |m, &v| m.max(v))
+                .min(1.0),
+        )
+    }
+}
+
+/// Energy-based voice activity detection over interleaved `samples`, returning the
+/// speech segments as fractions of the whole file.
+pub fn speech_segments(samples: &[f32], channels: u16, sample_rate: u32) -> Vec<Range<f32>> {
+    let channels = channels.max(1) as usize;
+    let frames = samples.len() / channels;
+    let window_frames = ((sample_rate * VAD_WINDOW_MS / 1000) as usize).max(1);
+    if frames == 0 {
+        return Vec::new();
+    }
+    let levels: Vec<f32> = samples[..frames * channels]
+        .chunks(window_frames * channels)
+        .map(|chunk| {
+            let sum: f32 = chunk.iter().map(|s| s * s).sum();
+            (sum / chunk.len() as f32).sqrt()
+        })
+        .collect();
+    let mut sorted = levels.clone();
+    sorted.sort_by(f32::total_cmp);
+    let threshold = (sorted[sorted.len() / 10] * VAD_FLOOR_MARGIN).max(VAD_MIN_LEVEL);
+
+    let windows_for = |ms: u32| (ms / VAD_WINDOW_MS) as usize;
+    let mut segments: Vec<Range<usize>> = Vec::new();
+    for (i, &level) in levels.iter().enumerate() {
+        if level < threshold {
+            continue;
+        }
+        match segments.last_mut() {
+            Some(last) if i - last.end <= windows_for(VAD_HANGOVER_MS) => last.end = i + 1,
+            _ => segments.push(i..i + 1),
+        }
+    }
+    segments
+        .into_iter()
+        .filter(|segment| segment.len() >= windows_for(VAD_MIN_SPEECH_MS))
+        .map(|segment| {
+            let at = |window: usize| (window * window_frames).min(frames) as f32 / frames as f32;
+            at(segment.start)..at(segment.end)
+        })
+        .collect()
+}
+
+/// Parses a `#rrggbb` (or `rrggbb`) color.
+pub fn parse_hex_color(hex: &str) -> Option<[u8; 3]> {
+    let hex = hex.trim().trim_start_matches('#');
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+/// Draws the whole file the way the canvas does, into an RGB buffer `width` by
+/// `height` pixels.
+pub fn render_rgb(
+    data: &WaveformData,
+    width: u32,
+    height: u32,
+    foreground: [u8; 3],
+    background: [u8; 3],
+) -> Vec<u8> {
+    let (width, height) = (width.max(1) as usize, height.max(1) as usize);
+    let mut pixels = background.repeat(width * height);
+    let mid = height as f32 / 2.0;
+    for x in 0..width {
+        let from = x as f32 / width as f32;
+        let to = (x + 1) as f32 / width as f32;
+        let peak = data.peak_between(from, to).unwrap_or(0.0);
+        let half = (peak * mid).max(0.5);
+        let top = (mid - half).floor().max(0.0) as usize;
+        let bottom = ((mid + half).ceil() as usize).min(height);
+        for y in top..bottom {
+            let i = (y * width + x) * 3;
+            pixels[i..i + 3].copy_from_slice(&foreground);
+        }
+    }
+    pixels
+}
+
+/// Writes `render_rgb` output to `path` as a PNG.
+pub fn save_png(
+    path: &str,
+    data: &WaveformData,
+    width: u32,
+    height: u32,
+    foreground: [u8; 3],
+    background: [u8; 3],
+) -> io::Result<()> {
+    let pixels = render_rgb(data, width, height, foreground, background);
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    writer.write_image_data(&pixels).map_err(io::Error::other)?;
+    writer.finish().map_err(io::Error::other)
+}
+
+/// Visible window of the waveform, as fractions of the whole file.
+#[derive(Debug, Clone, Copy)]
+pub struct View {
+    pub start: f32,
+    pub len: f32,
+}
+
+impl View {
+    pub fn new(zoom: f32, scroll: f32) -> Self {
+        let len = 1.0 / zoom.clamp(1.0, MAX_ZOOM);
+        Self {
+            start: scroll.clamp(0.0, 1.0) * (1.0 - len),
+            len,
+        }
+    }
+
+    /// Maps an x coordinate within `width` to a position in the whole file.
+    pub fn fraction_at(&self, x: f32, width: f32) -> f32 {
+        let within = if width > 0.0 {
+            (x / width).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        (self.start + within * self.len).clamp(0.0, 1.0)
+    }
+}
+
+pub struct Waveform<'a, Message> {
+    pub data: &'a WaveformData,
+    pub view: View,
+    /// Playhead as a fraction of the whole file, if this file is loaded for playback.
+    pub playhead: Option<f32>,
+    /// Chapter markers and cue points, as fractions of the whole file.
+    pub markers: Vec<f32>,
+    /// A second file drawn over this one for comparison, aligned by start time and
+    /// cut off at the end of `data`.
+    pub overlay: Option<&'a WaveformData>,
+    /// Part of the file to shade, e.g. what a zoomed-in view is showing.
+    pub highlight: Option<View>,
+    pub on_seek: fn(f32) -> Message,
+}
+
+impl<Message> canvas::Program<Message> for Waveform<'_, Message> {
+    type State = ();
+
+    fn update(
+        &self,
+        _state: &mut Self::State,
+        event: Event,
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
+    ) -> (canvas::event::Status, Option<Message>) {
+        if let Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) = event
+            && let Some(position) = cursor.position_in(bounds)
+        {
+            let fraction = self.view.fraction_at(position.x, bounds.width);
+            return (
+                canvas::event::Status::Captured,
+                Some((self.on_seek)(fraction)),
+            );
+        }
+        (canvas::event::Status::Ignored, None)
+    }
+
+    fn draw(
+        &self,
+        _state: &Self::State,
+        renderer: &Renderer,
+        theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<Geometry> {
+        let mut frame = Frame::new(renderer, bounds.size());
+        let palette = theme.extended_palette();
+        frame.fill_rectangle(Point::ORIGIN, bounds.size(), palette.background.weak.color);
+
+        let mid = bounds.height / 2.0;
+        let columns = bounds.width.max(1.0) as usize;
+        // Overlay positions are scaled so both files share a time axis
+        let overlay_scale = self.overlay.map(|overlay| {
+            self.data.duration_secs() / overlay.duration_secs().max(f32::MIN_POSITIVE)
+        });
+        for x in 0..columns {
+            let from = self.view.fraction_at(x as f32, bounds.width);
+            let to = self.view.fraction_at(x as f32 + 1.0, bounds.width);
+            let peak = self.data.peak_between(from, to).unwrap_or(0.0);
+            let half = (peak * mid).max(0.5);
+            frame.fill_rectangle(
+                Point::new(x as f32, mid - half),
+                Size::new(1.0, half * 2.0),
+                palette.primary.base.color,
+            );
+
+            if let (Some(overlay), Some(scale)) = (self.overlay, overlay_scale)
+                && let Some(peak) = overlay.peak_between(from * scale, to * scale)
+            {
+                let half = (peak * mid).max(0.5);
+                frame.fill_rectangle(
+                    Point::new(x as f32, mid - half),
+                    Size::new(1.0, half * 2.0),
+                    Color::from_rgba(1.0, 0.55, 0.1, 0.55),
+                );
+            }
+        }
+
+        if let Some(highlight) = self.highlight {
+            let x = (highlight.start - self.view.start) / self.view.len * bounds.width;
+            let width = highlight.len / self.view.len * bounds.width;
+            frame.fill_rectangle(
+                Point::new(x, 0.0),
+                Size::new(width, bounds.height),
+                Color::from_rgba(1.0, 1.0, 1.0, 0.2),
+            );
+        }
+
+        for &marker in &self.markers {
+            if marker >= self.view.start && marker <= self.view.start + self.view.len {
+                let x = (marker - self.view.start) / self.view.len * bounds.width;
+                frame.fill_rectangle(
+                    Point::new(x, 0.0),
+                    Size::new(1.0, bounds.height),
+                    Color::from_rgb(1.0, 0.8, 0.2),
+                );
+            }
+        }
+
+        if let Some(playhead) = self.playhead
+            && playhead >= self.view.start
+            && playhead <= self.view.start + self.view.len
+        {
+            let x = (playhead - self.view.start) / self.view.len * bounds.width;
+            frame.fill_rectangle(
+                Point::new(x, 0.0),
+                Size::new(2.0, bounds.height),
+                Color::from_rgb(1.0, 0.3, 0.2),
+            );
+        }
+
+        vec![frame.into_geometry()]
+    }
+}
+
+/// Strip showing where the whole file has speech; a click reports the position
+/// clicked, as a fraction of the file.
+pub struct SpeechTimeline<'a, Message> {
+    pub data: &'a WaveformData,
+    pub playhead: Option<f32>,
+    pub on_seek: fn(f32) -> Message,
+}
+
+impl<Message> canvas::Program<Message> for SpeechTimeline<'_, Message> {
+    type State = ();
+
+    fn update(
+        &self,
+        _state: &mut Self::State,
+        event: Event,
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
+    ) -> (canvas::event::Status, Option<Message>) {
+        if let Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) = event
+            && let Some(position) = cursor.position_in(bounds)
+        {
+            let fraction = View::new(1.0, 0.0).fraction_at(position.x, bounds.width);
+            return (
+                canvas::event::Status::Captured,
+                Some((self.on_seek)(fraction)),
+            );
+        }
+        (canvas::event::Status::Ignored, None)
+    }
+
+    fn draw(
+        &self,
+        _state: &Self::State,
+        renderer: &Renderer,
+        theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<Geometry> {
+        let mut frame = Frame::new(renderer, bounds.size());
+        let palette = theme.extended_palette();
+        frame.fill_rectangle(Point::ORIGIN, bounds.size(), palette.background.weak.color);
+
+        for segment in &self.data.speech {
+            frame.fill_rectangle(
+                Point::new(segment.start * bounds.width, 0.0),
+                Size::new(
+                    ((segment.end - segment.start) * bounds.width).max(1.0),
+                    bounds.height,
+                ),
+                palette.success.base.color,
+            );
+        }
+
+        if let Some(playhead) = self.playhead {
+            frame.fill_rectangle(
+                Point::new(playhead * bounds.width, 0.0),
+                Size::new(2.0, bounds.height),
+                Color::from_rgb(1.0, 0.3, 0.2),
+            );
+        }
+
+        vec![frame.into_geometry()]
+    }
+}
+
+/// Lowest level shown on the level history; anything quieter sits on the floor.
+const HISTORY_FLOOR_DB: f32 = -60.0;
+
+/// Recent input levels in dBFS, oldest first, drawn right-aligned so the newest is
+/// at the right edge and the graph scrolls left as it fills.
+/// Input meter: a bar on a linear scale, with an optional target level marked
+/// across it. The bar turns green while `on_target`.
+pub struct LevelMeter {
+    pub level: f32,
+    pub target: Option<f32>,
+    pub on_target: bool,
+}
+
+impl<Message> canvas::Program<Message> for LevelMeter {
+    type State = ();
+
+    fn draw(
+        &self,
+        _state: &Self::State,
+        renderer: &Renderer,
+        theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<Geometry> {
+        let mut frame = Frame::new(renderer, bounds.size());
+        let palette = theme.extended_palette();
+        frame.fill_rectangle(Point::ORIGIN, bounds.size(), palette.background.weak.color);
+
+        let color = if self.on_target {
+            palette.success.base.color
+        } else {
+            palette.primary.base.color
+        };
+        frame.fill_rectangle(
+            Point::ORIGIN,
+            Size::new(self.level.clamp(0.0, 1.0) * bounds.width, bounds.height),
+            color,
+        );
+        if let Some(target) = self.target {
+            let x = target.clamp(0.0, 1.0) * bounds.width;
+            frame.fill_rectangle(
+                Point::new((x - 1.0).max(0.0), 0.0),
+                Size::new(2.0, bounds.height),
+                palette.background.base.text,
+            );
+        }
+
+        vec![frame.into_geometry()]
+    }
+}
+
+pub struct LevelHistory<'a> {
+    pub levels: &'a VecDeque<f32>,
+    pub capacity: usize,
+}
+
+impl<Message> canvas::Program<Message> for LevelHistory<'_> {
+    type State = ();
+
+    fn draw(
+        &self,
+        _state: &Self::State,
+        renderer: &Renderer,
+        theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<Geometry> {
+        let mut frame = Frame::new(renderer, bounds.size());
+        let palette = theme.extended_palette();
+        frame.fill_rectangle(Point::ORIGIN, bounds.size(), palette.background.weak.color);
+
+        let width = bounds.width / self.capacity.max(1) as f32;
+        let offset = self.capacity.saturating_sub(self.levels.len());
+        for (i, &db) in self.levels.iter().enumerate() {
+            let height = (1.0 - db.max(HISTORY_FLOOR_DB) / HISTORY_FLOOR_DB) * bounds.height;
+            frame.fill_rectangle(
+                Point::new((offset + i) as f32 * width, bounds.height - height),
+                Size::new(width.max(1.0), height),
+                palette.primary.base.color,
+            );
+        }
+
+        vec![frame.into_geometry()]
+    }
+}